@@ -0,0 +1,2188 @@
+// Assembler
+//
+// Turns Motorola syntax source into bytes, for CPU tests and patches that
+// read better than hex words. It takes what the disassembler prints, PC
+// relative operands included, `($1000,pc)` being the word at $1000, and
+// the older `4(a0,d0.w)` style as well.
+//
+// A line is an optional label, in the first column or ending in a colon,
+// an instruction or directive, and a comment after a `;`. A `*` in the
+// first column makes the whole line a comment. Labels starting with a dot
+// belong to the last label without one. The directives are org, dc, ds,
+// even, end and equ (or `=`). Expressions have the usual C operators, `*`
+// is the address of the current line and numbers can be decimal, $hex,
+// %binary, @octal or 'c'haracters.
+//
+// Without a size, branches to labels above that are in reach are short
+// and everything else is a word. Absolute addresses are long unless given
+// a `.w`. Displacements that don't fit use the 020's full extension word
+// format where there is one.
+//
+// Each opcode is checked against the handler table the core builds for
+// the Version, so an instruction the part doesn't have, or an addressing
+// mode it can't take, is an error rather than bytes that would trap.
+
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::fmt;
+use instructions::optable::implemented;
+use instructions::optable::{IF_T, IF_F, IF_HI, IF_LS, IF_CC, IF_CS, IF_NE, IF_EQ,
+                            IF_VC, IF_VS, IF_PL, IF_MI, IF_GE, IF_LT, IF_GT, IF_LE};
+use AddressSpace;
+use Bus;
+use Version;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    pub line: usize,    // counting from 1
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for Error {}
+
+#[derive(Clone, Debug, Default)]
+pub struct Assembly {
+    pub chunks: Vec<(u32, Vec<u8>)>,    // start address and contents, a new one at each org
+    pub symbols: HashMap<String, u32>,
+}
+
+impl Assembly {
+    pub fn load<B: Bus + ?Sized>(&self, bus: &mut B, space: AddressSpace) {
+        for &(start, ref bytes) in self.chunks.iter() {
+            for (i, &byte) in bytes.iter().enumerate() {
+                bus.poke_8(space, start.wrapping_add(i as u32), byte);
+            }
+        }
+    }
+
+    // everything from the lowest address to the highest, gaps are 0
+    pub fn image(&self) -> (u32, Vec<u8>) {
+        let start = match self.chunks.iter().map(|&(start, _)| start).min() {
+            Some(start) => start,
+            None => return (0, Vec::new()),
+        };
+        let end = self.chunks.iter().map(|&(start, ref bytes)| start as u64 + bytes.len() as u64).max().unwrap();
+        let mut image = vec![0; (end - start as u64) as usize];
+        for &(chunk, ref bytes) in self.chunks.iter() {
+            let offset = (chunk - start) as usize;
+            image[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        (start, image)
+    }
+
+    // the image as words, to compare with an opcode table
+    pub fn words(&self) -> Vec<u16> {
+        self.image().1.chunks(2).map(|pair| (pair[0] as u16) << 8 | pair.get(1).cloned().unwrap_or(0) as u16).collect()
+    }
+}
+
+// Assembles `source` for `version`, starting at `origin` until an org says
+// otherwise.
+pub fn assemble(version: Version, origin: u32, source: &str) -> Result<Assembly, Error> {
+    assemble_for(version, &implemented(version), origin, source)
+}
+
+// the same, with the table of opcodes the Version implements already built
+fn assemble_for(version: Version, implemented: &[bool], origin: u32, source: &str) -> Result<Assembly, Error> {
+    // Sizes only ever depend on what is defined above, so addresses come
+    // out the same every pass. Passes go on until equates that refer
+    // forward have settled, and the last one reports the errors.
+    let mut symbols = HashMap::new();
+    for _ in 0..MAX_PASSES {
+        let mut pass = Assembler::new(version, implemented, origin, symbols.clone(), false);
+        let _ = pass.run(source);
+        if pass.symbols == symbols {
+            break;
+        }
+        symbols = pass.symbols;
+    }
+    let mut pass = Assembler::new(version, implemented, origin, symbols, true);
+    pass.run(source)?;
+    Ok(Assembly {
+        chunks: pass.chunks,
+        symbols: pass.symbols.into_iter().map(|(name, value)| (name, value as u32)).collect(),
+    })
+}
+
+
+const MAX_PASSES: usize = 8;
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Number(i64),
+    Symbol(String),
+    Here,
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),     // '<' and '>' are the shifts
+}
+
+enum EvalError {
+    Undefined(String),
+    Invalid(String),
+}
+
+#[derive(Clone, Debug)]
+enum Base {
+    Address(u16),
+    Pc,
+    SuppressedPc,   // zpc
+    None,           // the address register is suppressed
+}
+
+#[derive(Clone, Debug)]
+struct Index {
+    reg: u16,       // 0-15
+    long: bool,
+    scale: u16,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Indirect {
+    None,
+    Pre,    // ([bd,An,Xn],od)
+    Post,   // ([bd,An],Xn,od)
+}
+
+#[derive(Clone, Debug)]
+struct Indexed {
+    base: Base,
+    bd: Option<Expr>,
+    index: Option<Index>,
+    indirect: Indirect,
+    od: Option<Expr>,
+}
+
+#[derive(Clone, Debug)]
+enum Operand {
+    Data(u16),
+    Address(u16),
+    Indirect(u16),
+    PostInc(u16),
+    PreDec(u16),
+    Indexed(Indexed),           // displacements and indexes from An or the PC
+    Absolute(Expr, Option<char>),   // 'w' or 'l'
+    Immediate(Expr),
+    Special(String),            // sr, ccr, usp, control, MMU and MAC registers, caches
+    List(u16),                  // MOVEM registers, bit 0 is d0
+    Pair(Box<Operand>, Box<Operand>),   // x:y
+    Field(Box<Operand>, Box<Operand>, Box<Operand>),    // ea{offset:width}
+    Half(u16, bool),            // MAC operand, register and upper word
+    Shift(bool),                // MAC << or >>, true to the left
+    Masked(Box<Operand>),       // MAC load through the MASK register
+}
+
+const SPECIAL: [&str; 49] = [
+    "sr", "ccr", "usp", "sfc", "dfc", "cacr", "tc", "itt0", "itt1", "dtt0", "dtt1", "buscr",
+    "vbr", "caar", "msp", "isp", "mmusr", "urp", "srp", "pcr",
+    "acr0", "acr1", "acr2", "acr3", "rombar0", "rombar1", "rambar0", "rambar1", "mbar",
+    "nc", "dc", "ic", "bc",
+    "drp", "crp", "cal", "val", "scc", "ac", "psr", "pcsr",
+    "acc0", "acc1", "acc2", "acc3", "macsr", "accext01", "mask", "accext23",
+];
+
+const MAC_REGISTERS: [&str; 8] = ["acc0", "acc1", "acc2", "acc3", "macsr", "accext01", "mask", "accext23"];
+
+const PMMU_CONDITIONS: [&str; 16] = ["bs", "bc", "ls", "lc", "ss", "sc", "as", "ac",
+                                     "ws", "wc", "is", "ic", "gs", "gc", "cs", "cc"];
+
+const MNEMONICS: [&str; 137] = [
+    "ori", "andi", "subi", "addi", "eori", "cmpi", "btst", "bchg", "bclr", "bset", "movep", "moves",
+    "chk2", "cmp2", "callm", "rtm", "cas", "cas2", "bitrev", "byterev", "ff1",
+    "move", "movea", "moveq", "mov3q", "mvs", "mvz", "movem", "movec",
+    "negx", "clr", "neg", "not", "tst", "nbcd", "tas", "pea", "jmp", "jsr", "swap", "ext", "extb",
+    "lea", "chk", "link", "unlk", "trap", "bkpt", "stop", "rtd", "reset", "nop", "rte", "rts",
+    "trapv", "rtr", "illegal", "bgnd", "muls", "mulu", "divs", "divu", "divsl", "divul", "rems",
+    "remu", "sats", "addq", "subq", "dbra", "bra", "bsr",
+    "or", "and", "eor", "add", "sub", "cmp", "adda", "suba", "cmpa", "cmpm", "addx", "subx",
+    "abcd", "sbcd", "pack", "unpk", "exg",
+    "asl", "asr", "lsl", "lsr", "roxl", "roxr", "rol", "ror",
+    "bftst", "bfextu", "bfchg", "bfexts", "bfclr", "bfffo", "bfset", "bfins",
+    "pflusha", "pflush", "pflushs", "ploadr", "ploadw", "pvalid", "pmove", "pmovefd",
+    "ptestr", "ptestw", "pflushr", "psave", "prestore",
+    "cinvl", "cinvp", "cinva", "cpushl", "cpushp", "cpusha", "pflushn", "pflushan",
+    "plpaw", "plpar", "move16", "lpstop", "tbls", "tblu", "tblsn", "tblun",
+    "mac", "msac", "movclr",
+];
+
+const DIRECTIVES: [&str; 7] = ["org", "dc", "ds", "even", "end", "equ", "="];
+
+fn condition(name: &str) -> Option<u16> {
+    Some((match name {
+        "t" => IF_T,
+        "f" => IF_F,
+        "hi" => IF_HI,
+        "ls" => IF_LS,
+        "cc" | "hs" => IF_CC,
+        "cs" | "lo" => IF_CS,
+        "ne" => IF_NE,
+        "eq" => IF_EQ,
+        "vc" => IF_VC,
+        "vs" => IF_VS,
+        "pl" => IF_PL,
+        "mi" => IF_MI,
+        "ge" => IF_GE,
+        "lt" => IF_LT,
+        "gt" => IF_GT,
+        "le" => IF_LE,
+        _ => return None,
+    }) as u16)
+}
+
+fn pmmu_condition(name: &str) -> Option<u16> {
+    PMMU_CONDITIONS.iter().position(|&cc| cc == name).map(|cc| cc as u16)
+}
+
+// the Bcc, DBcc, Scc and TRAPcc families and their 68851 versions
+fn conditional(name: &str) -> bool {
+    let family = |prefix: &str, cc: &dyn Fn(&str) -> bool| name.starts_with(prefix) && cc(&name[prefix.len()..]);
+    let cc = |s: &str| condition(s).is_some();
+    let pcc = |s: &str| pmmu_condition(s).is_some();
+    family("b", &|s| cc(s) && s != "t" && s != "f") || family("db", &cc) || family("s", &cc)
+        || family("trap", &cc) || family("pb", &pcc) || family("pdb", &pcc)
+        || family("ps", &pcc) || family("ptrap", &pcc)
+}
+
+fn known_mnemonic(token: &str) -> bool {
+    let lower = token.to_lowercase();
+    let name = lower.split('.').next().unwrap_or("");
+    MNEMONICS.contains(&name) || DIRECTIVES.contains(&name) || conditional(name)
+}
+
+fn register(name: &str) -> Option<u16> {
+    if name == "sp" {
+        return Some(15);
+    }
+    let mut chars = name.chars();
+    let bank = match chars.next() {
+        Some('d') => 0,
+        Some('a') => 8,
+        _ => return None,
+    };
+    match (chars.next().and_then(|c| c.to_digit(10)), chars.next()) {
+        (Some(r), None) if r < 8 => Some(bank + r as u16),
+        _ => None,
+    }
+}
+
+fn as_register(r: u16) -> Operand {
+    if r < 8 { Operand::Data(r) } else { Operand::Address(r & 7) }
+}
+
+// Xn, Xn.w, Xn.l and with a *scale
+fn index_register(name: &str) -> Option<(Index, bool)> {
+    let (name, scale) = match name.find('*') {
+        Some(star) => (&name[..star], match name[star + 1..].trim() {
+            "1" => 1,
+            "2" => 2,
+            "4" => 4,
+            "8" => 8,
+            _ => return None,
+        }),
+        None => (name, 1),
+    };
+    let name = name.trim();
+    let (name, long, sized) = if let Some(name) = name.strip_suffix(".w") {
+        (name, false, true)
+    } else if let Some(name) = name.strip_suffix(".l") {
+        (name, true, true)
+    } else {
+        (name, false, false)
+    };
+    let reg = register(name)?;
+    Some((Index { reg, long, scale }, !sized && scale == 1))
+}
+
+fn register_list(text: &str) -> Option<u16> {
+    if !text.contains('/') && !text.contains('-') {
+        return None;
+    }
+    let mut mask = 0;
+    for part in text.split('/') {
+        let mut range = part.split('-');
+        let first = register(range.next()?.trim())?;
+        let last = match range.next() {
+            Some(last) => register(last.trim())?,
+            None => first,
+        };
+        if range.next().is_some() || last < first {
+            return None;
+        }
+        for r in first..=last {
+            mask |= 1 << r;
+        }
+    }
+    Some(mask)
+}
+
+fn strip_comment(line: &str) -> &str {
+    if line.starts_with('*') {
+        return "";
+    }
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == ';' => return &line[..i],
+            None => (),
+        }
+    }
+    line
+}
+
+// splits at `separator` outside of brackets and quotes
+fn split_top(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ if c == separator && depth == 0 => {
+                    parts.push(text[start..i].trim());
+                    start = i + c.len_utf8();
+                },
+                _ => (),
+            },
+        }
+    }
+    parts.push(text[start..].trim());
+    parts
+}
+
+// the ( that the ) at the end of `text` closes
+fn matching_open(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices().rev() {
+        match c {
+            ')' | ']' => depth += 1,
+            '(' | '[' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            },
+            _ => (),
+        }
+    }
+    None
+}
+
+struct ExprParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+fn parse_expr(text: &str) -> Result<Expr, String> {
+    let mut parser = ExprParser { text: text.as_bytes(), pos: 0 };
+    let expr = parser.binary(0)?;
+    parser.skip_space();
+    if parser.pos != parser.text.len() {
+        return Err(format!("can't make sense of `{}`", text.trim()));
+    }
+    Ok(expr)
+}
+
+impl<'a> ExprParser<'a> {
+    fn skip_space(&mut self) {
+        while self.pos < self.text.len() && (self.text[self.pos] as char).is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text.get(self.pos).map(|&c| c as char)
+    }
+
+    // lowest precedence first
+    fn operator(&self, level: usize) -> Option<(char, usize)> {
+        let rest = &self.text[self.pos..];
+        let c = *rest.first()? as char;
+        let op = match (level, c) {
+            (0, '|') | (1, '^') | (2, '&') => (c, 1),
+            (3, '<') if rest.starts_with(b"<<") => ('<', 2),
+            (3, '>') if rest.starts_with(b">>") => ('>', 2),
+            (4, '+') | (4, '-') | (5, '*') | (5, '/') | (5, '%') => (c, 1),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == 6 {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        loop {
+            self.skip_space();
+            let (op, len) = match self.operator(level) {
+                Some(op) => op,
+                None => return Ok(left),
+            };
+            self.pos += len;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn digits(&mut self, radix: u32) -> Result<Expr, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_digit(radix) || c == '_') {
+            self.pos += 1;
+        }
+        let digits: String = self.text[start..self.pos].iter().map(|&c| c as char).filter(|&c| c != '_').collect();
+        u64::from_str_radix(&digits, radix)
+            .map(|value| Expr::Number(value as i64))
+            .map_err(|_| format!("bad number `{}`", digits))
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        self.skip_space();
+        let c = self.peek().ok_or("missing value")?;
+        self.pos += 1;
+        match c {
+            '-' => Ok(Expr::Negate(Box::new(self.unary()?))),
+            '~' => Ok(Expr::Not(Box::new(self.unary()?))),
+            '+' => self.unary(),
+            '*' => Ok(Expr::Here),
+            '(' => {
+                let expr = self.binary(0)?;
+                self.skip_space();
+                if self.peek() != Some(')') {
+                    return Err("missing )".to_string());
+                }
+                self.pos += 1;
+                Ok(expr)
+            },
+            '$' => self.digits(16),
+            '%' => self.digits(2),
+            '@' => self.digits(8),
+            '0' if self.peek().is_some_and(|c| c == 'x' || c == 'X') => {
+                self.pos += 1;
+                self.digits(16)
+            },
+            '0'..='9' => {
+                self.pos -= 1;
+                self.digits(10)
+            },
+            '\'' | '"' => {
+                let mut value = 0i64;
+                loop {
+                    match self.peek() {
+                        None => return Err("missing closing quote".to_string()),
+                        Some(q) if q == c => break,
+                        Some(ch) => value = value << 8 | ch as i64,
+                    }
+                    self.pos += 1;
+                }
+                self.pos += 1;
+                Ok(Expr::Number(value))
+            },
+            c if c.is_alphabetic() || c == '_' || c == '.' => {
+                let start = self.pos - 1;
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+                    self.pos += 1;
+                }
+                let name = self.text[start..self.pos].iter().map(|&c| c as char).collect();
+                Ok(Expr::Symbol(name))
+            },
+            c => Err(format!("unexpected `{}`", c)),
+        }
+    }
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    let text = text.trim();
+    let lower = text.to_lowercase();
+    if text.is_empty() {
+        return Err("missing operand".to_string());
+    }
+    if let Some(rest) = text.strip_prefix('#') {
+        return Ok(Operand::Immediate(parse_expr(rest)?));
+    }
+    match lower.as_str() {
+        "<<" => return Ok(Operand::Shift(true)),
+        ">>" => return Ok(Operand::Shift(false)),
+        _ => (),
+    }
+    if let Some(rest) = text.strip_suffix('&') {
+        return Ok(Operand::Masked(Box::new(parse_operand(rest)?)));
+    }
+    if text.ends_with('}') {
+        let open = text.rfind('{').ok_or("missing {")?;
+        let parts = split_top(&text[open + 1..text.len() - 1], ':');
+        if parts.len() != 2 {
+            return Err("a bit field is {offset:width}".to_string());
+        }
+        let part = |text: &str| -> Result<Operand, String> {
+            match register(&text.to_lowercase()) {
+                Some(r) if r < 8 => Ok(Operand::Data(r)),
+                Some(_) => Err("bit field offsets and widths are in data registers".to_string()),
+                None => Ok(Operand::Absolute(parse_expr(text)?, None)),
+            }
+        };
+        return Ok(Operand::Field(Box::new(parse_operand(&text[..open])?), Box::new(part(parts[0])?), Box::new(part(parts[1])?)));
+    }
+    let pair = split_top(text, ':');
+    if pair.len() == 2 {
+        return Ok(Operand::Pair(Box::new(parse_operand(pair[0])?), Box::new(parse_operand(pair[1])?)));
+    }
+    if let Some(r) = register(&lower) {
+        return Ok(as_register(r));
+    }
+    if let Some(mask) = register_list(&lower) {
+        return Ok(Operand::List(mask));
+    }
+    if lower.len() > 2 && (lower.ends_with(".u") || lower.ends_with(".l")) {
+        if let Some(r) = register(&lower[..lower.len() - 2]) {
+            return Ok(Operand::Half(r, lower.ends_with(".u")));
+        }
+    }
+    if SPECIAL.contains(&lower.as_str()) || (lower.len() == 4 && (lower.starts_with("bad") || lower.starts_with("bac"))) {
+        return Ok(Operand::Special(lower));
+    }
+    if lower.starts_with("-(") && lower.ends_with(')') {
+        if let Some(r) = register(&lower[2..lower.len() - 1]).filter(|&r| r >= 8) {
+            return Ok(Operand::PreDec(r & 7));
+        }
+    }
+    if lower.starts_with('(') && lower.ends_with(")+") {
+        if let Some(r) = register(&lower[1..lower.len() - 2]).filter(|&r| r >= 8) {
+            return Ok(Operand::PostInc(r & 7));
+        }
+    }
+    if text.ends_with(')') {
+        if let Some(open) = matching_open(text) {
+            let prefix = text[..open].trim();
+            let inner = &text[open + 1..text.len() - 1];
+            if prefix.is_empty() {
+                if let Some(r) = register(&inner.trim().to_lowercase()).filter(|&r| r >= 8) {
+                    return Ok(Operand::Indirect(r & 7));
+                }
+            }
+            let mut elements = split_top(inner, ',');
+            if !prefix.is_empty() {
+                elements.insert(0, prefix);
+            }
+            let register_like = |e: &str| {
+                let e = e.to_lowercase();
+                e == "pc" || e == "zpc" || e.starts_with('[') || index_register(&e).is_some()
+            };
+            if elements.iter().any(|e| register_like(e)) {
+                return Ok(Operand::Indexed(parse_indexed(&elements)?));
+            }
+        }
+    }
+    if lower.ends_with(".w") || lower.ends_with(".l") {
+        let size = lower.chars().last();
+        return Ok(Operand::Absolute(parse_expr(&text[..text.len() - 2])?, size));
+    }
+    Ok(Operand::Absolute(parse_expr(text)?, None))
+}
+
+fn parse_indexed(elements: &[&str]) -> Result<Indexed, String> {
+    let mut indexed = Indexed { base: Base::None, bd: None, index: None, indirect: Indirect::None, od: None };
+    let mut memory = false;
+    let mut index_inside = false;
+    let mut base_set = false;
+    for element in elements {
+        if element.starts_with('[') {
+            if memory || !element.ends_with(']') || base_set || indexed.bd.is_some() || indexed.index.is_some() {
+                return Err("misplaced memory indirection".to_string());
+            }
+            for inner in split_top(&element[1..element.len() - 1], ',') {
+                index_element(&mut indexed, inner, false, &mut base_set)?;
+            }
+            index_inside = indexed.index.is_some();
+            memory = true;
+            continue;
+        }
+        index_element(&mut indexed, element, memory, &mut base_set)?;
+    }
+    if memory {
+        indexed.indirect = if indexed.index.is_some() && !index_inside { Indirect::Post } else { Indirect::Pre };
+    }
+    Ok(indexed)
+}
+
+fn index_element(indexed: &mut Indexed, element: &str, after_memory: bool, base_set: &mut bool) -> Result<(), String> {
+    let lower = element.to_lowercase();
+    let lower = lower.trim();
+    let base = match lower {
+        "pc" => Some(Base::Pc),
+        "zpc" => Some(Base::SuppressedPc),
+        _ => None,
+    };
+    if let Some(base) = base {
+        if *base_set || after_memory {
+            return Err("misplaced base register".to_string());
+        }
+        indexed.base = base;
+        *base_set = true;
+        return Ok(());
+    }
+    if let Some((index, plain)) = index_register(lower) {
+        if plain && index.reg >= 8 && !*base_set && !after_memory {
+            indexed.base = Base::Address(index.reg & 7);
+            *base_set = true;
+        } else if indexed.index.is_none() {
+            indexed.index = Some(index);
+        } else {
+            return Err("only one index register".to_string());
+        }
+        return Ok(());
+    }
+    let expr = parse_expr(element)?;
+    let slot = if after_memory { &mut indexed.od } else { &mut indexed.bd };
+    if slot.is_some() {
+        return Err("only one displacement".to_string());
+    }
+    *slot = Some(expr);
+    Ok(())
+}
+
+fn size_bytes(size: Option<char>, default: u32) -> Result<u32, String> {
+    match size {
+        None => Ok(default),
+        Some('b') => Ok(1),
+        Some('w') => Ok(2),
+        Some('l') => Ok(4),
+        Some(c) => Err(format!("no .{} size here", c)),
+    }
+}
+
+fn size_code(bytes: u32) -> u16 {
+    match bytes {
+        1 => 0,
+        2 => 1,
+        _ => 2,
+    }
+}
+
+// in range signed or unsigned
+fn fits(value: i64, bits: u32) -> bool {
+    value >= -(1 << (bits - 1)) && value < (1 << bits)
+}
+
+fn fits_signed(value: i64, bits: u32) -> bool {
+    value >= -(1 << (bits - 1)) && value < (1 << (bits - 1))
+}
+
+fn operands(ops: &[Operand], count: usize) -> Result<(), String> {
+    if ops.len() != count {
+        return Err(format!("expects {} operand{}", count, if count == 1 { "" } else { "s" }));
+    }
+    Ok(())
+}
+
+fn data(op: &Operand) -> Result<u16, String> {
+    match *op {
+        Operand::Data(r) => Ok(r),
+        _ => Err("expects a data register".to_string()),
+    }
+}
+
+fn address(op: &Operand) -> Result<u16, String> {
+    match *op {
+        Operand::Address(r) => Ok(r),
+        _ => Err("expects an address register".to_string()),
+    }
+}
+
+// any register, 0-15
+fn any_register(op: &Operand) -> Result<u16, String> {
+    match *op {
+        Operand::Data(r) => Ok(r),
+        Operand::Address(r) => Ok(8 + r),
+        _ => Err("expects a register".to_string()),
+    }
+}
+
+fn immediate(op: &Operand) -> Result<&Expr, String> {
+    match *op {
+        Operand::Immediate(ref e) => Ok(e),
+        _ => Err("expects an immediate".to_string()),
+    }
+}
+
+fn special(op: &Operand) -> Option<&str> {
+    match *op {
+        Operand::Special(ref name) => Some(name),
+        _ => None,
+    }
+}
+
+fn control_register(name: &str) -> Option<u16> {
+    Some(match name {
+        "sfc" => 0x000,
+        "dfc" => 0x001,
+        "cacr" => 0x002,
+        "tc" => 0x003,
+        "itt0" => 0x004,
+        "itt1" => 0x005,
+        "dtt0" => 0x006,
+        "dtt1" => 0x007,
+        "buscr" => 0x008,
+        "usp" => 0x800,
+        "vbr" => 0x801,
+        "caar" => 0x802,
+        "msp" => 0x803,
+        "isp" => 0x804,
+        "mmusr" => 0x805,
+        "urp" => 0x806,
+        "srp" => 0x807,
+        "pcr" => 0x808,
+        "acr0" => 0x004,
+        "acr1" => 0x005,
+        "acr2" => 0x006,
+        "acr3" => 0x007,
+        "rombar0" => 0xc00,
+        "rombar1" => 0xc01,
+        "rambar0" => 0xc04,
+        "rambar1" => 0xc05,
+        "mbar" => 0xc0f,
+        _ => return None,
+    })
+}
+
+fn mac_register(op: &Operand) -> Option<u16> {
+    special(op).and_then(|name| MAC_REGISTERS.iter().position(|&r| r == name)).map(|r| r as u16)
+}
+
+struct Assembler<'t> {
+    version: Version,
+    implemented: &'t [bool],
+    pc: u32,
+    symbols: HashMap<String, i64>,  // everything defined, forward references from the last pass
+    known: HashMap<String, i64>,    // defined above from values already known, for sizes
+    defined: HashSet<String>,       // this pass
+    last_pass: bool,
+    scope: String,                  // the last label, for the local ones
+    chunks: Vec<(u32, Vec<u8>)>,
+    new_chunk: bool,
+    ended: bool,
+}
+
+impl<'t> Assembler<'t> {
+    fn new(version: Version, implemented: &'t [bool], origin: u32, symbols: HashMap<String, i64>, last_pass: bool) -> Self {
+        Assembler {
+            version,
+            implemented,
+            pc: origin,
+            symbols,
+            known: HashMap::new(),
+            defined: HashSet::new(),
+            last_pass,
+            scope: String::new(),
+            chunks: Vec::new(),
+            new_chunk: true,
+            ended: false,
+        }
+    }
+
+    fn run(&mut self, source: &str) -> Result<(), Error> {
+        for (number, line) in source.lines().enumerate() {
+            if let Err(message) = self.line(line) {
+                if self.last_pass {
+                    return Err(Error { line: number + 1, message });
+                }
+            }
+            if self.ended {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn full_name(&self, name: &str) -> String {
+        if name.starts_with('.') { format!("{}{}", self.scope, name) } else { name.to_string() }
+    }
+
+    fn eval(&self, expr: &Expr, here: u32, sizing: bool) -> Result<i64, EvalError> {
+        let eval = |e: &Expr| self.eval(e, here, sizing);
+        Ok(match *expr {
+            Expr::Number(value) => value,
+            Expr::Here => here as i64,
+            Expr::Symbol(ref name) => {
+                let name = self.full_name(name);
+                let table = if sizing { &self.known } else { &self.symbols };
+                match table.get(&name) {
+                    Some(&value) => value,
+                    None => return Err(EvalError::Undefined(name)),
+                }
+            },
+            Expr::Negate(ref e) => eval(e)?.wrapping_neg(),
+            Expr::Not(ref e) => !eval(e)?,
+            Expr::Binary(op, ref left, ref right) => {
+                let (left, right) = (eval(left)?, eval(right)?);
+                match op {
+                    '+' => left.wrapping_add(right),
+                    '-' => left.wrapping_sub(right),
+                    '*' => left.wrapping_mul(right),
+                    '/' | '%' if right == 0 => return Err(EvalError::Invalid("division by zero".to_string())),
+                    '/' => left.wrapping_div(right),
+                    '%' => left.wrapping_rem(right),
+                    '&' => left & right,
+                    '|' => left | right,
+                    '^' => left ^ right,
+                    '<' => left.wrapping_shl(right as u32),
+                    _ => left.wrapping_shr(right as u32),
+                }
+            },
+        })
+    }
+
+    // the value, 0 for what isn't defined yet until the last pass
+    fn value(&self, expr: &Expr, here: u32) -> Result<i64, String> {
+        match self.eval(expr, here, false) {
+            Ok(value) => Ok(value),
+            Err(EvalError::Undefined(_)) if !self.last_pass => Ok(0),
+            Err(EvalError::Undefined(name)) => Err(format!("`{}` isn't defined", name)),
+            Err(EvalError::Invalid(message)) => Err(message),
+        }
+    }
+
+    // the value when it only depends on what is above, sizes are picked
+    // from these so they don't change between passes
+    fn sizing(&self, expr: &Expr, here: u32) -> Option<i64> {
+        self.eval(expr, here, true).ok()
+    }
+
+    // range errors only count on the last pass, earlier ones can be from
+    // symbols not known yet
+    fn check(&self, ok: bool, message: &str) -> Result<(), String> {
+        if ok || !self.last_pass { Ok(()) } else { Err(message.to_string()) }
+    }
+
+    fn define(&mut self, name: &str, value: i64, known: bool) -> Result<(), String> {
+        let name = self.full_name(name);
+        if !self.defined.insert(name.clone()) {
+            return Err(format!("`{}` is already defined", name));
+        }
+        if self.last_pass && self.symbols.get(&name) != Some(&value) {
+            return Err(format!("`{}` doesn't settle", name));
+        }
+        self.symbols.insert(name.clone(), value);
+        if known {
+            self.known.insert(name, value);
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        if self.new_chunk {
+            self.chunks.push((self.pc, Vec::new()));
+            self.new_chunk = false;
+        }
+        self.chunks.last_mut().unwrap().1.extend_from_slice(bytes);
+        self.pc = self.pc.wrapping_add(bytes.len() as u32);
+    }
+
+    fn line(&mut self, text: &str) -> Result<(), String> {
+        let text = strip_comment(text);
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        let first_column = !text.starts_with(char::is_whitespace);
+        let trimmed = text.trim();
+        let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+        let token = &trimmed[..end];
+        let (label, rest) = match token.find(':') {
+            Some(colon) => (Some(&token[..colon]), &trimmed[colon + 1..]),
+            None if first_column && !known_mnemonic(token) => (Some(token), &trimmed[end..]),
+            None => (None, trimmed),
+        };
+        let rest = rest.trim();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (mnemonic, operands) = (&rest[..end], rest[end..].trim());
+        let lower = mnemonic.to_lowercase();
+
+        if let Some(label) = label {
+            if label.is_empty() {
+                return Err("empty label".to_string());
+            }
+            if lower == "equ" || lower == "=" {
+                let expr = parse_expr(operands)?;
+                let value = self.value(&expr, self.pc)?;
+                let known = self.sizing(&expr, self.pc).is_some();
+                return self.define(label, value, known);
+            }
+            if !label.starts_with('.') {
+                self.scope = label.to_string();
+            }
+            let pc = self.pc as i64;
+            self.define(label, pc, true)?;
+        }
+        if mnemonic.is_empty() {
+            return Ok(());
+        }
+        let (name, size) = match lower.find('.') {
+            Some(dot) => (&lower[..dot], lower[dot + 1..].chars().next()),
+            None => (&lower[..], None),
+        };
+        match name {
+            "org" => {
+                let expr = parse_expr(operands)?;
+                let origin = self.sizing(&expr, self.pc).ok_or("org needs a value defined above")?;
+                self.pc = origin as u32;
+                self.new_chunk = true;
+                Ok(())
+            },
+            "even" => {
+                if self.pc & 1 != 0 {
+                    self.emit(&[0]);
+                }
+                Ok(())
+            },
+            "end" => {
+                self.ended = true;
+                Ok(())
+            },
+            "ds" => {
+                let expr = parse_expr(operands)?;
+                let count = self.sizing(&expr, self.pc).ok_or("ds needs a count defined above")?;
+                if count < 0 {
+                    return Err("ds can't be negative".to_string());
+                }
+                let bytes = vec![0; (count as u64 * size_bytes(size, 2)? as u64) as usize];
+                self.emit(&bytes);
+                Ok(())
+            },
+            "dc" => self.constants(size_bytes(size, 2)?, operands),
+            _ => self.instruction(name, size, operands),
+        }
+    }
+
+    fn constants(&mut self, size: u32, operands: &str) -> Result<(), String> {
+        let mut bytes = Vec::new();
+        for item in split_top(operands, ',') {
+            let quoted = item.len() >= 2 && (item.starts_with('"') || item.starts_with('\''))
+                && item.ends_with(item.chars().next().unwrap());
+            if size == 1 && quoted && item.len() != 3 {
+                bytes.extend_from_slice(&item.as_bytes()[1..item.len() - 1]);
+                continue;
+            }
+            let value = self.value(&parse_expr(item)?, self.pc)?;
+            self.check(size == 4 && fits(value, 32) || fits(value, size * 8), "constant out of range")?;
+            for i in (0..size).rev() {
+                bytes.push((value >> (i * 8)) as u8);
+            }
+        }
+        self.emit(&bytes);
+        Ok(())
+    }
+
+    fn instruction(&mut self, name: &str, size: Option<char>, operands: &str) -> Result<(), String> {
+        if self.pc & 1 != 0 {
+            return Err("instruction at an odd address".to_string());
+        }
+        let ops = if operands.is_empty() {
+            Vec::new()
+        } else {
+            split_top(operands, ',').into_iter().map(parse_operand).collect::<Result<Vec<_>, _>>()?
+        };
+        let words = {
+            let mut encoder = Encoder { asm: self, pc: self.pc, words: vec![0] };
+            encoder.words[0] = encoder.instruction(name, size, &ops)?;
+            encoder.words
+        };
+        if self.last_pass && !self.implemented[words[0] as usize] {
+            return Err(format!("`{} {}` isn't an instruction the {:?} has", name, operands, self.version));
+        }
+        let bytes: Vec<u8> = words.iter().flat_map(|&w| vec![(w >> 8) as u8, w as u8]).collect();
+        self.emit(&bytes);
+        Ok(())
+    }
+}
+
+struct Encoder<'a, 't: 'a> {
+    asm: &'a Assembler<'t>,
+    pc: u32,            // of the instruction
+    words: Vec<u16>,    // the opcode and extension words so far
+}
+
+impl<'a, 't> Encoder<'a, 't> {
+    // where the next extension word goes
+    fn here(&self) -> u32 {
+        self.pc.wrapping_add(2 * self.words.len() as u32)
+    }
+
+    fn value(&self, expr: &Expr) -> Result<i64, String> {
+        self.asm.value(expr, self.pc)
+    }
+
+    fn sizing(&self, expr: &Expr) -> Option<i64> {
+        self.asm.sizing(expr, self.pc)
+    }
+
+    fn check(&self, ok: bool, message: &str) -> Result<(), String> {
+        self.asm.check(ok, message)
+    }
+
+    fn word(&mut self, word: u16) {
+        self.words.push(word);
+    }
+
+    fn long(&mut self, long: u32) {
+        self.words.push((long >> 16) as u16);
+        self.words.push(long as u16);
+    }
+
+    fn version(&self) -> Version {
+        self.asm.version
+    }
+
+    // the full extension word format and the memory indirect modes
+    fn full_format(&self) -> bool {
+        matches!(self.version().base(), Version::MC68020 | Version::MC68040 | Version::MC68060)
+    }
+
+    fn long_branch(&self) -> bool {
+        self.full_format() || matches!(self.version(), Version::CPU32 | Version::ColdFireIsaB | Version::ColdFireIsaC)
+    }
+
+    fn scale(&self) -> bool {
+        self.version().base() != Version::MC68000 && self.version() != Version::MC68010
+    }
+
+    fn imm(&mut self, expr: &Expr, size: u32) -> Result<(), String> {
+        let value = self.value(expr)?;
+        match size {
+            1 => {
+                self.check(fits(value, 8), "immediate out of range for a byte")?;
+                self.word(value as u8 as u16);
+            },
+            2 => {
+                self.check(fits(value, 16), "immediate out of range for a word")?;
+                self.word(value as u16);
+            },
+            4 => {
+                self.check(fits(value, 32), "immediate out of range for a long")?;
+                self.long(value as u32);
+            },
+            8 => {
+                self.long((value >> 32) as u32);
+                self.long(value as u32);
+            },
+            _ => return Err("no immediate here".to_string()),
+        }
+        Ok(())
+    }
+
+    // a PC relative displacement, from the extension word it's in
+    fn displacement(&self, target: i64, at: u32) -> i64 {
+        (target as u32).wrapping_sub(at) as i32 as i64
+    }
+
+    fn ea(&mut self, op: &Operand, size: u32) -> Result<u16, String> {
+        Ok(match *op {
+            Operand::Data(r) => r,
+            Operand::Address(r) => 0x08 | r,
+            Operand::Indirect(r) => 0x10 | r,
+            Operand::PostInc(r) => 0x18 | r,
+            Operand::PreDec(r) => 0x20 | r,
+            Operand::Indexed(ref indexed) => return self.indexed(indexed),
+            Operand::Absolute(ref expr, Some('w')) => {
+                let value = self.value(expr)?;
+                let short = fits(value, 16) || (0xffff_8000..=0xffff_ffff).contains(&value);
+                self.check(short, "address out of range for .w")?;
+                self.word(value as u16);
+                0x38
+            },
+            Operand::Absolute(ref expr, _) => {
+                let value = self.value(expr)?;
+                self.check(fits(value, 32), "address out of range")?;
+                self.long(value as u32);
+                0x39
+            },
+            Operand::Immediate(ref expr) => {
+                self.imm(expr, size)?;
+                0x3c
+            },
+            _ => return Err("expects an effective address".to_string()),
+        })
+    }
+
+    fn indexed(&mut self, indexed: &Indexed) -> Result<u16, String> {
+        let at = self.here();
+        let pc_relative = matches!(indexed.base, Base::Pc);
+        let adjust = |value: i64| if pc_relative { (value as u32).wrapping_sub(at) as i32 as i64 } else { value };
+        let simple = indexed.indirect == Indirect::None && matches!(indexed.base, Base::Address(_) | Base::Pc);
+        let (mode, index_mode) = match indexed.base {
+            Base::Address(r) => (0x28 | r, 0x30 | r),
+            Base::Pc | Base::SuppressedPc => (0x3a, 0x3b),
+            Base::None => (0x30, 0x30),
+        };
+        let bd_fits = |bits| indexed.bd.as_ref().is_none_or(|bd| self.sizing(bd).is_none_or(|v| fits_signed(adjust(v), bits)));
+        if simple && indexed.index.is_none() && (bd_fits(16) || !self.full_format()) {
+            let disp = match indexed.bd {
+                Some(ref bd) => adjust(self.value(bd)?),
+                None if pc_relative => 0,
+                None => return Ok(0x10 | (mode & 7)),
+            };
+            self.check(fits_signed(disp, 16), "displacement out of range")?;
+            self.word(disp as u16);
+            return Ok(mode);
+        }
+        if let (true, Some(ref index)) = (simple && (bd_fits(8) || !self.full_format()), &indexed.index) {
+            if index.scale != 1 && !self.scale() {
+                return Err("index scaling needs a 68020".to_string());
+            }
+            let disp = match indexed.bd {
+                Some(ref bd) => adjust(self.value(bd)?),
+                None => 0,
+            };
+            self.check(fits_signed(disp, 8), "displacement out of range")?;
+            self.word(index.reg << 12 | (index.long as u16) << 11 | index_scale(index.scale) << 9 | disp as u8 as u16);
+            return Ok(index_mode);
+        }
+        if !self.full_format() {
+            return Err("that addressing mode needs a 68020".to_string());
+        }
+        let mut extension = 0x100;
+        match indexed.index {
+            Some(ref index) => extension |= index.reg << 12 | (index.long as u16) << 11 | index_scale(index.scale) << 9,
+            None => extension |= 0x40,
+        }
+        if matches!(indexed.base, Base::None | Base::SuppressedPc) {
+            extension |= 0x80;
+        }
+        let size = |expr: &Option<Expr>, adjust: &dyn Fn(i64) -> i64| match *expr {
+            None => 1,
+            Some(ref e) => match self.sizing(e) {
+                Some(v) if fits_signed(adjust(v), 16) => 2,
+                _ => 3,
+            },
+        };
+        let bd_size = size(&indexed.bd, &adjust);
+        let od_size = size(&indexed.od, &|v| v);
+        extension |= bd_size << 4;
+        extension |= match indexed.indirect {
+            Indirect::None if indexed.od.is_some() => return Err("outer displacement without memory indirection".to_string()),
+            Indirect::None => 0,
+            Indirect::Pre => od_size,
+            Indirect::Post => 4 | od_size,
+        };
+        self.word(extension);
+        for &(expr, size, pc) in [(&indexed.bd, bd_size, pc_relative), (&indexed.od, od_size, false)].iter() {
+            if let Some(ref expr) = *expr {
+                let value = self.value(expr)?;
+                let value = if pc { (value as u32).wrapping_sub(at) as i32 as i64 } else { value };
+                if size == 2 {
+                    self.check(fits_signed(value, 16), "displacement out of range")?;
+                    self.word(value as u16);
+                } else {
+                    self.long(value as u32);
+                }
+            }
+        }
+        Ok(index_mode)
+    }
+
+    // a branch displacement from the word after the opcode
+    fn target<'o>(&self, op: &'o Operand) -> Result<(&'o Expr, i64), String> {
+        match *op {
+            Operand::Absolute(ref e, None) => Ok((e, self.displacement(self.value(e)?, self.pc.wrapping_add(2)))),
+            _ => Err("expects a target address".to_string()),
+        }
+    }
+
+    fn instruction(&mut self, name: &str, size: Option<char>, ops: &[Operand]) -> Result<u16, String> {
+        match name {
+            "ori" | "andi" | "subi" | "addi" | "eori" | "cmpi" => self.immediate_op(name, size, ops),
+            "or" | "and" | "sub" | "add" | "cmp" | "eor"
+                if ops.len() == 2 && matches!(ops[0], Operand::Immediate(_))
+                    && (name == "eor" || !matches!(ops[1], Operand::Data(_) | Operand::Address(_))) => {
+                self.immediate_op(&format!("{}i", name), size, ops)
+            },
+            "btst" | "bchg" | "bclr" | "bset" => {
+                operands(ops, 2)?;
+                let kind = ["btst", "bchg", "bclr", "bset"].iter().position(|&n| n == name).unwrap() as u16;
+                match ops[0] {
+                    Operand::Data(r) => Ok(0x0100 | r << 9 | kind << 6 | self.ea(&ops[1], 1)?),
+                    Operand::Immediate(ref e) => {
+                        let bit = self.value(e)?;
+                        self.check((0..256).contains(&bit), "bit number out of range")?;
+                        self.word(bit as u16);
+                        Ok(0x0800 | kind << 6 | self.ea(&ops[1], 1)?)
+                    },
+                    _ => Err("the bit number is a data register or an immediate".to_string()),
+                }
+            },
+            "movep" => {
+                operands(ops, 2)?;
+                let long = size_bytes(size, 2)? == 4;
+                let (dn, memory, to_memory) = match (&ops[0], &ops[1]) {
+                    (&Operand::Data(r), memory) => (r, memory, true),
+                    (memory, &Operand::Data(r)) => (r, memory, false),
+                    _ => return Err("MOVEP is between a data register and memory".to_string()),
+                };
+                let (ay, disp) = match *memory {
+                    Operand::Indirect(r) => (r, 0),
+                    Operand::Indexed(Indexed { base: Base::Address(r), bd: Some(ref bd), index: None, indirect: Indirect::None, .. }) => {
+                        (r, self.value(bd)?)
+                    },
+                    _ => return Err("MOVEP needs (d16,An)".to_string()),
+                };
+                self.check(fits_signed(disp, 16), "displacement out of range")?;
+                self.word(disp as u16);
+                Ok(0x0108 | dn << 9 | (4 | (long as u16) | (to_memory as u16) << 1) << 6 | ay)
+            },
+            "moves" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                let (reg, ea, to_memory) = match any_register(&ops[0]) {
+                    Ok(r) if !matches!(ops[1], Operand::Data(_) | Operand::Address(_)) => (r, &ops[1], true),
+                    _ => (any_register(&ops[1])?, &ops[0], false),
+                };
+                self.word(reg << 12 | (to_memory as u16) << 11);
+                Ok(0x0e00 | size_code(bytes) << 6 | self.ea(ea, bytes)?)
+            },
+            "chk2" | "cmp2" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                self.word(any_register(&ops[1])? << 12 | if name == "chk2" { 0x800 } else { 0 });
+                Ok(size_code(bytes) << 9 | 0xc0 | self.ea(&ops[0], bytes)?)
+            },
+            "callm" => {
+                operands(ops, 2)?;
+                let arguments = self.value(immediate(&ops[0])?)?;
+                self.check((0..256).contains(&arguments), "argument count out of range")?;
+                self.word(arguments as u16);
+                Ok(0x06c0 | self.ea(&ops[1], 0)?)
+            },
+            "rtm" => {
+                operands(ops, 1)?;
+                Ok(0x06c0 | any_register(&ops[0])?)
+            },
+            "cas" => {
+                operands(ops, 3)?;
+                let bytes = size_bytes(size, 2)?;
+                self.word(data(&ops[1])? << 6 | data(&ops[0])?);
+                Ok(0x08c0 | (size_code(bytes) + 1) << 9 | self.ea(&ops[2], bytes)?)
+            },
+            "cas2" => {
+                operands(ops, 3)?;
+                let bytes = size_bytes(size, 2)?;
+                let pair = |op: &Operand| match *op {
+                    Operand::Pair(ref a, ref b) => Ok(((**a).clone(), (**b).clone())),
+                    _ => Err("CAS2 takes pairs of operands".to_string()),
+                };
+                let (dc1, dc2) = pair(&ops[0])?;
+                let (du1, du2) = pair(&ops[1])?;
+                let (rn1, rn2) = pair(&ops[2])?;
+                let register = |op: &Operand| match *op {
+                    Operand::Indirect(r) => Ok(8 + r),
+                    Operand::Indexed(Indexed { base: Base::None, bd: None, index: Some(ref index), indirect: Indirect::None, .. })
+                        if index.scale == 1 && !index.long => Ok(index.reg),
+                    _ => Err("CAS2 addresses are (Rn)".to_string()),
+                };
+                self.word(register(&rn1)? << 12 | data(&du1)? << 6 | data(&dc1)?);
+                self.word(register(&rn2)? << 12 | data(&du2)? << 6 | data(&dc2)?);
+                Ok(if bytes == 4 { 0x0efc } else { 0x0cfc })
+            },
+            "bitrev" | "byterev" | "ff1" => {
+                operands(ops, 1)?;
+                let op = match name {
+                    "bitrev" => 0x00c0,
+                    "byterev" => 0x02c0,
+                    _ => 0x04c0,
+                };
+                Ok(op | data(&ops[0])?)
+            },
+            "move" | "movea" => self.move_op(size, ops),
+            "moveq" => {
+                operands(ops, 2)?;
+                let value = self.value(immediate(&ops[0])?)?;
+                self.check(fits(value, 8), "MOVEQ data out of range")?;
+                Ok(0x7000 | data(&ops[1])? << 9 | value as u8 as u16)
+            },
+            "mov3q" => {
+                operands(ops, 2)?;
+                let value = self.value(immediate(&ops[0])?)?;
+                self.check(value == -1 || (1..8).contains(&value), "MOV3Q data is -1 or 1 to 7")?;
+                let data = if value == -1 { 0 } else { value as u16 & 7 };
+                Ok(0xa140 | data << 9 | self.ea(&ops[1], 4)?)
+            },
+            "mvs" | "mvz" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                let dn = data(&ops[1])?;
+                let ea = self.ea(&ops[0], bytes)?;
+                Ok(0x7100 | dn << 9 | if name == "mvz" { 0x80 } else { 0 } | if bytes == 2 { 0x40 } else { 0 } | ea)
+            },
+            "movem" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                // a mask can also be given as an immediate
+                let list = |op: &Operand| match *op {
+                    Operand::List(mask) => Some(mask),
+                    Operand::Immediate(ref e) => self.value(e).ok().map(|mask| mask as u16),
+                    Operand::Data(r) => Some(1 << r),
+                    Operand::Address(r) => Some(1 << (8 + r)),
+                    _ => None,
+                };
+                let long = if bytes == 4 { 0x40 } else { 0 };
+                match (list(&ops[0]), list(&ops[1])) {
+                    (Some(mask), _) if list(&ops[1]).is_none() => {
+                        let mask = if let Operand::PreDec(_) = ops[1] { mask.reverse_bits() } else { mask };
+                        self.word(mask);
+                        Ok(0x4880 | long | self.ea(&ops[1], bytes)?)
+                    },
+                    (None, Some(mask)) => {
+                        self.word(mask);
+                        Ok(0x4c80 | long | self.ea(&ops[0], bytes)?)
+                    },
+                    _ => Err("MOVEM is between a register list and memory".to_string()),
+                }
+            },
+            "movec" => {
+                operands(ops, 2)?;
+                // registers without a name are given by number
+                let cr = |op: &Operand| match *op {
+                    Operand::Absolute(ref e, None) => self.value(e).ok().filter(|&cr| (0..0x1000).contains(&cr)).map(|cr| cr as u16),
+                    _ => special(op).and_then(control_register),
+                };
+                match (cr(&ops[0]), cr(&ops[1])) {
+                    (Some(cr), None) => {
+                        self.word(any_register(&ops[1])? << 12 | cr);
+                        Ok(0x4e7a)
+                    },
+                    (None, Some(cr)) => {
+                        self.word(any_register(&ops[0])? << 12 | cr);
+                        Ok(0x4e7b)
+                    },
+                    _ => Err("MOVEC is between a register and a control register".to_string()),
+                }
+            },
+            "negx" | "clr" | "neg" | "not" | "tst" => {
+                operands(ops, 1)?;
+                let bytes = size_bytes(size, 2)?;
+                let op = match name {
+                    "negx" => 0x4000,
+                    "clr" => 0x4200,
+                    "neg" => 0x4400,
+                    "not" => 0x4600,
+                    _ => 0x4a00,
+                };
+                Ok(op | size_code(bytes) << 6 | self.ea(&ops[0], bytes)?)
+            },
+            "nbcd" | "tas" | "pea" | "jmp" | "jsr" => {
+                operands(ops, 1)?;
+                let (op, bytes) = match name {
+                    "nbcd" => (0x4800, 1),
+                    "tas" => (0x4ac0, 1),
+                    "pea" => (0x4840, 4),
+                    "jmp" => (0x4ec0, 4),
+                    _ => (0x4e80, 4),
+                };
+                Ok(op | self.ea(&ops[0], bytes)?)
+            },
+            "swap" => {
+                operands(ops, 1)?;
+                Ok(0x4840 | data(&ops[0])?)
+            },
+            "ext" => {
+                operands(ops, 1)?;
+                Ok(if size_bytes(size, 2)? == 4 { 0x48c0 } else { 0x4880 } | data(&ops[0])?)
+            },
+            "extb" => {
+                operands(ops, 1)?;
+                Ok(0x49c0 | data(&ops[0])?)
+            },
+            "lea" => {
+                operands(ops, 2)?;
+                let an = address(&ops[1])?;
+                Ok(0x41c0 | an << 9 | self.ea(&ops[0], 4)?)
+            },
+            "chk" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                let dn = data(&ops[1])?;
+                Ok(if bytes == 4 { 0x4100 } else { 0x4180 } | dn << 9 | self.ea(&ops[0], bytes)?)
+            },
+            "link" => {
+                operands(ops, 2)?;
+                let an = address(&ops[0])?;
+                let disp = self.value(immediate(&ops[1])?)?;
+                if size_bytes(size, 2)? == 4 {
+                    self.long(disp as u32);
+                    Ok(0x4808 | an)
+                } else {
+                    self.check(fits_signed(disp, 16), "displacement out of range")?;
+                    self.word(disp as u16);
+                    Ok(0x4e50 | an)
+                }
+            },
+            "unlk" => {
+                operands(ops, 1)?;
+                Ok(0x4e58 | address(&ops[0])?)
+            },
+            "trap" | "bkpt" => {
+                operands(ops, 1)?;
+                let value = self.value(immediate(&ops[0])?)?;
+                let (op, count) = if name == "trap" { (0x4e40, 16) } else { (0x4848, 8) };
+                self.check((0..count).contains(&value), "vector out of range")?;
+                Ok(op | value as u16)
+            },
+            "stop" | "rtd" | "lpstop" => {
+                operands(ops, 1)?;
+                if name == "lpstop" {
+                    self.word(0x01c0);
+                }
+                let value = self.value(immediate(&ops[0])?)?;
+                self.check(fits(value, 16), "immediate out of range for a word")?;
+                self.word(value as u16);
+                Ok(match name {
+                    "stop" => 0x4e72,
+                    "rtd" => 0x4e74,
+                    _ => 0xf800,
+                })
+            },
+            "reset" | "nop" | "rte" | "rts" | "trapv" | "rtr" | "illegal" | "bgnd" | "pflushan" => {
+                operands(ops, 0)?;
+                Ok(match name {
+                    "reset" => 0x4e70,
+                    "nop" => 0x4e71,
+                    "rte" => 0x4e73,
+                    "rts" => 0x4e75,
+                    "trapv" => 0x4e76,
+                    "rtr" => 0x4e77,
+                    "illegal" => 0x4afc,
+                    "bgnd" => 0x4afa,
+                    _ => 0xf510,
+                })
+            },
+            "muls" | "mulu" | "divs" | "divu" if size != Some('l') => {
+                operands(ops, 2)?;
+                let dn = data(&ops[1])?;
+                let op = match name {
+                    "muls" => 0xc1c0,
+                    "mulu" => 0xc0c0,
+                    "divs" => 0x81c0,
+                    _ => 0x80c0,
+                };
+                Ok(op | dn << 9 | self.ea(&ops[0], 2)?)
+            },
+            "muls" | "mulu" | "divs" | "divu" | "divsl" | "divul" | "rems" | "remu" => {
+                operands(ops, 2)?;
+                let signed = if name.as_bytes()[name.len() - 1] == b's' || name == "divsl" { 0x800 } else { 0 };
+                let extension = match (name, &ops[1]) {
+                    (_, &Operand::Data(low)) if !name.starts_with("rem") => {
+                        low << 12 | if name.starts_with("div") { low } else { 0 }
+                    },
+                    ("muls", &Operand::Pair(ref high, ref low)) | ("mulu", &Operand::Pair(ref high, ref low)) |
+                    ("divs", &Operand::Pair(ref high, ref low)) | ("divu", &Operand::Pair(ref high, ref low)) => {
+                        data(low)? << 12 | 0x400 | data(high)?
+                    },
+                    (_, Operand::Pair(high, low)) => data(low)? << 12 | data(high)?,
+                    _ => return Err("expects a data register or a pair".to_string()),
+                };
+                self.word(extension | signed);
+                Ok(if name.starts_with("mul") { 0x4c00 } else { 0x4c40 } | self.ea(&ops[0], 4)?)
+            },
+            "sats" => {
+                operands(ops, 1)?;
+                Ok(0x4c80 | data(&ops[0])?)
+            },
+            "addq" | "subq" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                let value = self.value(immediate(&ops[0])?)?;
+                self.check((1..=8).contains(&value), "quick data is 1 to 8")?;
+                let op = if name == "subq" { 0x5100 } else { 0x5000 };
+                Ok(op | (value as u16 & 7) << 9 | size_code(bytes) << 6 | self.ea(&ops[1], bytes)?)
+            },
+            "bra" | "bsr" => self.branch(if name == "bra" { IF_T } else { IF_F } as u16, size, ops),
+            "or" | "and" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                let op = if name == "or" { 0x8000 } else { 0xc000 };
+                match (&ops[0], &ops[1]) {
+                    (src, &Operand::Data(r)) => Ok(op | r << 9 | size_code(bytes) << 6 | self.ea(src, bytes)?),
+                    (&Operand::Data(r), dst) => Ok(op | r << 9 | (4 + size_code(bytes)) << 6 | self.ea(dst, bytes)?),
+                    _ => Err("one operand has to be a data register".to_string()),
+                }
+            },
+            "eor" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                let r = data(&ops[0])?;
+                Ok(0xb100 | r << 9 | size_code(bytes) << 6 | self.ea(&ops[1], bytes)?)
+            },
+            "add" | "sub" | "adda" | "suba" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                let op = if name.starts_with("add") { 0xd000 } else { 0x9000 };
+                match (&ops[0], &ops[1]) {
+                    (src, &Operand::Address(r)) => {
+                        let opmode = if bytes == 4 { 7 } else { 3 };
+                        Ok(op | r << 9 | opmode << 6 | self.ea(src, bytes)?)
+                    },
+                    _ if name.ends_with('a') => Err("expects an address register".to_string()),
+                    (src, &Operand::Data(r)) => Ok(op | r << 9 | size_code(bytes) << 6 | self.ea(src, bytes)?),
+                    (&Operand::Data(r), dst) => Ok(op | r << 9 | (4 + size_code(bytes)) << 6 | self.ea(dst, bytes)?),
+                    _ => Err("one operand has to be a register".to_string()),
+                }
+            },
+            "cmp" | "cmpa" | "cmpm" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                match (&ops[0], &ops[1]) {
+                    (&Operand::PostInc(y), &Operand::PostInc(x)) => Ok(0xb108 | x << 9 | size_code(bytes) << 6 | y),
+                    (src, &Operand::Address(r)) => {
+                        let opmode = if bytes == 4 { 7 } else { 3 };
+                        Ok(0xb000 | r << 9 | opmode << 6 | self.ea(src, bytes)?)
+                    },
+                    (src, &Operand::Data(r)) if name == "cmp" => Ok(0xb000 | r << 9 | size_code(bytes) << 6 | self.ea(src, bytes)?),
+                    _ => Err("can't compare those".to_string()),
+                }
+            },
+            "addx" | "subx" | "abcd" | "sbcd" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, if name.ends_with('x') { 2 } else { 1 })?;
+                let op = match name {
+                    "addx" => 0xd100,
+                    "subx" => 0x9100,
+                    "abcd" => 0xc100,
+                    _ => 0x8100,
+                };
+                let size = if name.ends_with('x') { size_code(bytes) << 6 } else { 0 };
+                match (&ops[0], &ops[1]) {
+                    (&Operand::Data(y), &Operand::Data(x)) => Ok(op | x << 9 | size | y),
+                    (&Operand::PreDec(y), &Operand::PreDec(x)) => Ok(op | x << 9 | size | 8 | y),
+                    _ => Err("expects Dy,Dx or -(Ay),-(Ax)".to_string()),
+                }
+            },
+            "pack" | "unpk" => {
+                operands(ops, 3)?;
+                let op = if name == "pack" { 0x8140 } else { 0x8180 };
+                let op = match (&ops[0], &ops[1]) {
+                    (&Operand::Data(y), &Operand::Data(x)) => op | x << 9 | y,
+                    (&Operand::PreDec(y), &Operand::PreDec(x)) => op | x << 9 | 8 | y,
+                    _ => return Err("expects Dy,Dx or -(Ay),-(Ax)".to_string()),
+                };
+                let adjustment = self.value(immediate(&ops[2])?)?;
+                self.check(fits(adjustment, 16), "adjustment out of range")?;
+                self.word(adjustment as u16);
+                Ok(op)
+            },
+            "exg" => {
+                operands(ops, 2)?;
+                match (&ops[0], &ops[1]) {
+                    (&Operand::Data(x), &Operand::Data(y)) => Ok(0xc140 | x << 9 | y),
+                    (&Operand::Address(x), &Operand::Address(y)) => Ok(0xc148 | x << 9 | y),
+                    (&Operand::Data(x), &Operand::Address(y)) | (&Operand::Address(y), &Operand::Data(x)) => Ok(0xc188 | x << 9 | y),
+                    _ => Err("EXG is between registers".to_string()),
+                }
+            },
+            "asl" | "asr" | "lsl" | "lsr" | "roxl" | "roxr" | "rol" | "ror" => {
+                let kind = ["as", "ls", "rox", "ro"].iter().position(|&n| n == &name[..name.len() - 1]).unwrap() as u16;
+                let left = if name.ends_with('l') { 0x100 } else { 0 };
+                match ops.len() {
+                    1 if !matches!(ops[0], Operand::Data(_)) => {
+                        if size_bytes(size, 2)? != 2 {
+                            return Err("memory shifts are word sized".to_string());
+                        }
+                        Ok(0xe0c0 | kind << 9 | left | self.ea(&ops[0], 2)?)
+                    },
+                    1 => {
+                        let bytes = size_bytes(size, 2)?;
+                        Ok(0xe000 | 1 << 9 | left | size_code(bytes) << 6 | kind << 3 | data(&ops[0])?)
+                    },
+                    _ => {
+                        operands(ops, 2)?;
+                        let bytes = size_bytes(size, 2)?;
+                        let count = match ops[0] {
+                            Operand::Data(r) => r << 9 | 0x20,
+                            Operand::Immediate(ref e) => {
+                                let count = self.value(e)?;
+                                self.check((1..=8).contains(&count), "shift count is 1 to 8")?;
+                                (count as u16 & 7) << 9
+                            },
+                            _ => return Err("the count is a data register or an immediate".to_string()),
+                        };
+                        Ok(0xe000 | count | left | size_code(bytes) << 6 | kind << 3 | data(&ops[1])?)
+                    },
+                }
+            },
+            "bftst" | "bfextu" | "bfchg" | "bfexts" | "bfclr" | "bfffo" | "bfset" | "bfins" => {
+                let kind = ["bftst", "bfextu", "bfchg", "bfexts", "bfclr", "bfffo", "bfset", "bfins"]
+                    .iter().position(|&n| n == name).unwrap() as u16;
+                let (field, dn) = match kind {
+                    1 | 3 | 5 => {
+                        operands(ops, 2)?;
+                        (&ops[0], data(&ops[1])?)
+                    },
+                    7 => {
+                        operands(ops, 2)?;
+                        (&ops[1], data(&ops[0])?)
+                    },
+                    _ => {
+                        operands(ops, 1)?;
+                        (&ops[0], 0)
+                    },
+                };
+                let (ea, offset, width) = match *field {
+                    Operand::Field(ref ea, ref offset, ref width) => (ea, offset, width),
+                    _ => return Err("expects a bit field, <ea>{offset:width}".to_string()),
+                };
+                let offset = match **offset {
+                    Operand::Data(r) => 0x800 | r << 6,
+                    Operand::Absolute(ref e, _) => {
+                        let offset = self.value(e)?;
+                        self.check((0..32).contains(&offset), "bit field offset is 0 to 31")?;
+                        (offset as u16) << 6
+                    },
+                    _ => unreachable!(),
+                };
+                let width = match **width {
+                    Operand::Data(r) => 0x20 | r,
+                    Operand::Absolute(ref e, _) => {
+                        let width = self.value(e)?;
+                        self.check((1..=32).contains(&width), "bit field width is 1 to 32")?;
+                        width as u16 & 0x1f
+                    },
+                    _ => unreachable!(),
+                };
+                self.word(dn << 12 | offset | width);
+                Ok(0xe8c0 | kind << 8 | self.ea(ea, 0)?)
+            },
+            "cinvl" | "cinvp" | "cinva" | "cpushl" | "cpushp" | "cpusha" => {
+                let caches = ops.first().and_then(special).and_then(|name| ["nc", "dc", "ic", "bc"].iter().position(|&c| c == name))
+                    .ok_or("expects nc, dc, ic or bc")? as u16;
+                let push = if name.starts_with("cpush") { 0x20 } else { 0 };
+                let (scope, r) = match name.as_bytes()[name.len() - 1] {
+                    b'a' => {
+                        operands(ops, 1)?;
+                        (3, 0)
+                    },
+                    scope => {
+                        operands(ops, 2)?;
+                        match ops[1] {
+                            Operand::Indirect(r) => (if scope == b'l' { 1 } else { 2 }, r),
+                            _ => return Err("expects (An)".to_string()),
+                        }
+                    },
+                };
+                Ok(0xf400 | caches << 6 | push | scope << 3 | r)
+            },
+            "pflushn" | "plpaw" | "plpar" => {
+                operands(ops, 1)?;
+                let r = match ops[0] {
+                    Operand::Indirect(r) => r,
+                    _ => return Err("expects (An)".to_string()),
+                };
+                Ok(match name {
+                    "pflushn" => 0xf500,
+                    "plpaw" => 0xf588,
+                    _ => 0xf5c8,
+                } | r)
+            },
+            "pflush" | "ptestw" | "ptestr" if self.version().base() != Version::MC68020 => {
+                operands(ops, 1)?;
+                let r = match ops[0] {
+                    Operand::Indirect(r) => r,
+                    _ => return Err("expects (An)".to_string()),
+                };
+                Ok(match name {
+                    "pflush" => 0xf508,
+                    "ptestw" => 0xf548,
+                    _ => 0xf568,
+                } | r)
+            },
+            "pflusha" if self.version().base() != Version::MC68020 => {
+                operands(ops, 0)?;
+                Ok(0xf518)
+            },
+            "pflusha" | "pflush" | "pflushs" | "ploadr" | "ploadw" | "pvalid" | "pmove" | "pmovefd"
+                | "ptestr" | "ptestw" | "pflushr" => self.pmmu(name, ops),
+            "psave" | "prestore" => {
+                operands(ops, 1)?;
+                Ok(if name == "psave" { 0xf100 } else { 0xf140 } | self.ea(&ops[0], 0)?)
+            },
+            "move16" => {
+                operands(ops, 2)?;
+                let address = |op: &Operand| match *op {
+                    Operand::Absolute(ref e, ref size) if *size != Some('w') => Some(e.clone()),
+                    _ => None,
+                };
+                let (op, r, absolute) = match (&ops[0], &ops[1]) {
+                    (&Operand::PostInc(x), &Operand::PostInc(y)) => {
+                        self.word(0x8000 | y << 12);
+                        return Ok(0xf620 | x);
+                    },
+                    (&Operand::PostInc(y), dst) => (0xf600, y, address(dst)),
+                    (src, &Operand::PostInc(y)) => (0xf608, y, address(src)),
+                    (&Operand::Indirect(y), dst) => (0xf610, y, address(dst)),
+                    (src, &Operand::Indirect(y)) => (0xf618, y, address(src)),
+                    _ => (0, 0, None),
+                };
+                let absolute = absolute.ok_or("MOVE16 is between (An)+ or (An) and an absolute long address")?;
+                let value = self.value(&absolute)?;
+                self.long(value as u32);
+                Ok(op | r)
+            },
+            "tbls" | "tblu" | "tblsn" | "tblun" => {
+                operands(ops, 2)?;
+                let bytes = size_bytes(size, 2)?;
+                let mut extension = data(&ops[1])? << 12 | size_code(bytes) << 6;
+                if name.as_bytes()[3] == b's' {
+                    extension |= 0x800;
+                }
+                if name.ends_with('n') {
+                    extension |= 0x400;
+                }
+                if let Operand::Pair(ref m, ref n) = ops[0] {
+                    self.word(extension | data(n)?);
+                    return Ok(0xf800 | data(m)?);
+                }
+                self.word(extension);
+                Ok(0xf800 | self.ea(&ops[0], bytes)?)
+            },
+            "mac" | "msac" => self.mac(name, size, ops),
+            "movclr" => {
+                operands(ops, 2)?;
+                match mac_register(&ops[0]) {
+                    Some(acc) if acc < 4 => Ok(0xa1c0 | acc << 9 | any_register(&ops[1])?),
+                    _ => Err("MOVCLR is from an accumulator".to_string()),
+                }
+            },
+            _ => self.conditional(name, size, ops),
+        }
+    }
+
+    fn immediate_op(&mut self, name: &str, size: Option<char>, ops: &[Operand]) -> Result<u16, String> {
+        operands(ops, 2)?;
+        let kind = match name {
+            "ori" => 0,
+            "andi" => 1,
+            "subi" => 2,
+            "addi" => 3,
+            "eori" => 5,
+            _ => 6,
+        };
+        let expr = immediate(&ops[0])?;
+        match special(&ops[1]) {
+            Some("ccr") if kind <= 1 || kind == 5 => {
+                self.imm(expr, 1)?;
+                return Ok(kind << 9 | 0x3c);
+            },
+            Some("sr") if kind <= 1 || kind == 5 => {
+                self.imm(expr, 2)?;
+                return Ok(kind << 9 | 0x7c);
+            },
+            _ => (),
+        }
+        let bytes = size_bytes(size, 2)?;
+        self.imm(expr, bytes)?;
+        Ok(kind << 9 | size_code(bytes) << 6 | self.ea(&ops[1], bytes)?)
+    }
+
+    fn move_op(&mut self, size: Option<char>, ops: &[Operand]) -> Result<u16, String> {
+        operands(ops, 2)?;
+        let (src, dst) = (&ops[0], &ops[1]);
+        match (special(src), special(dst)) {
+            (Some("macsr"), Some("ccr")) => return Ok(0xa9c0),
+            (Some("sr"), _) => return Ok(0x40c0 | self.ea(dst, 2)?),
+            (Some("ccr"), _) => return Ok(0x42c0 | self.ea(dst, 2)?),
+            (_, Some("ccr")) => return Ok(0x44c0 | self.ea(src, 2)?),
+            (_, Some("sr")) => return Ok(0x46c0 | self.ea(src, 2)?),
+            (_, Some("usp")) => return Ok(0x4e60 | address(src)?),
+            (Some("usp"), _) => return Ok(0x4e68 | address(dst)?),
+            _ => (),
+        }
+        match (mac_register(src), mac_register(dst)) {
+            (Some(y), Some(x)) if x < 4 && y < 4 => return Ok(0xa110 | x << 9 | y),
+            (_, Some(target)) => return Ok(0xa100 | target << 9 | self.ea(src, 4)?),
+            (Some(source), _) => return Ok(0xa180 | source << 9 | any_register(dst)?),
+            _ => (),
+        }
+        let bytes = size_bytes(size, 2)?;
+        let op = match bytes {
+            1 => 0x1000,
+            2 => 0x3000,
+            _ => 0x2000,
+        };
+        let src = self.ea(src, bytes)?;
+        let dst = self.ea(dst, bytes)?;
+        Ok(op | (dst & 7) << 9 | (dst >> 3) << 6 | src)
+    }
+
+    fn branch(&mut self, cc: u16, size: Option<char>, ops: &[Operand]) -> Result<u16, String> {
+        operands(ops, 1)?;
+        let (expr, disp) = self.target(&ops[0])?;
+        // a displacement of -1 is the long form where there is one
+        let long_branch = self.long_branch();
+        let short = |disp: i64| fits_signed(disp, 8) && disp != 0 && (disp != -1 || !long_branch);
+        let size = match size {
+            Some('s') | Some('b') => 's',
+            Some('w') => 'w',
+            Some('l') => 'l',
+            None => {
+                let next = self.pc.wrapping_add(2);
+                match self.sizing(expr) {
+                    Some(target) if short(self.displacement(target, next)) => 's',
+                    _ => 'w',
+                }
+            },
+            Some(c) => return Err(format!("no .{} size here", c)),
+        };
+        Ok(match size {
+            's' => {
+                self.check(short(disp), "short branch out of range")?;
+                0x6000 | cc | disp as u8 as u16
+            },
+            'w' => {
+                self.check(fits_signed(disp, 16), "branch out of range")?;
+                self.word(disp as u16);
+                0x6000 | cc
+            },
+            _ => {
+                self.long(disp as u32);
+                0x60ff | cc
+            },
+        })
+    }
+
+    // Bcc, DBcc, Scc, TRAPcc and the 68851 PBcc, PDBcc, PScc and PTRAPcc
+    fn conditional(&mut self, name: &str, size: Option<char>, ops: &[Operand]) -> Result<u16, String> {
+        let family = |prefix: &str| name.strip_prefix(prefix);
+        if let Some(cc) = family("b").and_then(condition).filter(|&cc| cc > IF_F as u16) {
+            return self.branch(cc, size, ops);
+        }
+        if let Some(cc) = family("db").and_then(|cc| if cc == "ra" { Some(IF_F as u16) } else { condition(cc) }) {
+            operands(ops, 2)?;
+            let dn = data(&ops[0])?;
+            let (_, disp) = self.target(&ops[1])?;
+            self.check(fits_signed(disp, 16), "branch out of range")?;
+            self.word(disp as u16);
+            return Ok(0x50c8 | cc | dn);
+        }
+        if let Some(cc) = family("trap").and_then(condition) {
+            return match size {
+                None if ops.is_empty() => Ok(0x50fc | cc),
+                _ => {
+                    operands(ops, 1)?;
+                    let bytes = size_bytes(size, 2)?;
+                    self.imm(immediate(&ops[0])?, bytes)?;
+                    Ok(if bytes == 4 { 0x50fb } else { 0x50fa } | cc)
+                },
+            };
+        }
+        if let Some(cc) = family("s").and_then(condition) {
+            operands(ops, 1)?;
+            return Ok(0x50c0 | cc | self.ea(&ops[0], 1)?);
+        }
+        if let Some(cc) = family("pb").and_then(pmmu_condition) {
+            operands(ops, 1)?;
+            let (_, disp) = self.target(&ops[0])?;
+            if size == Some('l') {
+                self.long(disp as u32);
+                return Ok(0xf0c0 | cc);
+            }
+            self.check(fits_signed(disp, 16), "branch out of range")?;
+            self.word(disp as u16);
+            return Ok(0xf080 | cc);
+        }
+        if let Some(cc) = family("pdb").and_then(pmmu_condition) {
+            operands(ops, 2)?;
+            let dn = data(&ops[0])?;
+            self.word(cc);
+            let target = match ops[1] {
+                Operand::Absolute(ref e, None) => self.value(e)?,
+                _ => return Err("expects a target address".to_string()),
+            };
+            let disp = self.displacement(target, self.here());
+            self.check(fits_signed(disp, 16), "branch out of range")?;
+            self.word(disp as u16);
+            return Ok(0xf048 | dn);
+        }
+        if let Some(cc) = family("ptrap").and_then(pmmu_condition) {
+            self.word(cc);
+            return match size {
+                None if ops.is_empty() => Ok(0xf07c),
+                _ => {
+                    operands(ops, 1)?;
+                    let bytes = size_bytes(size, 2)?;
+                    self.imm(immediate(&ops[0])?, bytes)?;
+                    Ok(if bytes == 4 { 0xf07b } else { 0xf07a })
+                },
+            };
+        }
+        if let Some(cc) = family("ps").and_then(pmmu_condition) {
+            operands(ops, 1)?;
+            self.word(cc);
+            return Ok(0xf040 | self.ea(&ops[0], 1)?);
+        }
+        Err(format!("unknown instruction `{}`", name))
+    }
+
+    fn function_code(&self, op: &Operand) -> Result<u16, String> {
+        match *op {
+            Operand::Special(ref name) if name == "sfc" => Ok(0),
+            Operand::Special(ref name) if name == "dfc" => Ok(1),
+            Operand::Data(r) => Ok(0x08 | r),
+            Operand::Immediate(ref e) => {
+                let fc = self.value(e)?;
+                self.check((0..16).contains(&fc), "function code is 0 to 15")?;
+                Ok(0x10 | fc as u16)
+            },
+            _ => Err("expects a function code, sfc, dfc, Dn or #n".to_string()),
+        }
+    }
+
+    // the 68851 general instructions, opcode $f000 and an extension word
+    fn pmmu(&mut self, name: &str, ops: &[Operand]) -> Result<u16, String> {
+        match name {
+            "pflusha" => {
+                operands(ops, 0)?;
+                self.word(0x2400);
+                Ok(0xf000)
+            },
+            "pflush" | "pflushs" => {
+                if ops.len() != 2 && ops.len() != 3 {
+                    return Err("expects fc,#mask or fc,#mask,<ea>".to_string());
+                }
+                let fc = self.function_code(&ops[0])?;
+                let mask = self.value(immediate(&ops[1])?)?;
+                self.check((0..16).contains(&mask), "mask is 0 to 15")?;
+                let mode = if ops.len() == 3 { 0b110 } else { 0b100 } | if name == "pflushs" { 1 } else { 0 };
+                self.word(0x2000 | mode << 10 | (mask as u16) << 5 | fc);
+                Ok(0xf000 | if ops.len() == 3 { self.ea(&ops[2], 4)? } else { 0 })
+            },
+            "ploadr" | "ploadw" => {
+                operands(ops, 2)?;
+                let fc = self.function_code(&ops[0])?;
+                self.word(0x2000 | if name == "ploadr" { 0x200 } else { 0 } | fc);
+                Ok(0xf000 | self.ea(&ops[1], 4)?)
+            },
+            "pvalid" => {
+                operands(ops, 2)?;
+                let extension = match ops[0] {
+                    Operand::Special(ref name) if name == "val" => 0x2800,
+                    Operand::Address(r) => 0x2c00 | r,
+                    _ => return Err("PVALID is against val or An".to_string()),
+                };
+                self.word(extension);
+                Ok(0xf000 | self.ea(&ops[1], 4)?)
+            },
+            "pmove" | "pmovefd" => {
+                operands(ops, 2)?;
+                let preg = |op: &Operand| -> Option<(u16, u32)> {
+                    let name = special(op)?;
+                    let numbered = |prefix: &str| name.strip_prefix(prefix).and_then(|n| n.parse::<u16>().ok()).filter(|&n| n < 8);
+                    Some(match name {
+                        "tc" => (0b010 << 3, 4),
+                        "drp" => (0b010 << 3 | 1, 8),
+                        "srp" => (0b010 << 3 | 2, 8),
+                        "crp" => (0b010 << 3 | 3, 8),
+                        "cal" => (0b010 << 3 | 4, 1),
+                        "val" => (0b010 << 3 | 5, 1),
+                        "scc" => (0b010 << 3 | 6, 1),
+                        "ac" => (0b010 << 3 | 7, 2),
+                        "psr" => (0b011 << 3, 2),
+                        "pcsr" => (0b011 << 3 | 1, 2),
+                        _ => match (numbered("bad"), numbered("bac")) {
+                            (Some(n), _) => (0b011 << 3 | 4 | n << 6, 2),
+                            (_, Some(n)) => (0b011 << 3 | 5 | n << 6, 2),
+                            _ => return None,
+                        },
+                    })
+                };
+                let fd = if name == "pmovefd" { 0x100 } else { 0 };
+                let (reg, ea, to_memory) = match (preg(&ops[0]), preg(&ops[1])) {
+                    (Some(reg), None) => (reg, &ops[1], 0x200),
+                    (None, Some(reg)) => (reg, &ops[0], 0),
+                    _ => return Err("PMOVE is between an MMU register and an effective address".to_string()),
+                };
+                let ((code, size), num) = ((reg.0 & 0x3f, reg.1), reg.0 >> 6);
+                self.word((code >> 3) << 13 | (code & 7) << 10 | to_memory | fd | num << 2);
+                Ok(0xf000 | self.ea(ea, size)?)
+            },
+            "ptestr" | "ptestw" => {
+                if ops.len() != 3 && ops.len() != 4 {
+                    return Err("expects fc,<ea>,#level or fc,<ea>,#level,An".to_string());
+                }
+                let fc = self.function_code(&ops[0])?;
+                let level = self.value(immediate(&ops[2])?)?;
+                self.check((0..8).contains(&level), "level is 0 to 7")?;
+                let an = match ops.get(3) {
+                    Some(op) => 0x100 | address(op)? << 5,
+                    None => 0,
+                };
+                self.word(0x8000 | (level as u16) << 10 | if name == "ptestr" { 0x200 } else { 0 } | an | fc);
+                Ok(0xf000 | self.ea(&ops[1], 4)?)
+            },
+            _ => {
+                operands(ops, 1)?;
+                self.word(0xa000);
+                Ok(0xf000 | self.ea(&ops[0], 8)?)
+            },
+        }
+    }
+
+    // ColdFire MAC and MSAC, Ry,Rx[,<< or >>][,<ea>[&],Rw],ACCx
+    fn mac(&mut self, name: &str, size: Option<char>, ops: &[Operand]) -> Result<u16, String> {
+        let long = size_bytes(size, 2)? == 4;
+        if ops.len() < 3 {
+            return Err("expects Ry,Rx and an accumulator".to_string());
+        }
+        let half = |op: &Operand| -> Result<(u16, bool), String> {
+            match *op {
+                Operand::Half(r, upper) if !long => Ok((r, upper)),
+                Operand::Data(_) | Operand::Address(_) if long => Ok((any_register(op)?, false)),
+                _ => Err(if long { "expects a register" } else { "expects Rn.u or Rn.l" }.to_string()),
+            }
+        };
+        let (ry, y_upper) = half(&ops[0])?;
+        let (rx, x_upper) = half(&ops[1])?;
+        let mut rest = &ops[2..];
+        let mut scale = 0;
+        if let Some(&Operand::Shift(left)) = rest.first() {
+            scale = if left { 1 } else { 3 };
+            rest = &rest[1..];
+        }
+        let acc = match rest.last().and_then(mac_register) {
+            Some(acc) if acc < 4 => acc,
+            _ => return Err("the last operand is the accumulator".to_string()),
+        };
+        let mut extension = if long { 0x800 } else { 0 } | scale << 9 | if name == "msac" { 0x100 } else { 0 }
+            | if x_upper { 0x80 } else { 0 } | if y_upper { 0x40 } else { 0 } | (acc >> 1) << 4;
+        match rest.len() {
+            1 => {
+                self.word(extension);
+                Ok(0xa000 | (rx & 7) << 9 | (rx & 8) << 3 | (acc & 1) << 7 | ry)
+            },
+            3 => {
+                let (ea, masked) = match rest[0] {
+                    Operand::Masked(ref ea) => (&**ea, true),
+                    ref ea => (ea, false),
+                };
+                let rw = any_register(&rest[1])?;
+                extension |= rx << 12 | ry | if masked { 0x20 } else { 0 };
+                self.word(extension);
+                Ok(0xa000 | (rw & 7) << 9 | (rw & 8) << 3 | (acc & 1) << 7 | self.ea(ea, 4)?)
+            },
+            _ => Err("expects Ry,Rx[,shift][,<ea>,Rw],ACCx".to_string()),
+        }
+    }
+}
+
+fn index_scale(scale: u16) -> u16 {
+    match scale {
+        1 => 0,
+        2 => 1,
+        4 => 2,
+        _ => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use disasm::disassemble_words;
+    use tests::small_map;
+    use {M68k, SUPERVISOR_DATA};
+
+    // Every opcode the disassembler prints has to assemble back to the same
+    // instruction where the core implements it, and be refused where the
+    // core would take the illegal instruction exception. Bits the encoding
+    // doesn't care about come back as zeros, so the words are compared by
+    // their disassembly.
+    #[test]
+    fn opcodes_match_the_handler_table() {
+        let versions = [Version::MC68000, Version::MC68010, Version::MC68020, Version::MC68040,
+                        Version::MC68060, Version::CPU32,
+                        Version::ColdFireIsaA, Version::ColdFireIsaB, Version::ColdFireIsaC];
+        let mut mismatches = Vec::new();
+        for &version in versions.iter() {
+            let implemented = implemented(version);
+            for op in 0..0x10000 {
+                let words = [op as u16, 0, 0, 0, 0, 0];
+                let decoded = disassemble_words(version, 0x1000, &words);
+                if decoded.mnemonic == "dc.w" {
+                    continue;
+                }
+                let source = format!(" {}", decoded);
+                let assembled = assemble_for(version, &implemented, 0x1000, &source).map(|assembly| assembly.words());
+                let ok = match assembled {
+                    Ok(ref assembled) => {
+                        let again = disassemble_words(version, 0x1000, assembled);
+                        implemented[op] && implemented[assembled[0] as usize]
+                            && again.len == decoded.len && again.to_string() == decoded.to_string()
+                    },
+                    Err(_) => !implemented[op],
+                };
+                if !ok {
+                    mismatches.push(format!("{:?} {:04x} `{}`: {:x?}", version, op, decoded, assembled));
+                }
+            }
+        }
+        assert!(mismatches.is_empty(), "{} mismatches:\n{}", mismatches.len(), mismatches.join("\n"));
+    }
+
+    // assembles `source` at $400, runs it with a0 = $1000, d1 = 3 and a
+    // couple of pointers in memory, and disassembles the words again
+    fn run(version: Version, source: &str) -> Result<(u32, String), Error> {
+        let words = assemble(version, 0x400, &format!(" {}", source))?.words();
+        let mut bus = small_map(0x8000, &words);
+        bus.poke_32(SUPERVISOR_DATA, 0x2000, 0x3000);
+        bus.poke_32(SUPERVISOR_DATA, 0x2018, 0x4000);
+        let mut cpu = M68k::new(version);
+        cpu.reset(&mut bus);
+        cpu.dar[8] = 0x1000;
+        cpu.dar[1] = 3;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x400 + 2 * words.len() as u32, "`{}` on the {:?}", source, version);
+        let text = disassemble_words(version, 0x400, &words).to_string();
+        Ok((cpu.dar[9], text.split_whitespace().collect::<Vec<_>>().join(" ")))
+    }
+
+    // The address the core works out has to be the one the source asked
+    // for, and come back out of the disassembler the same.
+    #[test]
+    fn assemble_execute_disassemble() {
+        let cases = [
+            ("lea ($4,a0,d1.w),a1", 0x1007, false, false),
+            ("lea ($4,a0,d1.l*4),a1", 0x1010, true, false),
+            ("lea ($412,pc,d1.l*2),a1", 0x418, true, false),
+            ("lea ($1234,a0,d1.l*2),a1", 0x223a, true, true),
+            ("lea ($123456,a0),a1", 0x124456, false, true),
+            ("lea ([$1000,a0],d1.w*8,$10),a1", 0x3028, true, true),
+            ("lea ([$1000,a0,d1.l*8]),a1", 0x4000, true, true),
+        ];
+        let versions = [Version::MC68000, Version::MC68010, Version::MC68020, Version::MC68040,
+                        Version::MC68060, Version::CPU32,
+                        Version::ColdFireIsaA, Version::ColdFireIsaB, Version::ColdFireIsaC];
+        for &version in versions.iter() {
+            let scale = version.base() != Version::MC68000 && version != Version::MC68010;
+            let full = matches!(version.base(), Version::MC68020 | Version::MC68040 | Version::MC68060);
+            for &(source, a1, scaled, full_format) in cases.iter() {
+                let has = (scale || !scaled) && (full || !full_format);
+                match run(version, source) {
+                    Ok((value, text)) => {
+                        assert!(has, "`{}` assembled for the {:?}", source, version);
+                        assert_eq!(value, a1, "`{}` on the {:?}", source, version);
+                        assert_eq!(text, source, "on the {:?}", version);
+                    },
+                    Err(e) => assert!(!has, "`{}` on the {:?}: {}", source, version, e.message),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,478 @@
+// Interactive debugger
+//
+// Loads a raw binary or an ELF executable into RAM and gives a command
+// prompt to step through it, set breakpoints, look at and change registers
+// and memory, and disassemble or assemble code in place.
+//
+//     m68k-dbg [--cpu 68000] [--ram 1000000] [--base 0] [--pc 400] image
+//
+// Numbers are hex, with or without a $ or 0x in front. A raw image goes at
+// --base and the core starts from the reset vectors at 0, an ELF one goes
+// where its program headers say and starts at its entry point. --pc sets
+// where to start either way. `help` at the prompt lists the commands.
+
+extern crate m68k;
+
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use m68k::asm::assemble;
+use m68k::disasm::disassemble_bus;
+use m68k::gdb::{self, GdbStub};
+use m68k::memory::MemoryMap;
+use m68k::trace::{Format, TraceWriter};
+use m68k::watch::{WatchKind, Watchpoint};
+use m68k::{Bus, M68k, ProcessingState, Version, SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA, USER_PROGRAM};
+
+const HISTORY: usize = 4;   // instructions shown before the pc
+
+// set by ^C to stop a running program rather than the debugger
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+fn catch_interrupt() {
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+    extern "C" fn interrupt(_: i32) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    const SIGINT: i32 = 2;
+    unsafe {
+        signal(SIGINT, interrupt);
+    }
+}
+
+#[cfg(not(unix))]
+fn catch_interrupt() {
+}
+
+const HELP: &str = "\
+s, step [n]            step n instructions
+n, next                step over a subroutine call or trap
+c, continue [n]        run until a breakpoint, a watchpoint, ^C or n instructions
+b, break [addr]        set a breakpoint, or list them
+d, delete addr         delete a breakpoint
+watch r|w|a addr [len] stop after an access to memory
+unwatch addr           delete a watchpoint
+r, regs                show the registers
+set reg value          change d0-d7, a0-a7, sp, pc, sr or ccr
+x addr [len]           hex dump memory
+w addr byte...         write bytes to memory
+l, dis [addr] [n]      disassemble, around the pc without an address
+a, asm addr text       assemble one line into memory
+trace [file|off]       trace instructions to the terminal or a file
+cycles                 clocks run, in total and by the last command
+reset                  reset the core
+gdb [port]             serve m68k-elf-gdb on localhost, 1234 by default
+q, quit                leave";
+
+struct Debugger {
+    core: M68k<'static>,
+    bus: MemoryMap,
+    breakpoints: Vec<u32>,
+    history: VecDeque<u32>,     // pcs of the last few instructions
+    last_clocks: u64,           // run by the last command
+}
+
+fn number(text: &str) -> Result<u32, String> {
+    let digits = text.trim_start_matches('$').trim_start_matches("0x");
+    u32::from_str_radix(digits, 16).map_err(|_| format!("`{}` isn't a hex number", text))
+}
+
+fn version(name: &str) -> Result<Version, String> {
+    Ok(match name.to_lowercase().trim_start_matches("mc") {
+        "68000" => Version::MC68000,
+        "68008" => Version::MC68008,
+        "68010" => Version::MC68010,
+        "68020" => Version::MC68020,
+        "68ec020" => Version::MC68EC020,
+        "68040" => Version::MC68040,
+        "68060" => Version::MC68060,
+        "cpu32" => Version::CPU32,
+        "isaa" | "isa-a" => Version::ColdFireIsaA,
+        "isab" | "isa-b" => Version::ColdFireIsaB,
+        "isac" | "isa-c" => Version::ColdFireIsaC,
+        _ => return Err(format!("unknown cpu `{}`", name)),
+    })
+}
+
+// Puts the PT_LOAD segments of a 32 bit big endian ELF file into memory
+// and returns the entry point, None when it isn't one.
+fn load_elf(bus: &mut MemoryMap, image: &[u8]) -> Result<Option<u32>, String> {
+    if image.len() < 52 || &image[..4] != b"\x7fELF" {
+        return Ok(None);
+    }
+    if image[4] != 1 || image[5] != 2 {
+        return Err("only 32 bit big endian ELF files".to_string());
+    }
+    let half = |at: usize| image.get(at..at + 2).map(|b| (b[0] as u32) << 8 | b[1] as u32);
+    let word = |at: usize| image.get(at..at + 4).map(|b| (b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32);
+    let truncated = || "truncated ELF file".to_string();
+    let entry = word(0x18).ok_or_else(truncated)?;
+    let phoff = word(0x1c).ok_or_else(truncated)? as usize;
+    let phentsize = half(0x2a).ok_or_else(truncated)? as usize;
+    let phnum = half(0x2c).ok_or_else(truncated)? as usize;
+    for i in 0..phnum {
+        let header = phoff + i * phentsize;
+        if word(header).ok_or_else(truncated)? != 1 {
+            continue;
+        }
+        let offset = word(header + 4).ok_or_else(truncated)? as usize;
+        let addr = word(header + 12).ok_or_else(truncated)?;   // physical, where a ROM image would be
+        let file_size = word(header + 16).ok_or_else(truncated)? as usize;
+        let mem_size = word(header + 20).ok_or_else(truncated)?;
+        let data = image.get(offset..offset + file_size).ok_or_else(truncated)?;
+        for i in 0..mem_size {
+            bus.poke_8(SUPERVISOR_DATA, addr.wrapping_add(i), data.get(i as usize).cloned().unwrap_or(0));
+        }
+    }
+    Ok(Some(entry))
+}
+
+impl Debugger {
+    fn program_space(&self) -> m68k::AddressSpace {
+        if self.core.s != 0 { SUPERVISOR_PROGRAM } else { USER_PROGRAM }
+    }
+
+    fn data_space(&self) -> m68k::AddressSpace {
+        if self.core.s != 0 { SUPERVISOR_DATA } else { USER_DATA }
+    }
+
+    fn disassemble(&self, pc: u32) -> (String, u32) {
+        let d = disassemble_bus(&self.bus, self.program_space(), self.core.version, pc);
+        let words: Vec<String> = (0..d.len / 2).map(|i| format!("{:04x}", self.bus.peek_16(self.program_space(), pc.wrapping_add(i * 2)))).collect();
+        (format!("{:08x}  {:<20} {}", pc, words.join(" "), d), d.len)
+    }
+
+    fn show_next(&self) {
+        println!("{}", self.disassemble(self.core.pc).0);
+    }
+
+    // one instruction, false when the core should stop running
+    fn step(&mut self) -> bool {
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.core.pc);
+        self.core.step(&mut self.bus);
+        if let Some(hit) = self.core.watch_hit.take() {
+            println!("watchpoint {}: {} of {:08x} = {:x} by the instruction at {:08x}",
+                     hit.index, if hit.write { "write" } else { "read" }, hit.addr, hit.value, hit.pc);
+            return false;
+        }
+        match self.core.processing_state {
+            ProcessingState::Halted => {
+                println!("halted, double bus fault");
+                false
+            },
+            ProcessingState::Stopped if self.core.irq_level as u32 <= self.core.int_mask => {
+                println!("stopped, waiting for an interrupt");
+                false
+            },
+            ProcessingState::Background => {
+                println!("in background mode");
+                false
+            },
+            _ => true,
+        }
+    }
+
+    // runs until `done` says so, a breakpoint or something that stops the
+    // core, `limit` instructions at most
+    fn run<F: Fn(&M68k) -> bool>(&mut self, limit: Option<u64>, done: F) {
+        let mut count = 0u64;
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        while self.step() {
+            count += 1;
+            if done(&self.core) || limit.is_some_and(|limit| count >= limit) {
+                break;
+            }
+            if self.breakpoints.contains(&self.core.pc) {
+                println!("breakpoint at {:08x}", self.core.pc);
+                break;
+            }
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                println!("interrupted");
+                break;
+            }
+        }
+    }
+
+    fn registers(&self) {
+        let core = &self.core;
+        for bank in 0..2 {
+            let name = if bank == 0 { 'd' } else { 'a' };
+            let line: Vec<String> = (0..8).map(|r| format!("{}{} {:08x}", name, r, core.dar[bank * 8 + r])).collect();
+            println!("{}", line.join("  "));
+        }
+        let sr = core.status_register();
+        let flags: String = [(15, 'T'), (13, 'S'), (12, 'M'), (4, 'X'), (3, 'N'), (2, 'Z'), (1, 'V'), (0, 'C')].iter()
+            .map(|&(bit, flag)| if sr & 1 << bit != 0 { flag } else { '-' }).collect();
+        println!("pc {:08x}  sr {:04x} {} I{}  usp {:08x}  clock {}",
+                 core.pc, sr, flags, (sr >> 8) & 7, if core.s != 0 { core.inactive_usp } else { core.dar[15] }, core.clock);
+    }
+
+    fn set(&mut self, name: &str, value: u32) -> Result<(), String> {
+        let name = name.to_lowercase();
+        let core = &mut self.core;
+        match name.as_str() {
+            "pc" => core.pc = value,
+            "sr" => core.sr_to_flags(value as u16),
+            "ccr" => core.ccr_to_flags(value as u16),
+            "sp" => core.dar[15] = value,
+            _ => {
+                let r = match (name.chars().next(), name.get(1..).unwrap_or("").parse::<usize>()) {
+                    (Some('d'), Ok(r)) if r < 8 => r,
+                    (Some('a'), Ok(r)) if r < 8 => 8 + r,
+                    _ => return Err(format!("no register `{}`", name)),
+                };
+                core.dar[r] = value;
+            },
+        }
+        Ok(())
+    }
+
+    fn dump(&self, start: u32, len: u32) {
+        let space = self.data_space();
+        for line in (0..len).step_by(16) {
+            let addr = start.wrapping_add(line);
+            let bytes: Vec<u8> = (0..16.min(len - line)).map(|i| self.bus.peek_8(space, addr.wrapping_add(i))).collect();
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            let text: String = bytes.iter().map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' }).collect();
+            println!("{:08x}  {:<48} {}", addr, hex.join(" "), text);
+        }
+    }
+
+    fn command(&mut self, line: &str) -> Result<bool, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let arg = |i: usize| words.get(i).map(|w| number(w)).transpose();
+        let start = self.core.clock;
+        match words.first().cloned().unwrap_or("") {
+            "" => return Ok(true),
+            "s" | "step" => {
+                let count = arg(1)?.unwrap_or(1);
+                self.run(Some(count as u64), |_| false);
+                self.show_next();
+            },
+            "n" | "next" => {
+                let pc = self.core.pc;
+                let d = disassemble_bus(&self.bus, self.program_space(), self.core.version, pc);
+                let call = ["bsr", "jsr", "trap", "callm"].iter().any(|c| d.mnemonic.starts_with(c));
+                if call {
+                    // back at the next instruction with the stack as it was
+                    let (ret, sp) = (pc.wrapping_add(d.len), self.core.dar[15]);
+                    self.run(None, |core| core.pc == ret && core.dar[15] >= sp);
+                } else {
+                    self.run(Some(1), |_| false);
+                }
+                self.show_next();
+            },
+            "c" | "continue" => {
+                let limit = arg(1)?.map(|n| n as u64);
+                self.run(limit, |_| false);
+                self.show_next();
+            },
+            "b" | "break" => match arg(1)? {
+                Some(addr) => {
+                    if !self.breakpoints.contains(&addr) {
+                        self.breakpoints.push(addr);
+                    }
+                },
+                None => {
+                    for addr in self.breakpoints.iter() {
+                        println!("{}", self.disassemble(*addr).0);
+                    }
+                },
+            },
+            "d" | "delete" => {
+                let addr = arg(1)?.ok_or("delete which breakpoint?")?;
+                self.breakpoints.retain(|&b| b != addr);
+            },
+            "watch" => {
+                let kind = match words.get(1).cloned() {
+                    Some("r") => WatchKind::Read,
+                    Some("w") => WatchKind::Write,
+                    Some("a") => WatchKind::Access,
+                    _ => return Err("watch r, w or a".to_string()),
+                };
+                let addr = arg(2)?.ok_or("watch where?")?;
+                let len = arg(3)?.unwrap_or(1).max(1);
+                self.core.watchpoints.push(Watchpoint::new(kind, addr, addr.wrapping_add(len - 1)));
+            },
+            "unwatch" => {
+                let addr = arg(1)?.ok_or("unwatch where?")?;
+                self.core.watchpoints.retain(|w| w.start != addr);
+            },
+            "r" | "regs" => self.registers(),
+            "set" => {
+                let name = words.get(1).ok_or("set which register?")?;
+                let value = arg(2)?.ok_or("set it to what?")?;
+                self.set(name, value)?;
+            },
+            "x" => {
+                let addr = arg(1)?.ok_or("dump where?")?;
+                self.dump(addr, arg(2)?.unwrap_or(0x40));
+            },
+            "w" => {
+                let addr = arg(1)?.ok_or("write where?")?;
+                let space = self.data_space();
+                for (i, byte) in words[2..].iter().enumerate() {
+                    let byte = number(byte)?;
+                    if byte > 0xff {
+                        return Err(format!("`{:x}` isn't a byte", byte));
+                    }
+                    self.bus.poke_8(space, addr.wrapping_add(i as u32), byte as u8);
+                }
+            },
+            "l" | "dis" => {
+                let count = arg(2)?.unwrap_or(10);
+                let mut pc = match arg(1)? {
+                    Some(addr) => addr,
+                    None => {
+                        for &pc in self.history.iter() {
+                            println!("{}", self.disassemble(pc).0);
+                        }
+                        self.core.pc
+                    },
+                };
+                for _ in 0..count {
+                    let (text, len) = self.disassemble(pc);
+                    println!("{}{}", text, if pc == self.core.pc { "   <" } else { "" });
+                    pc = pc.wrapping_add(len);
+                }
+            },
+            "a" | "asm" => {
+                let addr = arg(1)?.ok_or("assemble where?")?;
+                let text = line.splitn(3, char::is_whitespace).nth(2).ok_or("assemble what?")?;
+                let assembly = assemble(self.core.version, addr, &format!(" {}", text)).map_err(|e| e.message)?;
+                assembly.load(&mut self.bus, SUPERVISOR_DATA);
+                println!("{}", self.disassemble(addr).0);
+            },
+            "trace" => match words.get(1).cloned() {
+                Some("off") => self.core.tracer = None,
+                Some(path) => {
+                    let file = fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+                    let writer = TraceWriter::new(io::BufWriter::new(file), Format::Text).map_err(|e| e.to_string())?;
+                    self.core.tracer = Some(Box::new(writer));
+                },
+                None => {
+                    let writer = TraceWriter::new(io::stdout(), Format::Text).map_err(|e| e.to_string())?;
+                    self.core.tracer = Some(Box::new(writer));
+                },
+            },
+            "cycles" => {
+                println!("{} clocks, {} by the last command", self.core.clock, self.last_clocks);
+                return Ok(true);
+            },
+            "reset" => {
+                self.core.reset(&mut self.bus);
+                self.history.clear();
+                self.show_next();
+            },
+            "gdb" => {
+                let port = words.get(1).map(|p| p.parse::<u16>().map_err(|_| format!("bad port `{}`", p))).transpose()?.unwrap_or(1234);
+                println!("waiting for gdb on localhost:{}", port);
+                let mut conn = gdb::accept(("127.0.0.1", port)).map_err(|e| e.to_string())?;
+                let mut stub = GdbStub::new();
+                stub.breakpoints = self.breakpoints.clone();
+                let end = stub.serve(&mut conn, &mut self.core, &mut self.bus).map_err(|e| e.to_string())?;
+                self.breakpoints = stub.breakpoints;
+                println!("gdb {:?}", end);
+                self.show_next();
+            },
+            "h" | "help" | "?" => println!("{}", HELP),
+            "q" | "quit" => return Ok(false),
+            other => return Err(format!("unknown command `{}`, try help", other)),
+        }
+        self.last_clocks = self.core.clock - start;
+        Ok(true)
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: m68k-dbg [--cpu 68000] [--ram 1000000] [--base 0] [--pc addr] image");
+    process::exit(2);
+}
+
+fn main() {
+    let mut cpu = Version::MC68000;
+    let mut ram = 0x100_0000;
+    let mut base = 0;
+    let mut pc = None;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let result = match arg.as_str() {
+            "--cpu" => args.next().ok_or(String::new()).and_then(|v| version(&v)).map(|v| cpu = v),
+            "--ram" => match args.next().ok_or(String::new()).and_then(|v| number(&v)) {
+                Ok(0) => Err("--ram needs at least one byte".to_string()),
+                size => size.map(|v| ram = v),
+            },
+            "--base" => args.next().ok_or(String::new()).and_then(|v| number(&v)).map(|v| base = v),
+            "--pc" => args.next().ok_or(String::new()).and_then(|v| number(&v)).map(|v| pc = Some(v)),
+            _ if arg.starts_with("--") || path.is_some() => Err(String::new()),
+            _ => {
+                path = Some(arg);
+                Ok(())
+            },
+        };
+        if let Err(message) = result {
+            if !message.is_empty() {
+                eprintln!("{}", message);
+            }
+            usage();
+        }
+    }
+    let path = path.unwrap_or_else(|| usage());
+    let image = fs::read(&path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        process::exit(1);
+    });
+
+    let mut bus = MemoryMap::new();
+    bus.ram(0, ram);
+    let entry = match load_elf(&mut bus, &image) {
+        Ok(Some(entry)) => Some(entry),
+        Ok(None) => {
+            for (i, &byte) in image.iter().enumerate() {
+                bus.poke_8(SUPERVISOR_DATA, base.wrapping_add(i as u32), byte);
+            }
+            None
+        },
+        Err(message) => {
+            eprintln!("{}: {}", path, message);
+            process::exit(1);
+        },
+    };
+    let mut core = M68k::new(cpu);
+    core.reset(&mut bus);
+    if let Some(pc) = pc.or(entry) {
+        core.pc = pc;
+    }
+    if core.dar[15] == 0 {
+        // no reset vectors, the stack starts at the top of RAM
+        core.dar[15] = ram;
+    }
+
+    let mut debugger = Debugger { core, bus, breakpoints: Vec::new(), history: VecDeque::new(), last_clocks: 0 };
+    debugger.show_next();
+    catch_interrupt();
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        match debugger.command(line.trim()) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(message) => println!("{}", message),
+        }
+    }
+}
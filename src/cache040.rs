@@ -0,0 +1,333 @@
+// MC68040 on-chip caches (ref MC68040UM 4.3)
+// Each cache is 4K, four way set associative with 64 sets of 16 byte lines.
+// The '060 caches are the same but 8K, with 128 sets.
+// Caches are physically addressed, so these sit after address translation.
+// The data cache supports both writethrough and copyback, dirty lines are
+// pushed to memory when replaced or by CPUSH.
+
+use Bus;
+use AddressSpace;
+use M68k;
+use Result;
+use Segment;
+use SUPERVISOR_DATA;
+
+pub const CACR_DE: u32 = 0x8000_0000;   // data cache enable
+pub const CACR_IE: u32 = 0x0000_8000;   // instruction cache enable
+pub const CACR_MASK_040: u32 = CACR_DE | CACR_IE;
+// the '060 adds store buffer, branch cache and freeze/no-allocate bits
+pub const CACR_MASK_060: u32 = 0xf8e0_e000;
+
+pub const LINE_SIZE: u32 = 16;
+pub const SETS: usize = 64;
+pub const SETS_060: usize = 128;
+pub const WAYS: usize = 4;
+
+// cache mode field of the TTRs and page descriptors
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CacheMode {
+    Writethrough,
+    Copyback,
+    InhibitedSerialized,
+    InhibitedNonSerialized,
+}
+
+impl CacheMode {
+    pub fn from_bits(cm: u32) -> CacheMode {
+        match cm & 3 {
+            0 => CacheMode::Writethrough,
+            1 => CacheMode::Copyback,
+            2 => CacheMode::InhibitedSerialized,
+            _ => CacheMode::InhibitedNonSerialized,
+        }
+    }
+    pub fn cachable(&self) -> bool {
+        matches!(*self, CacheMode::Writethrough | CacheMode::Copyback)
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct CacheLine040 {
+    pub tag: u32,
+    pub v: bool,
+    pub dirty: bool,
+    pub data: [u8; 16],
+}
+
+pub struct Cache040 {
+    pub sets: Vec<[CacheLine040; WAYS]>,
+    replace: Vec<u8>,
+}
+
+impl Default for Cache040 {
+    fn default() -> Self {
+        Cache040::new()
+    }
+}
+
+impl Cache040 {
+    pub fn new() -> Self {
+        Cache040::with_sets(SETS)
+    }
+
+    pub fn with_sets(sets: usize) -> Self {
+        Cache040 {
+            sets: vec![[CacheLine040::default(); WAYS]; sets],
+            replace: vec![0; sets],
+        }
+    }
+
+    fn set_index(&self, addr: u32) -> usize {
+        ((addr >> 4) as usize) & (self.sets.len() - 1)
+    }
+
+    // the tag is everything above the set index
+    fn tag_shift(&self) -> u32 {
+        4 + self.sets.len().trailing_zeros()
+    }
+
+    fn tag(&self, addr: u32) -> u32 {
+        addr >> self.tag_shift()
+    }
+
+    fn line_address(&self, set: usize, tag: u32) -> u32 {
+        (tag << self.tag_shift()) | ((set as u32) << 4)
+    }
+
+    pub fn find(&self, addr: u32) -> Option<(usize, usize)> {
+        let set = self.set_index(addr);
+        let tag = self.tag(addr);
+        self.sets[set].iter()
+            .position(|line| line.v && line.tag == tag)
+            .map(|way| (set, way))
+    }
+
+    // the way a line for `addr` goes in, an invalid one if there is one,
+    // otherwise round robin
+    fn victim(&mut self, addr: u32) -> (usize, usize) {
+        let set = self.set_index(addr);
+        let way = match self.sets[set].iter().position(|line| !line.v) {
+            Some(way) => way,
+            None => {
+                let way = self.replace[set] as usize;
+                self.replace[set] = ((way + 1) % WAYS) as u8;
+                way
+            }
+        };
+        (set, way)
+    }
+
+    // the address and contents of a line that has to be pushed
+    fn dirty(&self, set: usize, way: usize) -> Option<(u32, [u32; 4])> {
+        let line = &self.sets[set][way];
+        if !line.v || !line.dirty {
+            return None;
+        }
+        let mut data = [0u32; 4];
+        for (i, value) in data.iter_mut().enumerate() {
+            *value = line.data[i * 4..i * 4 + 4].iter().fold(0, |acc, &b| acc << 8 | b as u32);
+        }
+        Some((self.line_address(set, line.tag), data))
+    }
+
+    fn install(&mut self, set: usize, way: usize, addr: u32, data: [u32; 4]) {
+        let mut line = CacheLine040 { tag: self.tag(addr), v: true, dirty: false, data: [0; 16] };
+        for (i, value) in data.iter().enumerate() {
+            line.data[i * 4..i * 4 + 4].copy_from_slice(&value.to_be_bytes());
+        }
+        self.sets[set][way] = line;
+    }
+
+    // CINV and CPUSH, `addr` is None for the whole cache and `page_mask`
+    // picks the lines to drop otherwise, a line is a page with a 16 byte mask
+    fn selected(&self, addr: Option<u32>, page_mask: u32) -> Vec<(usize, usize)> {
+        let mut lines = Vec::new();
+        for set in 0..self.sets.len() {
+            for way in 0..WAYS {
+                let line = &self.sets[set][way];
+                let base = self.line_address(set, line.tag);
+                if line.v && addr.is_none_or(|addr| base & !page_mask == addr & !page_mask) {
+                    lines.push((set, way));
+                }
+            }
+        }
+        lines
+    }
+
+    pub fn invalidate_all(&mut self) {
+        for set in self.sets.iter_mut() {
+            for line in set.iter_mut() {
+                line.v = false;
+                line.dirty = false;
+            }
+        }
+    }
+}
+
+// Fills and pushes go out through the core's bus path like any other
+// access, so they are cut down to the address lines and a line filled
+// from or pushed to nowhere ends in a bus error.
+impl<'a> M68k<'a> {
+    fn cache_040(&mut self, segment: Segment) -> &mut Cache040 {
+        match segment {
+            Segment::Program => &mut self.icache,
+            Segment::Data => &mut self.dcache,
+        }
+    }
+
+    // writes a dirty line back to memory, the line stays valid
+    fn push_line<T: Bus + ?Sized>(&mut self, bus: &mut T, segment: Segment, set: usize, way: usize) -> Result<()> {
+        if let Some((base, data)) = self.cache_040(segment).dirty(set, way) {
+            for (i, &value) in data.iter().enumerate() {
+                self.bus_write(bus, SUPERVISOR_DATA, base + i as u32 * 4, 4, value)?;
+            }
+            self.cache_040(segment).sets[set][way].dirty = false;
+        }
+        Ok(())
+    }
+
+    fn fill_line<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32) -> Result<(usize, usize)> {
+        let (set, way) = self.cache_040(space.1).victim(addr);
+        self.push_line(bus, space.1, set, way)?;
+        let base = addr & !(LINE_SIZE - 1);
+        let mut data = [0u32; 4];
+        for (i, value) in data.iter_mut().enumerate() {
+            *value = self.bus_read(bus, space, base + i as u32 * 4, 4)?;
+        }
+        self.cache_040(space.1).install(set, way, addr, data);
+        Ok((set, way))
+    }
+
+    // reads `size` bytes of a physical address, filling the line on a miss
+    pub fn cache_read<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32) -> Result<u32> {
+        let offset = addr & (LINE_SIZE - 1);
+        if offset + size > LINE_SIZE {
+            // crosses into the next line, take it a byte at a time
+            let mut value = 0;
+            for i in 0..size {
+                value = value << 8 | self.cache_read(bus, space, addr.wrapping_add(i), 1)?;
+            }
+            return Ok(value);
+        }
+        let (set, way) = match self.cache_040(space.1).find(addr) {
+            Some(hit) => hit,
+            None => self.fill_line(bus, space, addr)?,
+        };
+        let data = &self.cache_040(space.1).sets[set][way].data;
+        Ok((0..size).fold(0, |acc, i| acc << 8 | data[(offset + i) as usize] as u32))
+    }
+
+    // writethrough updates a hit and always writes memory, copyback
+    // allocates on a miss and leaves the line dirty
+    pub fn cache_write<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32, value: u32, copyback: bool) -> Result<()> {
+        let offset = addr & (LINE_SIZE - 1);
+        if offset + size > LINE_SIZE {
+            for i in 0..size {
+                let byte = value >> ((size - 1 - i) * 8);
+                self.cache_write(bus, space, addr.wrapping_add(i), 1, byte, copyback)?;
+            }
+            return Ok(());
+        }
+        let hit = match self.dcache.find(addr) {
+            None if copyback => Some(self.fill_line(bus, space, addr)?),
+            hit => hit,
+        };
+        if let Some((set, way)) = hit {
+            let line = &mut self.dcache.sets[set][way];
+            for i in 0..size {
+                line.data[(offset + i) as usize] = (value >> ((size - 1 - i) * 8)) as u8;
+            }
+            line.dirty |= copyback;
+        }
+        if !copyback {
+            self.bus_write(bus, space, addr, size, value)?;
+        }
+        Ok(())
+    }
+
+    // CINV and CPUSH on one of the caches, see Cache040::selected
+    pub(crate) fn cache_invalidate<T: Bus + ?Sized>(&mut self, bus: &mut T, segment: Segment, addr: Option<u32>, page_mask: u32, push: bool) -> Result<()> {
+        for (set, way) in self.cache_040(segment).selected(addr, page_mask) {
+            if push {
+                self.push_line(bus, segment, set, way)?;
+            }
+            let line = &mut self.cache_040(segment).sets[set][way];
+            line.v = false;
+            line.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory::{MemoryMap, RomWrites};
+    use tests::small_map;
+    use Version;
+
+    // the '040 with the data cache on and everything copyback through DTT0
+    fn copyback(code: &[u16]) -> (M68k<'static>, MemoryMap) {
+        let mut bus = small_map(0x8000, code);
+        bus.poke_32(SUPERVISOR_DATA, 8, 0x600);
+        let mut cpu = M68k::new(Version::MC68040);
+        cpu.reset(&mut bus);
+        cpu.cacr = CACR_DE;
+        cpu.dtt0 = 0x00ff_c020;
+        cpu.dar[0] = 0x12345678;
+        (cpu, bus)
+    }
+
+    #[test]
+    fn copyback_lines_are_pushed() {
+        // move.l d0,(a0) ; cpusha dc
+        let (mut cpu, mut bus) = copyback(&[0x2080, 0xf478]);
+        cpu.dar[8] = 0x1004;
+        cpu.step(&mut bus);
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x1004), 0);
+        assert!(cpu.dcache.find(0x1004).is_some());
+        cpu.step(&mut bus);
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x1004), 0x12345678);
+        assert!(cpu.dcache.find(0x1004).is_none());
+    }
+
+    #[test]
+    fn fills_from_nowhere_are_bus_errors() {
+        // move.l d0,(a0) with a0 past the end of the RAM
+        let (mut cpu, mut bus) = copyback(&[0x2080]);
+        cpu.dar[8] = 0x20000;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x600);
+        assert!(cpu.dcache.find(0x20000).is_none());
+    }
+
+    #[test]
+    fn pushes_into_rom_are_bus_errors() {
+        // move.l d0,(a0) ; cpusha dc, the line comes from ROM that faults writes
+        let (mut cpu, mut bus) = copyback(&[0x2080, 0xf478]);
+        bus.rom(0x1000, vec![0; 16]);
+        bus.rom_writes = RomWrites::BusError;
+        cpu.dar[8] = 0x1000;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x402);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x600);
+        let (set, way) = cpu.dcache.find(0x1000).expect("the line stays in the cache");
+        assert!(cpu.dcache.sets[set][way].dirty);
+    }
+
+    #[test]
+    fn move16_goes_around_the_cache() {
+        // move.l d0,(a1) ; move16 (a0)+,(a1)+
+        let (mut cpu, mut bus) = copyback(&[0x2280, 0xf620, 0x9000]);
+        cpu.dar[8] = 0x400;
+        cpu.dar[9] = 0x2000;
+        cpu.step(&mut bus);
+        assert!(cpu.dcache.find(0x2000).is_some());
+        cpu.step(&mut bus);
+        assert!(cpu.dcache.find(0x2000).is_none());
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x2000), 0x2280f620);
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x2004), 0x9000_0000);
+        assert_eq!((cpu.dar[8], cpu.dar[9]), (0x410, 0x2010));
+    }
+}
@@ -0,0 +1,1259 @@
+// Disassembler
+//
+// Turns an opcode and its extension words back into Motorola syntax, with
+// lower case mnemonics, `$` hex and d0-d7/a0-a7. What decodes depends on
+// the Version, an instruction the part doesn't have comes out as a dc.w of
+// the opcode, as does an addressing mode it can't take.
+//
+// PC relative operands are shown as the address they reach, so
+// `($1000,pc)` is the word at $1000 whatever the instruction's address,
+// and branches show their target. Absolute short addresses are shown as
+// the word in the instruction, `$8000.w` is $ffff8000.
+
+use std::fmt;
+use instructions::optable::{IF_T, IF_F, IF_HI, IF_LS, IF_CC, IF_CS, IF_NE, IF_EQ,
+                            IF_VC, IF_VS, IF_PL, IF_MI, IF_GE, IF_LT, IF_GT,
+                            OPER_DN, OPER_AN, OPER_AI, OPER_PI, OPER_PD, OPER_DI, OPER_IX,
+                            OPER_AW, OPER_AL, OPER_PCDI, OPER_PCIX, OPER_IMM,
+                            MASK_OUT_Y, OP_ILLEGAL, OP_BGND, OP_LPSTOP, OP_TBL,
+                            OP_MOV3Q_32, OP_SATS_32, OP_BITREV_32, OP_BYTEREV_32, OP_FF1_32};
+use AddressSpace;
+use Bus;
+use Version;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disassembly {
+    pub mnemonic: String,   // with the size suffix
+    pub operands: String,
+    pub len: u32,           // bytes, opcode and extension words
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.operands.is_empty() {
+            write!(f, "{}", self.mnemonic)
+        } else {
+            write!(f, "{:<7} {}", self.mnemonic, self.operands)
+        }
+    }
+}
+
+// `read` gives the word at an address, it's asked for the opcode at `pc`
+// and then for each extension word in turn
+pub fn disassemble<F: FnMut(u32) -> u16>(version: Version, pc: u32, read: F) -> Disassembly {
+    let mut decoder = Decoder { version, pc, next: pc, read };
+    let op = decoder.word();
+    match decoder.decode(op) {
+        Some((mnemonic, operands)) => Disassembly { mnemonic, operands, len: decoder.next.wrapping_sub(pc) },
+        None => Disassembly { mnemonic: "dc.w".to_string(), operands: hex(op as u32), len: 2 },
+    }
+}
+
+// from words already fetched, `words[0]` being the opcode at `pc`, words
+// past the end read as 0
+pub fn disassemble_words(version: Version, pc: u32, words: &[u16]) -> Disassembly {
+    disassemble(version, pc, |addr| {
+        words.get((addr.wrapping_sub(pc) / 2) as usize).cloned().unwrap_or(0)
+    })
+}
+
+// straight from memory, peeking so devices don't see it
+pub fn disassemble_bus<B: Bus + ?Sized>(bus: &B, space: AddressSpace, version: Version, pc: u32) -> Disassembly {
+    disassemble(version, pc, |addr| bus.peek_16(space, addr))
+}
+
+// addressing mode classes, one bit per mode
+const EA_DN: u16 = 1 << 0;
+const EA_AN: u16 = 1 << 1;
+const EA_AI: u16 = 1 << 2;
+const EA_PI: u16 = 1 << 3;
+const EA_PD: u16 = 1 << 4;
+const EA_DI: u16 = 1 << 5;
+const EA_IX: u16 = 1 << 6;
+const EA_AW: u16 = 1 << 7;
+const EA_AL: u16 = 1 << 8;
+const EA_PCDI: u16 = 1 << 9;
+const EA_PCIX: u16 = 1 << 10;
+const EA_IMM: u16 = 1 << 11;
+
+const ALL: u16 = 0xfff;
+const DATA: u16 = ALL & !EA_AN;
+const ALTERABLE: u16 = EA_DN | EA_AN | EA_AI | EA_PI | EA_PD | EA_DI | EA_IX | EA_AW | EA_AL;
+const DATA_ALT: u16 = ALTERABLE & !EA_AN;
+const MEMORY_ALT: u16 = DATA_ALT & !EA_DN;
+const CONTROL: u16 = EA_AI | EA_DI | EA_IX | EA_AW | EA_AL | EA_PCDI | EA_PCIX;
+const CONTROL_ALT: u16 = EA_AI | EA_DI | EA_IX | EA_AW | EA_AL;
+
+type Decoded = Option<(String, String)>;
+
+fn mode_class(ea: u16) -> u16 {
+    match ea as u32 & 0x38 {
+        OPER_DN => EA_DN,
+        OPER_AN => EA_AN,
+        OPER_AI => EA_AI,
+        OPER_PI => EA_PI,
+        OPER_PD => EA_PD,
+        OPER_DI => EA_DI,
+        OPER_IX => EA_IX,
+        _ => match ea as u32 {
+            OPER_AW => EA_AW,
+            OPER_AL => EA_AL,
+            OPER_PCDI => EA_PCDI,
+            OPER_PCIX => EA_PCIX,
+            OPER_IMM => EA_IMM,
+            _ => 0,
+        },
+    }
+}
+
+fn hex(value: u32) -> String {
+    format!("${:x}", value)
+}
+
+fn signed(value: i32) -> String {
+    if value < 0 {
+        format!("-${:x}", (value as i64).abs())
+    } else {
+        format!("${:x}", value)
+    }
+}
+
+fn suffix(size: u32) -> &'static str {
+    match size {
+        1 => ".b",
+        2 => ".w",
+        _ => ".l",
+    }
+}
+
+fn sized(name: &str, size: u32) -> String {
+    format!("{}{}", name, suffix(size))
+}
+
+// the usual size field in bits 7-6
+fn size_field(op: u16) -> Option<u32> {
+    match (op >> 6) & 3 {
+        0 => Some(1),
+        1 => Some(2),
+        2 => Some(4),
+        _ => None,
+    }
+}
+
+// register 0-15 as in the register fields of extension words
+fn reg(r: u16) -> String {
+    if r & 8 == 0 { format!("d{}", r & 7) } else { format!("a{}", r & 7) }
+}
+
+fn condition(op: u16) -> &'static str {
+    match op as u32 & 0xf00 {
+        IF_T => "t",
+        IF_F => "f",
+        IF_HI => "hi",
+        IF_LS => "ls",
+        IF_CC => "cc",
+        IF_CS => "cs",
+        IF_NE => "ne",
+        IF_EQ => "eq",
+        IF_VC => "vc",
+        IF_VS => "vs",
+        IF_PL => "pl",
+        IF_MI => "mi",
+        IF_GE => "ge",
+        IF_LT => "lt",
+        IF_GT => "gt",
+        _ => "le",
+    }
+}
+
+// the 68851 conditions of PBcc, PDBcc, PScc and PTRAPcc
+fn pmmu_condition(cc: u16) -> Option<&'static str> {
+    const NAMES: [&str; 16] = ["bs", "bc", "ls", "lc", "ss", "sc", "as", "ac",
+                               "ws", "wc", "is", "ic", "gs", "gc", "cs", "cc"];
+    NAMES.get((cc & 0x3f) as usize).cloned()
+}
+
+// the function code field of the 68851 instructions
+fn pmmu_fc(extension: u16) -> Option<String> {
+    match extension & 0x1f {
+        0b00000 => Some("sfc".to_string()),
+        0b00001 => Some("dfc".to_string()),
+        fc if fc & 0x18 == 0x08 => Some(format!("d{}", fc & 7)),
+        fc if fc & 0x10 != 0 => Some(format!("#{}", fc & 0xf)),
+        _ => None,
+    }
+}
+
+fn control_register(version: Version, cr: u16) -> String {
+    match cr {
+        0x004..=0x007 if version.is_coldfire() => return format!("acr{}", cr - 0x004),
+        0x000 => "sfc",
+        0x001 => "dfc",
+        0x002 => "cacr",
+        0x003 => "tc",
+        0x004 => "itt0",
+        0x005 => "itt1",
+        0x006 => "dtt0",
+        0x007 => "dtt1",
+        0x008 => "buscr",
+        0x800 => "usp",
+        0x801 => "vbr",
+        0x802 => "caar",
+        0x803 => "msp",
+        0x804 => "isp",
+        0x805 => "mmusr",
+        0x806 => "urp",
+        0x807 => "srp",
+        0x808 => "pcr",
+        0xc00 => "rombar0",
+        0xc01 => "rombar1",
+        0xc04 => "rambar0",
+        0xc05 => "rambar1",
+        0xc0f => "mbar",
+        _ => return hex(cr as u32),
+    }.to_string()
+}
+
+// d0-d3/a5 style, bit 0 is d0 and bit 15 is a7
+fn register_list(mask: u16) -> String {
+    let mut parts = Vec::new();
+    for &(base, prefix) in [(0, "d"), (8, "a")].iter() {
+        let mut r = 0;
+        while r < 8 {
+            if mask & 1 << (base + r) == 0 {
+                r += 1;
+                continue;
+            }
+            let start = r;
+            while r < 8 && mask & 1 << (base + r) != 0 {
+                r += 1;
+            }
+            if r - start == 1 {
+                parts.push(format!("{}{}", prefix, start));
+            } else {
+                parts.push(format!("{}{}-{}{}", prefix, start, prefix, r - 1));
+            }
+        }
+    }
+    if parts.is_empty() {
+        // nothing to move, the mask is all there is to show
+        return "#0".to_string();
+    }
+    parts.join("/")
+}
+
+struct Decoder<F> {
+    version: Version,
+    pc: u32,        // of the opcode
+    next: u32,      // of the next word to read
+    read: F,
+}
+
+impl<F: FnMut(u32) -> u16> Decoder<F> {
+    fn word(&mut self) -> u16 {
+        let word = (self.read)(self.next);
+        self.next = self.next.wrapping_add(2);
+        word
+    }
+
+    fn long(&mut self) -> u32 {
+        let high = self.word() as u32;
+        high << 16 | self.word() as u32
+    }
+
+    // 010 and later, CPU32 included
+    fn m010(&self) -> bool {
+        self.version.base() != Version::MC68000 && !self.version.is_coldfire()
+    }
+
+    // the full 020 instruction set
+    fn m020(&self) -> bool {
+        matches!(self.version.base(), Version::MC68020 | Version::MC68040 | Version::MC68060)
+    }
+
+    // the long multiplies and divides, EXTB, CHK.L, CHK2 and friends the
+    // CPU32 shares with the 020
+    fn long_ops(&self) -> bool {
+        self.m020() || self.version == Version::CPU32
+    }
+
+    fn isa_b(&self) -> bool {
+        matches!(self.version, Version::ColdFireIsaB | Version::ColdFireIsaC)
+    }
+
+    fn ea(&mut self, ea: u16, size: u32, allowed: u16) -> Option<String> {
+        let class = mode_class(ea);
+        if class & allowed == 0 {
+            return None;
+        }
+        let r = ea & 7;
+        Some(match class {
+            EA_DN => format!("d{}", r),
+            EA_AN => format!("a{}", r),
+            EA_AI => format!("(a{})", r),
+            EA_PI => format!("(a{})+", r),
+            EA_PD => format!("-(a{})", r),
+            EA_DI => format!("({},a{})", signed(self.word() as i16 as i32), r),
+            EA_IX => return self.indexed(Some(r)),
+            EA_AW => format!("{}.w", hex(self.word() as u32)),
+            EA_AL => format!("{}.l", hex(self.long())),
+            EA_PCDI => {
+                let at = self.next;
+                format!("({},pc)", hex(at.wrapping_add(self.word() as i16 as u32)))
+            },
+            EA_PCIX => return self.indexed(None),
+            _ => match size {
+                1 => format!("#{}", hex(self.word() as u32 & 0xff)),
+                2 => format!("#{}", hex(self.word() as u32)),
+                4 => format!("#{}", hex(self.long())),
+                _ => {
+                    let high = self.long() as u64;
+                    format!("#${:x}", high << 32 | self.long() as u64)
+                },
+            },
+        })
+    }
+
+    // (d8,An,Xn) and the 020 full format, `base` None for the PC
+    fn indexed(&mut self, base: Option<u16>) -> Option<String> {
+        let at = self.next;     // PC relative displacements count from here
+        let extension = self.word();
+        let scale = 1 << ((extension >> 9) & 3);
+        let xn = format!("{}{}", reg(extension >> 12), if extension & 0x800 != 0 { ".l" } else { ".w" });
+        // the 68000 and 68010 ignore the scale and the full format bit
+        let index = if scale == 1 || self.version.base() == Version::MC68000 || self.version == Version::MC68010 {
+            xn
+        } else {
+            format!("{}*{}", xn, scale)
+        };
+        let base_name = base.map_or("pc".to_string(), |r| format!("a{}", r));
+        let full = extension & 0x100 != 0 && self.version.base() != Version::MC68000 && self.version != Version::MC68010;
+        if !full {
+            let disp = extension as i8 as i32;
+            let disp = match base {
+                None => hex(at.wrapping_add(disp as u32)),
+                Some(_) => signed(disp),
+            };
+            return Some(format!("({},{},{})", disp, base_name, index));
+        }
+        if !self.m020() || extension & 8 != 0 {
+            return None;
+        }
+        let base_suppressed = extension & 0x80 != 0;
+        let index_suppressed = extension & 0x40 != 0;
+        let indirect = extension & 7;
+        if (index_suppressed && indirect > 3) || (!index_suppressed && indirect == 4) {
+            return None;
+        }
+        let bd = match (extension >> 4) & 3 {
+            0 => return None,
+            1 => None,
+            2 => Some(self.word() as i16 as u32),
+            _ => Some(self.long()),
+        };
+        let od = match indirect & 3 {
+            2 => Some(self.word() as i16 as i32),
+            3 => Some(self.long() as i32),
+            _ => None,
+        };
+        let bd = match (base, base_suppressed, bd) {
+            (None, false, bd) => Some(hex(at.wrapping_add(bd.unwrap_or(0)))),
+            (_, _, bd) => bd.map(|bd| signed(bd as i32)),
+        };
+        let base = match (base, base_suppressed) {
+            (_, false) => Some(base_name),
+            (None, true) => Some("zpc".to_string()),
+            (Some(_), true) => None,
+        };
+        let index = if index_suppressed { None } else { Some(index) };
+        let od = od.map(signed);
+        let join = |parts: Vec<Option<String>>| parts.into_iter().flatten().collect::<Vec<_>>().join(",");
+        Some(match indirect {
+            0 => {
+                let inner = join(vec![bd, base, index]);
+                if inner.is_empty() { "($0)".to_string() } else { format!("({})", inner) }
+            },
+            // postindexed, the index is added after the memory read
+            5..=7 => format!("([{}],{})", join(vec![bd, base]), join(vec![index, od])),
+            _ => {
+                let inner = format!("[{}]", join(vec![bd, base, index]));
+                format!("({})", join(vec![Some(inner), od]))
+            },
+        })
+    }
+
+    fn decode(&mut self, op: u16) -> Decoded {
+        match op >> 12 {
+            0x0 => self.group_0(op),
+            0x1..=0x3 => self.move_op(op),
+            0x4 => self.group_4(op),
+            0x5 => self.group_5(op),
+            0x6 => self.branch(op),
+            0x7 => self.group_7(op),
+            0x8 => self.group_8(op),
+            0x9 | 0xd => self.add_sub(op),
+            0xa => self.line_a(op),
+            0xb => self.group_b(op),
+            0xc => self.group_c(op),
+            0xe => self.group_e(op),
+            _ => self.line_f(op),
+        }
+    }
+
+    // bit operations, MOVEP and the immediate operations
+    fn group_0(&mut self, op: u16) -> Decoded {
+        let ea = op & 0x3f;
+        let mode = (op >> 3) & 7;
+        if self.version.is_coldfire() && mode == 0 {
+            let name = match op as u32 & MASK_OUT_Y {
+                OP_BYTEREV_32 => Some("byterev.l"),
+                OP_FF1_32 => Some("ff1.l"),
+                OP_BITREV_32 => Some("bitrev.l"),
+                _ => None,
+            };
+            if let Some(name) = name {
+                return if self.version == Version::ColdFireIsaC {
+                    Some((name.to_string(), format!("d{}", op & 7)))
+                } else {
+                    None
+                };
+            }
+        }
+        const BIT_OPS: [&str; 4] = ["btst", "bchg", "bclr", "bset"];
+        let bit_op = BIT_OPS[((op >> 6) & 3) as usize];
+        let bit_allowed = if op & 0xc0 == 0 { DATA } else { DATA_ALT };
+        if op & 0x100 != 0 {
+            let dn = (op >> 9) & 7;
+            if mode == 1 {
+                if self.version.is_coldfire() {
+                    return None;
+                }
+                let size = if op & 0x40 != 0 { 4 } else { 2 };
+                let memory = format!("({},a{})", signed(self.word() as i16 as i32), op & 7);
+                return Some(if op & 0x80 == 0 {
+                    (sized("movep", size), format!("{},d{}", memory, dn))
+                } else {
+                    (sized("movep", size), format!("d{},{}", dn, memory))
+                });
+            }
+            return Some((bit_op.to_string(), format!("d{},{}", dn, self.ea(ea, 1, bit_allowed)?)));
+        }
+        let kind = (op >> 9) & 7;
+        if kind == 4 {
+            let bit = self.word() & 0xff;
+            let dst = self.ea(ea, 1, bit_allowed & !EA_IMM)?;
+            return Some((bit_op.to_string(), format!("#{},{}", bit, dst)));
+        }
+        let size = match size_field(op) {
+            Some(size) => size,
+            None => return self.group_0_long(op),
+        };
+        if kind == 7 {
+            if !self.m010() {
+                return None;
+            }
+            let extension = self.word();
+            let dst = self.ea(ea, size, MEMORY_ALT)?;
+            return Some(if extension & 0x800 != 0 {
+                (sized("moves", size), format!("{},{}", reg(extension >> 12), dst))
+            } else {
+                (sized("moves", size), format!("{},{}", dst, reg(extension >> 12)))
+            });
+        }
+        const IMMEDIATE: [&str; 7] = ["ori", "andi", "subi", "addi", "", "eori", "cmpi"];
+        let name = IMMEDIATE[kind as usize];
+        // ColdFire only has them long to Dn, plus CMPI.B and CMPI.W on ISA_B
+        if self.version.is_coldfire() && (mode != 0 || (size != 4 && !(kind == 6 && self.isa_b()))) {
+            return None;
+        }
+        if ea as u32 == OPER_IMM {
+            // to CCR and SR
+            return match (kind, size) {
+                (0, 1) | (1, 1) | (5, 1) => Some((sized(name, 1), format!("#{},ccr", hex(self.word() as u32 & 0xff)))),
+                (0, 2) | (1, 2) | (5, 2) => Some((sized(name, 2), format!("#{},sr", hex(self.word() as u32)))),
+                _ => None,
+            };
+        }
+        let imm = match size {
+            1 => self.word() as u32 & 0xff,
+            2 => self.word() as u32,
+            _ => self.long(),
+        };
+        let allowed = if kind == 6 && self.long_ops() { DATA & !EA_IMM } else { DATA_ALT };
+        let dst = self.ea(ea, size, allowed)?;
+        Some((sized(name, size), format!("#{},{}", hex(imm), dst)))
+    }
+
+    // CHK2, CMP2, CALLM, RTM, CAS and CAS2, where the size field is 11
+    fn group_0_long(&mut self, op: u16) -> Decoded {
+        let ea = op & 0x3f;
+        match (op >> 9) & 7 {
+            kind @ 0..=2 if self.long_ops() => {
+                let size = 1 << kind;
+                let extension = self.word();
+                let name = if extension & 0x800 != 0 { "chk2" } else { "cmp2" };
+                let src = self.ea(ea, size, CONTROL)?;
+                Some((sized(name, size), format!("{},{}", src, reg(extension >> 12))))
+            },
+            3 if self.version.base() == Version::MC68020 => {
+                if (op >> 3) & 7 <= 1 {
+                    return Some(("rtm".to_string(), reg(op & 0xf)));
+                }
+                let arguments = self.word() & 0xff;
+                let dst = self.ea(ea, 0, CONTROL)?;
+                Some(("callm".to_string(), format!("#{},{}", arguments, dst)))
+            },
+            kind @ 5..=7 if self.m020() => {
+                let size = 1 << (kind - 5);
+                if ea as u32 == OPER_IMM && size != 1 {
+                    let first = self.word();
+                    let second = self.word();
+                    return Some((sized("cas2", size), format!("d{}:d{},d{}:d{},({}):({})",
+                        first & 7, second & 7, (first >> 6) & 7, (second >> 6) & 7, reg(first >> 12), reg(second >> 12))));
+                }
+                let extension = self.word();
+                let dst = self.ea(ea, size, MEMORY_ALT)?;
+                Some((sized("cas", size), format!("d{},d{},{}", extension & 7, (extension >> 6) & 7, dst)))
+            },
+            _ => None,
+        }
+    }
+
+    fn move_op(&mut self, op: u16) -> Decoded {
+        let size = match op >> 12 {
+            1 => 1,
+            3 => 2,
+            _ => 4,
+        };
+        let dst = (op >> 9) & 7 | (op >> 3) & 0x38;
+        if self.version.is_coldfire() && dst >> 3 >= 5 {
+            // ColdFire can't use two extension words for both operands,
+            // except that ISA_B can move an immediate to (d16,An)
+            let src = mode_class(op & 0x3f);
+            if src & (EA_IX | EA_AW | EA_AL | EA_PCIX) != 0
+                || (src == EA_IMM && !(self.isa_b() && dst >> 3 == 5))
+                || (src & (EA_DI | EA_PCDI) != 0 && dst >> 3 != 5) {
+                return None;
+            }
+        }
+        let src = self.ea(op & 0x3f, size, if size == 1 { DATA } else { ALL })?;
+        if dst >> 3 == 1 {
+            if size == 1 {
+                return None;
+            }
+            return Some((sized("movea", size), format!("{},a{}", src, dst & 7)));
+        }
+        let dst = self.ea(dst, size, DATA_ALT)?;
+        Some((sized("move", size), format!("{},{}", src, dst)))
+    }
+
+    fn movem(&mut self, op: u16, to_memory: bool) -> Decoded {
+        let size = if op & 0x40 != 0 { 4 } else { 2 };
+        let mask = self.word();
+        let ea = op & 0x3f;
+        // ColdFire only has it long to or from (An) and (d16,An)
+        if self.version.is_coldfire() && (size != 4 || mode_class(ea) & (EA_AI | EA_DI) == 0) {
+            return None;
+        }
+        if to_memory {
+            // -(An) has the mask the other way round
+            let mask = if mode_class(ea) == EA_PD { mask.reverse_bits() } else { mask };
+            let dst = self.ea(ea, size, CONTROL_ALT | EA_PD)?;
+            Some((sized("movem", size), format!("{},{}", register_list(mask), dst)))
+        } else {
+            let src = self.ea(ea, size, CONTROL | EA_PI)?;
+            Some((sized("movem", size), format!("{},{}", src, register_list(mask))))
+        }
+    }
+
+    fn group_4(&mut self, op: u16) -> Decoded {
+        let ea = op & 0x3f;
+        let mode = (op >> 3) & 7;
+        let r = op & 7;
+        if op & 0x100 != 0 {
+            let dn = (op >> 9) & 7;
+            return match (op >> 6) & 7 {
+                7 if op & 0xfff8 == 0x49c0 => {
+                    if self.long_ops() || self.version.is_coldfire() {
+                        Some(("extb.l".to_string(), format!("d{}", r)))
+                    } else {
+                        None
+                    }
+                },
+                7 => Some(("lea".to_string(), format!("{},a{}", self.ea(ea, 4, CONTROL)?, dn))),
+                6 if !self.version.is_coldfire() => Some(("chk.w".to_string(), format!("{},d{}", self.ea(ea, 2, DATA)?, dn))),
+                4 if self.long_ops() => Some(("chk.l".to_string(), format!("{},d{}", self.ea(ea, 4, DATA)?, dn))),
+                _ => None,
+            };
+        }
+        let size = size_field(op).unwrap_or(2);
+        if self.version.is_coldfire() {
+            // NEGX, NEG and NOT are long Dn only, MOVE from SR and CCR only
+            // have Dn, MOVE to CCR and SR only Dn or an immediate, and
+            // there's no NBCD
+            let from_dn = match (op >> 6) & 0x3f {
+                0x00..=0x02 | 0x10..=0x12 | 0x18..=0x1a => mode == 0 && size == 4,
+                0x03 | 0x0b => mode == 0,
+                0x13 | 0x1b => mode == 0 || ea as u32 == OPER_IMM,
+                0x20 => false,
+                _ => true,
+            };
+            if !from_dn {
+                return None;
+            }
+        }
+        match (op >> 6) & 0x3f {
+            0x00..=0x02 => Some((sized("negx", size), self.ea(ea, size, DATA_ALT)?)),
+            0x03 => Some(("move.w".to_string(), format!("sr,{}", self.ea(ea, 2, DATA_ALT)?))),
+            0x08..=0x0a => Some((sized("clr", size), self.ea(ea, size, DATA_ALT)?)),
+            0x0b if self.m010() || self.version.is_coldfire() => {
+                Some(("move.w".to_string(), format!("ccr,{}", self.ea(ea, 2, DATA_ALT)?)))
+            },
+            0x10..=0x12 => Some((sized("neg", size), self.ea(ea, size, DATA_ALT)?)),
+            0x13 => Some(("move.w".to_string(), format!("{},ccr", self.ea(ea, 2, DATA)?))),
+            0x18..=0x1a => Some((sized("not", size), self.ea(ea, size, DATA_ALT)?)),
+            0x1b => Some(("move.w".to_string(), format!("{},sr", self.ea(ea, 2, DATA)?))),
+            0x20 if mode == 1 => {
+                if !self.long_ops() {
+                    return None;
+                }
+                Some(("link.l".to_string(), format!("a{},#{}", r, signed(self.long() as i32))))
+            },
+            0x20 => Some(("nbcd".to_string(), self.ea(ea, 1, DATA_ALT)?)),
+            0x21 if mode == 0 => Some(("swap".to_string(), format!("d{}", r))),
+            0x21 if mode == 1 => {
+                if !self.m010() {
+                    return None;
+                }
+                Some(("bkpt".to_string(), format!("#{}", r)))
+            },
+            0x21 => Some(("pea".to_string(), self.ea(ea, 4, CONTROL)?)),
+            0x22 | 0x23 if mode == 0 => Some((sized("ext", if op & 0x40 != 0 { 4 } else { 2 }), format!("d{}", r))),
+            0x22 | 0x23 => self.movem(op, true),
+            0x28..=0x2a => {
+                let allowed = match (self.long_ops() || self.version.is_coldfire(), size) {
+                    (false, _) => DATA_ALT,
+                    (true, 1) => DATA,
+                    (true, _) => ALL,
+                };
+                Some((sized("tst", size), self.ea(ea, size, allowed)?))
+            },
+            0x2b => match op as u32 {
+                OP_ILLEGAL => Some(("illegal".to_string(), String::new())),
+                OP_BGND if self.version == Version::CPU32 => Some(("bgnd".to_string(), String::new())),
+                // ColdFire got TAS with ISA_B, to memory only
+                _ if self.version.is_coldfire() && (!self.isa_b() || mode == 0) => None,
+                _ => Some(("tas".to_string(), self.ea(ea, 1, DATA_ALT)?)),
+            },
+            0x30 | 0x31 if self.long_ops() || self.version.is_coldfire() => self.mul_div_long(op),
+            0x32 if self.isa_b() && op as u32 & MASK_OUT_Y == OP_SATS_32 => Some(("sats.l".to_string(), format!("d{}", r))),
+            0x32 | 0x33 => self.movem(op, false),
+            0x39 => self.group_4e(op),
+            0x3a => Some(("jsr".to_string(), self.ea(ea, 4, CONTROL)?)),
+            0x3b => Some(("jmp".to_string(), self.ea(ea, 4, CONTROL)?)),
+            _ => None,
+        }
+    }
+
+    fn mul_div_long(&mut self, op: u16) -> Decoded {
+        let extension = self.word();
+        let sign = if extension & 0x800 != 0 { "s" } else { "u" };
+        let quad = extension & 0x400 != 0;
+        if quad && self.version.is_coldfire() {
+            return None;
+        }
+        let low = (extension >> 12) & 7;
+        let high = extension & 7;
+        let src = self.ea(op & 0x3f, 4, DATA)?;
+        Some(if op & 0x40 == 0 {
+            let dst = if quad { format!("d{}:d{}", high, low) } else { format!("d{}", low) };
+            (format!("mul{}.l", sign), format!("{},{}", src, dst))
+        } else if quad || high == low {
+            let dst = if quad { format!("d{}:d{}", high, low) } else { format!("d{}", low) };
+            (format!("div{}.l", sign), format!("{},{}", src, dst))
+        } else if self.version.is_coldfire() {
+            (format!("rem{}.l", sign), format!("{},d{}:d{}", src, high, low))
+        } else {
+            (format!("div{}l.l", sign), format!("{},d{}:d{}", src, high, low))
+        })
+    }
+
+    // 0x4e40-0x4e7f
+    fn group_4e(&mut self, op: u16) -> Decoded {
+        let r = op & 7;
+        let none = |name: &str| Some((name.to_string(), String::new()));
+        match op & 0x3f {
+            0x00..=0x0f => Some(("trap".to_string(), format!("#{}", op & 0xf))),
+            0x10..=0x17 => Some(("link.w".to_string(), format!("a{},#{}", r, signed(self.word() as i16 as i32)))),
+            0x18..=0x1f => Some(("unlk".to_string(), format!("a{}", r))),
+            0x20..=0x2f if self.version.is_coldfire() && !self.isa_b() => None,
+            0x20..=0x27 => Some(("move.l".to_string(), format!("a{},usp", r))),
+            0x28..=0x2f => Some(("move.l".to_string(), format!("usp,a{}", r))),
+            0x30 | 0x36 | 0x37 | 0x3a if self.version.is_coldfire() => None,
+            0x30 => none("reset"),
+            0x31 => none("nop"),
+            0x32 => Some(("stop".to_string(), format!("#{}", hex(self.word() as u32)))),
+            0x33 => none("rte"),
+            0x34 if self.m010() => Some(("rtd".to_string(), format!("#{}", signed(self.word() as i16 as i32)))),
+            0x35 => none("rts"),
+            0x36 => none("trapv"),
+            0x37 => none("rtr"),
+            0x3a | 0x3b if self.m010() || self.version.is_coldfire() => {
+                let extension = self.word();
+                let cr = control_register(self.version, extension & 0xfff);
+                Some(if op & 1 == 0 {
+                    ("movec".to_string(), format!("{},{}", cr, reg(extension >> 12)))
+                } else {
+                    ("movec".to_string(), format!("{},{}", reg(extension >> 12), cr))
+                })
+            },
+            _ => None,
+        }
+    }
+
+    // ADDQ, SUBQ, Scc, DBcc and TRAPcc
+    fn group_5(&mut self, op: u16) -> Decoded {
+        let ea = op & 0x3f;
+        if let Some(size) = size_field(op) {
+            let data = match (op >> 9) & 7 {
+                0 => 8,
+                data => data,
+            };
+            if self.version.is_coldfire() && size != 4 {
+                return None;
+            }
+            let name = if op & 0x100 != 0 { "subq" } else { "addq" };
+            let allowed = if size == 1 { DATA_ALT } else { ALTERABLE };
+            return Some((sized(name, size), format!("#{},{}", data, self.ea(ea, size, allowed)?)));
+        }
+        let cc = condition(op);
+        match ea {
+            0x08..=0x0f if self.version.is_coldfire() => None,
+            0x08..=0x0f => {
+                let at = self.next;
+                let target = at.wrapping_add(self.word() as i16 as u32);
+                Some((format!("db{}", cc), format!("d{},{}", op & 7, hex(target))))
+            },
+            0x3a if self.long_ops() => Some((format!("trap{}.w", cc), format!("#{}", hex(self.word() as u32)))),
+            0x3b if self.long_ops() => Some((format!("trap{}.l", cc), format!("#{}", hex(self.long())))),
+            0x3c if self.long_ops() => Some((format!("trap{}", cc), String::new())),
+            _ if self.version.is_coldfire() && ea >> 3 != 0 => None,
+            _ => Some((format!("s{}", cc), self.ea(ea, 1, DATA_ALT)?)),
+        }
+    }
+
+    fn branch(&mut self, op: u16) -> Decoded {
+        let at = self.next;
+        let (size, disp) = match op & 0xff {
+            0 => (".w", self.word() as i16 as u32),
+            0xff if self.long_ops() || self.isa_b() => (".l", self.long()),
+            0xff if self.version.is_coldfire() => return None,
+            disp => (".s", disp as i8 as u32),
+        };
+        let name = match op as u32 & 0xf00 {
+            IF_T => "bra".to_string(),
+            IF_F => "bsr".to_string(),
+            _ => format!("b{}", condition(op)),
+        };
+        Some((name + size, hex(at.wrapping_add(disp))))
+    }
+
+    // MOVEQ, and ColdFire's MVS and MVZ
+    fn group_7(&mut self, op: u16) -> Decoded {
+        let dn = (op >> 9) & 7;
+        if op & 0x100 == 0 {
+            return Some(("moveq".to_string(), format!("#{},d{}", signed(op as i8 as i32), dn)));
+        }
+        if !self.isa_b() {
+            return None;
+        }
+        let size = if op & 0x40 != 0 { 2 } else { 1 };
+        let name = if op & 0x80 != 0 { "mvz" } else { "mvs" };
+        let src = self.ea(op & 0x3f, size, if size == 1 { DATA } else { ALL })?;
+        Some((sized(name, size), format!("{},d{}", src, dn)))
+    }
+
+    // the register to register and memory to memory forms of ABCD, ADDX
+    // and friends
+    fn extended(&self, name: String, op: u16) -> Decoded {
+        let (x, y) = ((op >> 9) & 7, op & 7);
+        Some(if op & 8 == 0 {
+            (name, format!("d{},d{}", y, x))
+        } else {
+            (name, format!("-(a{}),-(a{})", y, x))
+        })
+    }
+
+    // OR, DIVU, DIVS, SBCD, PACK and UNPK
+    fn group_8(&mut self, op: u16) -> Decoded {
+        let ea = op & 0x3f;
+        let mode = (op >> 3) & 7;
+        let dn = (op >> 9) & 7;
+        // ColdFire has no SBCD, and only OR.L
+        if self.version.is_coldfire() && !matches!((op >> 6) & 7, 2 | 3 | 6 | 7) {
+            return None;
+        }
+        match (op >> 6) & 7 {
+            3 => Some(("divu.w".to_string(), format!("{},d{}", self.ea(ea, 2, DATA)?, dn))),
+            7 => Some(("divs.w".to_string(), format!("{},d{}", self.ea(ea, 2, DATA)?, dn))),
+            4 if mode <= 1 => self.extended("sbcd".to_string(), op),
+            opmode @ 5..=6 if mode <= 1 => {
+                if !self.m020() {
+                    return None;
+                }
+                let adjustment = self.word();
+                let (name, operands) = self.extended((if opmode == 5 { "pack" } else { "unpk" }).to_string(), op)?;
+                Some((name, format!("{},#{}", operands, hex(adjustment as u32))))
+            },
+            opmode @ 0..=2 => {
+                let size = 1 << opmode;
+                Some((sized("or", size), format!("{},d{}", self.ea(ea, size, DATA)?, dn)))
+            },
+            opmode => {
+                let size = 1 << (opmode - 4);
+                Some((sized("or", size), format!("d{},{}", dn, self.ea(ea, size, MEMORY_ALT)?)))
+            },
+        }
+    }
+
+    fn add_sub(&mut self, op: u16) -> Decoded {
+        let name = if op >> 12 == 0x9 { "sub" } else { "add" };
+        let ea = op & 0x3f;
+        let r = (op >> 9) & 7;
+        // ColdFire only has the long forms, and ADDX and SUBX only to Dn
+        if self.version.is_coldfire() && (!matches!((op >> 6) & 7, 2 | 6 | 7) || op & 0x1f8 == 0x188) {
+            return None;
+        }
+        match (op >> 6) & 7 {
+            opmode @ 3 | opmode @ 7 => {
+                let size = if opmode == 3 { 2 } else { 4 };
+                Some((sized(&format!("{}a", name), size), format!("{},a{}", self.ea(ea, size, ALL)?, r)))
+            },
+            opmode @ 0..=2 => {
+                let size = 1 << opmode;
+                let src = self.ea(ea, size, if size == 1 { DATA } else { ALL })?;
+                Some((sized(name, size), format!("{},d{}", src, r)))
+            },
+            opmode if (op >> 3) & 7 <= 1 => self.extended(sized(&format!("{}x", name), 1 << (opmode - 4)), op),
+            opmode => {
+                let size = 1 << (opmode - 4);
+                Some((sized(name, size), format!("d{},{}", r, self.ea(ea, size, MEMORY_ALT)?)))
+            },
+        }
+    }
+
+    // CMP, CMPA, CMPM and EOR
+    fn group_b(&mut self, op: u16) -> Decoded {
+        let ea = op & 0x3f;
+        let r = (op >> 9) & 7;
+        // ColdFire has EOR.L and no CMPM, and CMP.B, CMP.W and CMPA.W came
+        // with ISA_B
+        if self.version.is_coldfire() {
+            let allowed = match (op >> 6) & 7 {
+                0 | 1 | 3 => self.isa_b(),
+                opmode => opmode == 2 || opmode == 7 || (opmode == 6 && (op >> 3) & 7 != 1),
+            };
+            if !allowed {
+                return None;
+            }
+        }
+        match (op >> 6) & 7 {
+            opmode @ 3 | opmode @ 7 => {
+                let size = if opmode == 3 { 2 } else { 4 };
+                Some((sized("cmpa", size), format!("{},a{}", self.ea(ea, size, ALL)?, r)))
+            },
+            opmode @ 0..=2 => {
+                let size = 1 << opmode;
+                let src = self.ea(ea, size, if size == 1 { DATA } else { ALL })?;
+                Some((sized("cmp", size), format!("{},d{}", src, r)))
+            },
+            opmode if (op >> 3) & 7 == 1 => {
+                Some((sized("cmpm", 1 << (opmode - 4)), format!("(a{})+,(a{})+", op & 7, r)))
+            },
+            opmode => {
+                let size = 1 << (opmode - 4);
+                Some((sized("eor", size), format!("d{},{}", r, self.ea(ea, size, DATA_ALT)?)))
+            },
+        }
+    }
+
+    // AND, MULU, MULS, ABCD and EXG
+    fn group_c(&mut self, op: u16) -> Decoded {
+        let ea = op & 0x3f;
+        let mode = (op >> 3) & 7;
+        let (x, y) = ((op >> 9) & 7, op & 7);
+        // ColdFire has no ABCD or EXG, and only AND.L
+        if self.version.is_coldfire() && (!matches!((op >> 6) & 7, 2 | 3 | 6 | 7) || op & 0x1f8 == 0x188) {
+            return None;
+        }
+        match ((op >> 6) & 7, mode) {
+            (3, _) => Some(("mulu.w".to_string(), format!("{},d{}", self.ea(ea, 2, DATA)?, x))),
+            (7, _) => Some(("muls.w".to_string(), format!("{},d{}", self.ea(ea, 2, DATA)?, x))),
+            (4, 0) | (4, 1) => self.extended("abcd".to_string(), op),
+            (5, 0) => Some(("exg".to_string(), format!("d{},d{}", x, y))),
+            (5, 1) => Some(("exg".to_string(), format!("a{},a{}", x, y))),
+            (6, 1) => Some(("exg".to_string(), format!("d{},a{}", x, y))),
+            (opmode @ 0..=2, _) => {
+                let size = 1 << opmode;
+                Some((sized("and", size), format!("{},d{}", self.ea(ea, size, DATA)?, x)))
+            },
+            (opmode, _) => {
+                let size = 1 << (opmode - 4);
+                Some((sized("and", size), format!("d{},{}", x, self.ea(ea, size, MEMORY_ALT)?)))
+            },
+        }
+    }
+
+    // shifts, rotates and the 020 bit fields
+    fn group_e(&mut self, op: u16) -> Decoded {
+        const SHIFTS: [&str; 4] = ["as", "ls", "rox", "ro"];
+        let direction = if op & 0x100 != 0 { "l" } else { "r" };
+        let ea = op & 0x3f;
+        match size_field(op) {
+            // ColdFire only has ASx and LSx.L on a register
+            Some(size) if self.version.is_coldfire() && (size != 4 || op & 0x10 != 0) => None,
+            Some(size) => {
+                let name = format!("{}{}", SHIFTS[((op >> 3) & 3) as usize], direction);
+                let count = match (op & 0x20 != 0, (op >> 9) & 7) {
+                    (true, r) => format!("d{}", r),
+                    (false, 0) => "#8".to_string(),
+                    (false, count) => format!("#{}", count),
+                };
+                Some((sized(&name, size), format!("{},d{}", count, op & 7)))
+            },
+            None if op & 0x800 == 0 => {
+                if self.version.is_coldfire() {
+                    return None;
+                }
+                let name = format!("{}{}", SHIFTS[((op >> 9) & 3) as usize], direction);
+                Some((sized(&name, 2), self.ea(ea, 2, MEMORY_ALT)?))
+            },
+            None => {
+                if !self.m020() {
+                    return None;
+                }
+                const FIELDS: [&str; 8] = ["bftst", "bfextu", "bfchg", "bfexts", "bfclr", "bfffo", "bfset", "bfins"];
+                let kind = (op >> 8) & 7;
+                let extension = self.word();
+                let allowed = if kind & 1 == 1 || kind == 0 { EA_DN | CONTROL } else { EA_DN | CONTROL_ALT };
+                let offset = if extension & 0x800 != 0 {
+                    format!("d{}", (extension >> 6) & 7)
+                } else {
+                    format!("{}", (extension >> 6) & 0x1f)
+                };
+                let width = match (extension & 0x20 != 0, extension & 0x1f) {
+                    (true, r) => format!("d{}", r & 7),
+                    (false, 0) => "32".to_string(),
+                    (false, width) => format!("{}", width),
+                };
+                let field = format!("{}{{{}:{}}}", self.ea(ea, 0, allowed)?, offset, width);
+                let dn = (extension >> 12) & 7;
+                let operands = match kind {
+                    1 | 3 | 5 => format!("{},d{}", field, dn),
+                    7 => format!("d{},{}", dn, field),
+                    _ => field,
+                };
+                Some((FIELDS[kind as usize].to_string(), operands))
+            },
+        }
+    }
+
+    // ColdFire's MOV3Q and MAC unit, the A-line traps everywhere else
+    fn line_a(&mut self, op: u16) -> Decoded {
+        if !self.version.is_coldfire() {
+            return None;
+        }
+        if op as u32 & 0xf1c0 == OP_MOV3Q_32 {
+            if !self.isa_b() {
+                return None;
+            }
+            let data = match (op >> 9) & 7 {
+                0 => -1,
+                data => data as i32,
+            };
+            return Some(("mov3q.l".to_string(), format!("#{},{}", data, self.ea(op & 0x3f, 4, DATA_ALT)?)));
+        }
+        const MAC_REGISTERS: [&str; 8] = ["acc0", "acc1", "acc2", "acc3", "macsr", "accext01", "mask", "accext23"];
+        let target = ((op >> 9) & 7) as usize;
+        if op & 0x100 == 0 {
+            // MAC and MSAC, with or without a parallel load
+            let extension = self.word();
+            let load = op & 0x30 != 0;
+            let (rx, ry) = if load { (extension >> 12, extension & 0xf) } else { ((op >> 9) & 7 | (op >> 3) & 8, op & 0xf) };
+            let long = extension & 0x800 != 0;
+            let half = |r: u16, upper: bool| if long { reg(r) } else { format!("{}.{}", reg(r), if upper { "u" } else { "l" }) };
+            let mut operands = format!("{},{}", half(ry, extension & 0x40 != 0), half(rx, extension & 0x80 != 0));
+            match (extension >> 9) & 3 {
+                1 => operands.push_str(",<<"),
+                3 => operands.push_str(",>>"),
+                _ => (),
+            }
+            if load {
+                let src = self.ea(op & 0x3f, 4, EA_AI | EA_PI | EA_PD | EA_DI)?;
+                let mask = if extension & 0x20 != 0 { "&" } else { "" };
+                operands = format!("{},{}{},{}", operands, src, mask, reg((op >> 9) & 7 | (op >> 3) & 8));
+            }
+            let acc = (op >> 7) & 1 | (extension >> 3) & 2;
+            operands = format!("{},acc{}", operands, acc);
+            let name = if extension & 0x100 != 0 { "msac" } else { "mac" };
+            return Some((sized(name, if long { 4 } else { 2 }), operands));
+        }
+        match (op >> 6) & 3 {
+            0 if (op >> 3) & 7 == 2 && target < 4 => Some(("move.l".to_string(), format!("acc{},acc{}", op & 3, target))),
+            0 if (op >> 3) & 7 == 2 => None,
+            0 => {
+                let src = self.ea(op & 0x3f, 4, EA_DN | EA_AN | EA_IMM)?;
+                Some(("move.l".to_string(), format!("{},{}", src, MAC_REGISTERS[target])))
+            },
+            _ if op == 0xa9c0 => Some(("move.l".to_string(), "macsr,ccr".to_string())),
+            _ if op & 0x30 != 0 => None,
+            _ if op & 0x40 != 0 => Some(("movclr.l".to_string(), format!("acc{},{}", target & 3, reg(op & 0xf)))),
+            _ => Some(("move.l".to_string(), format!("{},{}", MAC_REGISTERS[target], reg(op & 0xf)))),
+        }
+    }
+
+    fn line_f(&mut self, op: u16) -> Decoded {
+        match self.version.base() {
+            Version::MC68020 if (op >> 9) & 7 == 0 => self.pmmu(op),
+            Version::MC68040 | Version::MC68060 if op & 0xfe00 == 0xf400 => self.mmu_040(op),
+            Version::MC68040 | Version::MC68060 if op & 0xffc0 == 0xf600 => self.move16(op),
+            Version::MC68060 | Version::CPU32 if op as u32 == OP_LPSTOP => {
+                let extension = self.word();
+                if extension == 0x01c0 {
+                    return Some(("lpstop".to_string(), format!("#{}", hex(self.word() as u32))));
+                }
+                if self.version != Version::CPU32 {
+                    return None;
+                }
+                self.table(op, extension)
+            },
+            Version::CPU32 if op & 0xffc0 == OP_TBL as u16 => {
+                let extension = self.word();
+                self.table(op, extension)
+            },
+            _ => None,
+        }
+    }
+
+    // CPU32 TBLS and TBLU, the extension word has already been read
+    fn table(&mut self, op: u16, extension: u16) -> Decoded {
+        let size = size_field(extension)?;
+        if extension & 0x8100 != 0 {
+            return None;
+        }
+        let name = format!("tbl{}{}", if extension & 0x800 != 0 { "s" } else { "u" },
+                           if extension & 0x400 != 0 { "n" } else { "" });
+        let dx = (extension >> 12) & 7;
+        if op & 0x38 == 0 {
+            return Some((sized(&name, size), format!("d{}:d{},d{}", op & 7, extension & 7, dx)));
+        }
+        let src = self.ea(op & 0x3f, size, CONTROL)?;
+        Some((sized(&name, size), format!("{},d{}", src, dx)))
+    }
+
+    // the 68851 on an 020, coprocessor 0
+    fn pmmu(&mut self, op: u16) -> Decoded {
+        let ea = op & 0x3f;
+        match (op >> 6) & 7 {
+            0 => self.pmmu_general(op),
+            1 => {
+                let extension = self.word();
+                let cc = pmmu_condition(extension)?;
+                match ea {
+                    0x08..=0x0f => {
+                        let at = self.next;
+                        let target = at.wrapping_add(self.word() as i16 as u32);
+                        Some((format!("pdb{}", cc), format!("d{},{}", op & 7, hex(target))))
+                    },
+                    0x3a => Some((format!("ptrap{}.w", cc), format!("#{}", hex(self.word() as u32)))),
+                    0x3b => Some((format!("ptrap{}.l", cc), format!("#{}", hex(self.long())))),
+                    0x3c => Some((format!("ptrap{}", cc), String::new())),
+                    _ => Some((format!("ps{}", cc), self.ea(ea, 1, DATA_ALT)?)),
+                }
+            },
+            kind @ 2..=3 => {
+                let cc = pmmu_condition(op)?;
+                let at = self.next;
+                let (size, disp) = if kind == 2 { (".w", self.word() as i16 as u32) } else { (".l", self.long()) };
+                Some((format!("pb{}{}", cc, size), hex(at.wrapping_add(disp))))
+            },
+            4 => Some(("psave".to_string(), self.ea(ea, 0, CONTROL_ALT | EA_PD)?)),
+            5 => Some(("prestore".to_string(), self.ea(ea, 0, CONTROL | EA_PI)?)),
+            _ => None,
+        }
+    }
+
+    fn pmmu_general(&mut self, op: u16) -> Decoded {
+        let ea = op & 0x3f;
+        let extension = self.word();
+        let none = |name: &str| Some((name.to_string(), String::new()));
+        match extension >> 13 {
+            0b001 if extension & 0xfc00 == 0x2800 => {
+                Some(("pvalid".to_string(), format!("val,{}", self.ea(ea, 4, CONTROL_ALT)?)))
+            },
+            0b001 if extension & 0xfc00 == 0x2c00 => {
+                Some(("pvalid".to_string(), format!("a{},{}", extension & 7, self.ea(ea, 4, CONTROL_ALT)?)))
+            },
+            0b001 => {
+                let mode = (extension >> 10) & 7;
+                if mode == 0b001 {
+                    return none("pflusha");
+                }
+                let fc = pmmu_fc(extension)?;
+                let mask = (extension >> 5) & 0xf;
+                let name = if mode & 1 == 1 { "pflushs" } else { "pflush" };
+                match mode {
+                    0b000 => {
+                        let name = if extension & 0x200 != 0 { "ploadr" } else { "ploadw" };
+                        Some((name.to_string(), format!("{},{}", fc, self.ea(ea, 4, CONTROL_ALT)?)))
+                    },
+                    0b100 | 0b101 => Some((name.to_string(), format!("{},#{}", fc, mask))),
+                    0b110 | 0b111 => Some((name.to_string(), format!("{},#{},{}", fc, mask, self.ea(ea, 4, CONTROL_ALT)?))),
+                    _ => None,
+                }
+            },
+            kind @ 0b010..=0b011 => {
+                let preg = (extension >> 10) & 7;
+                let num = (extension >> 2) & 7;
+                let (name, size) = match (kind, preg) {
+                    (0b010, 0b000) => ("tc".to_string(), 4),
+                    (0b010, 0b001) => ("drp".to_string(), 8),
+                    (0b010, 0b010) => ("srp".to_string(), 8),
+                    (0b010, 0b011) => ("crp".to_string(), 8),
+                    (0b010, 0b100) => ("cal".to_string(), 1),
+                    (0b010, 0b101) => ("val".to_string(), 1),
+                    (0b010, 0b110) => ("scc".to_string(), 1),
+                    (0b010, _) => ("ac".to_string(), 2),
+                    (_, 0b000) => ("psr".to_string(), 2),
+                    (_, 0b001) => ("pcsr".to_string(), 2),
+                    (_, 0b100) => (format!("bad{}", num), 2),
+                    (_, 0b101) => (format!("bac{}", num), 2),
+                    _ => return None,
+                };
+                let mnemonic = if extension & 0x100 != 0 { "pmovefd" } else { "pmove" };
+                let registers = if size == 8 { !(EA_DN | EA_AN) } else { ALL };
+                Some(if extension & 0x200 != 0 {
+                    (mnemonic.to_string(), format!("{},{}", name, self.ea(ea, size, ALTERABLE & registers)?))
+                } else {
+                    (mnemonic.to_string(), format!("{},{}", self.ea(ea, size, ALL & registers)?, name))
+                })
+            },
+            0b100 => {
+                let fc = pmmu_fc(extension)?;
+                let level = (extension >> 10) & 7;
+                let name = if extension & 0x200 != 0 { "ptestr" } else { "ptestw" };
+                let dst = self.ea(ea, 4, CONTROL_ALT)?;
+                let mut operands = format!("{},{},#{}", fc, dst, level);
+                if extension & 0x100 != 0 {
+                    operands = format!("{},a{}", operands, (extension >> 5) & 7);
+                }
+                Some((name.to_string(), operands))
+            },
+            0b101 => Some(("pflushr".to_string(), self.ea(ea, 8, ALL & !(EA_DN | EA_AN))?)),
+            _ => None,
+        }
+    }
+
+    // '040 CINV, CPUSH, PFLUSH and PTEST, and the '060 PLPA
+    fn mmu_040(&mut self, op: u16) -> Decoded {
+        let r = op & 7;
+        if op & 0xff00 == 0xf400 {
+            const CACHES: [&str; 4] = ["nc", "dc", "ic", "bc"];
+            let caches = CACHES[((op >> 6) & 3) as usize];
+            let name = if op & 0x20 != 0 { "cpush" } else { "cinv" };
+            return match (op >> 3) & 3 {
+                1 => Some((format!("{}l", name), format!("{},(a{})", caches, r))),
+                2 => Some((format!("{}p", name), format!("{},(a{})", caches, r))),
+                3 => Some((format!("{}a", name), caches.to_string())),
+                _ => None,
+            };
+        }
+        match op & 0xfff8 {
+            0xf500 => Some(("pflushn".to_string(), format!("(a{})", r))),
+            0xf508 => Some(("pflush".to_string(), format!("(a{})", r))),
+            0xf510 => Some(("pflushan".to_string(), String::new())),
+            0xf518 => Some(("pflusha".to_string(), String::new())),
+            0xf548 => Some(("ptestw".to_string(), format!("(a{})", r))),
+            0xf568 => Some(("ptestr".to_string(), format!("(a{})", r))),
+            0xf588 if self.version == Version::MC68060 => Some(("plpaw".to_string(), format!("(a{})", r))),
+            0xf5c8 if self.version == Version::MC68060 => Some(("plpar".to_string(), format!("(a{})", r))),
+            _ => None,
+        }
+    }
+
+    fn move16(&mut self, op: u16) -> Decoded {
+        let r = op & 7;
+        let operands = match (op >> 3) & 7 {
+            0b100 => {
+                let extension = self.word();
+                if extension & 0x8fff != 0x8000 {
+                    return None;
+                }
+                format!("(a{})+,(a{})+", r, (extension >> 12) & 7)
+            },
+            0b000 => format!("(a{})+,{}.l", r, hex(self.long())),
+            0b001 => format!("{}.l,(a{})+", hex(self.long()), r),
+            0b010 => format!("(a{}),{}.l", r, hex(self.long())),
+            0b011 => format!("{}.l,(a{})", hex(self.long()), r),
+            _ => return None,
+        };
+        Some(("move16".to_string(), operands))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::small_map;
+    use SUPERVISOR_PROGRAM;
+
+    fn check(version: Version, words: &[u16], text: &str, len: u32) {
+        let decoded = disassemble_words(version, 0x1000, words);
+        assert_eq!((decoded.to_string().as_str(), decoded.len), (text, len), "{:?} {:04x?}", version, words);
+    }
+
+    #[test]
+    fn operands_come_out_in_motorola_syntax() {
+        check(Version::MC68000, &[0x4e71], "nop", 2);
+        check(Version::MC68000, &[0xd081], "add.l   d1,d0", 2);
+        check(Version::MC68000, &[0x0c40, 0x1234], "cmpi.w  #$1234,d0", 4);
+        check(Version::MC68000, &[0x2039, 0x0001, 0x2345], "move.l  $12345.l,d0", 6);
+        check(Version::MC68000, &[0x3038, 0x8000], "move.w  $8000.w,d0", 4);
+        check(Version::MC68000, &[0x48e7, 0xc0c0], "movem.l d0-d1/a0-a1,-(a7)", 4);
+        check(Version::MC68000, &[0x4cdf, 0x0303], "movem.l (a7)+,d0-d1/a0-a1", 4);
+        check(Version::MC68020, &[0x2030, 0x0a10], "move.l  ($10,a0,d0.l*2),d0", 4);
+    }
+
+    #[test]
+    fn pc_relative_operands_show_the_address_they_reach() {
+        check(Version::MC68000, &[0x303a, 0x0010], "move.w  ($1012,pc),d0", 4);
+        check(Version::MC68000, &[0x6700, 0x0010], "beq.w   $1012", 4);
+        check(Version::MC68000, &[0x66fe], "bne.s   $1000", 2);
+    }
+
+    #[test]
+    fn what_the_version_lacks_is_a_dc_w() {
+        check(Version::MC68000, &[0x4c00, 0x0800], "dc.w    $4c00", 2);
+        check(Version::MC68020, &[0x4c00, 0x0800], "muls.l  d0,d0", 4);
+        check(Version::MC68000, &[0x4e7a, 0x0801], "dc.w    $4e7a", 2);
+        check(Version::MC68010, &[0x4e7a, 0x0801], "movec   vbr,d0", 4);
+        check(Version::MC68000, &[0x4afc], "illegal", 2);
+    }
+
+    #[test]
+    fn coldfire_shows_only_its_own_forms() {
+        check(Version::ColdFireIsaA, &[0xd081], "add.l   d1,d0", 2);
+        check(Version::ColdFireIsaA, &[0xd041], "dc.w    $d041", 2);
+        check(Version::ColdFireIsaA, &[0x4cd8, 0x0003], "dc.w    $4cd8", 2);
+        check(Version::ColdFireIsaA, &[0x00c0], "dc.w    $c0", 2);
+        check(Version::ColdFireIsaC, &[0x00c0], "bitrev.l d0", 2);
+        check(Version::ColdFireIsaB, &[0xa340], "mov3q.l #1,d0", 2);
+    }
+
+    #[test]
+    fn the_bus_is_peeked_from_pc() {
+        let bus = small_map(0x8000, &[0x303a, 0x0010]);
+        let decoded = disassemble_bus(&bus, SUPERVISOR_PROGRAM, Version::MC68000, 0x400);
+        assert_eq!((decoded.to_string().as_str(), decoded.len), ("move.w  ($412,pc),d0", 4));
+    }
+}
@@ -0,0 +1,337 @@
+// Exception processing (ref M68000PRM 1.3, MC68010 5.5, MC68020UM 6.4,
+// MC68040UM 8.4, MC68060UM 8.4, CPU32RM 6.2, ColdFire PRM 11.1). Each model stacks its own frame formats, everything
+// from the '010 on has a format/vector offset word and RTE uses it to
+// know how much to unstack.
+
+use Bus;
+use M68k;
+use Exception;
+use Version;
+use ProcessingState;
+use Result;
+use instructions::constants::*;
+
+// approximate exception processing times, these come from Musashi. In
+// cycle exact mode the 68000 sequences of prefetch.rs are placed instead.
+fn exception_cycles(version: Version, vector: u8) -> u32 {
+    match version {
+        Version::MC68000 | Version::MC68008 => match vector {
+            EXCEPTION_BUS_ERROR | EXCEPTION_ADDRESS_ERROR => 50,
+            EXCEPTION_ZERO_DIVIDE => 38,
+            EXCEPTION_CHK => 40,
+            24..=31 => 44,
+            _ => 34,
+        },
+        Version::MC68010 | Version::CPU32 => match vector {
+            EXCEPTION_BUS_ERROR | EXCEPTION_ADDRESS_ERROR => 126,
+            EXCEPTION_ZERO_DIVIDE | EXCEPTION_CHK => 44,
+            EXCEPTION_TRAPV => 34,
+            24..=31 => 46,
+            _ => 38,
+        },
+        Version::MC68020 | Version::MC68EC020 | Version::MC68040 | Version::MC68060 => match vector {
+            EXCEPTION_BUS_ERROR | EXCEPTION_ADDRESS_ERROR => 50,
+            EXCEPTION_ZERO_DIVIDE => 38,
+            EXCEPTION_CHK => 40,
+            EXCEPTION_PRIVILEGE_VIOLATION => 34,
+            EXCEPTION_TRACE => 25,
+            24..=31 => 26,
+            _ => 20,
+        },
+        Version::ColdFireIsaA | Version::ColdFireIsaB | Version::ColdFireIsaC => match vector {
+            24..=31 => 14,
+            _ => 12,
+        },
+    }
+}
+
+impl<'a> M68k<'a> {
+    // Takes the exception, returns the cycles used including any the
+    // instruction spent before it faulted
+    pub fn exception<T: Bus + ?Sized>(&mut self, bus: &mut T, e: Exception) -> u32 {
+        let (vector, pc, cycles) = match e {
+            Exception::AddressError => (EXCEPTION_ADDRESS_ERROR, self.ppc, 0),
+            Exception::BusError(..) => (EXCEPTION_BUS_ERROR, self.ppc, 0),
+            // faulted '040 writes are reported after the instruction
+            Exception::AccessFault(_, _, write) => {
+                let after = write && self.version == Version::MC68040;
+                (EXCEPTION_BUS_ERROR, if after { self.pc } else { self.ppc }, 0)
+            },
+            Exception::IllegalInstruction(_, pc) => (EXCEPTION_ILLEGAL_INSTRUCTION, pc, 0),
+            Exception::Trap(vector, cycles) => (vector, self.pc, cycles),
+            Exception::PrivilegeViolation(_, pc) => (EXCEPTION_PRIVILEGE_VIOLATION, pc, 0),
+            Exception::UnimplementedInstruction(_, pc, vector) => (vector, pc, 0),
+            Exception::Interrupt(_, vector) => (vector, self.pc, 0),
+        };
+        let group_0 = matches!(e, Exception::AddressError | Exception::BusError(..) | Exception::AccessFault(..));
+        if self.exact() {
+            self.plan_exception(&e, vector);
+        }
+
+        // anything else restarts the instruction, its held back writes
+        // will happen again
+        if !matches!(e, Exception::AccessFault(_, _, true)) {
+            self.writebacks.clear();
+        }
+
+        let sr = self.status_register();
+        // sr_to_flags swaps in the supervisor stack for us
+        self.sr_to_flags(sr | SFLAG_SET as u16);
+        if let Exception::Interrupt(irq, _) = e {
+            self.int_mask = irq as u32;
+        }
+
+        let result = self.stack_frame(bus, &e, vector, sr, pc)
+            .and_then(|_| match self.writebacks.pop() {
+                // a held back '040 write here means the stack itself faulted
+                Some(wb) => Err(Exception::AccessFault(wb.addr, wb.fc, true)),
+                None => Ok(()),
+            })
+            .and_then(|_| {
+                let vbr = self.vbr;
+                self.read_data_32(bus, vbr.wrapping_add(vector as u32 * 4))
+            })
+            .and_then(|handler| {
+                self.pc = handler;
+                // in cycle exact mode the handler's first words are fetched here
+                if self.placing() { self.finish_step(bus, 0) } else { Ok(()) }
+            });
+        match result {
+            Ok(()) => cycles + exception_cycles(self.version, vector),
+            Err(fault) => {
+                if group_0 {
+                    // double bus fault
+                    self.processing_state = ProcessingState::Halted;
+                    cycles + 4
+                } else {
+                    cycles + self.exception(bus, fault)
+                }
+            },
+        }
+    }
+
+    fn stack_frame<T: Bus + ?Sized>(&mut self, bus: &mut T, e: &Exception, vector: u8, sr: u16, pc: u32) -> Result<()> {
+        let (fault_address, fc, write) = match *e {
+            Exception::BusError(address, fc, write) |
+            Exception::AccessFault(address, fc, write) => (address, fc, write),
+            // we only raise address errors for instruction fetches
+            _ => (self.pc, self.program_space().fc() as u8, false),
+        };
+        let ir = self.ir;
+        let vo = vector as u16 * 4;
+        let group_0 = matches!(*e, Exception::AddressError | Exception::BusError(..) | Exception::AccessFault(..));
+        let pc_hi = (pc >> 16) as u16;
+        let pc_lo = pc as u16;
+        let fa_hi = (fault_address >> 16) as u16;
+        let fa_lo = fault_address as u16;
+        let ppc = self.ppc;
+
+        match self.version {
+            Version::MC68000 | Version::MC68008 => {
+                if group_0 {
+                    let instruction = match *e { Exception::AddressError => 0, _ => 0x08 };
+                    let ssw = if write { 0 } else { 0x10 } | instruction | fc as u16;
+                    self.write_frame_in(bus, &[ssw, fa_hi, fa_lo, ir, sr, pc_hi, pc_lo], &[6, 4, 5, 3, 2, 0, 1])
+                } else {
+                    self.write_frame_in(bus, &[sr, pc_hi, pc_lo], &[2, 0, 1])
+                }
+            },
+            Version::MC68010 => {
+                if group_0 {
+                    // format 8, long bus fault
+                    let ssw = if write { 0 } else { 0x0100 } | fc as u16;
+                    let mut frame = vec![sr, pc_hi, pc_lo, 0x8000 | vo, ssw, fa_hi, fa_lo, 0, 0, 0, 0, 0, ir];
+                    frame.extend_from_slice(&[0; 16]);
+                    self.write_frame(bus, &frame)
+                } else {
+                    self.write_frame(bus, &[sr, pc_hi, pc_lo, vo])
+                }
+            },
+            Version::MC68020 | Version::MC68EC020 => {
+                match vector {
+                    _ if group_0 => {
+                        // format A, short bus cycle fault
+                        let ssw = 0x0100 | if write { 0 } else { 0x0040 } | fc as u16;
+                        self.write_frame(bus, &[sr, pc_hi, pc_lo, 0xa000 | vo, 0, ssw, 0, 0, fa_hi, fa_lo, 0, 0, 0, 0, 0, 0])
+                    },
+                    EXCEPTION_ZERO_DIVIDE | EXCEPTION_CHK | EXCEPTION_TRAPV | EXCEPTION_TRACE => {
+                        self.write_frame(bus, &[sr, pc_hi, pc_lo, 0x2000 | vo, (ppc >> 16) as u16, ppc as u16])
+                    },
+                    24..=31 => self.interrupt_frame(bus, sr, pc, vo),
+                    _ => self.write_frame(bus, &[sr, pc_hi, pc_lo, vo]),
+                }
+            },
+            Version::MC68040 => {
+                match *e {
+                    Exception::BusError(..) | Exception::AccessFault(..) => {
+                        // format 7, access error, with the held back writes
+                        // in WB3 and WB2 for the handler to complete
+                        let atc = if let Exception::AccessFault(..) = *e { 0x0400 } else { 0 };
+                        let ssw = atc | if write { 0 } else { 0x0100 } | fc as u16;
+                        let mut wb = [(0u16, 0u32, 0u32); 2];
+                        for (slot, w) in wb.iter_mut().zip(self.writebacks.drain(..)) {
+                            *slot = (w.status(), w.addr, w.data);
+                        }
+                        let mut frame = vec![sr, pc_hi, pc_lo, 0x7000 | vo, fa_hi, fa_lo, ssw, wb[0].0, wb[1].0, 0, fa_hi, fa_lo];
+                        for &(_, addr, data) in wb.iter() {
+                            frame.extend_from_slice(&[(addr >> 16) as u16, addr as u16, (data >> 16) as u16, data as u16]);
+                        }
+                        frame.extend_from_slice(&[0; 10]);
+                        self.write_frame(bus, &frame)
+                    },
+                    Exception::AddressError => {
+                        self.write_frame(bus, &[sr, pc_hi, pc_lo, 0x2000 | vo, fa_hi, fa_lo & 0xfffe])
+                    },
+                    _ => match vector {
+                        EXCEPTION_ZERO_DIVIDE | EXCEPTION_CHK | EXCEPTION_TRAPV | EXCEPTION_TRACE => {
+                            self.write_frame(bus, &[sr, pc_hi, pc_lo, 0x2000 | vo, (ppc >> 16) as u16, ppc as u16])
+                        },
+                        24..=31 => self.interrupt_frame(bus, sr, pc, vo),
+                        _ => self.write_frame(bus, &[sr, pc_hi, pc_lo, vo]),
+                    },
+                }
+            },
+            Version::CPU32 => {
+                match vector {
+                    _ if group_0 => {
+                        // format C, bus error, the faulted instruction restarts
+                        let ssw = if write { 0 } else { 0x0040 } | fc as u16;
+                        self.write_frame(bus, &[sr, pc_hi, pc_lo, 0xc000 | vo, fa_hi, fa_lo, 0, 0, (ppc >> 16) as u16, ppc as u16, 0, ssw])
+                    },
+                    EXCEPTION_ZERO_DIVIDE | EXCEPTION_CHK | EXCEPTION_TRAPV | EXCEPTION_TRACE => {
+                        self.write_frame(bus, &[sr, pc_hi, pc_lo, 0x2000 | vo, (ppc >> 16) as u16, ppc as u16])
+                    },
+                    _ => self.write_frame(bus, &[sr, pc_hi, pc_lo, vo]),
+                }
+            },
+            Version::ColdFireIsaA | Version::ColdFireIsaB | Version::ColdFireIsaC => {
+                // the one frame there is, SP gets long aligned first and the
+                // format field records by how much. The fault status is
+                // split around the vector.
+                let align = sp!(self) & 3;
+                sp!(self) &= !3;
+                let fs = if !group_0 { 0 } else if fc & 3 == 2 { 0x4 } else if write { 0x8 } else { 0xc };
+                let format = (4 + align as u16) << 12 | (fs >> 2) << 10 | vo | (fs & 3);
+                self.write_frame(bus, &[format, sr, pc_hi, pc_lo])
+            },
+            Version::MC68060 => {
+                match *e {
+                    Exception::BusError(..) | Exception::AccessFault(..) => {
+                        // format 4, access error, the fault status long word
+                        // has the direction, TM and the cause. MMU faults are
+                        // all reported as page faults.
+                        let rw = if write { 0x0080_0000 } else { 0x0100_0000 };
+                        let io = if fc & 3 == 2 { 0x8000 } else { 0 };
+                        let cause = match *e {
+                            Exception::AccessFault(..) => 0x0200,
+                            _ if write => 0x0010,
+                            _ => 0x0020,
+                        };
+                        let fslw = rw | (fc as u32) << 16 | io | cause;
+                        self.write_frame(bus, &[sr, pc_hi, pc_lo, 0x4000 | vo, fa_hi, fa_lo, (fslw >> 16) as u16, fslw as u16])
+                    },
+                    Exception::AddressError => {
+                        self.write_frame(bus, &[sr, pc_hi, pc_lo, 0x2000 | vo, fa_hi, fa_lo & 0xfffe])
+                    },
+                    // there's no master stack, interrupts use format 0 too
+                    _ => match vector {
+                        EXCEPTION_ZERO_DIVIDE | EXCEPTION_CHK | EXCEPTION_TRAPV | EXCEPTION_TRACE => {
+                            self.write_frame(bus, &[sr, pc_hi, pc_lo, 0x2000 | vo, (ppc >> 16) as u16, ppc as u16])
+                        },
+                        _ => self.write_frame(bus, &[sr, pc_hi, pc_lo, vo]),
+                    },
+                }
+            },
+        }
+    }
+
+    // '020+ interrupts taken on the master stack leave a format 0 frame
+    // there and a format 1 throwaway frame on the interrupt stack
+    fn interrupt_frame<T: Bus + ?Sized>(&mut self, bus: &mut T, sr: u16, pc: u32, vo: u16) -> Result<()> {
+        let pc_hi = (pc >> 16) as u16;
+        self.write_frame(bus, &[sr, pc_hi, pc as u16, vo])?;
+        if self.m != 0 {
+            self.inactive_msp = sp!(self);
+            sp!(self) = self.inactive_isp;
+            self.m = MFLAG_CLEAR;
+            let sr = self.status_register();
+            self.write_frame(bus, &[sr, pc_hi, pc as u16, 0x1000 | vo])?;
+        }
+        Ok(())
+    }
+
+    // words are in memory order, lowest address first
+    fn write_frame<T: Bus + ?Sized>(&mut self, bus: &mut T, frame: &[u16]) -> Result<()> {
+        let sp = sp!(self).wrapping_sub(frame.len() as u32 * 2);
+        for (i, &word) in frame.iter().enumerate() {
+            self.write_data_16(bus, sp.wrapping_add(i as u32 * 2), word)?;
+        }
+        sp!(self) = sp;
+        Ok(())
+    }
+
+    // the same, the words written in `order`, indices into the frame. The
+    // 68000 writes the low word of the pc first (ref yacht.txt).
+    fn write_frame_in<T: Bus + ?Sized>(&mut self, bus: &mut T, frame: &[u16], order: &[usize]) -> Result<()> {
+        let sp = sp!(self).wrapping_sub(frame.len() as u32 * 2);
+        for &i in order {
+            self.write_data_16(bus, sp.wrapping_add(i as u32 * 2), frame[i])?;
+        }
+        sp!(self) = sp;
+        Ok(())
+    }
+
+    // RTE, faulted bus cycles aren't rerun since the instruction that
+    // caused them restarts from the stacked PC
+    pub fn return_from_exception<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<()> {
+        let sp = sp!(self);
+        if self.version.is_coldfire() {
+            let format = self.read_data_16(bus, sp)? >> 12;
+            let sr = self.read_data_16(bus, sp.wrapping_add(2))?;
+            let pc = self.read_data_32(bus, sp.wrapping_add(4))?;
+            if format & 0xc != 4 {
+                return Err(Exception::Trap(EXCEPTION_FORMAT_ERROR, 0));
+            }
+            sp!(self) = sp.wrapping_add(8 + (format as u32 & 3));
+            self.pc = pc;
+            self.sr_to_flags(sr);
+            return Ok(());
+        }
+        let sr = self.read_data_16(bus, sp)?;
+        let pc = self.read_data_32(bus, sp.wrapping_add(2))?;
+        let length = if self.version.base() == Version::MC68000 {
+            6
+        } else {
+            let format = self.read_data_16(bus, sp.wrapping_add(6))? >> 12;
+            match (self.version.base(), format) {
+                (_, 0x0) => 8,
+                (Version::MC68020, 0x1) |
+                (Version::MC68040, 0x1) => {
+                    // throwaway, carry on with the frame on the master stack
+                    sp!(self) = sp.wrapping_add(8);
+                    if sr & MFLAG_SET as u16 != 0 {
+                        self.inactive_isp = sp!(self);
+                        sp!(self) = self.inactive_msp;
+                        self.m = MFLAG_SET;
+                    }
+                    return self.return_from_exception(bus);
+                },
+                (Version::MC68020, 0x2) |
+                (Version::MC68040, 0x2) |
+                (Version::MC68060, 0x2) |
+                (Version::CPU32, 0x2) => 12,
+                (Version::MC68060, 0x4) => 16,
+                (Version::CPU32, 0xc) => 24,
+                (Version::MC68040, 0x7) => 60,
+                (Version::MC68010, 0x8) => 58,
+                (Version::MC68020, 0xa) => 32,
+                (Version::MC68020, 0xb) => 92,
+                _ => return Err(Exception::Trap(EXCEPTION_FORMAT_ERROR, 0)),
+            }
+        };
+        sp!(self) = sp.wrapping_add(length);
+        self.pc = pc;
+        self.sr_to_flags(sr);
+        Ok(())
+    }
+}
@@ -0,0 +1,605 @@
+// GDB remote serial protocol
+//
+// A stub that lets m68k-elf-gdb debug code running on the core. It serves
+// one debugger over anything that moves bytes both ways, a TCP socket
+// from `accept` or the process's stdin and stdout for `target remote |`.
+//
+// Registers go in GDB's m68k order, d0-d7, a0-a7, sr and pc, 32 bits
+// each. The core has no FPU so there are no fp registers, the target
+// description GDB asks for says so. Memory is peeked and poked in the
+// current mode's data space, so looking doesn't disturb devices. Addresses
+// go through the MMU without loading its ATC and are cut down to the
+// address lines the part has, the same bytes the core would get.
+//
+// Breakpoints are kept here and checked against the pc before each
+// instruction, nothing is written into memory, so software and hardware
+// ones are the same thing and work in ROM too. Watchpoints go on the
+// core's own list. While the core runs the connection is polled now and
+// then for GDB's break (^C).
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use watch::{WatchKind, Watchpoint};
+use AddressSpace;
+use Bus;
+use M68k;
+use ProcessingState;
+use Version;
+use SUPERVISOR_DATA;
+use USER_DATA;
+
+// GDB's signal numbers, for the stop replies
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+const SIGBUS: u8 = 10;
+
+const PACKET_SIZE: usize = 0x4000;
+const POLL_STEPS: u32 = 0x1000;     // instructions between looks for a break
+
+// A link to the debugger. `poll` returns a byte that has already arrived
+// without waiting for one, it's how a break gets through while the core
+// runs. A connection that can't tell just can't be broken into.
+pub trait Connection: Read + Write {
+    fn poll(&mut self) -> Option<u8> {
+        None
+    }
+}
+
+impl Connection for TcpStream {
+    fn poll(&mut self) -> Option<u8> {
+        let mut byte = [0];
+        self.set_nonblocking(true).ok()?;
+        let read = self.read(&mut byte);
+        let _ = self.set_nonblocking(false);
+        match read {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+}
+
+// waits for one debugger to connect, "localhost:1234" is what GDB's
+// `target remote :1234` looks for
+pub fn accept<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+    let (stream, _) = TcpListener::bind(addr)?.accept()?;
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+// the process's stdin and stdout
+pub struct Stdio;
+
+impl Read for Stdio {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::stdin().read(buf)
+    }
+}
+
+impl Write for Stdio {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::stdout().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+impl Connection for Stdio {}
+
+enum Packet {
+    Command(Vec<u8>),
+    Break,
+}
+
+enum Action {
+    Reply(String),
+    Resume(bool),   // true for a single step
+    Detach,
+    Kill,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum End {
+    Detached,
+    Killed,
+    Disconnected,
+}
+
+pub struct GdbStub {
+    pub breakpoints: Vec<u32>,  // stop before the instruction at each
+    no_ack: bool,
+    stop: String,               // the last stop reply, for `?`
+}
+
+impl GdbStub {
+    pub fn new() -> Self {
+        GdbStub {
+            breakpoints: Vec::new(),
+            no_ack: false,
+            stop: format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    // Serves the debugger until it detaches, kills the target or goes
+    // away. The core is left where it stopped.
+    pub fn serve<'a, C: Connection, B: Bus + 'a>(&mut self, conn: &mut C, core: &mut M68k<'a>, bus: &mut B) -> io::Result<End> {
+        self.no_ack = false;
+        loop {
+            let packet = match self.receive(conn) {
+                Ok(packet) => packet,
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(End::Disconnected),
+                Err(e) => return Err(e),
+            };
+            let command = match packet {
+                Packet::Command(command) => command,
+                Packet::Break => continue,  // the core isn't running
+            };
+            match self.command(&command, core, bus) {
+                Action::Reply(reply) => self.send(conn, &reply)?,
+                Action::Resume(step) => {
+                    let stop = self.resume(conn, core, bus, step);
+                    self.stop = stop.clone();
+                    self.send(conn, &stop)?;
+                },
+                Action::Detach => {
+                    self.send(conn, "OK")?;
+                    return Ok(End::Detached);
+                },
+                Action::Kill => return Ok(End::Killed),
+            }
+        }
+    }
+
+    fn receive<C: Connection>(&mut self, conn: &mut C) -> io::Result<Packet> {
+        let mut byte = [0];
+        loop {
+            conn.read_exact(&mut byte)?;
+            match byte[0] {
+                b'$' => (),
+                0x03 => return Ok(Packet::Break),
+                _ => continue,  // acks and line noise
+            }
+            let mut data = Vec::new();
+            loop {
+                conn.read_exact(&mut byte)?;
+                if byte[0] == b'#' {
+                    break;
+                }
+                data.push(byte[0]);
+            }
+            let mut checksum = [0; 2];
+            conn.read_exact(&mut checksum)?;
+            let sum = data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+            let good = hex_value(&checksum) == Some(sum as u64);
+            if !self.no_ack {
+                conn.write_all(if good { b"+" } else { b"-" })?;
+                conn.flush()?;
+            }
+            if good {
+                return Ok(Packet::Command(unescape(&data)));
+            }
+        }
+    }
+
+    fn send<C: Connection>(&mut self, conn: &mut C, reply: &str) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(reply.len() + 4);
+        packet.push(b'$');
+        for &b in reply.as_bytes() {
+            // the characters that mean something in a packet are escaped
+            if b == b'$' || b == b'#' || b == b'}' || b == b'*' {
+                packet.push(b'}');
+                packet.push(b ^ 0x20);
+            } else {
+                packet.push(b);
+            }
+        }
+        let sum = packet[1..].iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        packet.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+        loop {
+            conn.write_all(&packet)?;
+            conn.flush()?;
+            if self.no_ack {
+                return Ok(());
+            }
+            let mut byte = [0];
+            loop {
+                conn.read_exact(&mut byte)?;
+                match byte[0] {
+                    b'+' => return Ok(()),
+                    b'-' => break,
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    fn command<'a, B: Bus + 'a>(&mut self, command: &[u8], core: &mut M68k<'a>, bus: &mut B) -> Action {
+        let text = String::from_utf8_lossy(command).into_owned();
+        let (kind, args) = match text.chars().next() {
+            Some(kind) => (kind, &text[kind.len_utf8()..]),
+            None => return Action::Reply(String::new()),
+        };
+        let reply = match kind {
+            '?' => self.stop.clone(),
+            'g' => (0..18).map(|n| format!("{:08x}", register(core, n).unwrap())).collect(),
+            'G' => {
+                let values: Option<Vec<u32>> = (0..18).map(|n| args.get(n * 8..n * 8 + 8).and_then(|h| hex_value(h.as_bytes())).map(|v| v as u32)).collect();
+                match values {
+                    Some(values) => {
+                        // sr first, it can swap the stack pointers over
+                        set_register(core, 16, values[16]);
+                        for (n, &value) in values.iter().enumerate() {
+                            set_register(core, n, value);
+                        }
+                        "OK".to_string()
+                    },
+                    None => "E01".to_string(),
+                }
+            },
+            'p' => match hex_value(args.as_bytes()).and_then(|n| register(core, n as usize)) {
+                Some(value) => format!("{:08x}", value),
+                None => "E01".to_string(),
+            },
+            'P' => {
+                let mut parts = args.splitn(2, '=');
+                let n = parts.next().and_then(|n| hex_value(n.as_bytes())).map(|n| n as usize);
+                let value = parts.next().and_then(|v| hex_value(v.as_bytes()));
+                match (n, value) {
+                    (Some(n), Some(value)) if n < 18 => {
+                        set_register(core, n, value as u32);
+                        "OK".to_string()
+                    },
+                    _ => "E01".to_string(),
+                }
+            },
+            'm' => match address_length(args) {
+                Some((addr, len)) => {
+                    // as much as translates, an error if none of it does
+                    let space = data_space(core);
+                    let bytes: String = (0..len.min(PACKET_SIZE as u32 / 2))
+                        .map_while(|i| core.probe(bus, space, addr.wrapping_add(i)))
+                        .map(|physical| format!("{:02x}", bus.peek_8(space, physical)))
+                        .collect();
+                    if bytes.is_empty() && len > 0 { "E01".to_string() } else { bytes }
+                },
+                None => "E01".to_string(),
+            },
+            'M' => {
+                let mut parts = args.splitn(2, ':');
+                match (parts.next().and_then(address_length), parts.next()) {
+                    (Some((addr, len)), Some(data)) if data.len() == len as usize * 2 => {
+                        // all of it has to translate before any is written
+                        let space = data_space(core);
+                        let physical: Option<Vec<u32>> = (0..len).map(|i| core.probe(bus, space, addr.wrapping_add(i))).collect();
+                        match physical {
+                            Some(physical) => {
+                                for (i, &at) in physical.iter().enumerate() {
+                                    let byte = hex_value(&data.as_bytes()[i * 2..i * 2 + 2]).unwrap_or(0);
+                                    bus.poke_8(space, at, byte as u8);
+                                }
+                                "OK".to_string()
+                            },
+                            None => "E01".to_string(),
+                        }
+                    },
+                    _ => "E01".to_string(),
+                }
+            },
+            'c' | 's' => {
+                if let Some(addr) = hex_value(args.as_bytes()) {
+                    core.pc = addr as u32;
+                }
+                return Action::Resume(kind == 's');
+            },
+            'Z' | 'z' => self.breakpoint(kind == 'Z', args, core),
+            'H' => "OK".to_string(),
+            'T' => "OK".to_string(),
+            'D' => return Action::Detach,
+            'k' => return Action::Kill,
+            'v' if text == "vCont?" => "vCont;c;C;s;S".to_string(),
+            'v' if text.starts_with("vCont;") => {
+                // one thread, the first action is the one for it
+                let step = matches!(text[6..].chars().next(), Some('s') | Some('S'));
+                return Action::Resume(step);
+            },
+            'v' if text == "vMustReplyEmpty" => String::new(),
+            'q' => self.query(args, core),
+            'Q' if text == "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK".to_string()
+            },
+            _ => String::new(),
+        };
+        Action::Reply(reply)
+    }
+
+    fn query(&mut self, args: &str, core: &M68k) -> String {
+        if args.starts_with("Supported") {
+            return format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+;vContSupported+", PACKET_SIZE);
+        }
+        if let Some(rest) = args.strip_prefix("Xfer:features:read:target.xml:") {
+            let description = target_description(core.version);
+            return match address_length(rest) {
+                Some((offset, len)) => {
+                    let start = (offset as usize).min(description.len());
+                    let end = (start + len as usize).min(description.len());
+                    format!("{}{}", if end < description.len() { "m" } else { "l" }, &description[start..end])
+                },
+                None => "E01".to_string(),
+            };
+        }
+        match args {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            "Offsets" => "Text=0;Data=0;Bss=0".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    // Z0 and Z1 are breakpoints, Z2 to Z4 write, read and access watchpoints
+    fn breakpoint(&mut self, insert: bool, args: &str, core: &mut M68k) -> String {
+        let mut parts = args.split(',');
+        let kind = parts.next().and_then(|k| k.parse::<u32>().ok());
+        let addr = parts.next().and_then(|a| hex_value(a.as_bytes())).map(|a| a as u32);
+        let len = parts.next().and_then(|l| hex_value(l.as_bytes())).map(|l| l as u32);
+        let (kind, addr, len) = match (kind, addr, len) {
+            (Some(kind), Some(addr), Some(len)) => (kind, addr, len.max(1)),
+            _ => return "E01".to_string(),
+        };
+        let watch = match kind {
+            0 | 1 => {
+                if insert {
+                    self.breakpoints.push(addr);
+                } else if let Some(i) = self.breakpoints.iter().position(|&b| b == addr) {
+                    self.breakpoints.remove(i);
+                }
+                return "OK".to_string();
+            },
+            2 => WatchKind::Write,
+            3 => WatchKind::Read,
+            4 => WatchKind::Access,
+            _ => return String::new(),
+        };
+        let watchpoint = Watchpoint::new(watch, addr, addr.wrapping_add(len - 1));
+        if insert {
+            core.watchpoints.push(watchpoint);
+        } else if let Some(i) = core.watchpoints.iter().position(|w| *w == watchpoint) {
+            core.watchpoints.remove(i);
+        }
+        "OK".to_string()
+    }
+
+    // runs the core, or steps it once, and returns the stop reply
+    fn resume<'a, C: Connection, B: Bus + 'a>(&mut self, conn: &mut C, core: &mut M68k<'a>, bus: &mut B, step: bool) -> String {
+        let mut steps = 0u32;
+        loop {
+            core.step(bus);
+            if let Some(hit) = core.watch_hit.take() {
+                let name = match core.watchpoints.get(hit.index).map(|w| w.kind) {
+                    Some(WatchKind::Read) => "rwatch",
+                    Some(WatchKind::Access) => "awatch",
+                    _ => "watch",
+                };
+                return format!("T{:02x}{}:{:08x};", SIGTRAP, name, hit.addr);
+            }
+            if core.processing_state == ProcessingState::Halted {
+                return format!("S{:02x}", SIGBUS);
+            }
+            if step || self.breakpoints.contains(&core.pc) {
+                return format!("S{:02x}", SIGTRAP);
+            }
+            steps += 1;
+            if steps.is_multiple_of(POLL_STEPS) && conn.poll() == Some(0x03) {
+                return format!("S{:02x}", SIGINT);
+            }
+        }
+    }
+}
+
+impl Default for GdbStub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn data_space(core: &M68k) -> AddressSpace {
+    if core.s != 0 { SUPERVISOR_DATA } else { USER_DATA }
+}
+
+// GDB's numbering, d0-d7 are 0-7, a0-a7 8-15, then sr and pc
+fn register(core: &M68k, n: usize) -> Option<u32> {
+    match n {
+        0..=15 => Some(core.dar[n]),
+        16 => Some(core.status_register() as u32),
+        17 => Some(core.pc),
+        _ => None,
+    }
+}
+
+fn set_register(core: &mut M68k, n: usize, value: u32) {
+    match n {
+        0..=15 => core.dar[n] = value,
+        16 => core.sr_to_flags(value as u16),
+        _ => core.pc = value,
+    }
+}
+
+fn hex_value(digits: &[u8]) -> Option<u64> {
+    if digits.is_empty() || digits.len() > 16 {
+        return None;
+    }
+    u64::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+// "addr,length" as in the m and M packets
+fn address_length(args: &str) -> Option<(u32, u32)> {
+    let mut parts = args.splitn(2, ',');
+    let addr = hex_value(parts.next()?.as_bytes())?;
+    let len = hex_value(parts.next()?.as_bytes())?;
+    Some((addr as u32, len as u32))
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&b) = bytes.next() {
+        if b == b'}' {
+            if let Some(&next) = bytes.next() {
+                out.push(next ^ 0x20);
+            }
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+fn target_description(version: Version) -> String {
+    let architecture = match version {
+        Version::MC68000 => "m68k:68000",
+        Version::MC68008 => "m68k:68008",
+        Version::MC68010 => "m68k:68010",
+        Version::MC68020 | Version::MC68EC020 => "m68k:68020",
+        Version::MC68040 => "m68k:68040",
+        Version::MC68060 => "m68k:68060",
+        Version::CPU32 => "m68k:cpu32",
+        Version::ColdFireIsaA => "m68k:isa-a",
+        Version::ColdFireIsaB => "m68k:isa-b",
+        Version::ColdFireIsaC => "m68k:isa-c",
+    };
+    let feature = if version.is_coldfire() { "org.gnu.gdb.coldfire.core" } else { "org.gnu.gdb.m68k.core" };
+    let mut registers = String::new();
+    for n in 0..8 {
+        registers.push_str(&format!("<reg name=\"d{}\" bitsize=\"32\"/>", n));
+    }
+    for n in 0..6 {
+        registers.push_str(&format!("<reg name=\"a{}\" bitsize=\"32\" type=\"data_ptr\"/>", n));
+    }
+    registers.push_str("<reg name=\"fp\" bitsize=\"32\" type=\"data_ptr\"/>");
+    registers.push_str("<reg name=\"sp\" bitsize=\"32\" type=\"data_ptr\"/>");
+    registers.push_str("<reg name=\"ps\" bitsize=\"32\"/>");
+    registers.push_str("<reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\"/>");
+    format!("<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+             <target version=\"1.0\"><architecture>{}</architecture>\
+             <feature name=\"{}\">{}</feature></target>", architecture, feature, registers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::small_map;
+
+    // GDB's side of the link, written out ahead, and what the stub sent back
+    struct Script {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Script {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Script {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Connection for Script {}
+
+    fn packet(command: &str) -> String {
+        let sum = command.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        format!("${}#{:02x}", command, sum)
+    }
+
+    // runs the commands on a core with four NOPs at $400, acks turned off
+    // first, and returns the replies after the OK for that
+    fn session(version: Version, commands: &[&str]) -> (Vec<String>, M68k<'static>, End) {
+        let mut input = packet("QStartNoAckMode");
+        input.push('+');
+        for command in commands.iter() {
+            input.push_str(&packet(command));
+        }
+        let mut conn = Script { input: io::Cursor::new(input.into_bytes()), output: Vec::new() };
+        let mut bus = small_map(0x8000, &[0x4e71, 0x4e71, 0x4e71, 0x4e71]);
+        let mut core = M68k::new(version);
+        core.reset(&mut bus);
+        let end = GdbStub::new().serve(&mut conn, &mut core, &mut bus).unwrap();
+        let output = String::from_utf8(conn.output).unwrap();
+        let replies: Vec<String> = output.split('$').skip(1).map(|reply| {
+            let (data, sum) = reply.split_at(reply.find('#').unwrap());
+            assert_eq!(&packet(data)[data.len() + 1..], &sum[..3], "checksum of {}", data);
+            data.to_string()
+        }).collect();
+        assert_eq!(replies[0], "OK");
+        (replies[1..].to_vec(), core, end)
+    }
+
+    #[test]
+    fn registers_go_in_gdbs_order() {
+        let (replies, core, end) = session(Version::MC68000, &["g", "p11", "P3=12345678", "p3", "p12", "D"]);
+        assert_eq!(replies[0].len(), 18 * 8);
+        // a7, sr and pc last
+        assert_eq!(&replies[0][15 * 8..], "000080000000270000000400");
+        assert_eq!(replies[1], "00000400");
+        assert_eq!(replies[2], "OK");
+        assert_eq!(replies[3], "12345678");
+        assert_eq!(replies[4], "E01");
+        assert_eq!(replies[5], "OK");
+        assert_eq!(core.dar[3], 0x1234_5678);
+        assert_eq!(end, End::Detached);
+    }
+
+    #[test]
+    fn memory_is_peeked_and_poked_through_the_address_lines() {
+        let (replies, _, _) = session(Version::MC68000, &["M1000,2:abcd", "m1000,2", "m1001000,2", "m400,2", "M1000,2:ab", "k"]);
+        assert_eq!(replies, ["OK", "abcd", "abcd", "4e71", "E01"]);
+    }
+
+    #[test]
+    fn breakpoints_and_steps_stop_the_core() {
+        let (replies, core, end) = session(Version::MC68000, &["Z0,404,2", "c", "p11", "s", "p11", "z0,404,2", "?", "k"]);
+        assert_eq!(replies, ["OK", "S05", "00000404", "S05", "00000406", "OK", "S05"]);
+        assert_eq!(core.pc, 0x406);
+        assert_eq!(end, End::Killed);
+    }
+
+    #[test]
+    fn the_target_description_names_the_part() {
+        let (replies, _, _) = session(Version::ColdFireIsaB, &["qSupported:xmlRegisters=m68k", "qXfer:features:read:target.xml:0,1000", "qXfer:features:read:target.xml:0,10", "vMustReplyEmpty"]);
+        assert!(replies[0].contains("qXfer:features:read+"));
+        assert!(replies[1].starts_with('l'));
+        assert!(replies[1].contains("<architecture>m68k:isa-b</architecture>"));
+        assert!(replies[1].contains("org.gnu.gdb.coldfire.core"));
+        assert_eq!(replies[2], "m<?xml version=\"1");
+        assert_eq!(replies[3], "");
+    }
+
+    #[test]
+    fn bad_checksums_are_nacked_and_escapes_undone() {
+        // acks still on, the stub waits for one after each reply
+        let mut input = String::from("$m400,2#00");
+        input.push_str(&packet("m400,2"));
+        input.push('+');
+        // "m400,2" with its 4 escaped
+        input.push_str(&packet("m}\u{14}00,2"));
+        input.push('+');
+        let mut conn = Script { input: io::Cursor::new(input.into_bytes()), output: Vec::new() };
+        let mut bus = small_map(0x8000, &[0x4e71]);
+        let mut core = M68k::new(Version::MC68000);
+        core.reset(&mut bus);
+        let end = GdbStub::new().serve(&mut conn, &mut core, &mut bus).unwrap();
+        assert_eq!(end, End::Disconnected);
+        let output = String::from_utf8(conn.output).unwrap();
+        assert_eq!(output, format!("-+{}+{}", packet("4e71"), packet("4e71")));
+    }
+}
@@ -4,6 +4,7 @@
 #![macro_use]
 
 use M68k;
+use Version;
 use instructions::constants::*;
 use std::num::Wrapping;
 
@@ -544,6 +545,39 @@ pub fn divs_16(core: &mut M68k, dst: u32, src: i16) {
     }
 }
 
+// The 68000 divides a bit at a time in microcode, so the time depends on
+// the operands. These follow Jorge Cwik's analysis of the microcode and
+// are the whole instruction with a register operand. The '010 manual only
+// gives the worst case, or the overflow when it is caught early. Other
+// models keep the handler's number.
+pub fn divs_16_cycles(version: Version, dst: u32, src: i16) -> Option<u32> {
+    let dividend = dst as i32;
+    let overflow = dividend.unsigned_abs() >> 16 >= src.unsigned_abs() as u32;
+    match version {
+        Version::MC68000 | Version::MC68008 => {
+            let mut mcycles = if dividend < 0 { 7 } else { 6 };
+            if overflow {
+                return Some((mcycles + 2) * 2);
+            }
+            let mut quotient = dividend.unsigned_abs() / src.unsigned_abs() as u32;
+            mcycles += 55;
+            if src >= 0 {
+                if dividend >= 0 { mcycles -= 1; } else { mcycles += 1; }
+            }
+            // one more for each of the upper 15 quotient bits that's clear
+            for _ in 0..15 {
+                if quotient & 0x8000 == 0 {
+                    mcycles += 1;
+                }
+                quotient <<= 1;
+            }
+            Some(mcycles * 2)
+        },
+        Version::MC68010 => Some(if overflow { 16 } else { 122 }),
+        _ => None,
+    }
+}
+
 // Put common implementation of DIVU here
 pub fn divu_16(core: &mut M68k, dst: u32, src: u16) {
     let quotient: u32 = dst / (src as u32);
@@ -559,6 +593,35 @@ pub fn divu_16(core: &mut M68k, dst: u32, src: u16) {
     }
 }
 
+pub fn divu_16_cycles(version: Version, dst: u32, src: u16) -> Option<u32> {
+    let overflow = dst >> 16 >= src as u32;
+    match version {
+        Version::MC68000 | Version::MC68008 => {
+            if overflow {
+                return Some(10);
+            }
+            let divisor = (src as u32) << 16;
+            let mut dividend = dst;
+            let mut mcycles = 38;
+            for _ in 0..15 {
+                let carry = dividend & 0x8000_0000 != 0;
+                dividend <<= 1;
+                if carry {
+                    dividend = dividend.wrapping_sub(divisor);
+                } else if dividend < divisor {
+                    mcycles += 2;
+                } else {
+                    mcycles += 1;
+                    dividend -= divisor;
+                }
+            }
+            Some(mcycles * 2)
+        },
+        Version::MC68010 => Some(if overflow { 10 } else { 108 }),
+        _ => None,
+    }
+}
+
 // Put common implementation of EOR here
 pub fn eor_8(core: &mut M68k, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
@@ -802,6 +865,15 @@ pub fn muls_16(core: &mut M68k, dst: i16, src: i16) -> u32 {
     core.c = 0;
     res
 }
+// 38 plus 2 for each 01 or 10 pair in the source with a 0 appended
+pub fn muls_16_cycles(version: Version, src: i16) -> Option<u32> {
+    let src = src as u16;
+    match version {
+        Version::MC68000 | Version::MC68008 => Some(38 + 2 * (src ^ (src << 1)).count_ones()),
+        Version::MC68010 => Some(42),
+        _ => None,
+    }
+}
 // Put common implementation of MULU here
 pub fn mulu_16(core: &mut M68k, dst: u16, src: u16) -> u32 {
     let res = (dst as u32).wrapping_mul(src as u32) as u32;
@@ -811,6 +883,14 @@ pub fn mulu_16(core: &mut M68k, dst: u16, src: u16) -> u32 {
     core.c = 0;
     res
 }
+// 38 plus 2 for each bit set in the source
+pub fn mulu_16_cycles(version: Version, src: u16) -> Option<u32> {
+    match version {
+        Version::MC68000 | Version::MC68008 => Some(38 + 2 * src.count_ones()),
+        Version::MC68010 => Some(40),
+        _ => None,
+    }
+}
 // Put common implementation of NBCD here
 pub fn nbcd(core: &mut M68k, dst: u32) -> Option<u32> {
     let mut res = mask_out_above_8!((0x9a as u32).wrapping_sub(dst).wrapping_sub(x_as_1!(core)));
@@ -48,22 +48,56 @@ pub const CAAR: u16 = 0x802;
 pub const MSP:  u16 = 0x803;
 pub const ISP:  u16 = 0x804;
 // '040+
-// TODO !
+pub const TC:    u16 = 0x003;
+pub const ITT0:  u16 = 0x004;
+pub const ITT1:  u16 = 0x005;
+pub const DTT0:  u16 = 0x006;
+pub const DTT1:  u16 = 0x007;
+pub const MMUSR: u16 = 0x805;
+pub const URP:   u16 = 0x806;
+pub const SRP:   u16 = 0x807;
+// '060
+pub const BUSCR: u16 = 0x008;
+pub const PCR:   u16 = 0x808;
+// ColdFire, write only
+pub const ACR0:    u16 = 0x004;
+pub const ACR1:    u16 = 0x005;
+pub const ACR2:    u16 = 0x006;
+pub const ACR3:    u16 = 0x007;
+pub const ROMBAR0: u16 = 0xc00;
+pub const ROMBAR1: u16 = 0xc01;
+pub const RAMBAR0: u16 = 0xc04;
+pub const RAMBAR1: u16 = 0xc05;
+pub const MBAR:    u16 = 0xc0f;
+
+pub const PCR_ID_060: u32 = 0x0430_0100;    // MC68060, revision 1
+pub const PCR_MASK_060: u32 = 0x0000_0083;  // EDEBUG, DFP and ESS
+
+// '020 CACR, C and CE only act when written and read back as 0
+pub const CACR_E:  u32 = 0x0001;    // enable
+pub const CACR_F:  u32 = 0x0002;    // freeze, misses don't replace entries
+pub const CACR_CE: u32 = 0x0004;    // clear the entry CAAR points at
+pub const CACR_C:  u32 = 0x0008;    // clear all entries
 
 // Exception Vectors
-//pub const EXCEPTION_BUS_ERROR: u8               =  2;
+pub const EXCEPTION_BUS_ERROR: u8               =  2;
 pub const EXCEPTION_ADDRESS_ERROR: u8           =  3;
 pub const EXCEPTION_ILLEGAL_INSTRUCTION: u8     =  4;
 pub const EXCEPTION_ZERO_DIVIDE: u8             =  5;
 pub const EXCEPTION_CHK: u8                     =  6;
 pub const EXCEPTION_TRAPV: u8                   =  7;
 pub const EXCEPTION_PRIVILEGE_VIOLATION: u8     =  8;
-// pub const EXCEPTION_TRACE: u8                   =  9;
+pub const EXCEPTION_TRACE: u8                   =  9;
 pub const EXCEPTION_UNIMPLEMENTED_1010: u8      = 10;
 pub const EXCEPTION_UNIMPLEMENTED_1111: u8      = 11;
-// pub const EXCEPTION_FORMAT_ERROR: u8            = 14;
+pub const EXCEPTION_FORMAT_ERROR: u8            = 14;
 // pub const EXCEPTION_UNINITIALIZED_INTERRUPT: u8 = 15;
 // pub const EXCEPTION_SPURIOUS_INTERRUPT: u8      = 24;
-// pub const EXCEPTION_INTERRUPT_AUTOVECTOR: u8    = 24;
+pub const EXCEPTION_INTERRUPT_AUTOVECTOR: u8    = 24;
 pub const EXCEPTION_TRAP_BASE: u8               = 32;
+// MC68851
+pub const EXCEPTION_MMU_CONFIGURATION: u8       = 56;
+pub const EXCEPTION_MMU_ILLEGAL_OPERATION: u8   = 57;
+pub const EXCEPTION_MMU_ACCESS_LEVEL: u8        = 58;
+pub const EXCEPTION_UNIMPLEMENTED_INTEGER: u8   = 61;
 
@@ -0,0 +1,114 @@
+// Per model cycle tables
+//
+// The handlers return 68000 clock counts, with whatever depends on the
+// operands (shift counts, taken branches, MULx/DIVx) worked out as they
+// run. Other models mostly take the same time, so rather than a full table
+// per model each one gets a table, indexed by opcode like the handlers,
+// of the clocks to add to or take from the 68000 count. A model plugs in
+// its numbers by listing the opcodes that differ.
+//
+// The '020 works its timing out in timing020.rs instead, the '040, '060,
+// CPU32 and ColdFire have no numbers of their own yet.
+
+use Version;
+use instructions::optable::*;
+
+pub type CycleTable = Vec<i8>;
+
+struct CycleEntry {
+    mask: u32,
+    matching: u32,
+    delta: i8,
+}
+
+macro_rules! cycle_entry {
+    ($mask:expr, $matching:expr, $delta:expr) => (CycleEntry { mask: $mask, matching: $matching, delta: $delta, })
+}
+
+// MC68010 (ref MC68010UM section 10, Musashi's 010 column)
+//
+// The '010 doesn't read before CLR, drops the pipeline refill from the
+// immediate long to Dn forms and the status register ops, and reads the
+// format word in RTE. Loop mode DBcc isn't modelled, nor is Scc Dn taking
+// 4 clocks when true as that depends on the condition, only ST is listed.
+fn mc68010_cycles() -> Vec<CycleEntry> {
+    vec![
+        cycle_entry!(MASK_OUT_Y, OP_ADDI_32_DN, -2),
+
+        cycle_entry!(MASK_EXACT, OP_ANDI_16_TOC, -4),
+        cycle_entry!(MASK_EXACT, OP_ANDI_16_TOS, -4),
+
+        cycle_entry!(MASK_OUT_X_Y, OP_CHK_16_AI,   -2),
+        cycle_entry!(MASK_OUT_X,   OP_CHK_16_AL,   -2),
+        cycle_entry!(MASK_OUT_X,   OP_CHK_16_AW,   -2),
+        cycle_entry!(MASK_OUT_X_Y, OP_CHK_16_DN,   -2),
+        cycle_entry!(MASK_OUT_X_Y, OP_CHK_16_DI,   -2),
+        cycle_entry!(MASK_OUT_X,   OP_CHK_16_IMM,  -2),
+        cycle_entry!(MASK_OUT_X_Y, OP_CHK_16_IX,   -2),
+        cycle_entry!(MASK_OUT_X,   OP_CHK_16_PCDI, -2),
+        cycle_entry!(MASK_OUT_X,   OP_CHK_16_PCIX, -2),
+        cycle_entry!(MASK_OUT_X_Y, OP_CHK_16_PD,   -2),
+        cycle_entry!(MASK_OUT_X_Y, OP_CHK_16_PI,   -2),
+
+        cycle_entry!(MASK_OUT_Y, OP_CLR_8_AI, -4),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_8_PI, -4),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_8_PD, -4),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_8_DI, -4),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_8_IX, -4),
+        cycle_entry!(MASK_EXACT, OP_CLR_8_AW, -4),
+        cycle_entry!(MASK_EXACT, OP_CLR_8_AL, -4),
+
+        cycle_entry!(MASK_OUT_Y, OP_CLR_16_AI, -4),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_16_PI, -4),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_16_PD, -4),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_16_DI, -4),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_16_IX, -4),
+        cycle_entry!(MASK_EXACT, OP_CLR_16_AW, -4),
+        cycle_entry!(MASK_EXACT, OP_CLR_16_AL, -4),
+
+        cycle_entry!(MASK_OUT_Y, OP_CLR_32_AI, -6),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_32_PI, -6),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_32_PD, -6),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_32_DI, -6),
+        cycle_entry!(MASK_OUT_Y, OP_CLR_32_IX, -6),
+        cycle_entry!(MASK_EXACT, OP_CLR_32_AW, -6),
+        cycle_entry!(MASK_EXACT, OP_CLR_32_AL, -6),
+
+        cycle_entry!(MASK_OUT_Y, OP_CMPI_32_DN, -2),
+
+        cycle_entry!(MASK_OUT_Y, OP_EORI_32_DN, -2),
+        cycle_entry!(MASK_EXACT, OP_EORI_16_TOC, -4),
+        cycle_entry!(MASK_EXACT, OP_EORI_16_TOS, -4),
+
+        cycle_entry!(MASK_OUT_Y, OP_MOVE_16_FRS_DN, -2),
+
+        cycle_entry!(MASK_OUT_Y, OP_MOVE_32_TOU, 2),
+        cycle_entry!(MASK_OUT_Y, OP_MOVE_32_FRU, 2),
+
+        cycle_entry!(MASK_OUT_Y, OP_ORI_32_DN, -2),
+        cycle_entry!(MASK_EXACT, OP_ORI_16_TOC, -4),
+        cycle_entry!(MASK_EXACT, OP_ORI_16_TOS, -4),
+
+        cycle_entry!(MASK_EXACT, OP_RTE_32, 4),
+
+        cycle_entry!(MASK_OUT_Y, OP_ST_8_DN, -2),
+
+        cycle_entry!(MASK_OUT_Y, OP_SUBI_32_DN, -2),
+    ]
+}
+
+pub fn generate(version: Version) -> CycleTable {
+    let mut table: CycleTable = vec![0; 0x10000];
+    let entries = match version {
+        Version::MC68010 => mc68010_cycles(),
+        _ => Vec::new(),
+    };
+    for entry in entries {
+        for opcode in entry.matching..0x10000 {
+            if opcode & entry.mask == entry.matching {
+                table[opcode as usize] = entry.delta;
+            }
+        }
+    }
+    table
+}
@@ -3,8 +3,19 @@
 
 use Bus;
 use M68k;
+use Version;
 use std::num::Wrapping;
 use super::super::Result;
+use Exception::IllegalInstruction;
+
+// Where an operand lives, for instructions that resolve their <ea> at run
+// time rather than through one handler per addressing mode
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Location {
+    Register(usize),    // index into dar
+    Memory(u32),
+    Immediate(u64),     // the root pointers PMOVE loads take eight bytes
+}
 
 pub fn absolute_word<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
     //core.read_imm_i16().map(|res| res as u32)
@@ -123,6 +134,7 @@ fn postincrement_16<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T, reg_ndx: usi
 fn predecrement_32<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T, reg_ndx: usize) -> u32 {
     // pre-decrement
     core.dar[reg_ndx] = (Wrapping(core.dar[reg_ndx]) - Wrapping(4)).0;
+    core.predec_long = Some(core.dar[reg_ndx]);
     core.dar[reg_ndx]
 }
 fn postincrement_32<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T, reg_ndx: usize) -> u32 {
@@ -136,7 +148,7 @@ pub fn displacement<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, reg_val: u32)
     let ea = (Wrapping(reg_val) + Wrapping(displacement as u32)).0;
     Ok(ea)
 }
-// Brief Extension Word format (see M68000 PRM section 2.1)
+// Brief Extension Word format (see M68000 PRM section 2.1), scaled from the 020 on
 const LONG_INDEX_MASK: u16 = 0x0800;
 fn index<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, reg_val: u32) -> Result<u32> {
     let extension = try!(core.read_imm_data_16(bus));
@@ -144,8 +156,148 @@ fn index<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, reg_val: u32) -> Result<
     let xreg_ndx = (extension>>12) as usize;
     let xn = core.dar[xreg_ndx];
     let xn = if (extension & LONG_INDEX_MASK) > 0 {xn} else {(xn as i16) as u32};
+    // the 68000 and 68010 ignore the scale bits
+    let scaled = core.version.base() != Version::MC68000 && core.version != Version::MC68010;
+    let xn = if scaled { xn << ((extension >> 9) & 3) } else { xn };
+    if scaled && (extension & FULL_FORMAT_MASK) > 0 {
+        return full_format(core, bus, reg_val, extension, xn);
+    }
 
       let index = extension as i8;
     let ea = (Wrapping(reg_val) + Wrapping(xn) + Wrapping(index as u32)).0;
     Ok(ea)
-}
\ No newline at end of file
+}
+// Full Extension Word format, the '020, '040 and '060 only. CPU32 and
+// ColdFire have just the brief one and take the illegal instruction
+// exception on the rest.
+const FULL_FORMAT_MASK: u16 = 0x0100;
+const BASE_SUPPRESS_MASK: u16 = 0x0080;
+const INDEX_SUPPRESS_MASK: u16 = 0x0040;
+fn full_format<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, base: u32, extension: u16, xn: u32) -> Result<u32> {
+    let indirect = extension & 7;
+    let index_suppressed = (extension & INDEX_SUPPRESS_MASK) > 0;
+    let reserved = extension & 8 != 0 || (extension >> 4) & 3 == 0
+        || if index_suppressed { indirect > 3 } else { indirect == 4 };
+    if reserved || !matches!(core.version.base(), Version::MC68020 | Version::MC68040 | Version::MC68060) {
+        return Err(IllegalInstruction(core.ir, core.ppc));
+    }
+    let base = if (extension & BASE_SUPPRESS_MASK) > 0 {0} else {base};
+    let xn = if index_suppressed {0} else {xn};
+    let bd = match (extension >> 4) & 3 {
+        2 => core.read_imm_data_16(bus)? as i16 as u32,
+        3 => core.read_imm_data_32(bus)?,
+        _ => 0,
+    };
+    // the outer displacement is in the instruction stream too, fetch it
+    // before going to memory
+    let od = match indirect & 3 {
+        2 => core.read_imm_data_16(bus)? as i16 as u32,
+        3 => core.read_imm_data_32(bus)?,
+        _ => 0,
+    };
+    let ea = (Wrapping(base) + Wrapping(bd)).0;
+    match indirect {
+        0 => Ok((Wrapping(ea) + Wrapping(xn)).0),
+        // memory indirect preindexed, ([bd,An,Xn],od)
+        1..=3 => {
+            let pointer = core.read_data_32(bus, (Wrapping(ea) + Wrapping(xn)).0)?;
+            Ok((Wrapping(pointer) + Wrapping(od)).0)
+        },
+        // memory indirect postindexed, ([bd,An],Xn,od)
+        _ => {
+            let pointer = core.read_data_32(bus, ea)?;
+            Ok((Wrapping(pointer) + Wrapping(xn) + Wrapping(od)).0)
+        },
+    }
+}
+// Decodes the <ea> in the low six bits of IR. Used by instructions whose
+// operand size is only known once the extension word has been read, like
+// the coprocessor ones. Immediate data is fetched here, from the
+// instruction stream, and returned as its value.
+pub fn location<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, size: u32) -> Result<Location> {
+    let reg_ndx = ir_ay!(core);
+    match (core.ir >> 3) & 7 {
+        0 => Ok(Location::Register(ir_dy!(core))),
+        1 => Ok(Location::Register(reg_ndx)),
+        2 => Ok(Location::Memory(core.dar[reg_ndx])),
+        3 => {
+            let ea = core.dar[reg_ndx];
+            let step = if size == 1 && reg_ndx == 15 { 2 } else { size }; // A7 is kept even
+            core.dar[reg_ndx] = ea.wrapping_add(step);
+            Ok(Location::Memory(ea))
+        },
+        4 => {
+            let step = if size == 1 && reg_ndx == 15 { 2 } else { size };
+            core.dar[reg_ndx] = core.dar[reg_ndx].wrapping_sub(step);
+            if size == 4 {
+                core.predec_long = Some(core.dar[reg_ndx]);
+            }
+            Ok(Location::Memory(core.dar[reg_ndx]))
+        },
+        5 => displacement_ay(core, bus).map(Location::Memory),
+        6 => index_ay(core, bus).map(Location::Memory),
+        _ => match core.ir & 7 {
+            0 => absolute_word(core, bus).map(Location::Memory),
+            1 => absolute_long(core, bus).map(Location::Memory),
+            2 => displacement_pc(core, bus).map(Location::Memory),
+            3 => index_pc(core, bus).map(Location::Memory),
+            4 => Ok(Location::Immediate(match size {
+                // byte immediates still take up a whole word
+                1 => (core.read_imm_data_16(bus)? & 0xff) as u64,
+                2 => core.read_imm_data_16(bus)? as u64,
+                4 => core.read_imm_data_32(bus)? as u64,
+                _ => {
+                    let high = core.read_imm_data_32(bus)? as u64;
+                    high << 32 | core.read_imm_data_32(bus)? as u64
+                },
+            })),
+            _ => Err(IllegalInstruction(core.ir, core.pc.wrapping_sub(2))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tests::small_map;
+    use {Bus, M68k, Version, SUPERVISOR_DATA};
+
+    // lea (4,a0,d1.l*4),a1
+    fn lea_scaled(version: Version) -> u32 {
+        let mut bus = small_map(0x8000, &[0x43f0, 0x1c04]);
+        let mut cpu = M68k::new(version);
+        cpu.reset(&mut bus);
+        cpu.dar[8] = 0x1000;
+        cpu.dar[1] = 3;
+        cpu.step(&mut bus);
+        cpu.dar[9]
+    }
+
+    #[test]
+    fn index_scale() {
+        assert_eq!(lea_scaled(Version::MC68000), 0x1007);
+        assert_eq!(lea_scaled(Version::MC68010), 0x1007);
+        for &version in [Version::MC68020, Version::MC68040, Version::CPU32,
+                         Version::ColdFireIsaA, Version::ColdFireIsaB, Version::ColdFireIsaC].iter() {
+            assert_eq!(lea_scaled(version), 0x1010, "{:?}", version);
+        }
+    }
+
+    #[test]
+    fn full_format_only_from_the_020() {
+        // lea ($1234,a0,d1.l*2),a1
+        for &(version, pc) in [(Version::MC68020, 0x406), (Version::CPU32, 0x600),
+                               (Version::ColdFireIsaC, 0x600)].iter() {
+            let mut bus = small_map(0x8000, &[0x43f0, 0x1b20, 0x1234]);
+            bus.poke_32(SUPERVISOR_DATA, 0x10, 0x600);
+            let mut cpu = M68k::new(version);
+            cpu.reset(&mut bus);
+            cpu.dar[8] = 0x1000;
+            cpu.dar[1] = 3;
+            cpu.step(&mut bus);
+            assert_eq!(cpu.pc, pc, "{:?}", version);
+            if pc == 0x406 {
+                assert_eq!(cpu.dar[9], 0x223a);
+            }
+        }
+    }
+}
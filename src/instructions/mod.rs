@@ -2,6 +2,7 @@
 
 mod common;
 pub mod constants;
+pub mod cycles;
 mod op_functions;
 mod operator;
 mod effective_address;
@@ -8,9 +8,15 @@ use Exception::*;
 use std::num::Wrapping;
 use M68k;
 use Bus;
+use AddressSpace;
+use Version;
+use Segment;
+use ProcessingState;
 use instructions::common::*;
 use instructions::operator::*;
 use super::super::Result;
+use pmmu::{Pmmu, TC_E};
+use cache040::{CACR_MASK_040, CACR_MASK_060, LINE_SIZE};
 
 macro_rules! impl_op {
     (-, $common:ident, $name:ident, $src:ident, dx, $cycles:expr) => (
@@ -151,6 +157,15 @@ pub fn unimplemented_1111<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Res
     Err(UnimplementedInstruction(core.ir, core.pc.wrapping_sub(2), EXCEPTION_UNIMPLEMENTED_1111))
 }
 
+// instructions the '060 left out for software to emulate, the stacked PC
+// is the instruction itself. Everything else has no handler for these yet.
+pub fn unimplemented_integer<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version != Version::MC68060 {
+        return illegal(core, bus);
+    }
+    Err(UnimplementedInstruction(core.ir, core.ppc, EXCEPTION_UNIMPLEMENTED_INTEGER))
+}
+
 impl_op!(8, abcd, abcd_8_rr, dy, dx, 6);
 impl_op!(8, abcd, abcd_8_mm, ay_pd_8, ea_ax_pd_8, 18);
 
@@ -660,6 +675,20 @@ macro_rules! branch {
             }
         }
     };
+    (32, $name:ident, $cond:tt) => {
+        pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+            if core.condition($cond)
+            {
+                let offset = core.read_imm_data_32(bus)?;
+                core.pc = core.pc.wrapping_sub(4);
+                core.pc = core.pc.wrapping_add(offset);
+                Ok(10)
+            } else {
+                core.pc = core.pc.wrapping_add(4);
+                Ok(12)
+            }
+        }
+    };
     (16, $name:ident, $cond:tt, dy) => {
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             if !core.condition($cond)
@@ -714,6 +743,21 @@ branch!(16, blt_16, LT);
 branch!(16, bgt_16, GT);
 branch!(16, ble_16, LE);
 
+branch!(32, bhi_32, HI);
+branch!(32, bls_32, LS);
+branch!(32, bcc_32, CC);
+branch!(32, bcs_32, CS);
+branch!(32, bne_32, NE);
+branch!(32, beq_32, EQ);
+branch!(32, bvc_32, VC);
+branch!(32, bvs_32, VS);
+branch!(32, bpl_32, PL);
+branch!(32, bmi_32, MI);
+branch!(32, bge_32, GE);
+branch!(32, blt_32, LT);
+branch!(32, bgt_32, GT);
+branch!(32, ble_32, LE);
+
 macro_rules! bchg_8 {
     ($name:ident, $src:ident, $dst:ident, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
@@ -919,10 +963,17 @@ pub fn bra_16<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
     Ok(10)
 }
 
+pub fn bra_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let offset = core.read_imm_data_32(bus)?;
+    core.pc = core.pc.wrapping_sub(4);
+    core.pc = core.pc.wrapping_add(offset);
+    Ok(10)
+}
+
 pub fn bsr_8<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
     let offset = mask_out_above_8!(core.ir) as i8;
     let pc = core.pc;
-    core.push_32(bus, pc);
+    core.push_32(bus, pc)?;
     core.pc = core.pc.wrapping_add(offset as u32);
     Ok(18)
 }
@@ -930,12 +981,21 @@ pub fn bsr_8<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
 pub fn bsr_16<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
     let offset = core.read_imm_data_16(bus)? as i16;
     let pc = core.pc;
-    core.push_32(bus, pc);
+    core.push_32(bus, pc)?;
     core.pc = core.pc.wrapping_sub(2);
     core.pc = core.pc.wrapping_add(offset as u32);
     Ok(18)
 }
 
+pub fn bsr_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let offset = core.read_imm_data_32(bus)?;
+    let pc = core.pc;
+    core.push_32(bus, pc)?;
+    core.pc = core.pc.wrapping_sub(4);
+    core.pc = core.pc.wrapping_add(offset);
+    Ok(18)
+}
+
 macro_rules! chk_16 {
     ($name:ident, $dst:ident, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
@@ -969,12 +1029,46 @@ chk_16!(chk_16_pcix, pcix_16,   10 + 10);
 chk_16!(chk_16_pd,   ay_pd_16,  10 +  6);
 chk_16!(chk_16_pi,   ay_pi_16,  10 +  4);
 
+// CHK.L is '020 and CPU32
+macro_rules! chk_32 {
+    ($name:ident, $dst:ident, $cycles:expr) => (
+        pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+            let src = dx!(core) as i32;
+            let bound = $dst(core, bus)? as i32;
+
+            core.not_z = src as u32;
+            core.v = 0;
+            core.c = 0;
+
+            if src >= 0 && src <= bound
+            {
+                Ok($cycles)
+            } else {
+                core.n = if src < 0 {NFLAG_SET} else {0};
+                Err(Trap(EXCEPTION_CHK, 40 + $cycles - 10))
+            }
+        });
+}
+chk_32!(chk_32_ai,   ay_ai_32,  10 +  8);
+chk_32!(chk_32_al,   al_32,     10 + 16);
+chk_32!(chk_32_aw,   aw_32,     10 + 12);
+chk_32!(chk_32_dn,   dy,        10 +  0);
+chk_32!(chk_32_di,   ay_di_32,  10 + 12);
+chk_32!(chk_32_imm,  imm_32,    10 +  8);
+chk_32!(chk_32_ix,   ay_ix_32,  10 + 14);
+chk_32!(chk_32_pcdi, pcdi_32,   10 + 12);
+chk_32!(chk_32_pcix, pcix_32,   10 + 14);
+chk_32!(chk_32_pd,   ay_pd_32,  10 + 10);
+chk_32!(chk_32_pi,   ay_pi_32,  10 +  8);
+
 macro_rules! clr {
-    ($name:ident, $dst:ident, $write_op:ident, $cycles:expr) => (
+    ($name:ident, $dst:ident, $write_op:ident, $size:expr, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             // The MC68000PRM says: In the MC68000 and MC68008 a memory location is read before it is cleared.
-            // We skip this as Musashi doesn't do that either.
+            // As in Musashi that read is skipped, except in cycle exact mode.
             let ea = $dst(core, bus)?;
+            let space = core.data_space();
+            core.dummy_read(bus, space, ea, $size)?;
 
             core.$write_op(bus, ea, 0)?;
 
@@ -995,13 +1089,13 @@ pub fn clr_8_dn<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
     core.not_z = 0;
     Ok(4)
 }
-clr!(clr_8_ai, address_indirect_ay, write_data_8, 8+4);
-clr!(clr_8_pi, postincrement_ay_8,  write_data_8, 8+4);
-clr!(clr_8_pd, predecrement_ay_8,   write_data_8, 8+6);
-clr!(clr_8_di, displacement_ay,     write_data_8, 8+8);
-clr!(clr_8_ix, index_ay,            write_data_8, 8+10);
-clr!(clr_8_aw, absolute_word,       write_data_8, 8+8);
-clr!(clr_8_al, absolute_long,       write_data_8, 8+12);
+clr!(clr_8_ai, address_indirect_ay, write_data_8, 1, 8+4);
+clr!(clr_8_pi, postincrement_ay_8,  write_data_8, 1, 8+4);
+clr!(clr_8_pd, predecrement_ay_8,   write_data_8, 1, 8+6);
+clr!(clr_8_di, displacement_ay,     write_data_8, 1, 8+8);
+clr!(clr_8_ix, index_ay,            write_data_8, 1, 8+10);
+clr!(clr_8_aw, absolute_word,       write_data_8, 1, 8+8);
+clr!(clr_8_al, absolute_long,       write_data_8, 1, 8+12);
 
 pub fn clr_16_dn<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
     dy!(core) &= 0xffff0000;
@@ -1012,13 +1106,13 @@ pub fn clr_16_dn<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32>
     core.not_z = 0;
     Ok(4)
 }
-clr!(clr_16_ai, address_indirect_ay, write_data_16, 8+4);
-clr!(clr_16_pi, postincrement_ay_16, write_data_16, 8+4);
-clr!(clr_16_pd, predecrement_ay_16,  write_data_16, 8+6);
-clr!(clr_16_di, displacement_ay,     write_data_16, 8+8);
-clr!(clr_16_ix, index_ay,            write_data_16, 8+10);
-clr!(clr_16_aw, absolute_word,       write_data_16, 8+8);
-clr!(clr_16_al, absolute_long,       write_data_16, 8+12);
+clr!(clr_16_ai, address_indirect_ay, write_data_16, 2, 8+4);
+clr!(clr_16_pi, postincrement_ay_16, write_data_16, 2, 8+4);
+clr!(clr_16_pd, predecrement_ay_16,  write_data_16, 2, 8+6);
+clr!(clr_16_di, displacement_ay,     write_data_16, 2, 8+8);
+clr!(clr_16_ix, index_ay,            write_data_16, 2, 8+10);
+clr!(clr_16_aw, absolute_word,       write_data_16, 2, 8+8);
+clr!(clr_16_al, absolute_long,       write_data_16, 2, 8+12);
 
 pub fn clr_32_dn<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
     dy!(core) = 0;
@@ -1029,13 +1123,13 @@ pub fn clr_32_dn<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32>
     core.not_z = 0;
     Ok(6)
 }
-clr!(clr_32_ai, address_indirect_ay, write_data_32, 12+8);
-clr!(clr_32_pi, postincrement_ay_32, write_data_32, 12+8);
-clr!(clr_32_pd, predecrement_ay_32,  write_data_32, 12+10);
-clr!(clr_32_di, displacement_ay,     write_data_32, 12+12);
-clr!(clr_32_ix, index_ay,            write_data_32, 12+14);
-clr!(clr_32_aw, absolute_word,       write_data_32, 12+12);
-clr!(clr_32_al, absolute_long,       write_data_32, 12+16);
+clr!(clr_32_ai, address_indirect_ay, write_data_32, 4, 12+8);
+clr!(clr_32_pi, postincrement_ay_32, write_data_32, 4, 12+8);
+clr!(clr_32_pd, predecrement_ay_32,  write_data_32, 4, 12+10);
+clr!(clr_32_di, displacement_ay,     write_data_32, 4, 12+12);
+clr!(clr_32_ix, index_ay,            write_data_32, 4, 12+14);
+clr!(clr_32_aw, absolute_word,       write_data_32, 4, 12+12);
+clr!(clr_32_al, absolute_long,       write_data_32, 4, 12+16);
 
 impl_op!(-, cmp_8, cmp_8_dn,   dy,      dx, 4+0);
 impl_op!(-, cmp_8, cmp_8_ai,   ay_ai_8, dx, 4+4);
@@ -1137,8 +1231,8 @@ cmpi_8!(cmpi_8_di, ay_di_8,  8+8);
 cmpi_8!(cmpi_8_ix, ay_ix_8,  8+10);
 cmpi_8!(cmpi_8_aw, aw_8,     8+8);
 cmpi_8!(cmpi_8_al, al_8,     8+12);
-// cmpi_8!(cmpi_8_pcdi, pcdi_8, 8+8);  not present on 68000
-// cmpi_8!(cmpi_8_pcix, pcix_8, 8+10); not present on 68000
+cmpi_8!(cmpi_8_pcdi, pcdi_8, 8+8); // '020 and CPU32
+cmpi_8!(cmpi_8_pcix, pcix_8, 8+10); // '020 and CPU32
 // cmpi_8!(..., imm) not present
 
 cmpi_16!(cmpi_16_dn, dy,           8+0);
@@ -1150,8 +1244,8 @@ cmpi_16!(cmpi_16_di, ay_di_16,  8+8);
 cmpi_16!(cmpi_16_ix, ay_ix_16,  8+10);
 cmpi_16!(cmpi_16_aw, aw_16,     8+8);
 cmpi_16!(cmpi_16_al, al_16,     8+12);
-// cmpi_16!(cmpi_16_pcdi, pcdi_16, 8+8);  not present on 68000
-// cmpi_16!(cmpi_16_pcix, pcix_16, 8+10); not present on 68000
+cmpi_16!(cmpi_16_pcdi, pcdi_16, 8+8); // '020 and CPU32
+cmpi_16!(cmpi_16_pcix, pcix_16, 8+10); // '020 and CPU32
 // cmpi_16!(..., imm) not present
 
 cmpi_32!(cmpi_32_dn, dy,           14+0);
@@ -1163,14 +1257,212 @@ cmpi_32!(cmpi_32_di, ay_di_32,  12+12);
 cmpi_32!(cmpi_32_ix, ay_ix_32,  12+14);
 cmpi_32!(cmpi_32_aw, aw_32,     12+12);
 cmpi_32!(cmpi_32_al, al_32,     12+16);
-// cmpi_32!(cmpi_32_pcdi, pcdi_32, 12+12); not present on 68000
-// cmpi_32!(cmpi_32_pcix, pcix_32, 12+14); not present on 68000
+cmpi_32!(cmpi_32_pcdi, pcdi_32, 12+12); // '020 and CPU32
+cmpi_32!(cmpi_32_pcix, pcix_32, 12+14); // '020 and CPU32
 // cmpi_32!(..., imm) not present
 
 impl_op!(-, cmp_8,  cmpm_8, ay_pi_8, ax_pi_8, 12);
 impl_op!(-, cmp_16, cmpm_16, ay_pi_16, ax_pi_16, 12);
 impl_op!(-, cmp_32, cmpm_32, ay_pi_32, ax_pi_32, 20);
 
+// Put implementation of BGND ops here
+// CPU32 only, an illegal instruction unless background debug mode was
+// enabled at reset
+pub fn bgnd<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version != Version::CPU32 || !core.bdm_enabled {
+        return illegal(core, bus);
+    }
+    core.processing_state = ProcessingState::Background;
+    Ok(4)
+}
+
+// Put implementation of BITREV ops here
+// ColdFire ISA_C, flags are left alone
+pub fn bitrev_32<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
+    dy!(core) = dy!(core).reverse_bits();
+    Ok(1)
+}
+
+// Put implementation of BKPT ops here
+// '010 on. Nothing answers the breakpoint acknowledge cycle, which makes
+// it an illegal instruction, unless the CPU32 has background debug mode
+// enabled
+pub fn bkpt<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version == Version::CPU32 && core.bdm_enabled {
+        core.processing_state = ProcessingState::Background;
+        return Ok(4);
+    }
+    illegal(core, bus)
+}
+
+// Put implementation of BYTEREV ops here
+// ColdFire ISA_C, flags are left alone
+pub fn byterev_32<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
+    dy!(core) = dy!(core).swap_bytes();
+    Ok(1)
+}
+
+// Put implementation of CAS, CAS2, CHK2, CMP2 ops here
+// '020 on, not the CPU32. The compares and the updates are one locked read
+// modify write, nothing else gets the bus in between. When a compare fails
+// the compare registers get the memory operands instead. The '060 leaves
+// misaligned CAS and all of CAS2 to software.
+fn cas_compare(core: &mut M68k, size: u32, dst: u32, src: u32) -> bool {
+    let res = match size {
+        1 => cmp_8(core, dst, src),
+        2 => cmp_16(core, dst, src),
+        _ => cmp_32(core, dst, src),
+    };
+    res == 0
+}
+fn cas_update(core: &mut M68k, dc: usize, size: u32, value: u32) {
+    core.dar[dc] = match size {
+        1 => mask_out_below_8!(core.dar[dc]) | value,
+        2 => mask_out_below_16!(core.dar[dc]) | value,
+        _ => value,
+    };
+}
+pub fn cas<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let extension = core.read_imm_data_16(bus)?;
+    let size = 1 << (((core.ir >> 9) & 3) - 1);
+    let registers = core.dar;
+    let ea = match location(core, bus, size)? {
+        Location::Memory(ea) => ea,
+        _ => return illegal(core, bus),
+    };
+    if core.version == Version::MC68060 && ea & (size - 1) != 0 {
+        // the handler decodes it again, An is as it was
+        core.dar = registers;
+        return unimplemented_integer(core, bus);
+    }
+    let dc = (extension & 7) as usize;
+    let du = ((extension >> 6) & 7) as usize;
+    let space = core.data_space();
+    core.lock_bus(bus, true);
+    let dst = core.load(bus, space, ea, size)?;
+    let src = core.dar[dc];
+    if cas_compare(core, size, dst, src) {
+        let value = core.dar[du];
+        core.store(bus, space, ea, size, value)?;
+    } else {
+        cas_update(core, dc, size, dst);
+    }
+    core.lock_bus(bus, false);
+    Ok(16)
+}
+pub fn cas2<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version == Version::MC68060 {
+        return unimplemented_integer(core, bus);
+    }
+    let extension1 = core.read_imm_data_16(bus)?;
+    let extension2 = core.read_imm_data_16(bus)?;
+    let size = if core.ir & 0x0200 != 0 { 4 } else { 2 };
+    let (ea1, ea2) = (core.dar[(extension1 >> 12) as usize], core.dar[(extension2 >> 12) as usize]);
+    let (dc1, dc2) = ((extension1 & 7) as usize, (extension2 & 7) as usize);
+    let (du1, du2) = (((extension1 >> 6) & 7) as usize, ((extension2 >> 6) & 7) as usize);
+    let space = core.data_space();
+    core.lock_bus(bus, true);
+    let dst1 = core.load(bus, space, ea1, size)?;
+    let dst2 = core.load(bus, space, ea2, size)?;
+    let (src1, src2) = (core.dar[dc1], core.dar[dc2]);
+    if cas_compare(core, size, dst1, src1) && cas_compare(core, size, dst2, src2) {
+        let (value1, value2) = (core.dar[du1], core.dar[du2]);
+        core.store(bus, space, ea1, size, value1)?;
+        core.store(bus, space, ea2, size, value2)?;
+    } else {
+        // with the same register for both, the first operand ends up in it
+        cas_update(core, dc2, size, dst2);
+        cas_update(core, dc1, size, dst1);
+    }
+    core.lock_bus(bus, false);
+    Ok(30)
+}
+// The bounds pair is at <ea>. Data registers are compared at the operation
+// size, address registers in full against sign extended bounds. The
+// compare is unsigned, a lower bound above the upper one wraps around.
+// The '060 leaves these to software.
+pub fn chk2_cmp2<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version == Version::MC68060 {
+        return unimplemented_integer(core, bus);
+    }
+    let extension = core.read_imm_data_16(bus)?;
+    let size = 1 << ((core.ir >> 9) & 3);
+    let ea = match location(core, bus, size)? {
+        Location::Memory(ea) => ea,
+        _ => return illegal(core, bus),
+    };
+    let (lower, upper) = match size {
+        1 => (core.read_data_8(bus, ea)? as u32, core.read_data_8(bus, ea.wrapping_add(1))? as u32),
+        2 => (core.read_data_16(bus, ea)? as u32, core.read_data_16(bus, ea.wrapping_add(2))? as u32),
+        _ => (core.read_data_32(bus, ea)?, core.read_data_32(bus, ea.wrapping_add(4))?),
+    };
+    let reg = (extension >> 12) as usize;
+    let (lower, upper, value) = match size {
+        _ if reg < 8 => (lower, upper, core.dar[reg] & (0xffff_ffff >> (32 - size * 8))),
+        1 => (lower as u8 as i8 as u32, upper as u8 as i8 as u32, core.dar[reg]),
+        2 => (lower as u16 as i16 as u32, upper as u16 as i16 as u32, core.dar[reg]),
+        _ => (lower, upper, core.dar[reg]),
+    };
+    let out_of_bounds = if lower <= upper {
+        value < lower || value > upper
+    } else {
+        value < lower && value > upper
+    };
+    core.not_z = if value == lower || value == upper { 0 } else { 1 };
+    core.c = if out_of_bounds { CFLAG_SET } else { 0 };
+    if out_of_bounds && extension & 0x0800 != 0 {
+        return Err(Trap(EXCEPTION_CHK, 40 + 8));
+    }
+    Ok(18)
+}
+
+// Put implementation of CINV, CPUSH ops here
+// '040 and '060 only, bit 6 selects the data cache and bit 7 the instruction cache.
+// An holds a physical address for the line and page forms. Cycle counts
+// are approximate.
+fn cache_040<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, addr: Option<u32>, page_mask: u32, push: bool) -> Result<u32> {
+    if !core.version.is_040_class() {
+        return unimplemented_1111(core, bus);
+    }
+    if core.s == 0 {
+        return Err(PrivilegeViolation(core.ir, core.pc.wrapping_sub(2)));
+    }
+    if core.ir & 0x40 != 0 {
+        core.cache_invalidate(bus, Segment::Data, addr, page_mask, push)?;
+    }
+    if core.ir & 0x80 != 0 {
+        core.cache_invalidate(bus, Segment::Program, addr, page_mask, false)?;
+    }
+    Ok(if push { 16 } else { 8 })
+}
+fn page_mask_040(core: &M68k) -> u32 {
+    if core.tc & 0x4000 != 0 { 0x1fff } else { 0x0fff }
+}
+pub fn cinvl_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let addr = ay!(core);
+    cache_040(core, bus, Some(addr), LINE_SIZE - 1, false)
+}
+pub fn cinvp_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let addr = ay!(core);
+    let page_mask = page_mask_040(core);
+    cache_040(core, bus, Some(addr), page_mask, false)
+}
+pub fn cinva_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    cache_040(core, bus, None, 0, false)
+}
+pub fn cpushl_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let addr = ay!(core);
+    cache_040(core, bus, Some(addr), LINE_SIZE - 1, true)
+}
+pub fn cpushp_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let addr = ay!(core);
+    let page_mask = page_mask_040(core);
+    cache_040(core, bus, Some(addr), page_mask, true)
+}
+pub fn cpusha_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    cache_040(core, bus, None, 0, true)
+}
+
 // Put implementation of DBcc ops here
 branch!(16, dbt_16,  True, dy);
 branch!(16, dbf_16,  False, dy);
@@ -1190,7 +1482,7 @@ branch!(16, dbgt_16, GT, dy);
 branch!(16, dble_16, LE, dy);
 
 macro_rules! div_op {
-    ($common:ident, $srctype:ty, $name:ident, $src:ident, $base_cycles:expr, $cycles:expr) => (
+    ($common:ident, $timing:ident, $srctype:ty, $name:ident, $src:ident, $base_cycles:expr, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             // as opposed to ADDA, we execute src op first
             // even though the PI/PD addressing modes will change AX (if AX=AY)
@@ -1198,7 +1490,8 @@ macro_rules! div_op {
             let dst = dx!(core);
             if src != 0 {
                 $common(core, dst, src);
-                Ok($cycles)
+                // swap the worst case for the operand dependent time, keeping the EA time
+                Ok($timing(core.version, dst, src).map_or($cycles, |cycles| cycles + $cycles - $base_cycles))
             } else {
                 // 38 cycles for the ZERO_DIVIDE trap + EA calculation time
                 // deduct the base cycles for the instruction, to extract EA cycles.
@@ -1207,10 +1500,10 @@ macro_rules! div_op {
         })
 }
 macro_rules! divs {
-    ($name:ident, $src:ident, $cycles:expr) => (div_op!(divs_16, i16, $name, $src, 158, $cycles);)
+    ($name:ident, $src:ident, $cycles:expr) => (div_op!(divs_16, divs_16_cycles, i16, $name, $src, 158, $cycles);)
 }
 macro_rules! divu {
-    ($name:ident, $src:ident, $cycles:expr) => (div_op!(divu_16, u16, $name, $src, 140, $cycles);)
+    ($name:ident, $src:ident, $cycles:expr) => (div_op!(divu_16, divu_16_cycles, u16, $name, $src, 140, $cycles);)
 }
 
 divs!(divs_16_dn, dy, 158+0);
@@ -1393,6 +1686,31 @@ pub fn ext_wl<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
     core.not_z = res;
     Ok(4)
 }
+// '020, CPU32 and ColdFire
+pub fn extb_32<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
+    let res = dy!(core) as u8 as i8 as u32;
+    dy!(core) = res;
+
+    core.n = res >> 24;
+    core.v = 0;
+    core.c = 0;
+    core.not_z = res;
+    Ok(4)
+}
+
+// Put implementation of FF1 ops here
+// ColdFire ISA_C, the bit offset of the first set bit counting from the
+// msb, 32 if there is none. Flags come from the operand.
+pub fn ff1_32<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
+    let src = dy!(core);
+    dy!(core) = src.leading_zeros();
+
+    core.n = src >> 24;
+    core.not_z = src;
+    core.v = 0;
+    core.c = 0;
+    Ok(1)
+}
 
 // Put implementation of ILLEGAL op here
 
@@ -1409,12 +1727,13 @@ macro_rules! jump {
     ($name:ident, $dst:ident, $push:expr, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             let ea = $dst(core, bus)?;
+            // the 68000 fetches from the target before JSR pushes
+            let pc = core.pc;
+            core.pc = ea;
             // using a constant expression will optimize this check away
             if $push {
-                let pc = core.pc;
-                core.push_32(bus, pc);
+                core.push_32(bus, pc)?;
             }
-            core.pc = ea;
             Ok($cycles)
         })
 }
@@ -1457,15 +1776,51 @@ lea!(lea_32_pcix, index_pc, 12);
 // Put implementation of LINK ops here
 pub fn link_16<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
     let sp = if ir_ay!(core) == STACK_POINTER_REG {
-        core.push_sp(bus)
+        core.push_sp(bus)?
     } else {
         let ay = ay!(core);
-        core.push_32(bus, ay)
+        core.push_32(bus, ay)?
     };
     ay!(core) = sp;
     sp!(core) = displacement(core, bus, sp)?;
     Ok(16)
 }
+// '020 and CPU32, a 32 bit displacement
+pub fn link_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let sp = if ir_ay!(core) == STACK_POINTER_REG {
+        core.push_sp(bus)?
+    } else {
+        let ay = ay!(core);
+        core.push_32(bus, ay)?
+    };
+    ay!(core) = sp;
+    let displacement = core.read_imm_data_32(bus)?;
+    sp!(core) = sp.wrapping_add(displacement);
+    Ok(6)
+}
+
+// Put implementation of LPSTOP ops here
+// Like STOP, but the extension word has to match or it's an F-line op. On
+// the CPU32 it shares its first word with TBL Dm:Dn using D0.
+pub fn lpstop<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if !matches!(core.version, Version::MC68060 | Version::CPU32) {
+        return unimplemented_1111(core, bus);
+    }
+    let extension = core.read_imm_data_16(bus)?;
+    if extension != 0x01c0 {
+        if core.version == Version::CPU32 {
+            return tbl_registers(core, extension);
+        }
+        return Err(UnimplementedInstruction(core.ir, core.ppc, EXCEPTION_UNIMPLEMENTED_1111));
+    }
+    if core.s == 0 {
+        return Err(PrivilegeViolation(core.ir, core.ppc));
+    }
+    let sr = core.read_imm_data_16(bus)?;
+    core.sr_to_flags(sr);
+    core.processing_state = ProcessingState::Stopped;
+    Ok(8)
+}
 
 // Put implementation of LSL, LSR ops here
 macro_rules! lsr_8 {
@@ -1520,6 +1875,158 @@ lsr_16!(lsr_16_ix, ea_ay_ix_16, 18);
 lsr_16!(lsr_16_aw, ea_aw_16,    16);
 lsr_16!(lsr_16_al, ea_al_16,    20);
 
+// Put implementation of MAC, EMAC ops here
+// ColdFire only, and only with a MAC or EMAC unit attached, the A-line
+// traps otherwise. The plain MAC has just ACC0 and no extension words.
+// Register fields are 4 bits with An as 8 and up, like dar.
+pub fn mac<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let emac = match core.mac {
+        Some(ref mac) if core.version.is_coldfire() => mac.emac,
+        _ => return unimplemented_1010(core, bus),
+    };
+    if core.ir & 0x100 == 0 {
+        mac_multiply(core, bus, emac)
+    } else {
+        match (core.ir >> 6) & 3 {
+            0 => mac_move_to(core, bus, emac),
+            2 | 3 => mac_move_from(core, emac),
+            _ => unimplemented_1010(core, bus),
+        }
+    }
+}
+fn mac_unavailable(core: &M68k) -> Result<u32> {
+    Err(UnimplementedInstruction(core.ir, core.ppc, EXCEPTION_UNIMPLEMENTED_1010))
+}
+// MAC, MSAC and the forms with a parallel load, which is when the low
+// six bits are an (An), (An)+, -(An) or (d16,An) EA instead of Ry
+fn mac_multiply<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, emac: bool) -> Result<u32> {
+    let ir = core.ir;
+    let extension = core.read_imm_data_16(bus)?;
+    let acc = ((ir >> 7) & 1 | (extension >> 3) & 2) as usize;
+    let load = ir & 0x30 != 0;
+    if (acc != 0 && !emac) || (load && (ir >> 3) & 7 > 5) {
+        return mac_unavailable(core);
+    }
+    let (rx, ry) = if load {
+        ((extension >> 12) as usize, (extension & 0xf) as usize)
+    } else {
+        (((ir >> 9) & 7 | (ir >> 3) & 8) as usize, (ir & 0xf) as usize)
+    };
+    let word = extension & 0x0800 == 0;
+    let operand = |value: u32, upper: bool| if !word { value } else if upper { value >> 16 } else { value & 0xffff };
+    let x = operand(core.dar[rx], extension & 0x80 != 0);
+    let y = operand(core.dar[ry], extension & 0x40 != 0);
+    let loaded = if load {
+        let ea = match location(core, bus, 4)? {
+            Location::Memory(ea) => ea,
+            _ => return mac_unavailable(core),
+        };
+        // the mask only applies to the access, not to the An update
+        let ea = match core.mac {
+            Some(ref mac) if extension & 0x20 != 0 => ea & (0xffff_0000 | mac.mask),
+            _ => ea,
+        };
+        Some(core.read_data_32(bus, ea)?)
+    } else {
+        None
+    };
+    if let Some(ref mut mac) = core.mac {
+        let product = mac.product(x, y, word, (extension >> 9) & 3);
+        mac.accumulate(acc, product, extension & 0x100 != 0);
+    }
+    if let Some(value) = loaded {
+        core.dar[((ir >> 9) & 7 | (ir >> 3) & 8) as usize] = value;
+        return Ok(5);
+    }
+    Ok(3)
+}
+// MOVE Ry or #imm to ACCx, MACSR, MASK or the ACCEXTs, and the EMAC's
+// MOVE ACCy,ACCx which uses the (An) encoding
+fn mac_move_to<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, emac: bool) -> Result<u32> {
+    let target = ((core.ir >> 9) & 7) as usize;
+    let mode = (core.ir >> 3) & 7;
+    if mode == 2 {
+        if !emac || target > 3 {
+            return mac_unavailable(core);
+        }
+        if let Some(ref mut mac) = core.mac {
+            mac.move_acc((core.ir & 3) as usize, target);
+        }
+        return Ok(1);
+    }
+    if mode > 1 && core.ir & 0x3f != 0x3c {
+        return mac_unavailable(core);
+    }
+    let loc = location(core, bus, 4)?;
+    let value = read_location_32(core, bus, loc)?;
+    if !emac && (target == 5 || target == 7 || (target < 4 && target != 0)) {
+        return mac_unavailable(core);
+    }
+    if let Some(ref mut mac) = core.mac {
+        match target {
+            0..=3 => mac.write_acc(target, value),
+            4 => mac.macsr = value & if emac { 0x0fff } else { 0x00ff },
+            6 => mac.mask = value & 0xffff,
+            _ => mac.set_accext((target >> 1) & 1, value),
+        }
+    }
+    Ok(1)
+}
+// MOVE ACCx, MACSR, MASK or the ACCEXTs to Rx, MOVCLR and MOVE MACSR,CCR
+fn mac_move_from(core: &mut M68k, emac: bool) -> Result<u32> {
+    let ir = core.ir;
+    let source = ((ir >> 9) & 7) as usize;
+    let clear = ir & 0x40 != 0;
+    if ir == 0xa9c0 {
+        let macsr = core.mac.as_ref().map_or(0, |mac| mac.macsr);
+        core.ccr_to_flags((macsr & 0x0f) as u16);
+        return Ok(1);
+    }
+    if ir & 0x30 != 0 || (clear && source > 3) {
+        return mac_unavailable(core);
+    }
+    if !emac && (source == 5 || source == 7 || (source < 4 && source != 0)) {
+        return mac_unavailable(core);
+    }
+    let value = match core.mac {
+        Some(ref mut mac) => match source {
+            0..=3 => {
+                let value = mac.read_acc(source);
+                if clear {
+                    mac.write_acc(source, 0);
+                }
+                value
+            },
+            4 => mac.macsr,
+            6 => 0xffff_0000 | mac.mask,
+            _ => mac.accext((source >> 1) & 1),
+        },
+        None => 0,
+    };
+    core.dar[(ir & 0xf) as usize] = value;
+    Ok(1)
+}
+
+// Put implementation of MOV3Q ops here
+// ColdFire ISA_B, the 3 bit immediate is -1 and 1 to 7
+pub fn mov3q_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.ir & 0x3f > 0x39 {
+        return illegal(core, bus);
+    }
+    let data = match ((core.ir >> 9) & 7) as u32 {
+        0 => 0xffff_ffff,
+        data => data,
+    };
+    let loc = location(core, bus, 4)?;
+    write_location_32(core, bus, loc, data)?;
+
+    core.n = data >> 24;
+    core.not_z = data;
+    core.v = 0;
+    core.c = 0;
+    Ok(1)
+}
+
 // Put implementation of MOVE ops here
 macro_rules! impl_move {
     (8, $name:ident, dx, $src:ident, $cycles:expr) => (
@@ -1929,6 +2436,31 @@ movea_32!(movea_32_pcdi, pcdi_32, 16);
 movea_32!(movea_32_pcix, pcix_32, 18);
 movea_32!(movea_32_imm, imm_32, 12);
 
+// Put implementation of MOVE from CCR ops here
+// '010 on, the upper byte is zero
+macro_rules! move_frc {
+    ($name:ident, dy, $cycles:expr) => (
+        pub fn $name<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
+            dy!(core) = mask_out_below_16!(dy!(core)) | core.condition_code_register() as u32;
+            Ok($cycles)
+        });
+    ($name:ident, $src:ident, $cycles:expr) => (
+        pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+            let ccr = core.condition_code_register();
+            let ea = $src(core, bus)?;
+            core.write_data_16(bus, ea, ccr)?;
+            Ok($cycles)
+        })
+}
+move_frc!(move_16_frc_dn, dy, 4);
+move_frc!(move_16_frc_ai, address_indirect_ay, 8+4);
+move_frc!(move_16_frc_pi, postincrement_ay_16, 8+4);
+move_frc!(move_16_frc_pd, predecrement_ay_16,  8+6);
+move_frc!(move_16_frc_di, displacement_ay,     8+8);
+move_frc!(move_16_frc_ix, index_ay,            8+10);
+move_frc!(move_16_frc_aw, absolute_word,       8+8);
+move_frc!(move_16_frc_al, absolute_long,       8+12);
+
 // Put implementation of MOVE to CCR ops here
 macro_rules! move_toc {
     ($name:ident, $src:ident, $cycles:expr) => (
@@ -1964,6 +2496,8 @@ macro_rules! move_frs {
   // return;
             let sr = core.status_register();
             let ea = $src(core, bus)?;
+            let space = core.data_space();
+            core.dummy_read(bus, space, ea, 2)?;
             core.write_data_16(bus, ea, sr)?;
             Ok($cycles)
         })
@@ -2021,20 +2555,39 @@ pub fn move_32_fru<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32
 }
 // MOVEC
 pub fn move_32_cr<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version.base() == Version::MC68000 {
+        return illegal(core, bus);
+    }
     if core.s != 0 {
         let extension = imm_16(core, bus)? as u16;
-        let ad = if extension >> 14 == 0 { 0 } else { 8 };
-        let reg = (((extension >> 11) & 7) + ad) as usize;
-        core.dar[reg] = match extension & 0x0fff {
+        let ad = if extension >> 15 == 0 { 0 } else { 8 };
+        let reg = (((extension >> 12) & 7) + ad) as usize;
+        let mc68040 = core.version.is_040_class();
+        let mc68060 = core.version == Version::MC68060;
+        let mc68020 = !matches!(core.version, Version::MC68010 | Version::CPU32);
+        let msp = core.version.has_master_stack();
+        let cr = extension & 0x0fff;
+        core.dar[reg] = match cr {
+            // the '010 and CPU32 only have SFC, DFC, USP and VBR
             SFC  => core.sfc,
             DFC  => core.dfc,
             USP  => core.inactive_usp,
             VBR  => core.vbr,
-            CACR => core.cacr,
-            CAAR => core.caar,
-            MSP  => core.inactive_msp,  // ssp is called msp on `020+
-            ISP  => core.inactive_isp,
-            _ => unimplemented!("Control Register not yet implemented."),
+            CACR if mc68020 => core.cacr,
+            CAAR if mc68020 && !mc68040 => core.caar,
+            MSP  if msp => core.inactive_msp,  // ssp is called msp on `020+
+            ISP  if msp => core.inactive_isp,
+            TC    if mc68040 => core.tc,
+            ITT0  if mc68040 => core.itt0,
+            ITT1  if mc68040 => core.itt1,
+            DTT0  if mc68040 => core.dtt0,
+            DTT1  if mc68040 => core.dtt1,
+            MMUSR if mc68040 && !mc68060 => core.mmusr,
+            URP   if mc68040 => core.urp,
+            SRP   if mc68040 => core.srp,
+            BUSCR if mc68060 => core.buscr,
+            PCR   if mc68060 => core.pcr,
+            _ => return Err(IllegalInstruction(core.ir, core.ppc)),
         };
         Ok(6)   // this is cache case
     } else {
@@ -2042,20 +2595,47 @@ pub fn move_32_cr<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32>
     }
 }
 pub fn move_32_rc<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version.base() == Version::MC68000 {
+        return illegal(core, bus);
+    }
     if core.s != 0 {
         let extension = imm_16(core, bus)? as u16;
-        let ad = if extension >> 14 == 0 { 0 } else { 8 };
-        let reg = (((extension >> 11) & 7) + ad) as usize;
-        match extension & 0x0fff {
+        let ad = if extension >> 15 == 0 { 0 } else { 8 };
+        let reg = (((extension >> 12) & 7) + ad) as usize;
+        let mc68040 = core.version.is_040_class();
+        let mc68060 = core.version == Version::MC68060;
+        let mc68020 = !matches!(core.version, Version::MC68010 | Version::CPU32);
+        let msp = core.version.has_master_stack();
+        let coldfire = core.version.is_coldfire();
+        let cr = extension & 0x0fff;
+        match cr {
+            ACR0 | ACR1 | ACR2 | ACR3 if coldfire => core.acr[(cr - ACR0) as usize] = core.dar[reg],
+            ROMBAR0 | ROMBAR1 if coldfire => core.rombar[(cr - ROMBAR0) as usize] = core.dar[reg],
+            RAMBAR0 | RAMBAR1 if coldfire => core.rambar[(cr - RAMBAR0) as usize] = core.dar[reg],
+            MBAR if coldfire => core.mbar = core.dar[reg],
+            _ if coldfire && !matches!(cr, VBR | CACR) => return Err(IllegalInstruction(core.ir, core.ppc)),
             SFC  => core.sfc = core.dar[reg],
             DFC  => core.dfc = core.dar[reg],
             USP  => core.inactive_usp = core.dar[reg],
             VBR  => core.vbr = core.dar[reg],
-            CACR => core.cacr = core.dar[reg],
-            CAAR => core.caar = core.dar[reg],
-            MSP  => core.inactive_msp = core.dar[reg],  
-            ISP  => core.inactive_isp = core.dar[reg],
-            _ => unimplemented!("Control Register not yet implemented."),
+            CACR if mc68060 => core.cacr = core.dar[reg] & CACR_MASK_060,
+            CACR if mc68040 => core.cacr = core.dar[reg] & CACR_MASK_040,
+            CACR if core.version.base() == Version::MC68020 => core.write_cacr_020(core.dar[reg]),
+            CACR if mc68020 => core.cacr = core.dar[reg],
+            CAAR if mc68020 && !mc68040 => core.caar = core.dar[reg],
+            MSP  if msp => core.inactive_msp = core.dar[reg],
+            ISP  if msp => core.inactive_isp = core.dar[reg],
+            TC    if mc68040 => core.tc = core.dar[reg] & 0xc000,
+            ITT0  if mc68040 => core.itt0 = core.dar[reg] & 0xffff_e364,
+            ITT1  if mc68040 => core.itt1 = core.dar[reg] & 0xffff_e364,
+            DTT0  if mc68040 => core.dtt0 = core.dar[reg] & 0xffff_e364,
+            DTT1  if mc68040 => core.dtt1 = core.dar[reg] & 0xffff_e364,
+            MMUSR if mc68040 && !mc68060 => core.mmusr = core.dar[reg],
+            URP   if mc68040 => core.urp = core.dar[reg] & 0xffff_fe00,
+            SRP   if mc68040 => core.srp = core.dar[reg] & 0xffff_fe00,
+            BUSCR if mc68060 => core.buscr = core.dar[reg] & 0xf000_0000,
+            PCR   if mc68060 => core.pcr = PCR_ID_060 | (core.dar[reg] & PCR_MASK_060),
+            _ => return Err(IllegalInstruction(core.ir, core.ppc)),
         };
         Ok(12)   // this is cache case
     } else {
@@ -2063,6 +2643,71 @@ pub fn move_32_rc<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32>
     }
 }
 
+// Put implementation of MOVE16 ops here
+// '040 and '060 only. Copies one 16 byte line, both addresses are truncated to a
+// line boundary. The destination line is written around the data cache,
+// any copy of it there is pushed and invalidated first.
+fn move16<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, src: u32, dst: u32) -> Result<()> {
+    let src = src & !(LINE_SIZE - 1);
+    let dst = dst & !(LINE_SIZE - 1);
+    let mut line = [0u32; 4];
+    for (i, value) in line.iter_mut().enumerate() {
+        *value = core.read_data_32(bus, src.wrapping_add(i as u32 * 4))?;
+    }
+    let space = core.data_space();
+    let (physical, _) = core.translate(bus, space, dst, true)?;
+    core.cache_invalidate(bus, Segment::Data, Some(physical), LINE_SIZE - 1, true)?;
+    for (i, &value) in line.iter().enumerate() {
+        core.store_around(bus, space, dst.wrapping_add(i as u32 * 4), 4, value)?;
+    }
+    Ok(())
+}
+pub fn move16_32_pi_pi<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if !core.version.is_040_class() {
+        return unimplemented_1111(core, bus);
+    }
+    let extension = imm_16(core, bus)?;
+    let src_reg = ir_ay!(core);
+    let dst_reg = 8 + ((extension >> 12) & 7) as usize;
+    let (src, dst) = (core.dar[src_reg], core.dar[dst_reg]);
+    move16(core, bus, src, dst)?;
+    core.dar[src_reg] = src.wrapping_add(LINE_SIZE);
+    core.dar[dst_reg] = dst.wrapping_add(LINE_SIZE);
+    Ok(18)
+}
+macro_rules! move16 {
+    ($name:ident, to_abs, $inc:expr) => (
+        pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+            if !core.version.is_040_class() {
+                return unimplemented_1111(core, bus);
+            }
+            let dst = absolute_long(core, bus)?;
+            let src = ay!(core);
+            move16(core, bus, src, dst)?;
+            if $inc {
+                ay!(core) = src.wrapping_add(LINE_SIZE);
+            }
+            Ok(18)
+        });
+    ($name:ident, from_abs, $inc:expr) => (
+        pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+            if !core.version.is_040_class() {
+                return unimplemented_1111(core, bus);
+            }
+            let src = absolute_long(core, bus)?;
+            let dst = ay!(core);
+            move16(core, bus, src, dst)?;
+            if $inc {
+                ay!(core) = dst.wrapping_add(LINE_SIZE);
+            }
+            Ok(18)
+        });
+}
+move16!(move16_32_pi_al, to_abs,   true);
+move16!(move16_32_al_pi, from_abs, true);
+move16!(move16_32_ai_al, to_abs,   false);
+move16!(move16_32_al_ai, from_abs, false);
+
 // Put implementation of MOVEM ops here
 macro_rules! movem_16_re {
     ($name:ident, predecrement_ay_16, $cycles:expr) => (
@@ -2099,7 +2744,7 @@ macro_rules! movem_16_re {
 }
 
 macro_rules! movem_16_er {
-    ($name:ident, $src:ident, pc, $cycles:expr) => (movem_16_er!($name, $src, read_prog_16, $cycles););
+    ($name:ident, $src:ident, pc, $cycles:expr) => (movem_16_er!($name, $src, read_prog_16, program_space, $cycles););
     ($name:ident, postincrement_ay_16, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             let registers = imm_16(core, bus)?;
@@ -2112,11 +2757,14 @@ macro_rules! movem_16_er {
                     moves += 1;
                 }
             }
+            // the 68000 reads a word past the last register
+            let space = core.data_space();
+            core.dummy_read(bus, space, ea, 2)?;
             ay!(core) = ea;
             Ok($cycles + 4 * moves)
         });
-    ($name:ident, $src:ident, $cycles:expr) => (movem_16_er!($name, $src, read_data_16, $cycles););
-    ($name:ident, $src:ident, $read_word:ident, $cycles:expr) => (
+    ($name:ident, $src:ident, $cycles:expr) => (movem_16_er!($name, $src, read_data_16, data_space, $cycles););
+    ($name:ident, $src:ident, $read_word:ident, $space:ident, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             let registers = imm_16(core, bus)?;
             let mut ea = $src(core, bus)?;
@@ -2128,6 +2776,8 @@ macro_rules! movem_16_er {
                     moves += 1;
                 }
             }
+            let space = core.$space();
+            core.dummy_read(bus, space, ea, 2)?;
             Ok($cycles + 4 * moves)
         })
 }
@@ -2141,6 +2791,7 @@ macro_rules! movem_32_re {
                 if registers & (1 << i) > 0 {
                     ea = ea.wrapping_sub(4);
                     let reg = core.dar[15-i];
+                    core.predec_long = Some(ea);
                     core.write_data_32(bus, ea, reg)?;
                     moves += 1;
                 }
@@ -2165,7 +2816,7 @@ macro_rules! movem_32_re {
         })
 }
 macro_rules! movem_32_er {
-    ($name:ident, $src:ident, pc, $cycles:expr) => (movem_32_er!($name, $src, read_prog_32, $cycles););
+    ($name:ident, $src:ident, pc, $cycles:expr) => (movem_32_er!($name, $src, read_prog_32, program_space, $cycles););
     ($name:ident, postincrement_ay_32, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             let registers = imm_16(core, bus)?;
@@ -2178,11 +2829,13 @@ macro_rules! movem_32_er {
                     moves += 1;
                 }
             }
+            let space = core.data_space();
+            core.dummy_read(bus, space, ea, 2)?;
             ay!(core) = ea;
             Ok($cycles + 8 * moves)
         });
-    ($name:ident, $src:ident, $cycles:expr) => (movem_32_er!($name, $src, read_data_32, $cycles););
-    ($name:ident, $src:ident, $read_long:ident, $cycles:expr) => (
+    ($name:ident, $src:ident, $cycles:expr) => (movem_32_er!($name, $src, read_data_32, data_space, $cycles););
+    ($name:ident, $src:ident, $read_long:ident, $space:ident, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             let registers = imm_16(core, bus)?;
             let mut ea = $src(core, bus)?;
@@ -2194,6 +2847,8 @@ macro_rules! movem_32_er {
                     moves += 1;
                 }
             }
+            let space = core.$space();
+            core.dummy_read(bus, space, ea, 2)?;
             Ok($cycles + 8 * moves)
         })
 }
@@ -2229,6 +2884,9 @@ movem_32_er!(movem_32_er_pcix, index_pc, pc, 18);
 
 // Put implementation of MOVEP ops here
 pub fn movep_16_er<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version == Version::MC68060 {
+        return unimplemented_integer(core, bus);
+    }
     let ea = displacement_ay(core, bus)?;
     dx!(core) = mask_out_below_16!(dx!(core))
     | (core.read_data_8(bus, ea)? as u32) << 8
@@ -2236,6 +2894,9 @@ pub fn movep_16_er<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32>
     Ok(16)
 }
 pub fn movep_16_re<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version == Version::MC68060 {
+        return unimplemented_integer(core, bus);
+    }
     let ea = displacement_ay(core, bus)?;
     let data = mask_out_above_16!(dx!(core));
     core.write_data_8(bus, ea, mask_out_above_8!(data >> 8) as u8)?;
@@ -2243,6 +2904,9 @@ pub fn movep_16_re<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32>
     Ok(16)
 }
 pub fn movep_32_er<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version == Version::MC68060 {
+        return unimplemented_integer(core, bus);
+    }
     let ea = displacement_ay(core, bus)?;
     dx!(core) = ((core.read_data_8(bus, ea))? as u32) << 24
               | ((core.read_data_8(bus, ea.wrapping_add(2)))? as u32) << 16
@@ -2251,6 +2915,9 @@ pub fn movep_32_er<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32>
     Ok(24)
 }
 pub fn movep_32_re<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version == Version::MC68060 {
+        return unimplemented_integer(core, bus);
+    }
     let ea = displacement_ay(core, bus)?;
     let data = dx!(core);
     core.write_data_8(bus, ea, mask_out_above_8!(data >> 24) as u8)?;
@@ -2260,6 +2927,38 @@ pub fn movep_32_re<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32>
     Ok(24)
 }
 
+// Put implementation of MOVES ops here
+// '010 on, a register to or from the DFC or SFC space. Address registers
+// are loaded with the whole sign extended operand.
+pub fn moves<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.s == 0 {
+        return Err(PrivilegeViolation(core.ir, core.pc.wrapping_sub(2)));
+    }
+    let extension = core.read_imm_data_16(bus)?;
+    let size = 1 << ((core.ir >> 6) & 3);
+    let reg = (extension >> 12) as usize;
+    let ea = match location(core, bus, size)? {
+        Location::Memory(ea) => ea,
+        _ => return illegal(core, bus),
+    };
+    if extension & 0x0800 != 0 {
+        let space = AddressSpace::from_fc(core.dfc);
+        let value = core.dar[reg];
+        core.store(bus, space, ea, size, value)?;
+    } else {
+        let space = AddressSpace::from_fc(core.sfc);
+        let value = core.load(bus, space, ea, size)?;
+        core.dar[reg] = match size {
+            1 if reg < 8 => mask_out_below_8!(core.dar[reg]) | value,
+            2 if reg < 8 => mask_out_below_16!(core.dar[reg]) | value,
+            1 => value as u8 as i8 as u32,
+            2 => value as u16 as i16 as u32,
+            _ => value,
+        };
+    }
+    Ok(18)
+}
+
 // Put implementation of MOVEQ ops here
 pub fn moveq_32<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
     let res = mask_out_above_8!(core.ir) as i8 as u32;
@@ -2273,21 +2972,204 @@ pub fn moveq_32<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
     Ok(4)
 }
 
+// Put implementation of MULL, DIVL ops here
+// '020 and CPU32, the '060 leaves the 64 bit forms for software. ColdFire
+// has the 32 bit results, with REMS and REMU in place of the 64 bit
+// divides.
+pub fn mull_divl<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version.is_coldfire() {
+        return mull_divl_coldfire(core, bus);
+    }
+    let extension = core.read_imm_data_16(bus)?;
+    let wide = extension & 0x0400 != 0;
+    if wide && core.version == Version::MC68060 {
+        return unimplemented_integer(core, bus);
+    }
+    let loc = location(core, bus, 4)?;
+    let src = read_location_32(core, bus, loc)?;
+    let dl = ((extension >> 12) & 7) as usize;
+    let dh = (extension & 7) as usize;
+    let signed = extension & 0x0800 != 0;
+    let (mul_cycles, div_cycles) = if core.version == Version::MC68060 { (2, 38) } else { (43, 90) };
+    if core.ir & 0x40 == 0 {
+        if wide {
+            mull_64(core, dh, dl, src, signed);
+        } else {
+            mull_32(core, dl, src, signed);
+        }
+        Ok(mul_cycles)
+    } else {
+        if wide {
+            divl_64(core, dl, dh, src, signed)?;
+        } else {
+            divl_32(core, dl, dh, src, signed)?;
+        }
+        Ok(div_cycles)
+    }
+}
+
+// MULS.L and MULU.L <ea>,Dl, V is set when the product doesn't fit
+fn mull_32(core: &mut M68k, dl: usize, src: u32, signed: bool) {
+    let (res, overflow) = if signed {
+        let (res, overflow) = (core.dar[dl] as i32).overflowing_mul(src as i32);
+        (res as u32, overflow)
+    } else {
+        core.dar[dl].overflowing_mul(src)
+    };
+    core.dar[dl] = res;
+    core.n = res >> 24;
+    core.not_z = res;
+    core.v = if overflow { VFLAG_SET } else { 0 };
+    core.c = 0;
+}
+
+// MULS.L and MULU.L <ea>,Dh:Dl, the whole 64 bit product
+fn mull_64(core: &mut M68k, dh: usize, dl: usize, src: u32, signed: bool) {
+    let res = if signed {
+        (core.dar[dl] as i32 as i64).wrapping_mul(src as i32 as i64) as u64
+    } else {
+        core.dar[dl] as u64 * src as u64
+    };
+    core.dar[dh] = (res >> 32) as u32;
+    core.dar[dl] = res as u32;
+    core.n = (res >> 56) as u32;
+    core.not_z = (res | res >> 32) as u32;
+    core.v = 0;
+    core.c = 0;
+}
+
+// DIVS.L and DIVU.L <ea>,Dr:Dq, 64 bit dividend in Dr:Dq. V is set when
+// the quotient doesn't fit, the registers are left alone then.
+fn divl_64(core: &mut M68k, dq: usize, dr: usize, src: u32, signed: bool) -> Result<()> {
+    if src == 0 {
+        core.c = 0;
+        return Err(Trap(EXCEPTION_ZERO_DIVIDE, 0));
+    }
+    let dividend = (core.dar[dr] as u64) << 32 | core.dar[dq] as u64;
+    let result = if signed {
+        let (dividend, divisor) = (dividend as i64, src as i32 as i64);
+        // i64::MIN / -1 is the one quotient that doesn't fit in an i64
+        match dividend.checked_div(divisor) {
+            Some(quotient) if quotient == quotient as i32 as i64 => Some((quotient as u32, (dividend % divisor) as u32)),
+            _ => None,
+        }
+    } else {
+        let quotient = dividend / src as u64;
+        if quotient >> 32 == 0 { Some((quotient as u32, (dividend % src as u64) as u32)) } else { None }
+    };
+    let (quotient, remainder) = match result {
+        Some(result) => result,
+        None => {
+            core.v = VFLAG_SET;
+            core.c = 0;
+            return Ok(());
+        },
+    };
+    core.dar[dr] = remainder;
+    core.dar[dq] = quotient;
+    core.n = quotient >> 24;
+    core.not_z = quotient;
+    core.v = 0;
+    core.c = 0;
+    Ok(())
+}
+
+// DIVS.L and DIVU.L <ea>,Dr:Dq, 32 bit dividend in Dq. The remainder
+// goes to Dr unless it is Dq, on overflow the registers are left alone.
+fn divl_32(core: &mut M68k, dq: usize, dr: usize, src: u32, signed: bool) -> Result<()> {
+    if src == 0 {
+        core.c = 0;
+        return Err(Trap(EXCEPTION_ZERO_DIVIDE, 0));
+    }
+    let dividend = core.dar[dq];
+    if signed && dividend == 0x8000_0000 && src == 0xffff_ffff {
+        core.v = VFLAG_SET;
+        core.c = 0;
+        return Ok(());
+    }
+    let (quotient, remainder) = if signed {
+        ((dividend as i32 / src as i32) as u32, (dividend as i32 % src as i32) as u32)
+    } else {
+        (dividend / src, dividend % src)
+    };
+    if dr != dq {
+        core.dar[dr] = remainder;
+    }
+    core.dar[dq] = quotient;
+    core.n = quotient >> 24;
+    core.not_z = quotient;
+    core.v = 0;
+    core.c = 0;
+    Ok(())
+}
+
+fn mull_divl_coldfire<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let extension = core.read_imm_data_16(bus)?;
+    if (core.ir >> 3) & 7 > 5 || extension & 0x0400 != 0 {
+        return Err(IllegalInstruction(core.ir, core.ppc));
+    }
+    let loc = location(core, bus, 4)?;
+    let src = read_location_32(core, bus, loc)?;
+    let dl = ((extension >> 12) & 7) as usize;
+    let signed = extension & 0x0800 != 0;
+    if core.ir & 0x40 == 0 {
+        let res = if signed {
+            (core.dar[dl] as i32).wrapping_mul(src as i32) as u32
+        } else {
+            core.dar[dl].wrapping_mul(src)
+        };
+        core.dar[dl] = res;
+        core.n = res >> 24;
+        core.not_z = res;
+        core.v = 0;
+        core.c = 0;
+        return Ok(5);
+    }
+    if src == 0 {
+        return Err(Trap(EXCEPTION_ZERO_DIVIDE, 0));
+    }
+    let dr = (extension & 7) as usize;
+    let dividend = core.dar[dl];
+    if signed && dividend == 0x8000_0000 && src == 0xffff_ffff {
+        core.v = VFLAG_SET;
+        core.c = 0;
+        return Ok(20);
+    }
+    let (quotient, remainder) = if signed {
+        ((dividend as i32 / src as i32) as u32, (dividend as i32 % src as i32) as u32)
+    } else {
+        (dividend / src, dividend % src)
+    };
+    // DIVx.L when the two registers are the same, REMx.L otherwise
+    let res = if dl == dr {
+        core.dar[dl] = quotient;
+        quotient
+    } else {
+        core.dar[dr] = remainder;
+        remainder
+    };
+    core.n = res >> 24;
+    core.not_z = res;
+    core.v = 0;
+    core.c = 0;
+    Ok(20)
+}
+
 // Put implementation of MULS ops here
 macro_rules! mul_op {
-    ($common:ident, $srctype:ty, $name:ident, $src:ident, $cycles:expr) => (
+    ($common:ident, $timing:ident, $srctype:ty, $name:ident, $src:ident, $base_cycles:expr, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             let src = $src(core, bus)? as $srctype;
             let dst = dx!(core) as $srctype;
             dx!(core) = $common(core, dst, src);
-            Ok($cycles)
+            Ok($timing(core.version, src).map_or($cycles, |cycles| cycles + $cycles - $base_cycles))
         })
 }
 macro_rules! muls {
-    ($name:ident, $src:ident, $cycles:expr) => (mul_op!(muls_16, i16, $name, $src, $cycles);)
+    ($name:ident, $src:ident, $cycles:expr) => (mul_op!(muls_16, muls_16_cycles, i16, $name, $src, 54, $cycles);)
 }
 macro_rules! mulu {
-    ($name:ident, $src:ident, $cycles:expr) => (mul_op!(mulu_16, u16, $name, $src, $cycles);)
+    ($name:ident, $src:ident, $cycles:expr) => (mul_op!(mulu_16, mulu_16_cycles, u16, $name, $src, 54, $cycles);)
 }
 muls!(muls_16_dn, dy, 54+0);
 muls!(muls_16_ai, ay_ai_16, 54+4);
@@ -2314,6 +3196,27 @@ mulu!(mulu_16_pcdi, pcdi_16, 54+8);
 mulu!(mulu_16_pcix, pcix_16, 54+10);
 mulu!(mulu_16_imm, imm_16, 54+4);
 
+// Put implementation of MVS, MVZ ops here
+// ColdFire ISA_B, sign or zero extend into all of Dx
+macro_rules! mv_extend {
+    ($name:ident, $size:expr, $read:ident, $extend:expr) => (
+        pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+            let loc = location(core, bus, $size)?;
+            let res = $extend($read(core, bus, loc)?);
+            dx!(core) = res;
+
+            core.n = res >> 24;
+            core.not_z = res;
+            core.v = 0;
+            core.c = 0;
+            Ok(1)
+        })
+}
+mv_extend!(mvs_8, 1, read_location_8, |v: u32| v as u8 as i8 as u32);
+mv_extend!(mvs_16, 2, read_location_16, |v: u32| v as u16 as i16 as u32);
+mv_extend!(mvz_8, 1, read_location_8, |v: u32| v);
+mv_extend!(mvz_16, 2, read_location_16, |v: u32| v);
+
 // Put implementation of NBCD ops here
 macro_rules! nbcd {
     ($name:ident, dy, $cycles:expr) => (
@@ -2726,7 +3629,7 @@ macro_rules! pea {
     ($name:ident, $src:ident, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             let ea = $src(core, bus)?;
-            core.push_32(bus, ea);
+            core.push_32(bus, ea)?;
             Ok($cycles)
         });
 }
@@ -2738,6 +3641,352 @@ pea!(pea_32_al, absolute_long, 20);
 pea!(pea_32_pcdi, displacement_pc, 16);
 pea!(pea_32_pcix, index_pc, 20);
 
+// Put implementation of MC68851 PMMU ops here
+// The PMMU is coprocessor id 0 on the F-line. Without one attached these
+// opcodes take the F-line emulator trap like any other. Coprocessor
+// interface timing isn't modelled, the cycle counts are rough estimates.
+
+// the FC field of PFLUSH, PLOAD and PTEST
+fn pmmu_fc(core: &M68k, extension: u16) -> Option<u32> {
+    match extension & 0x1f {
+        0b00000 => Some(core.sfc),
+        0b00001 => Some(core.dfc),
+        fc if fc & 0x18 == 0x08 => Some(core.dar[(fc & 7) as usize] & 0xf),
+        fc if fc & 0x10 != 0 => Some((fc & 0xf) as u32),
+        _ => None,
+    }
+}
+
+// the control addressing modes used by PFLUSH, PLOAD, PTEST and PVALID
+fn pmmu_control_ea<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    match location(core, bus, 4)? {
+        Location::Memory(ea) => Ok(ea),
+        _ => unimplemented_1111(core, bus),
+    }
+}
+
+fn pmmu_condition(core: &M68k, cc: u16) -> bool {
+    match core.pmmu {
+        Some(ref pmmu) => pmmu.condition(cc & 0x3f),
+        None => false,
+    }
+}
+
+pub fn pmmu_gen<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if !core.has_pmmu() {
+        return unimplemented_1111(core, bus);
+    }
+    let extension = imm_16(core, bus)? as u16;
+    // only PVALID may be used from user mode
+    let pvalid = extension & 0xf800 == 0x2800;
+    if core.s == 0 && !pvalid {
+        return Err(PrivilegeViolation(core.ir, core.pc.wrapping_sub(2)));
+    }
+    match extension >> 13 {
+        0b001 if pvalid => pvalid_32(core, bus, extension),
+        0b001 if (extension >> 10) & 7 == 0 => pload_32(core, bus, extension),
+        0b001 => pflush_32(core, bus, extension),
+        0b010 | 0b011 => pmove_32(core, bus, extension),
+        0b100 => ptest_32(core, bus, extension),
+        0b101 => {
+            // PFLUSHR, there is no root pointer table to search so
+            // everything not shared globally goes
+            let _ = location(core, bus, 8)?;
+            core.pmmu.as_mut().unwrap().flush(0, 0, None, false);
+            Ok(20)
+        },
+        _ => unimplemented_1111(core, bus),
+    }
+}
+
+fn pmove_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, extension: u16) -> Result<u32> {
+    let to_memory = extension & 0x0200 != 0;
+    let preg = (extension >> 10) & 7;
+    let num = ((extension >> 2) & 7) as usize;
+    let size = match (extension >> 13, preg) {
+        (0b010, 0b000) => 4,                        // TC
+        (0b010, 0b001) |
+        (0b010, 0b010) |
+        (0b010, 0b011) => 8,                        // DRP, SRP, CRP
+        (0b010, 0b100) |
+        (0b010, 0b101) |
+        (0b010, 0b110) => 1,                        // CAL, VAL, SCC
+        (0b010, _) => 2,                            // AC
+        (_, 0b000) | (_, 0b001) => 2,               // PSR, PCSR
+        (_, 0b100) | (_, 0b101) => 2,               // BADx, BACx
+        _ => return unimplemented_1111(core, bus),
+    };
+    let loc = location(core, bus, size)?;
+    let ea = match loc {
+        Location::Memory(ea) => ea,
+        Location::Register(_) if size == 8 => return unimplemented_1111(core, bus),
+        Location::Immediate(_) if to_memory => return unimplemented_1111(core, bus),
+        _ => 0,
+    };
+
+    if to_memory {
+        let value = {
+            let pmmu = core.pmmu.as_ref().unwrap();
+            match (extension >> 13, preg) {
+                (0b010, 0b000) => pmmu.tc as u64,
+                (0b010, 0b001) => pmmu.drp,
+                (0b010, 0b010) => pmmu.srp,
+                (0b010, 0b011) => pmmu.crp,
+                (0b010, 0b100) => pmmu.cal as u64,
+                (0b010, 0b101) => pmmu.val as u64,
+                (0b010, 0b110) => pmmu.scc as u64,
+                (0b010, _) => pmmu.ac as u64,
+                (_, 0b000) => pmmu.psr as u64,
+                (_, 0b001) => pmmu.pcsr as u64,
+                (_, 0b100) => pmmu.bad[num] as u64,
+                _ => pmmu.bac[num] as u64,
+            }
+        };
+        match size {
+            1 => write_location_8(core, bus, loc, value as u32)?,
+            2 => write_location_16(core, bus, loc, value as u32)?,
+            4 => write_location_32(core, bus, loc, value as u32)?,
+            _ => {
+                core.write_data_32(bus, ea, (value >> 32) as u32)?;
+                core.write_data_32(bus, ea.wrapping_add(4), value as u32)?;
+            }
+        }
+        Ok(20)
+    } else {
+        let value = match size {
+            1 => read_location_8(core, bus, loc)? as u64,
+            2 => read_location_16(core, bus, loc)? as u64,
+            4 => read_location_32(core, bus, loc)? as u64,
+            _ => match loc {
+                Location::Immediate(value) => value,
+                _ => {
+                    let hi = core.read_data_32(bus, ea)? as u64;
+                    let lo = core.read_data_32(bus, ea.wrapping_add(4))? as u64;
+                    (hi << 32) | lo
+                }
+            }
+        };
+        let pmmu = core.pmmu.as_mut().unwrap();
+        match (extension >> 13, preg) {
+            (0b010, 0b000) => {
+                if !Pmmu::tc_valid(value as u32) {
+                    pmmu.tc &= !TC_E;
+                    return Err(Trap(EXCEPTION_MMU_CONFIGURATION, 20));
+                }
+                pmmu.tc = value as u32;
+                pmmu.flush_all();
+            },
+            (0b010, 0b001) |
+            (0b010, 0b010) |
+            (0b010, 0b011) => {
+                if !Pmmu::root_pointer_valid(value) {
+                    return Err(Trap(EXCEPTION_MMU_CONFIGURATION, 20));
+                }
+                match preg {
+                    0b001 => pmmu.drp = value,
+                    0b010 => pmmu.srp = value,
+                    _ => pmmu.crp = value,
+                }
+                pmmu.flush_all();
+            },
+            (0b010, 0b100) => pmmu.cal = value as u8 & 0xe0,
+            (0b010, 0b101) => pmmu.val = value as u8 & 0xe0,
+            (0b010, 0b110) => pmmu.scc = value as u8,
+            (0b010, _) => pmmu.ac = value as u16,
+            (_, 0b000) => pmmu.psr = value as u16,
+            (_, 0b001) => (),                           // PCSR is read only
+            (_, 0b100) => pmmu.bad[num] = value as u16,
+            _ => pmmu.bac[num] = value as u16,
+        }
+        Ok(20)
+    }
+}
+
+fn pflush_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, extension: u16) -> Result<u32> {
+    let mode = (extension >> 10) & 7;
+    let mask = ((extension >> 5) & 0xf) as u32;
+    let shared = mode & 1 == 1;
+    if mode == 0b001 {
+        // PFLUSHA
+        core.pmmu.as_mut().unwrap().flush_all();
+        return Ok(12);
+    }
+    let fc = match pmmu_fc(core, extension) {
+        Some(fc) => fc,
+        None => return unimplemented_1111(core, bus),
+    };
+    match mode {
+        0b100 | 0b101 => {
+            core.pmmu.as_mut().unwrap().flush(fc, mask, None, shared);
+            Ok(12)
+        },
+        0b110 | 0b111 => {
+            let ea = pmmu_control_ea(core, bus)?;
+            core.pmmu.as_mut().unwrap().flush(fc, mask, Some(ea), shared);
+            Ok(16)
+        },
+        _ => unimplemented_1111(core, bus),
+    }
+}
+
+fn pload_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, extension: u16) -> Result<u32> {
+    let read = extension & 0x0200 != 0;
+    let fc = match pmmu_fc(core, extension) {
+        Some(fc) => fc,
+        None => return unimplemented_1111(core, bus),
+    };
+    let ea = pmmu_control_ea(core, bus)?;
+    core.pmmu.as_mut().unwrap().load(bus, fc, ea, !read);
+    Ok(40)
+}
+
+fn ptest_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, extension: u16) -> Result<u32> {
+    let level = ((extension >> 10) & 7) as u32;
+    let read = extension & 0x0200 != 0;
+    let return_address = extension & 0x0100 != 0;
+    let reg = 8 + ((extension >> 5) & 7) as usize;
+    if return_address && level == 0 {
+        return Err(Trap(EXCEPTION_MMU_ILLEGAL_OPERATION, 20));
+    }
+    let fc = match pmmu_fc(core, extension) {
+        Some(fc) => fc,
+        None => return unimplemented_1111(core, bus),
+    };
+    let ea = pmmu_control_ea(core, bus)?;
+    let descriptor = core.pmmu.as_mut().unwrap().test(bus, fc, ea, !read, level);
+    if return_address {
+        core.dar[reg] = descriptor;
+    }
+    Ok(if level == 0 { 20 } else { 40 + 10 * level })
+}
+
+fn pvalid_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, extension: u16) -> Result<u32> {
+    let ea = pmmu_control_ea(core, bus)?;
+    let an = core.dar[8 + (extension & 7) as usize];
+    let pmmu = core.pmmu.as_ref().unwrap();
+    if let Some(level) = pmmu.access_level(ea) {
+        let allowed = if extension & 0x0400 != 0 {
+            pmmu.access_level(an).unwrap_or(0)
+        } else {
+            pmmu.val_level()
+        };
+        if level < allowed {
+            return Err(Trap(EXCEPTION_MMU_ACCESS_LEVEL, 20));
+        }
+    }
+    Ok(12)
+}
+
+pub fn pscc_8<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if !core.has_pmmu() {
+        return unimplemented_1111(core, bus);
+    }
+    let cc = imm_16(core, bus)? as u16;
+    let loc = location(core, bus, 1)?;
+    let t = if pmmu_condition(core, cc) { 0xff } else { 0x00 };
+    write_location_8(core, bus, loc, t)?;
+    Ok(12)
+}
+
+pub fn pdbcc_16<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if !core.has_pmmu() {
+        return unimplemented_1111(core, bus);
+    }
+    let cc = imm_16(core, bus)? as u16;
+    if !pmmu_condition(core, cc) {
+        let dst = dy!(core);
+        let res = mask_out_above_16!(dst.wrapping_sub(1));
+        dy!(core) = mask_out_below_16!(dst) | res;
+        if res != 0xffff {
+            let offset = core.read_imm_data_16(bus)? as i16;
+            core.pc = core.pc.wrapping_sub(2);
+            core.pc = core.pc.wrapping_add(offset as u32);
+            return Ok(14);
+        }
+    }
+    core.pc = core.pc.wrapping_add(2);
+    Ok(12)
+}
+
+pub fn ptrapcc<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if !core.has_pmmu() {
+        return unimplemented_1111(core, bus);
+    }
+    let cc = imm_16(core, bus)? as u16;
+    // the optional operand is only there for the trap handler to look at
+    let operand = match core.ir & 7 {
+        0b010 => 2,
+        0b011 => 4,
+        _ => 0,
+    };
+    core.pc = core.pc.wrapping_add(operand);
+    if pmmu_condition(core, cc) {
+        Err(Trap(EXCEPTION_TRAPV, 40))
+    } else {
+        Ok(8)
+    }
+}
+
+pub fn pbcc_16<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if !core.has_pmmu() {
+        return unimplemented_1111(core, bus);
+    }
+    let cc = core.ir;
+    if pmmu_condition(core, cc) {
+        let offset = core.read_imm_data_16(bus)? as i16;
+        core.pc = core.pc.wrapping_sub(2);
+        core.pc = core.pc.wrapping_add(offset as u32);
+        Ok(10)
+    } else {
+        core.pc = core.pc.wrapping_add(2);
+        Ok(8)
+    }
+}
+
+pub fn pbcc_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if !core.has_pmmu() {
+        return unimplemented_1111(core, bus);
+    }
+    let cc = core.ir;
+    if pmmu_condition(core, cc) {
+        let offset = core.read_imm_data_32(bus)?;
+        core.pc = core.pc.wrapping_sub(4);
+        core.pc = core.pc.wrapping_add(offset);
+        Ok(12)
+    } else {
+        core.pc = core.pc.wrapping_add(4);
+        Ok(8)
+    }
+}
+
+// Put implementation of MC68040 PFLUSH, PTEST ops here
+// These share the F-line with the MC68851 ops but use the '040 encoding,
+// the function code always comes from DFC. The '060 dropped PTEST.
+macro_rules! mmu_040 {
+    ($name:ident, $($models:pat)|+, $core:ident, $bus:ident, $body:expr, $cycles:expr) => (
+        pub fn $name<T: Bus + ?Sized>($core: &mut M68k, $bus: &mut T) -> Result<u32> {
+            if !matches!($core.version, $($models)|+) {
+                return unimplemented_1111($core, $bus);
+            }
+            if $core.s == 0 {
+                return Err(PrivilegeViolation($core.ir, $core.pc.wrapping_sub(2)));
+            }
+            $body;
+            Ok($cycles)
+        })
+}
+mmu_040!(pflushn_040,  Version::MC68040 | Version::MC68060, core, _bus, { let addr = ay!(core); core.pflush_040(Some(addr), false) }, 16);
+mmu_040!(pflush_040,   Version::MC68040 | Version::MC68060, core, _bus, { let addr = ay!(core); core.pflush_040(Some(addr), true) }, 16);
+mmu_040!(pflushan_040, Version::MC68040 | Version::MC68060, core, _bus, core.pflush_040(None, false), 16);
+mmu_040!(pflusha_040,  Version::MC68040 | Version::MC68060, core, _bus, core.pflush_040(None, true), 16);
+mmu_040!(ptestw_040,   Version::MC68040, core, bus, { let addr = ay!(core); core.ptest_040(bus, addr, true) }, 32);
+mmu_040!(ptestr_040,   Version::MC68040, core, bus, { let addr = ay!(core); core.ptest_040(bus, addr, false) }, 32);
+
+// Put implementation of MC68060 PLPA ops here
+// Replaces An with its physical address, in the DFC space
+mmu_040!(plpaw_060, Version::MC68060, core, bus, { let addr = ay!(core); ay!(core) = core.plpa_060(bus, addr, true)? }, 16);
+mmu_040!(plpar_060, Version::MC68060, core, bus, { let addr = ay!(core); ay!(core) = core.plpa_060(bus, addr, false)? }, 16);
+
 // Put implementation of RESET ops here
 pub fn reset<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
     if core.s != 0 {
@@ -2854,16 +4103,20 @@ roxr_16!(roxr_16_ix, ea_ay_ix_16, 18);
 roxr_16!(roxr_16_aw, ea_aw_16,    16);
 roxr_16!(roxr_16_al, ea_al_16,    20);
 
+// Put implementation of RTD ops here
+// '010 on, frees the arguments after the return address
+pub fn rtd_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    let displacement = core.read_imm_data_16(bus)? as i16 as u32;
+    let new_pc = core.pop_32(bus)?;
+    sp!(core) = sp!(core).wrapping_add(displacement);
+    core.pc = new_pc;
+    Ok(16)
+}
+
 // Put implementation of RTE ops here
 pub fn rte_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
     if core.s != 0 {
-        let new_sr = core.pop_16(bus);
-        let new_pc = core.pop_32(bus);
-        core.pc = new_pc;
-        core.sr_to_flags(new_sr);
-
-        //core.processing_state = ProcessingState::Normal;
-
+        core.return_from_exception(bus)?;
         Ok(20)
     } else {
         Err(PrivilegeViolation(core.ir, core.pc.wrapping_sub(2)))
@@ -2872,8 +4125,8 @@ pub fn rte_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
 
 // Put implementation of RTR ops here
 pub fn rtr_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
-    let new_ccr = core.pop_16(bus);
-    let new_pc = core.pop_32(bus);
+    let new_ccr = core.pop_16(bus)?;
+    let new_pc = core.pop_32(bus)?;
     core.pc = new_pc;
     core.ccr_to_flags(new_ccr);
     Ok(20)
@@ -2881,7 +4134,7 @@ pub fn rtr_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
 
 // Put implementation of RTS ops here
 pub fn rts_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
-    let new_pc = core.pop_32(bus);
+    let new_pc = core.pop_32(bus)?;
     core.pc = new_pc;
     Ok(16)
 }
@@ -2912,6 +4165,8 @@ macro_rules! sxx_8 {
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
             let t = match core.condition($cond) { false => 0u8, true => 0xffu8 };
             let ea = $dst(core, bus)?;
+            let space = core.data_space();
+            core.dummy_read(bus, space, ea, 1)?;
             core.write_data_8(bus, ea, t)?;
             Ok($cycles)
         }
@@ -3062,6 +4317,22 @@ sxx_8!(svs_8_ix, VS, index_ay,            18);
 sxx_8!(svs_8_pd, VS, predecrement_ay_8,   14);
 sxx_8!(svs_8_pi, VS, postincrement_ay_8,  12);
 
+// Put implementation of SATS ops here
+// ColdFire ISA_B, if the last op overflowed replace Dy with the limit
+// in the direction the result came from
+pub fn sats_32<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
+    if core.v != 0 {
+        dy!(core) = if dy!(core) & 0x8000_0000 != 0 { 0x7fff_ffff } else { 0x8000_0000 };
+    }
+    let res = dy!(core);
+
+    core.n = res >> 24;
+    core.not_z = res;
+    core.v = 0;
+    core.c = 0;
+    Ok(1)
+}
+
 // Put implementation of STOP ops here
 pub fn stop<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
     if core.s != 0 {
@@ -3079,7 +4350,7 @@ pub fn stop<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
         // halted state, nor vice versa.
         let sr = core.read_imm_data_16(bus)?;
         core.sr_to_flags(sr);
-        //core.processing_state = ProcessingState::Stopped;
+        core.processing_state = ProcessingState::Stopped;
         Ok(4)
     } else {
         Err(PrivilegeViolation(core.ir, core.pc.wrapping_sub(2)))
@@ -3382,6 +4653,7 @@ macro_rules! tas_8 {
         });
     ($name:ident, $dst:ident, $cycles:expr) => (
         pub fn $name<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+            core.lock_bus(bus, true);
             let (dst, ea) = $dst(core, bus)?;
 
             core.not_z = dst;
@@ -3390,6 +4662,7 @@ macro_rules! tas_8 {
             core.c = 0;
 
             core.write_data_8(bus, ea, mask_out_above_8!(dst | 0x80) as u8)?;
+            core.lock_bus(bus, false);
             Ok($cycles)
         });
 }
@@ -3402,6 +4675,73 @@ tas_8!(tas_8_ix, ea_ay_ix_8, 14+10);
 tas_8!(tas_8_aw, ea_aw_8, 14+8);
 tas_8!(tas_8_al, ea_al_8, 14+12);
 
+// Put implementation of TBLS, TBLU ops here
+// CPU32 only. Linear interpolation between two table entries or two
+// registers. Dx bits 15-8 select the table entry and bits 7-0 are the
+// fraction, the extension word has signed (bit 11), unrounded (bit 10)
+// and the size. Unrounded results keep their 8 fraction bits.
+fn tbl(core: &mut M68k, extension: u16, y0: u32, y1: u32) -> Result<u32> {
+    let size = (extension >> 6) & 3;
+    if extension & 0x8100 != 0 || size == 3 {
+        return Err(UnimplementedInstruction(core.ir, core.ppc, EXCEPTION_UNIMPLEMENTED_1111));
+    }
+    let signed = extension & 0x0800 != 0;
+    let bits = 8 << size;
+    let extend = |y: u32| -> i64 {
+        let y = (y as u64) << (64 - bits);
+        if signed { (y as i64) >> (64 - bits) } else { (y >> (64 - bits)) as i64 }
+    };
+    let (y0, y1) = (extend(y0), extend(y1));
+    let dx_ndx = ((extension >> 12) & 7) as usize;
+    let fraction = (core.dar[dx_ndx] & 0xff) as i64;
+    let result = y0 * 256 + (y1 - y0) * fraction;
+    let (value, mask, overflow) = if extension & 0x0400 == 0 {
+        let mask = (1u64 << bits) - 1;
+        (((result + 0x80) >> 8) as u32, mask as u32, false)
+    } else {
+        let overflow = if signed { result != result as i32 as i64 } else { result != result as u32 as i64 };
+        (result as u32, 0xffff_ffff, overflow)
+    };
+    core.dar[dx_ndx] = (core.dar[dx_ndx] & !mask) | (value & mask);
+    let msb = (mask >> 1) + 1;
+    core.n = if value & msb != 0 { NFLAG_SET } else { NFLAG_CLEAR };
+    core.not_z = value & mask;
+    core.v = if overflow { VFLAG_SET } else { VFLAG_CLEAR };
+    core.c = CFLAG_CLEAR;
+    Ok(0)
+}
+pub fn tbl_registers(core: &mut M68k, extension: u16) -> Result<u32> {
+    let y0 = core.dar[(core.ir & 7) as usize];
+    let y1 = core.dar[(extension & 7) as usize];
+    tbl(core, extension, y0, y1).map(|_| 26)
+}
+pub fn tbl_dn<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version != Version::CPU32 {
+        return unimplemented_1111(core, bus);
+    }
+    let extension = core.read_imm_data_16(bus)?;
+    tbl_registers(core, extension)
+}
+pub fn tbl_ea<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
+    if core.version != Version::CPU32 {
+        return unimplemented_1111(core, bus);
+    }
+    let extension = core.read_imm_data_16(bus)?;
+    let size = 1 << ((extension >> 6) & 3);
+    let table = match location(core, bus, size)? {
+        Location::Memory(ea) => ea,
+        _ => return Err(UnimplementedInstruction(core.ir, core.ppc, EXCEPTION_UNIMPLEMENTED_1111)),
+    };
+    let dx = core.dar[((extension >> 12) & 7) as usize];
+    let entry = table.wrapping_add(((dx >> 8) & 0xff) * size);
+    let (y0, y1) = match size {
+        1 => (core.read_data_8(bus, entry)? as u32, core.read_data_8(bus, entry.wrapping_add(1))? as u32),
+        2 => (core.read_data_16(bus, entry)? as u32, core.read_data_16(bus, entry.wrapping_add(2))? as u32),
+        _ => (core.read_data_32(bus, entry)?, core.read_data_32(bus, entry.wrapping_add(4))?),
+    };
+    tbl(core, extension, y0, y1).map(|_| 34)
+}
+
 // Put implementation of TRAP ops here
 pub fn trap<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
     Err(Trap(EXCEPTION_TRAP_BASE + low_nibble!(core.ir) as u8, 34))
@@ -3451,6 +4791,17 @@ macro_rules! tst_16 {
             core.v = 0;
             core.c = 0;
 
+            Ok($cycles)
+        });
+    ($name:ident, ay, $cycles:expr) => (
+        pub fn $name<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
+            let src = mask_out_above_16!(ay!(core));
+
+            core.not_z = src;
+            core.n = src >> 8;
+            core.v = 0;
+            core.c = 0;
+
             Ok($cycles)
         });
     ($name:ident, $src:ident, $cycles:expr) => (
@@ -3478,6 +4829,7 @@ macro_rules! tst_32 {
             Ok($cycles)
         });
 }
+// the An, PC relative and immediate forms are '020, CPU32 and ColdFire
 tst_8!(tst_8_dn,   dy,      4);
 tst_8!(tst_8_ai,   ay_ai_8, 4+4);
 tst_8!(tst_8_pi,   ay_pi_8, 4+4);
@@ -3486,12 +4838,12 @@ tst_8!(tst_8_di,   ay_di_8, 4+8);
 tst_8!(tst_8_ix,   ay_ix_8, 4+10);
 tst_8!(tst_8_aw,   aw_8,    4+8);
 tst_8!(tst_8_al,   al_8,    4+12);
-// tst_8!(tst_8_pcdi, pcdi_8,  4+8);
-// tst_8!(tst_8_pcix, pcix_8,  4+10);
-// tst_8!(tst_8_imm,  imm_8,   4+4);
+tst_8!(tst_8_pcdi, pcdi_8,  4+8);
+tst_8!(tst_8_pcix, pcix_8,  4+10);
+tst_8!(tst_8_imm,  imm_8,   4+4);
 
 tst_16!(tst_16_dn,   dy,       4);
-// tst_16!(tst_16_an,   ay,       4);
+tst_16!(tst_16_an,   ay,       4);
 tst_16!(tst_16_ai,   ay_ai_16, 4+4);
 tst_16!(tst_16_pi,   ay_pi_16, 4+4);
 tst_16!(tst_16_pd,   ay_pd_16, 4+6);
@@ -3499,12 +4851,12 @@ tst_16!(tst_16_di,   ay_di_16, 4+8);
 tst_16!(tst_16_ix,   ay_ix_16, 4+10);
 tst_16!(tst_16_aw,   aw_16,    4+8);
 tst_16!(tst_16_al,   al_16,    4+12);
-// tst_16!(tst_16_pcdi, pcdi_16,  4+8);
-// tst_16!(tst_16_pcix, pcix_16,  4+10);
-// tst_16!(tst_16_imm,  imm_16,   4+4);
+tst_16!(tst_16_pcdi, pcdi_16,  4+8);
+tst_16!(tst_16_pcix, pcix_16,  4+10);
+tst_16!(tst_16_imm,  imm_16,   4+4);
 
 tst_32!(tst_32_dn,   dy,        4);
-// tst_32!(tst_32_an,   ay,        4);
+tst_32!(tst_32_an,   ay,        4);
 tst_32!(tst_32_ai,   ay_ai_32,  4+8);
 tst_32!(tst_32_pi,   ay_pi_32,  4+8);
 tst_32!(tst_32_pd,   ay_pd_32,  4+10);
@@ -3512,15 +4864,176 @@ tst_32!(tst_32_di,   ay_di_32,  4+12);
 tst_32!(tst_32_ix,   ay_ix_32,  4+14);
 tst_32!(tst_32_aw,   aw_32,     4+12);
 tst_32!(tst_32_al,   al_32,     4+16);
-// tst_32!(tst_32_pcdi, pcdi_32,   4+12);
-// tst_32!(tst_32_pcix, pcix_32,   4+14);
-// tst_32!(tst_32_imm,  imm_32,    4+8);
+tst_32!(tst_32_pcdi, pcdi_32,   4+12);
+tst_32!(tst_32_pcix, pcix_32,   4+14);
+tst_32!(tst_32_imm,  imm_32,    4+8);
 
 // Put implementation of UNLK ops here
 pub fn unlk_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<u32> {
     let ay = ay!(core);
     sp!(core) = ay;
-    ay!(core) = core.pop_32(bus);
+    ay!(core) = core.pop_32(bus)?;
 
     Ok(12)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use asm::assemble;
+    use memory::MemoryMap;
+    use tests::small_map;
+    use {AddressSpace, Bus, M68k, Version, SUPERVISOR_DATA};
+
+    // assembles `source` at $400 and steps it once with `dar` set and
+    // longs in memory, every exception vector points at $600
+    fn run<B: Bus + 'static>(version: Version, source: &str, dar: &[(usize, u32)], memory: &[(u32, u32)], wrap: fn(MemoryMap) -> B) -> (M68k<'static>, B) {
+        let words = assemble(version, 0x400, &format!(" {}", source)).unwrap_or_else(|e| panic!("`{}`: {}", source, e.message)).words();
+        let mut map = small_map(0x8000, &words);
+        for vector in 2..64 {
+            map.poke_32(SUPERVISOR_DATA, vector * 4, 0x600);
+        }
+        for &(addr, value) in memory.iter() {
+            map.poke_32(SUPERVISOR_DATA, addr, value);
+        }
+        let mut bus = wrap(map);
+        let mut cpu = M68k::new(version);
+        cpu.reset(&mut bus);
+        for &(reg, value) in dar.iter() {
+            cpu.dar[reg] = value;
+        }
+        cpu.step(&mut bus);
+        (cpu, bus)
+    }
+
+    fn step(version: Version, source: &str, dar: &[(usize, u32)], memory: &[(u32, u32)]) -> (M68k<'static>, MemoryMap) {
+        run(version, source, dar, memory, |map| map)
+    }
+
+    const Z: u16 = 0x04;
+    const N: u16 = 0x08;
+    const V: u16 = 0x02;
+
+    // the vector of the exception just taken, from the format word of the
+    // '010 on frame
+    fn vector(cpu: &M68k, bus: &MemoryMap) -> u16 {
+        (bus.peek_16(SUPERVISOR_DATA, cpu.dar[15].wrapping_add(6)) & 0xfff) / 4
+    }
+
+    #[test]
+    fn cas_swaps_only_when_the_compare_matches() {
+        let (cpu, bus) = step(Version::MC68020, "cas.l d0,d1,(a0)", &[(0, 5), (1, 9), (8, 0x1000)], &[(0x1000, 5)]);
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x1000), 9);
+        assert_eq!(cpu.status_register() & Z, Z);
+
+        let (cpu, bus) = step(Version::MC68020, "cas.l d0,d1,(a0)", &[(0, 5), (1, 9), (8, 0x1000)], &[(0x1000, 6)]);
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x1000), 6);
+        assert_eq!(cpu.dar[0], 6);
+        assert_eq!(cpu.status_register() & Z, 0);
+
+        // a word compare only loads the low word of Dc
+        let (cpu, _) = step(Version::MC68020, "cas.w d0,d1,(a0)", &[(0, 0xffff_0000), (8, 0x1000)], &[(0x1000, 0x1234_5678)]);
+        assert_eq!(cpu.dar[0], 0xffff_1234);
+    }
+
+    // reads, writes and RMC as the bus sees them
+    struct Locked {
+        map: MemoryMap,
+        log: Vec<(char, u32)>,
+    }
+
+    impl Bus for Locked {
+        fn read_8(&mut self, space: AddressSpace, addr: u32) -> u8 { self.log.push(('r', addr)); self.map.read_8(space, addr) }
+        fn read_16(&mut self, space: AddressSpace, addr: u32) -> u16 { self.log.push(('r', addr)); self.map.read_16(space, addr) }
+        fn read_32(&mut self, space: AddressSpace, addr: u32) -> u32 { self.log.push(('r', addr)); self.map.read_32(space, addr) }
+        fn write_8(&mut self, space: AddressSpace, addr: u32, value: u8) { self.log.push(('w', addr)); self.map.write_8(space, addr, value) }
+        fn write_16(&mut self, space: AddressSpace, addr: u32, value: u16) { self.log.push(('w', addr)); self.map.write_16(space, addr, value) }
+        fn write_32(&mut self, space: AddressSpace, addr: u32, value: u32) { self.log.push(('w', addr)); self.map.write_32(space, addr, value) }
+        fn peek_8(&self, space: AddressSpace, addr: u32) -> u8 { self.map.peek_8(space, addr) }
+        fn poke_8(&mut self, space: AddressSpace, addr: u32, value: u8) { self.map.poke_8(space, addr, value) }
+        fn read_modify_write(&mut self, locked: bool) {
+            self.log.push((if locked { 'L' } else { 'U' }, 0));
+        }
+    }
+
+    #[test]
+    fn cas2_is_one_locked_read_modify_write() {
+        let dar = [(0, 1), (1, 2), (2, 10), (3, 20), (8, 0x1000), (9, 0x2000)];
+        let memory = [(0x1000, 1), (0x2000, 2)];
+        let (cpu, bus) = run(Version::MC68020, "cas2.l d0:d1,d2:d3,(a0):(a1)", &dar, &memory,
+                             |map| Locked { map, log: Vec::new() });
+        let data: Vec<(char, u32)> = bus.log.into_iter().filter(|&(kind, addr)| kind.is_uppercase() || addr >= 0x1000).collect();
+        assert_eq!(data, vec![('L', 0), ('r', 0x1000), ('r', 0x2000), ('w', 0x1000), ('w', 0x2000), ('U', 0)]);
+        assert_eq!(bus.map.peek_32(SUPERVISOR_DATA, 0x1000), 10);
+        assert_eq!(bus.map.peek_32(SUPERVISOR_DATA, 0x2000), 20);
+        assert_eq!(cpu.status_register() & Z, Z);
+
+        // the second compare fails, both compare registers are loaded and
+        // nothing is written
+        let memory = [(0x1000, 1), (0x2000, 3)];
+        let (cpu, bus) = step(Version::MC68020, "cas2.l d0:d1,d2:d3,(a0):(a1)", &dar, &memory);
+        assert_eq!((cpu.dar[0], cpu.dar[1]), (1, 3));
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x1000), 1);
+        assert_eq!(cpu.status_register() & Z, 0);
+    }
+
+    #[test]
+    fn the_060_leaves_cas2_and_misaligned_cas_to_software() {
+        let (cpu, _) = step(Version::MC68060, "cas2.w d0:d1,d2:d3,(a0):(a1)", &[(8, 0x1000), (9, 0x2000)], &[]);
+        assert_eq!(cpu.pc, 0x600);
+
+        let (cpu, bus) = step(Version::MC68060, "cas.l d0,d1,(a0)+", &[(1, 9), (8, 0x1002)], &[]);
+        assert_eq!(cpu.pc, 0x600);
+        assert_eq!(cpu.dar[8], 0x1002);
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x1002), 0);
+
+        let (cpu, bus) = step(Version::MC68060, "cas.l d0,d1,(a0)+", &[(1, 9), (8, 0x1000)], &[]);
+        assert_eq!(cpu.pc, 0x404);
+        assert_eq!(cpu.dar[8], 0x1004);
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x1000), 9);
+    }
+
+    #[test]
+    fn the_060_multiplies_and_divides_32_bit_operands() {
+        let (cpu, _) = step(Version::MC68060, "muls.l d1,d2", &[(1, -3i32 as u32), (2, 7)], &[]);
+        assert_eq!(cpu.dar[2], -21i32 as u32);
+        assert_eq!(cpu.status_register() & (N | V), N);
+
+        let (cpu, _) = step(Version::MC68060, "mulu.l d1,d2", &[(1, 0x10000), (2, 0x10000)], &[]);
+        assert_eq!(cpu.dar[2], 0);
+        assert_eq!(cpu.status_register() & (Z | V), Z | V);
+
+        let (cpu, _) = step(Version::MC68060, "divu.l d1,d2", &[(1, 7), (2, 100)], &[]);
+        assert_eq!(cpu.dar[2], 14);
+
+        let (cpu, _) = step(Version::MC68060, "divsl.l d1,d3:d2", &[(1, 7), (2, -100i32 as u32)], &[]);
+        assert_eq!((cpu.dar[2], cpu.dar[3]), (-14i32 as u32, -2i32 as u32));
+        assert_eq!(cpu.pc, 0x404);
+
+        let (cpu, bus) = step(Version::MC68060, "divul.l d1,d3:d2", &[(2, 100)], &[]);
+        assert_eq!(cpu.pc, 0x600);
+        assert_eq!(vector(&cpu, &bus), 5);
+    }
+
+    #[test]
+    fn the_060_leaves_64_bit_products_and_dividends_to_software() {
+        for source in ["mulu.l d1,d3:d2", "muls.l d1,d3:d2", "divu.l d1,d3:d2", "divs.l d1,d3:d2"].iter() {
+            let (cpu, bus) = step(Version::MC68060, source, &[(1, 7), (2, 100), (3, 1)], &[]);
+            assert_eq!(cpu.pc, 0x600, "{}", source);
+            assert_eq!(vector(&cpu, &bus), 61, "{}", source);
+            assert_eq!((cpu.dar[2], cpu.dar[3]), (100, 1), "{}", source);
+        }
+    }
+
+    #[test]
+    fn coldfire_divides_into_the_quotient_or_the_remainder() {
+        // with Dq == Dr the quotient wins
+        let (cpu, _) = step(Version::ColdFireIsaA, "divu.l d1,d2", &[(1, 7), (2, 100)], &[]);
+        assert_eq!(cpu.dar[2], 14);
+
+        let (cpu, _) = step(Version::ColdFireIsaA, "remu.l d1,d3:d2", &[(1, 7), (2, 100)], &[]);
+        assert_eq!((cpu.dar[2], cpu.dar[3]), (100, 2));
+
+        let (cpu, _) = step(Version::ColdFireIsaA, "rems.l d1,d3:d2", &[(1, 7), (2, -100i32 as u32)], &[]);
+        assert_eq!((cpu.dar[2], cpu.dar[3]), (-100i32 as u32, -2i32 as u32));
+        assert_eq!(cpu.pc, 0x404);
+    }
+}
@@ -5,6 +5,8 @@ use Bus;
 use M68k;
 use super::*;
 use super::super::Result;
+use super::effective_address::Location;
+use Exception::IllegalInstruction;
 
 pub fn ea_ay_pd_8<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T) -> Result<(u32, u32)> {
     effective_address::predecrement_ay_8(core, bus)
@@ -252,4 +254,56 @@ pub fn ax<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
 }
 pub fn quick<T: Bus + ?Sized>(core: &mut M68k, _bus: &mut T) -> Result<u32> {
     Ok((((core.ir as u32 >> 9) - 1) & 7) + 1)
-}
\ No newline at end of file
+}
+// Operand access through a run time decoded Location, see effective_address::location
+pub fn read_location_8<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, loc: Location) -> Result<u32> {
+    match loc {
+        Location::Register(ndx) => Ok(mask_out_above_8!(core.dar[ndx])),
+        Location::Immediate(value) => Ok(mask_out_above_8!(value as u32)),
+        Location::Memory(ea) => core.read_data_8(bus, ea).map(|val| val as u32),
+    }
+}
+pub fn read_location_16<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, loc: Location) -> Result<u32> {
+    match loc {
+        Location::Register(ndx) => Ok(mask_out_above_16!(core.dar[ndx])),
+        Location::Immediate(value) => Ok(mask_out_above_16!(value as u32)),
+        Location::Memory(ea) => core.read_data_16(bus, ea).map(|val| val as u32),
+    }
+}
+pub fn read_location_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, loc: Location) -> Result<u32> {
+    match loc {
+        Location::Register(ndx) => Ok(core.dar[ndx]),
+        Location::Immediate(value) => Ok(value as u32),
+        Location::Memory(ea) => core.read_data_32(bus, ea),
+    }
+}
+pub fn write_location_8<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, loc: Location, value: u32) -> Result<()> {
+    match loc {
+        Location::Register(ndx) => {
+            core.dar[ndx] = mask_out_below_8!(core.dar[ndx]) | mask_out_above_8!(value);
+            Ok(())
+        },
+        Location::Memory(ea) => core.write_data_8(bus, ea, value as u8),
+        Location::Immediate(_) => Err(IllegalInstruction(core.ir, core.ppc)),
+    }
+}
+pub fn write_location_16<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, loc: Location, value: u32) -> Result<()> {
+    match loc {
+        Location::Register(ndx) => {
+            core.dar[ndx] = mask_out_below_16!(core.dar[ndx]) | mask_out_above_16!(value);
+            Ok(())
+        },
+        Location::Memory(ea) => core.write_data_16(bus, ea, value as u16),
+        Location::Immediate(_) => Err(IllegalInstruction(core.ir, core.ppc)),
+    }
+}
+pub fn write_location_32<T: Bus + ?Sized>(core: &mut M68k, bus: &mut T, loc: Location, value: u32) -> Result<()> {
+    match loc {
+        Location::Register(ndx) => {
+            core.dar[ndx] = value;
+            Ok(())
+        },
+        Location::Memory(ea) => core.write_data_32(bus, ea, value),
+        Location::Immediate(_) => Err(IllegalInstruction(core.ir, core.ppc)),
+    }
+}
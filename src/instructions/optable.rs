@@ -3,6 +3,7 @@
 //use Bus;
 use InstructionSet;
 use Handler;
+use Version;
 use instructions::op_functions::*;
 use std::collections::HashMap;
 
@@ -44,6 +45,7 @@ pub const OP_NOT   : u32 = 0b0100_0110_0000_0000;
 pub const OP_OR    : u32 = 0b1000_0000_0000_0000;
 pub const OP_ORI   : u32 = 0b0000_0000_0000_0000;
 pub const OP_PEA   : u32 = 0b0100_1000_0100_0000;
+pub const OP_PMMU  : u32 = 0b1111_0000_0000_0000;
 pub const OP_SUB   : u32 = 0b1001_0000_0000_0000;
 pub const OP_SUBI  : u32 = 0b0000_0100_0000_0000;
 pub const OP_SUBQ  : u32 = 0b0101_0001_0000_0000;
@@ -68,6 +70,12 @@ pub const MASK_LOBYTE : u32 = 0b1111111100000000; // masks out low byte
 pub const MASK_LOBYTX : u32 = 0b1111000100000000; // masks out low byte and X register bits
 pub const MASK_LO3NIB : u32 = 0b1111000000000000; // masks out lower three nibbles
 pub const MASK_LONIB  : u32 = 0b1111111111110000; // masks out low nibble
+pub const MASK_OUT_EA : u32 = 0b1111111111000000; // masks out the effective address (??????????mmmrrr)
+pub const MASK_OUT_X_EA: u32 = 0b1111000111000000; // masks out X register and effective address bits
+pub const MASK_OUT_CC_Y: u32 = 0b1111000011111000; // masks out condition and Y register bits (????cccc?????yyy)
+pub const MASK_OUT_X_SZ_Y: u32 = 0b1111000100111000; // masks out X register, size and Y register bits (????xxx?ss???yyy)
+pub const MASK_OUT_SHIFT_EA: u32 = 0b1111100011000000; // masks out shift type, direction and effective address (?????ttd??mmmrrr)
+pub const MASK_OUT_SHIFT_REG: u32 = 0b1111000011011000; // masks out count, direction, i/r and Y register bits (????cccd??i??yyy)
 
 pub const IF_T : u32 = 0b0000_0000_0000; // True            1
 pub const IF_F : u32 = 0b0001_0000_0000; // False           0
@@ -136,12 +144,12 @@ pub const OP_UNIMPLEMENTED_1111 : u32 = 0b1111_0000_0000_0000;
 struct OpcodeHandler<'a> {
     mask: u32,
     matching: u32,
-    //name: &'static str,
+    name: &'static str,
     handler: Handler<'a>,
 }
 
 macro_rules! op_entry {
-    ($mask:expr, $matching:expr, $handler:ident) => (OpcodeHandler { mask: $mask, matching: $matching, handler: $handler, }) //name: stringify!($handler) })
+    ($mask:expr, $matching:expr, $handler:ident) => (OpcodeHandler { mask: $mask, matching: $matching, handler: $handler, name: stringify!($handler) })
 }
 
 // -- OP-constants -------------------------------
@@ -555,7 +563,7 @@ pub const OP_BTST_8_S_PCDI  : u32 = OP_BITOPS | BIT_TST | SRC_IMM | OPER_PCDI;
 pub const OP_BTST_8_S_PCIX  : u32 = OP_BITOPS | BIT_TST | SRC_IMM | OPER_PCIX;
 
 const WORD_OP: u32 = 0x180;
-// const LONG_OP: u32 = 0x100;  only implemented by MC68020+
+const LONG_OP: u32 = 0x100;     // '020 and CPU32
 pub const OP_CHK_16_DN      : u32 = OP_CHK | WORD_OP | OPER_DN;
 pub const OP_CHK_16_AI      : u32 = OP_CHK | WORD_OP | OPER_AI;
 pub const OP_CHK_16_PI      : u32 = OP_CHK | WORD_OP | OPER_PI;
@@ -568,6 +576,18 @@ pub const OP_CHK_16_PCDI    : u32 = OP_CHK | WORD_OP | OPER_PCDI;
 pub const OP_CHK_16_PCIX    : u32 = OP_CHK | WORD_OP | OPER_PCIX;
 pub const OP_CHK_16_IMM     : u32 = OP_CHK | WORD_OP | OPER_IMM;
 
+pub const OP_CHK_32_DN      : u32 = OP_CHK | LONG_OP | OPER_DN;
+pub const OP_CHK_32_AI      : u32 = OP_CHK | LONG_OP | OPER_AI;
+pub const OP_CHK_32_PI      : u32 = OP_CHK | LONG_OP | OPER_PI;
+pub const OP_CHK_32_PD      : u32 = OP_CHK | LONG_OP | OPER_PD;
+pub const OP_CHK_32_DI      : u32 = OP_CHK | LONG_OP | OPER_DI;
+pub const OP_CHK_32_IX      : u32 = OP_CHK | LONG_OP | OPER_IX;
+pub const OP_CHK_32_AW      : u32 = OP_CHK | LONG_OP | OPER_AW;
+pub const OP_CHK_32_AL      : u32 = OP_CHK | LONG_OP | OPER_AL;
+pub const OP_CHK_32_PCDI    : u32 = OP_CHK | LONG_OP | OPER_PCDI;
+pub const OP_CHK_32_PCIX    : u32 = OP_CHK | LONG_OP | OPER_PCIX;
+pub const OP_CHK_32_IMM     : u32 = OP_CHK | LONG_OP | OPER_IMM;
+
 pub const OP_CLR_8_DN      : u32 = OP_CLR | BYTE_SIZED | OPER_DN;
 pub const OP_CLR_8_AI      : u32 = OP_CLR | BYTE_SIZED | OPER_AI;
 pub const OP_CLR_8_PI      : u32 = OP_CLR | BYTE_SIZED | OPER_PI;
@@ -667,6 +687,8 @@ pub const OP_CMPI_8_DI     : u32 = OP_CMPI | BYTE_SIZED | OPER_DI;
 pub const OP_CMPI_8_IX     : u32 = OP_CMPI | BYTE_SIZED | OPER_IX;
 pub const OP_CMPI_8_AW     : u32 = OP_CMPI | BYTE_SIZED | OPER_AW;
 pub const OP_CMPI_8_AL     : u32 = OP_CMPI | BYTE_SIZED | OPER_AL;
+pub const OP_CMPI_8_PCDI   : u32 = OP_CMPI | BYTE_SIZED | OPER_PCDI;
+pub const OP_CMPI_8_PCIX   : u32 = OP_CMPI | BYTE_SIZED | OPER_PCIX;
 
 pub const OP_CMPI_16_DN    : u32 = OP_CMPI | WORD_SIZED | OPER_DN;
 pub const OP_CMPI_16_AI    : u32 = OP_CMPI | WORD_SIZED | OPER_AI;
@@ -676,6 +698,8 @@ pub const OP_CMPI_16_DI    : u32 = OP_CMPI | WORD_SIZED | OPER_DI;
 pub const OP_CMPI_16_IX    : u32 = OP_CMPI | WORD_SIZED | OPER_IX;
 pub const OP_CMPI_16_AW    : u32 = OP_CMPI | WORD_SIZED | OPER_AW;
 pub const OP_CMPI_16_AL    : u32 = OP_CMPI | WORD_SIZED | OPER_AL;
+pub const OP_CMPI_16_PCDI  : u32 = OP_CMPI | WORD_SIZED | OPER_PCDI;
+pub const OP_CMPI_16_PCIX  : u32 = OP_CMPI | WORD_SIZED | OPER_PCIX;
 
 pub const OP_CMPI_32_DN    : u32 = OP_CMPI | LONG_SIZED | OPER_DN;
 pub const OP_CMPI_32_AI    : u32 = OP_CMPI | LONG_SIZED | OPER_AI;
@@ -685,11 +709,46 @@ pub const OP_CMPI_32_DI    : u32 = OP_CMPI | LONG_SIZED | OPER_DI;
 pub const OP_CMPI_32_IX    : u32 = OP_CMPI | LONG_SIZED | OPER_IX;
 pub const OP_CMPI_32_AW    : u32 = OP_CMPI | LONG_SIZED | OPER_AW;
 pub const OP_CMPI_32_AL    : u32 = OP_CMPI | LONG_SIZED | OPER_AL;
+pub const OP_CMPI_32_PCDI  : u32 = OP_CMPI | LONG_SIZED | OPER_PCDI;
+pub const OP_CMPI_32_PCIX  : u32 = OP_CMPI | LONG_SIZED | OPER_PCIX;
 
 pub const OP_CMPM_8        : u32 = OP_CMPM | BYTE_SIZED | MM_MODE;
 pub const OP_CMPM_16       : u32 = OP_CMPM | WORD_SIZED | MM_MODE;
 pub const OP_CMPM_32       : u32 = OP_CMPM | LONG_SIZED | MM_MODE;
 
+// Put constants for BGND here
+pub const OP_BGND : u32 = 0b0100_1010_1111_1010;
+
+// Put constants for BITREV here
+pub const OP_BITREV_32 : u32 = 0b0000_0000_1100_0000;
+
+// Put constants for BKPT here
+pub const OP_BKPT : u32 = 0b0100_1000_0100_1000;
+
+// Put constants for BYTEREV here
+pub const OP_BYTEREV_32 : u32 = 0b0000_0010_1100_0000;
+
+// Put constants for CAS, CAS2, CHK2, CMP2 here
+pub const OP_CAS_8   : u32 = 0b0000_1010_1100_0000;
+pub const OP_CAS_16  : u32 = 0b0000_1100_1100_0000;
+pub const OP_CAS_32  : u32 = 0b0000_1110_1100_0000;
+pub const OP_CAS2_16 : u32 = 0b0000_1100_1111_1100;
+pub const OP_CAS2_32 : u32 = 0b0000_1110_1111_1100;
+pub const OP_CHK2_CMP2_8  : u32 = 0b0000_0000_1100_0000;
+pub const OP_CHK2_CMP2_16 : u32 = 0b0000_0010_1100_0000;
+pub const OP_CHK2_CMP2_32 : u32 = 0b0000_0100_1100_0000;
+
+// Put constants for CINV, CPUSH here
+const CACHES_DC: u32 = 0x40;
+const CACHES_IC: u32 = 0x80;
+const CACHES_BC: u32 = 0xc0;
+pub const OP_CINVL_32  : u32 = 0b1111_0100_0000_1000;
+pub const OP_CINVP_32  : u32 = 0b1111_0100_0001_0000;
+pub const OP_CINVA_32  : u32 = 0b1111_0100_0001_1000;
+pub const OP_CPUSHL_32 : u32 = 0b1111_0100_0010_1000;
+pub const OP_CPUSHP_32 : u32 = 0b1111_0100_0011_0000;
+pub const OP_CPUSHA_32 : u32 = 0b1111_0100_0011_1000;
+
 // Put constants for DBcc here
 pub const OP_DBT_16        : u32 = OP_DBCC | IF_T;
 pub const OP_DBF_16        : u32 = OP_DBCC | IF_F;
@@ -805,11 +864,14 @@ pub const OP_EXG_32_DA: u32 = OP_EXG | EXG_DATA_ADDR;
 // DEST_AX_LONG, perhaps there's a better common name somewhere)
 const BYTE_TO_WORD: u32 = 0x080;
 const WORD_TO_LONG: u32 = 0x0C0;
-// const BYTE_TO_LONG: u32 = 0x1C0; // 020+
+const BYTE_TO_LONG: u32 = 0x1C0; // '020, CPU32 and ColdFire
 
 pub const OP_EXT_BW: u32 = OP_EXT | BYTE_TO_WORD;
 pub const OP_EXT_WL: u32 = OP_EXT | WORD_TO_LONG;
-// pub const OP_EXT_BL: u32 = OP_EXT | BYTE_TO_LONG; // 020+
+pub const OP_EXT_BL: u32 = OP_EXT | BYTE_TO_LONG;
+
+// Put constants for FF1 here
+pub const OP_FF1_32 : u32 = 0b0000_0100_1100_0000;
 
 // Put constants for ILLEGAL here
 pub const OP_ILLEGAL : u32 = 0b0100_1010_1111_1100;
@@ -843,6 +905,10 @@ pub const OP_LEA_32_PCIX : u32 = OP_LEA | OPER_PCIX;
 
 // Put constants for LINK here
 pub const OP_LINK_16     : u32 = 0b0100_1110_0101_0000;
+pub const OP_LINK_32     : u32 = 0b0100_1000_0000_1000;
+
+// Put constants for LPSTOP here
+pub const OP_LPSTOP : u32 = 0b1111_1000_0000_0000;
 
 // Put constants for LSL, LSR here
 pub const OP_LSL_8_R        : u32 = OP_SHIFT | SHIFT_LEFT  | BYTE_SIZED | LOGI_REG_SHIFT | REG_COUNT;
@@ -875,6 +941,9 @@ pub const OP_LSR_16_IX      : u32 = OP_SHIFT | SHIFT_RIGHT | WORD_SIZED | LOGI_M
 pub const OP_LSR_16_AW      : u32 = OP_SHIFT | SHIFT_RIGHT | WORD_SIZED | LOGI_MEM_SHIFT | OPER_AW;
 pub const OP_LSR_16_AL      : u32 = OP_SHIFT | SHIFT_RIGHT | WORD_SIZED | LOGI_MEM_SHIFT | OPER_AL;
 
+// Put constants for MAC, EMAC here
+pub const OP_MAC : u32 = 0b1010_0000_0000_0000;
+
 // Put constants for MOVE here
 const BYTE_MOVE: u32 = 0x1000;
 const WORD_MOVE: u32 = 0x3000;
@@ -1238,7 +1307,7 @@ pub const OP_MOVEA_32_IMM     : u32 = OP_MOVE | LONG_MOVE | MOVE_TO_AN | OPER_IM
 
 // Put constants for MOVE to CCR here
 const MOVE_FROM_SR : u32 = 0x0c0;
-// const MOVE_FROM_CCR : u32 = 0x2c0; // Only 010+
+const MOVE_FROM_CCR : u32 = 0x2c0; // '010 on
 const MOVE_TO_CCR  : u32 = 0x4c0;
 const MOVE_TO_SR   : u32 = 0x6c0;
 
@@ -1254,6 +1323,16 @@ pub const OP_MOVE_16_TOC_PCDI : u32 = OP_MOVE2 | MOVE_TO_CCR | OPER_PCDI;
 pub const OP_MOVE_16_TOC_PCIX : u32 = OP_MOVE2 | MOVE_TO_CCR | OPER_PCIX;
 pub const OP_MOVE_16_TOC_IMM  : u32 = OP_MOVE2 | MOVE_TO_CCR | OPER_IMM;
 
+// Put constants for MOVE from CCR here
+pub const OP_MOVE_16_FRC_DN   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_DN;
+pub const OP_MOVE_16_FRC_AI   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_AI;
+pub const OP_MOVE_16_FRC_PI   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_PI;
+pub const OP_MOVE_16_FRC_PD   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_PD;
+pub const OP_MOVE_16_FRC_DI   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_DI;
+pub const OP_MOVE_16_FRC_IX   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_IX;
+pub const OP_MOVE_16_FRC_AW   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_AW;
+pub const OP_MOVE_16_FRC_AL   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_AL;
+
 // Put constants for MOVE from SR here
 pub const OP_MOVE_16_FRS_DN   : u32 = OP_MOVE2 | MOVE_FROM_SR | OPER_DN;
 pub const OP_MOVE_16_FRS_AI   : u32 = OP_MOVE2 | MOVE_FROM_SR | OPER_AI;
@@ -1288,6 +1367,13 @@ pub const OP_MOVE_32_FRU : u32 = OP_MOVE2 | MOVE_USP | FROM_AN;
 pub const OP_MOVE_32_CR : u32 = 0b0100_1110_0111_1010;
 pub const OP_MOVE_32_RC : u32 = 0b0100_1110_0111_1011;
 
+// Put constants for MOVE16 here
+pub const OP_MOVE16_32_PI_PI : u32 = 0b1111_0110_0010_0000;
+pub const OP_MOVE16_32_PI_AL : u32 = 0b1111_0110_0000_0000;
+pub const OP_MOVE16_32_AL_PI : u32 = 0b1111_0110_0000_1000;
+pub const OP_MOVE16_32_AI_AL : u32 = 0b1111_0110_0001_0000;
+pub const OP_MOVE16_32_AL_AI : u32 = 0b1111_0110_0001_1000;
+
 // Put constants for MOVEM here
 const WORD_TRANSFER: u32 = 0x00;
 const LONG_TRANSFER: u32 = 0x40;
@@ -1334,9 +1420,21 @@ pub const OP_MOVEP_16_RE: u32 = OP_MOVEP | WORD_TRANSFER | MOVEP_REGISTER_TO_MEM
 pub const OP_MOVEP_32_ER: u32 = OP_MOVEP | LONG_TRANSFER | MOVEP_MEMORY_TO_REGISTER;
 pub const OP_MOVEP_32_RE: u32 = OP_MOVEP | LONG_TRANSFER | MOVEP_REGISTER_TO_MEMORY;
 
+// Put constants for MOV3Q here
+pub const OP_MOV3Q_32 : u32 = 0b1010_0001_0100_0000;
+
+// Put constants for MOVES here
+pub const OP_MOVES_8  : u32 = 0b0000_1110_0000_0000;
+pub const OP_MOVES_16 : u32 = 0b0000_1110_0100_0000;
+pub const OP_MOVES_32 : u32 = 0b0000_1110_1000_0000;
+
 // Put constants for MOVEQ here
 pub const OP_MOVEQ_32: u32 = 0b0111_0000_0000_0000;
 
+// Put constants for MULL, DIVL here
+pub const OP_MULL_32 : u32 = 0b0100_1100_0000_0000;
+pub const OP_DIVL_32 : u32 = 0b0100_1100_0100_0000;
+
 // Put constants for MULS here
 pub const OP_MULS_16_DN:   u32 = OP_MULS | OPER_DN;
 pub const OP_MULS_16_AI:   u32 = OP_MULS | OPER_AI;
@@ -1363,6 +1461,12 @@ pub const OP_MULU_16_PCDI: u32 = OP_MULU | OPER_PCDI;
 pub const OP_MULU_16_PCIX: u32 = OP_MULU | OPER_PCIX;
 pub const OP_MULU_16_IMM:  u32 = OP_MULU | OPER_IMM;
 
+// Put constants for MVS, MVZ here
+pub const OP_MVS_8  : u32 = 0b0111_0001_0000_0000;
+pub const OP_MVS_16 : u32 = 0b0111_0001_0100_0000;
+pub const OP_MVZ_8  : u32 = 0b0111_0001_1000_0000;
+pub const OP_MVZ_16 : u32 = 0b0111_0001_1100_0000;
+
 // Put constants for NBCD here
 pub const OP_NBCD_8_DN:   u32 = OP_NBCD | OPER_DN;
 pub const OP_NBCD_8_AI:   u32 = OP_NBCD | OPER_AI;
@@ -1565,6 +1669,46 @@ pub const OP_PEA_32_AL   : u32 = OP_PEA | OPER_AL;
 pub const OP_PEA_32_PCDI : u32 = OP_PEA | OPER_PCDI;
 pub const OP_PEA_32_PCIX : u32 = OP_PEA | OPER_PCIX;
 
+// Put constants for PMMU (MC68851) here
+pub const OP_PMMU_GEN_DN    : u32 = OP_PMMU | OPER_DN;
+pub const OP_PMMU_GEN_AN    : u32 = OP_PMMU | OPER_AN;
+pub const OP_PMMU_GEN_AI    : u32 = OP_PMMU | OPER_AI;
+pub const OP_PMMU_GEN_PI    : u32 = OP_PMMU | OPER_PI;
+pub const OP_PMMU_GEN_PD    : u32 = OP_PMMU | OPER_PD;
+pub const OP_PMMU_GEN_DI    : u32 = OP_PMMU | OPER_DI;
+pub const OP_PMMU_GEN_IX    : u32 = OP_PMMU | OPER_IX;
+pub const OP_PMMU_GEN_AW    : u32 = OP_PMMU | OPER_AW;
+pub const OP_PMMU_GEN_AL    : u32 = OP_PMMU | OPER_AL;
+pub const OP_PMMU_GEN_PCDI  : u32 = OP_PMMU | OPER_PCDI;
+pub const OP_PMMU_GEN_PCIX  : u32 = OP_PMMU | OPER_PCIX;
+pub const OP_PMMU_GEN_IMM   : u32 = OP_PMMU | OPER_IMM;
+pub const OP_PSCC_8_DN      : u32 = OP_PMMU | 0x40 | OPER_DN;
+pub const OP_PSCC_8_AI      : u32 = OP_PMMU | 0x40 | OPER_AI;
+pub const OP_PSCC_8_PI      : u32 = OP_PMMU | 0x40 | OPER_PI;
+pub const OP_PSCC_8_PD      : u32 = OP_PMMU | 0x40 | OPER_PD;
+pub const OP_PSCC_8_DI      : u32 = OP_PMMU | 0x40 | OPER_DI;
+pub const OP_PSCC_8_IX      : u32 = OP_PMMU | 0x40 | OPER_IX;
+pub const OP_PSCC_8_AW      : u32 = OP_PMMU | 0x40 | OPER_AW;
+pub const OP_PSCC_8_AL      : u32 = OP_PMMU | 0x40 | OPER_AL;
+pub const OP_PDBCC_16       : u32 = OP_PMMU | 0x48;
+pub const OP_PTRAPCC_16     : u32 = OP_PMMU | 0x7a;
+pub const OP_PTRAPCC_32     : u32 = OP_PMMU | 0x7b;
+pub const OP_PTRAPCC_0      : u32 = OP_PMMU | 0x7c;
+pub const OP_PBCC_16        : u32 = OP_PMMU | 0x80;
+pub const OP_PBCC_32        : u32 = OP_PMMU | 0xc0;
+
+// Put constants for MC68040 PFLUSH, PTEST here
+pub const OP_PFLUSHN_040  : u32 = 0b1111_0101_0000_0000;
+pub const OP_PFLUSH_040   : u32 = 0b1111_0101_0000_1000;
+pub const OP_PFLUSHAN_040 : u32 = 0b1111_0101_0001_0000;
+pub const OP_PFLUSHA_040  : u32 = 0b1111_0101_0001_1000;
+pub const OP_PTESTW_040   : u32 = 0b1111_0101_0100_1000;
+pub const OP_PTESTR_040   : u32 = 0b1111_0101_0110_1000;
+
+// Put constants for MC68060 PLPA here
+pub const OP_PLPAW_060 : u32 = 0b1111_0101_1000_1000;
+pub const OP_PLPAR_060 : u32 = 0b1111_0101_1100_1000;
+
 // Put constants for RESET here
 pub const OP_RESET : u32 = 0b0100_1110_0111_0000;
 
@@ -1630,6 +1774,9 @@ pub const OP_ROXR_16_IX      : u32 = OP_SHIFT | SHIFT_RIGHT | WORD_SIZED | ROTX_
 pub const OP_ROXR_16_AW      : u32 = OP_SHIFT | SHIFT_RIGHT | WORD_SIZED | ROTX_MEM_SHIFT | OPER_AW;
 pub const OP_ROXR_16_AL      : u32 = OP_SHIFT | SHIFT_RIGHT | WORD_SIZED | ROTX_MEM_SHIFT | OPER_AL;
 
+// Put constants for RTD here
+pub const OP_RTD_32 : u32 = 0b0100111001110100;
+
 // Put constants for RTE here
 pub const OP_RTE_32 : u32 = 0b0100111001110011;
 
@@ -1786,6 +1933,9 @@ pub const OP_SVS_8_IX      : u32 = OP_SCC | IF_VS | OPER_IX;
 pub const OP_SVS_8_PD      : u32 = OP_SCC | IF_VS | OPER_PD;
 pub const OP_SVS_8_PI      : u32 = OP_SCC | IF_VS | OPER_PI;
 
+// Put constants for SATS here
+pub const OP_SATS_32 : u32 = 0b0100_1100_1000_0000;
+
 // Put constants for Scc here
 // Put constants for STOP here
 pub const OP_STOP          : u32 = 0b0100111001110010;
@@ -1955,6 +2105,9 @@ pub const OP_TAS_8_IX    : u32 = OP_TAS | OPER_IX;
 pub const OP_TAS_8_AW    : u32 = OP_TAS | OPER_AW;
 pub const OP_TAS_8_AL    : u32 = OP_TAS | OPER_AL;
 
+// Put constants for TBLS, TBLU here
+pub const OP_TBL : u32 = 0b1111_1000_0000_0000;
+
 // Put constants for TRAP here
 pub const OP_TRAP  : u32 = 0b0100_1110_0100_0000;
 
@@ -2317,23 +2470,22 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_EXACT, OP_BRA_16, bra_16),
         op_entry!(MASK_EXACT, OP_BSR_16, bsr_16),
 
-        // for M68000, Bcc does not support 32-bit displacements
-        op_entry!(MASK_EXACT, OP_BHI_32, illegal),
-        op_entry!(MASK_EXACT, OP_BLS_32, illegal),
-        op_entry!(MASK_EXACT, OP_BCC_32, illegal),
-        op_entry!(MASK_EXACT, OP_BCS_32, illegal),
-        op_entry!(MASK_EXACT, OP_BNE_32, illegal),
-        op_entry!(MASK_EXACT, OP_BEQ_32, illegal),
-        op_entry!(MASK_EXACT, OP_BVC_32, illegal),
-        op_entry!(MASK_EXACT, OP_BVS_32, illegal),
-        op_entry!(MASK_EXACT, OP_BPL_32, illegal),
-        op_entry!(MASK_EXACT, OP_BMI_32, illegal),
-        op_entry!(MASK_EXACT, OP_BGE_32, illegal),
-        op_entry!(MASK_EXACT, OP_BLT_32, illegal),
-        op_entry!(MASK_EXACT, OP_BGT_32, illegal),
-        op_entry!(MASK_EXACT, OP_BLE_32, illegal),
-        op_entry!(MASK_EXACT, OP_BRA_32, illegal),
-        op_entry!(MASK_EXACT, OP_BSR_32, illegal),
+        op_entry!(MASK_EXACT, OP_BHI_32, bhi_32),
+        op_entry!(MASK_EXACT, OP_BLS_32, bls_32),
+        op_entry!(MASK_EXACT, OP_BCC_32, bcc_32),
+        op_entry!(MASK_EXACT, OP_BCS_32, bcs_32),
+        op_entry!(MASK_EXACT, OP_BNE_32, bne_32),
+        op_entry!(MASK_EXACT, OP_BEQ_32, beq_32),
+        op_entry!(MASK_EXACT, OP_BVC_32, bvc_32),
+        op_entry!(MASK_EXACT, OP_BVS_32, bvs_32),
+        op_entry!(MASK_EXACT, OP_BPL_32, bpl_32),
+        op_entry!(MASK_EXACT, OP_BMI_32, bmi_32),
+        op_entry!(MASK_EXACT, OP_BGE_32, bge_32),
+        op_entry!(MASK_EXACT, OP_BLT_32, blt_32),
+        op_entry!(MASK_EXACT, OP_BGT_32, bgt_32),
+        op_entry!(MASK_EXACT, OP_BLE_32, ble_32),
+        op_entry!(MASK_EXACT, OP_BRA_32, bra_32),
+        op_entry!(MASK_EXACT, OP_BSR_32, bsr_32),
 
         op_entry!(MASK_OUT_X_Y, OP_BCHG_32_R_DN,bchg_32_r_dn),
         op_entry!(MASK_OUT_Y,   OP_BCHG_32_S_DN,bchg_32_s_dn),
@@ -2420,6 +2572,18 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_OUT_X_Y, OP_CHK_16_PD,   chk_16_pd),
         op_entry!(MASK_OUT_X_Y, OP_CHK_16_PI,   chk_16_pi),
 
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_AI,   chk_32_ai),
+        op_entry!(MASK_OUT_X,   OP_CHK_32_AL,   chk_32_al),
+        op_entry!(MASK_OUT_X,   OP_CHK_32_AW,   chk_32_aw),
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_DN,   chk_32_dn),
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_DI,   chk_32_di),
+        op_entry!(MASK_OUT_X,   OP_CHK_32_IMM,  chk_32_imm),
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_IX,   chk_32_ix),
+        op_entry!(MASK_OUT_X,   OP_CHK_32_PCDI, chk_32_pcdi),
+        op_entry!(MASK_OUT_X,   OP_CHK_32_PCIX, chk_32_pcix),
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_PD,   chk_32_pd),
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_PI,   chk_32_pi),
+
         op_entry!(MASK_OUT_Y, OP_CLR_8_DN, clr_8_dn),
         op_entry!(MASK_OUT_Y, OP_CLR_8_AI, clr_8_ai),
         op_entry!(MASK_OUT_Y, OP_CLR_8_PI, clr_8_pi),
@@ -2519,6 +2683,8 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_OUT_Y, OP_CMPI_8_IX,   cmpi_8_ix),
         op_entry!(MASK_EXACT, OP_CMPI_8_AW,   cmpi_8_aw),
         op_entry!(MASK_EXACT, OP_CMPI_8_AL,   cmpi_8_al),
+        op_entry!(MASK_EXACT, OP_CMPI_8_PCDI, cmpi_8_pcdi),
+        op_entry!(MASK_EXACT, OP_CMPI_8_PCIX, cmpi_8_pcix),
 
         op_entry!(MASK_OUT_Y, OP_CMPI_16_DN,   cmpi_16_dn),
         op_entry!(MASK_OUT_Y, OP_CMPI_16_AI,   cmpi_16_ai),
@@ -2528,6 +2694,8 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_OUT_Y, OP_CMPI_16_IX,   cmpi_16_ix),
         op_entry!(MASK_EXACT, OP_CMPI_16_AW,   cmpi_16_aw),
         op_entry!(MASK_EXACT, OP_CMPI_16_AL,   cmpi_16_al),
+        op_entry!(MASK_EXACT, OP_CMPI_16_PCDI, cmpi_16_pcdi),
+        op_entry!(MASK_EXACT, OP_CMPI_16_PCIX, cmpi_16_pcix),
 
         op_entry!(MASK_OUT_Y, OP_CMPI_32_DN,   cmpi_32_dn),
         op_entry!(MASK_OUT_Y, OP_CMPI_32_AI,   cmpi_32_ai),
@@ -2537,11 +2705,85 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_OUT_Y, OP_CMPI_32_IX,   cmpi_32_ix),
         op_entry!(MASK_EXACT, OP_CMPI_32_AW,   cmpi_32_aw),
         op_entry!(MASK_EXACT, OP_CMPI_32_AL,   cmpi_32_al),
+        op_entry!(MASK_EXACT, OP_CMPI_32_PCDI, cmpi_32_pcdi),
+        op_entry!(MASK_EXACT, OP_CMPI_32_PCIX, cmpi_32_pcix),
 
         op_entry!(MASK_OUT_X_Y, OP_CMPM_8,  cmpm_8),
         op_entry!(MASK_OUT_X_Y, OP_CMPM_16, cmpm_16),
         op_entry!(MASK_OUT_X_Y, OP_CMPM_32, cmpm_32),
 
+        // Put op-entries for BGND here
+        op_entry!(MASK_EXACT, OP_BGND, bgnd),
+
+        // Put op-entries for BKPT here
+        op_entry!(MASK_OUT_Y, OP_BKPT, bkpt),
+
+        // Put op-entries for CAS, CAS2, CHK2, CMP2 here
+        op_entry!(MASK_OUT_Y, OP_CAS_8  | OPER_AI, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_8  | OPER_PI, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_8  | OPER_PD, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_8  | OPER_DI, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_8  | OPER_IX, cas),
+        op_entry!(MASK_EXACT, OP_CAS_8  | OPER_AW, cas),
+        op_entry!(MASK_EXACT, OP_CAS_8  | OPER_AL, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_16 | OPER_AI, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_16 | OPER_PI, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_16 | OPER_PD, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_16 | OPER_DI, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_16 | OPER_IX, cas),
+        op_entry!(MASK_EXACT, OP_CAS_16 | OPER_AW, cas),
+        op_entry!(MASK_EXACT, OP_CAS_16 | OPER_AL, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_32 | OPER_AI, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_32 | OPER_PI, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_32 | OPER_PD, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_32 | OPER_DI, cas),
+        op_entry!(MASK_OUT_Y, OP_CAS_32 | OPER_IX, cas),
+        op_entry!(MASK_EXACT, OP_CAS_32 | OPER_AW, cas),
+        op_entry!(MASK_EXACT, OP_CAS_32 | OPER_AL, cas),
+        op_entry!(MASK_EXACT, OP_CAS2_16, cas2),
+        op_entry!(MASK_EXACT, OP_CAS2_32, cas2),
+        op_entry!(MASK_OUT_Y, OP_CHK2_CMP2_8 | OPER_AI, chk2_cmp2),
+        op_entry!(MASK_OUT_Y, OP_CHK2_CMP2_8 | OPER_DI, chk2_cmp2),
+        op_entry!(MASK_OUT_Y, OP_CHK2_CMP2_8 | OPER_IX, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_8 | OPER_AW, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_8 | OPER_AL, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_8 | OPER_PCDI, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_8 | OPER_PCIX, chk2_cmp2),
+        op_entry!(MASK_OUT_Y, OP_CHK2_CMP2_16 | OPER_AI, chk2_cmp2),
+        op_entry!(MASK_OUT_Y, OP_CHK2_CMP2_16 | OPER_DI, chk2_cmp2),
+        op_entry!(MASK_OUT_Y, OP_CHK2_CMP2_16 | OPER_IX, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_16 | OPER_AW, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_16 | OPER_AL, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_16 | OPER_PCDI, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_16 | OPER_PCIX, chk2_cmp2),
+        op_entry!(MASK_OUT_Y, OP_CHK2_CMP2_32 | OPER_AI, chk2_cmp2),
+        op_entry!(MASK_OUT_Y, OP_CHK2_CMP2_32 | OPER_DI, chk2_cmp2),
+        op_entry!(MASK_OUT_Y, OP_CHK2_CMP2_32 | OPER_IX, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_32 | OPER_AW, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_32 | OPER_AL, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_32 | OPER_PCDI, chk2_cmp2),
+        op_entry!(MASK_EXACT, OP_CHK2_CMP2_32 | OPER_PCIX, chk2_cmp2),
+
+        // Put op-entries for CINV, CPUSH here
+        op_entry!(MASK_OUT_Y, OP_CINVL_32 | CACHES_DC,  cinvl_32),
+        op_entry!(MASK_OUT_Y, OP_CINVL_32 | CACHES_IC,  cinvl_32),
+        op_entry!(MASK_OUT_Y, OP_CINVL_32 | CACHES_BC,  cinvl_32),
+        op_entry!(MASK_OUT_Y, OP_CINVP_32 | CACHES_DC,  cinvp_32),
+        op_entry!(MASK_OUT_Y, OP_CINVP_32 | CACHES_IC,  cinvp_32),
+        op_entry!(MASK_OUT_Y, OP_CINVP_32 | CACHES_BC,  cinvp_32),
+        op_entry!(MASK_OUT_Y, OP_CINVA_32 | CACHES_DC,  cinva_32),
+        op_entry!(MASK_OUT_Y, OP_CINVA_32 | CACHES_IC,  cinva_32),
+        op_entry!(MASK_OUT_Y, OP_CINVA_32 | CACHES_BC,  cinva_32),
+        op_entry!(MASK_OUT_Y, OP_CPUSHL_32 | CACHES_DC, cpushl_32),
+        op_entry!(MASK_OUT_Y, OP_CPUSHL_32 | CACHES_IC, cpushl_32),
+        op_entry!(MASK_OUT_Y, OP_CPUSHL_32 | CACHES_BC, cpushl_32),
+        op_entry!(MASK_OUT_Y, OP_CPUSHP_32 | CACHES_DC, cpushp_32),
+        op_entry!(MASK_OUT_Y, OP_CPUSHP_32 | CACHES_IC, cpushp_32),
+        op_entry!(MASK_OUT_Y, OP_CPUSHP_32 | CACHES_BC, cpushp_32),
+        op_entry!(MASK_OUT_Y, OP_CPUSHA_32 | CACHES_DC, cpusha_32),
+        op_entry!(MASK_OUT_Y, OP_CPUSHA_32 | CACHES_IC, cpusha_32),
+        op_entry!(MASK_OUT_Y, OP_CPUSHA_32 | CACHES_BC, cpusha_32),
+
         // Put op-entries for DBcc here
         op_entry!(MASK_OUT_Y, OP_DBT_16,  dbt_16),
         op_entry!(MASK_OUT_Y, OP_DBF_16,  dbf_16),
@@ -2652,6 +2894,7 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         // Put op-entries for EXT here
         op_entry!(MASK_OUT_Y, OP_EXT_BW, ext_bw),
         op_entry!(MASK_OUT_Y, OP_EXT_WL, ext_wl),
+        op_entry!(MASK_OUT_Y, OP_EXT_BL, extb_32),
 
         // Put op-entries for ILLEGAL here
         op_entry!(MASK_EXACT, OP_ILLEGAL, real_illegal),
@@ -2685,6 +2928,10 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
 
         // Put op-entries for LINK here
         op_entry!(MASK_OUT_Y, OP_LINK_16, link_16),
+        op_entry!(MASK_OUT_Y, OP_LINK_32, link_32),
+
+        // Put op-entries for LPSTOP here
+        // (after TBLS, TBLU, it shares a first word with them)
 
         // Put op-entries for LSL, LSR here
         op_entry!(MASK_OUT_X_Y, OP_LSR_8_S,  lsr_8_s),
@@ -3060,6 +3307,16 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_OUT_X,   OP_MOVEA_32_PCIX, movea_32_pcix),
         op_entry!(MASK_OUT_X,   OP_MOVEA_32_IMM,  movea_32_imm),
 
+        // Put op-entries for MOVE from CCR here
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_DN, move_16_frc_dn),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_AI, move_16_frc_ai),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_PI, move_16_frc_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_PD, move_16_frc_pd),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_DI, move_16_frc_di),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_IX, move_16_frc_ix),
+        op_entry!(MASK_EXACT, OP_MOVE_16_FRC_AW, move_16_frc_aw),
+        op_entry!(MASK_EXACT, OP_MOVE_16_FRC_AL, move_16_frc_al),
+
         // Put op-entries for MOVE to CCR here
         op_entry!(MASK_OUT_Y, OP_MOVE_16_TOC_DN,   move_16_toc_dn),
         op_entry!(MASK_OUT_Y, OP_MOVE_16_TOC_AI,   move_16_toc_ai),
@@ -3104,6 +3361,13 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_EXACT, OP_MOVE_32_RC, move_32_rc),
         op_entry!(MASK_EXACT, OP_MOVE_32_CR, move_32_cr),
 
+        // Put op-entries for MOVE16 here
+        op_entry!(MASK_OUT_Y, OP_MOVE16_32_PI_PI, move16_32_pi_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVE16_32_PI_AL, move16_32_pi_al),
+        op_entry!(MASK_OUT_Y, OP_MOVE16_32_AL_PI, move16_32_al_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVE16_32_AI_AL, move16_32_ai_al),
+        op_entry!(MASK_OUT_Y, OP_MOVE16_32_AL_AI, move16_32_al_ai),
+
         // Put op-entries for MOVEM here
         op_entry!(MASK_OUT_Y, OP_MOVEM_16_RE_AI,   movem_16_re_ai),
         op_entry!(MASK_OUT_Y, OP_MOVEM_16_RE_PD,   movem_16_re_pd),
@@ -3143,9 +3407,56 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_OUT_X_Y, OP_MOVEP_32_ER, movep_32_er),
         op_entry!(MASK_OUT_X_Y, OP_MOVEP_32_RE, movep_32_re),
 
+        // Put op-entries for MOVES here
+        op_entry!(MASK_OUT_Y, OP_MOVES_8  | OPER_AI, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8  | OPER_PI, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8  | OPER_PD, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8  | OPER_DI, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8  | OPER_IX, moves),
+        op_entry!(MASK_EXACT, OP_MOVES_8  | OPER_AW, moves),
+        op_entry!(MASK_EXACT, OP_MOVES_8  | OPER_AL, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16 | OPER_AI, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16 | OPER_PI, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16 | OPER_PD, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16 | OPER_DI, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16 | OPER_IX, moves),
+        op_entry!(MASK_EXACT, OP_MOVES_16 | OPER_AW, moves),
+        op_entry!(MASK_EXACT, OP_MOVES_16 | OPER_AL, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32 | OPER_AI, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32 | OPER_PI, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32 | OPER_PD, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32 | OPER_DI, moves),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32 | OPER_IX, moves),
+        op_entry!(MASK_EXACT, OP_MOVES_32 | OPER_AW, moves),
+        op_entry!(MASK_EXACT, OP_MOVES_32 | OPER_AL, moves),
+
         // Put op-entries for MOVEQ here
         op_entry!(MASK_LOBYTX, OP_MOVEQ_32, moveq_32),
 
+        // Put op-entries for MULL, DIVL here
+        op_entry!(MASK_OUT_Y, OP_MULL_32 | OPER_DN, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_MULL_32 | OPER_AI, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_MULL_32 | OPER_PI, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_MULL_32 | OPER_PD, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_MULL_32 | OPER_DI, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_MULL_32 | OPER_IX, mull_divl),
+        op_entry!(MASK_EXACT, OP_MULL_32 | OPER_AW, mull_divl),
+        op_entry!(MASK_EXACT, OP_MULL_32 | OPER_AL, mull_divl),
+        op_entry!(MASK_EXACT, OP_MULL_32 | OPER_PCDI, mull_divl),
+        op_entry!(MASK_EXACT, OP_MULL_32 | OPER_PCIX, mull_divl),
+        op_entry!(MASK_EXACT, OP_MULL_32 | OPER_IMM, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32 | OPER_DN, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32 | OPER_AI, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32 | OPER_PI, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32 | OPER_PD, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32 | OPER_DI, mull_divl),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32 | OPER_IX, mull_divl),
+        op_entry!(MASK_EXACT, OP_DIVL_32 | OPER_AW, mull_divl),
+        op_entry!(MASK_EXACT, OP_DIVL_32 | OPER_AL, mull_divl),
+        op_entry!(MASK_EXACT, OP_DIVL_32 | OPER_PCDI, mull_divl),
+        op_entry!(MASK_EXACT, OP_DIVL_32 | OPER_PCIX, mull_divl),
+        op_entry!(MASK_EXACT, OP_DIVL_32 | OPER_IMM, mull_divl),
+
         // Put op-entries for MULS here
         op_entry!(MASK_OUT_X_Y, OP_MULS_16_DN, muls_16_dn),
         op_entry!(MASK_OUT_X_Y, OP_MULS_16_AI, muls_16_ai),
@@ -3373,6 +3684,46 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_EXACT, OP_PEA_32_PCDI, pea_32_pcdi),
         op_entry!(MASK_EXACT, OP_PEA_32_PCIX, pea_32_pcix),
 
+        // Put op-entries for PMMU (MC68851) here
+        op_entry!(MASK_OUT_Y, OP_PMMU_GEN_DN,   pmmu_gen),
+        op_entry!(MASK_OUT_Y, OP_PMMU_GEN_AN,   pmmu_gen),
+        op_entry!(MASK_OUT_Y, OP_PMMU_GEN_AI,   pmmu_gen),
+        op_entry!(MASK_OUT_Y, OP_PMMU_GEN_PI,   pmmu_gen),
+        op_entry!(MASK_OUT_Y, OP_PMMU_GEN_PD,   pmmu_gen),
+        op_entry!(MASK_OUT_Y, OP_PMMU_GEN_DI,   pmmu_gen),
+        op_entry!(MASK_OUT_Y, OP_PMMU_GEN_IX,   pmmu_gen),
+        op_entry!(MASK_EXACT, OP_PMMU_GEN_AW,   pmmu_gen),
+        op_entry!(MASK_EXACT, OP_PMMU_GEN_AL,   pmmu_gen),
+        op_entry!(MASK_EXACT, OP_PMMU_GEN_PCDI, pmmu_gen),
+        op_entry!(MASK_EXACT, OP_PMMU_GEN_PCIX, pmmu_gen),
+        op_entry!(MASK_EXACT, OP_PMMU_GEN_IMM,  pmmu_gen),
+        op_entry!(MASK_OUT_Y, OP_PSCC_8_DN,     pscc_8),
+        op_entry!(MASK_OUT_Y, OP_PSCC_8_AI,     pscc_8),
+        op_entry!(MASK_OUT_Y, OP_PSCC_8_PI,     pscc_8),
+        op_entry!(MASK_OUT_Y, OP_PSCC_8_PD,     pscc_8),
+        op_entry!(MASK_OUT_Y, OP_PSCC_8_DI,     pscc_8),
+        op_entry!(MASK_OUT_Y, OP_PSCC_8_IX,     pscc_8),
+        op_entry!(MASK_EXACT, OP_PSCC_8_AW,     pscc_8),
+        op_entry!(MASK_EXACT, OP_PSCC_8_AL,     pscc_8),
+        op_entry!(MASK_OUT_Y, OP_PDBCC_16,      pdbcc_16),
+        op_entry!(MASK_EXACT, OP_PTRAPCC_16,    ptrapcc),
+        op_entry!(MASK_EXACT, OP_PTRAPCC_32,    ptrapcc),
+        op_entry!(MASK_EXACT, OP_PTRAPCC_0,     ptrapcc),
+        op_entry!(MASK_LONIB, OP_PBCC_16,       pbcc_16),
+        op_entry!(MASK_LONIB, OP_PBCC_32,       pbcc_32),
+
+        // Put op-entries for MC68040 PFLUSH, PTEST here
+        op_entry!(MASK_OUT_Y, OP_PFLUSHN_040,  pflushn_040),
+        op_entry!(MASK_OUT_Y, OP_PFLUSH_040,   pflush_040),
+        op_entry!(MASK_EXACT, OP_PFLUSHAN_040, pflushan_040),
+        op_entry!(MASK_EXACT, OP_PFLUSHA_040,  pflusha_040),
+        op_entry!(MASK_OUT_Y, OP_PTESTW_040,   ptestw_040),
+        op_entry!(MASK_OUT_Y, OP_PTESTR_040,   ptestr_040),
+
+        // Put op-entries for MC68060 PLPA here
+        op_entry!(MASK_OUT_Y, OP_PLPAW_060, plpaw_060),
+        op_entry!(MASK_OUT_Y, OP_PLPAR_060, plpar_060),
+
         // Put op-entries for RESET here
         op_entry!(MASK_EXACT, OP_RESET, reset),
 
@@ -3438,6 +3789,9 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_EXACT, OP_ROXR_16_AW, roxr_16_aw),
         op_entry!(MASK_EXACT, OP_ROXR_16_AL, roxr_16_al),
 
+        // Put op-entries for RTD here
+        op_entry!(MASK_EXACT, OP_RTD_32, rtd_32),
+
         // Put op-entries for RTE here
         op_entry!(MASK_EXACT, OP_RTE_32, rte_32),
 
@@ -3764,6 +4118,17 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
         op_entry!(MASK_EXACT, OP_TAS_8_AW, tas_8_aw),
         op_entry!(MASK_EXACT, OP_TAS_8_AL, tas_8_al),
 
+        // Put op-entries for TBLS, TBLU here
+        op_entry!(MASK_OUT_Y, OP_TBL | OPER_DN, tbl_dn),
+        op_entry!(MASK_OUT_Y, OP_TBL | OPER_AI, tbl_ea),
+        op_entry!(MASK_OUT_Y, OP_TBL | OPER_DI, tbl_ea),
+        op_entry!(MASK_OUT_Y, OP_TBL | OPER_IX, tbl_ea),
+        op_entry!(MASK_EXACT, OP_TBL | OPER_AW, tbl_ea),
+        op_entry!(MASK_EXACT, OP_TBL | OPER_AL, tbl_ea),
+        op_entry!(MASK_EXACT, OP_TBL | OPER_PCDI, tbl_ea),
+        op_entry!(MASK_EXACT, OP_TBL | OPER_PCIX, tbl_ea),
+        op_entry!(MASK_EXACT, OP_LPSTOP, lpstop),
+
         // Put op-entries for TRAP here
         op_entry!(MASK_LONIB, OP_TRAP, trap),
 
@@ -3779,8 +4144,12 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
 		op_entry!(MASK_OUT_Y, OP_TST_8_IX, tst_8_ix),
 		op_entry!(MASK_EXACT, OP_TST_8_AW, tst_8_aw),
 		op_entry!(MASK_EXACT, OP_TST_8_AL, tst_8_al),
+		op_entry!(MASK_EXACT, OP_TST_8_PCDI, tst_8_pcdi),
+		op_entry!(MASK_EXACT, OP_TST_8_PCIX, tst_8_pcix),
+		op_entry!(MASK_EXACT, OP_TST_8_IMM, tst_8_imm),
 
 		op_entry!(MASK_OUT_Y, OP_TST_16_DN, tst_16_dn),
+		op_entry!(MASK_OUT_Y, OP_TST_16_AN, tst_16_an),
 		op_entry!(MASK_OUT_Y, OP_TST_16_AI, tst_16_ai),
 		op_entry!(MASK_OUT_Y, OP_TST_16_PI, tst_16_pi),
 		op_entry!(MASK_OUT_Y, OP_TST_16_PD, tst_16_pd),
@@ -3788,8 +4157,12 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
 		op_entry!(MASK_OUT_Y, OP_TST_16_IX, tst_16_ix),
 		op_entry!(MASK_EXACT, OP_TST_16_AW, tst_16_aw),
 		op_entry!(MASK_EXACT, OP_TST_16_AL, tst_16_al),
+		op_entry!(MASK_EXACT, OP_TST_16_PCDI, tst_16_pcdi),
+		op_entry!(MASK_EXACT, OP_TST_16_PCIX, tst_16_pcix),
+		op_entry!(MASK_EXACT, OP_TST_16_IMM, tst_16_imm),
 
 		op_entry!(MASK_OUT_Y, OP_TST_32_DN, tst_32_dn),
+		op_entry!(MASK_OUT_Y, OP_TST_32_AN, tst_32_an),
 		op_entry!(MASK_OUT_Y, OP_TST_32_AI, tst_32_ai),
 		op_entry!(MASK_OUT_Y, OP_TST_32_PI, tst_32_pi),
 		op_entry!(MASK_OUT_Y, OP_TST_32_PD, tst_32_pd),
@@ -3797,6 +4170,9 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
 		op_entry!(MASK_OUT_Y, OP_TST_32_IX, tst_32_ix),
 		op_entry!(MASK_EXACT, OP_TST_32_AW, tst_32_aw),
 		op_entry!(MASK_EXACT, OP_TST_32_AL, tst_32_al),
+		op_entry!(MASK_EXACT, OP_TST_32_PCDI, tst_32_pcdi),
+		op_entry!(MASK_EXACT, OP_TST_32_PCIX, tst_32_pcix),
+		op_entry!(MASK_EXACT, OP_TST_32_IMM, tst_32_imm),
 
         // Put op-entries for UNLK here
         op_entry!(MASK_OUT_Y, OP_UNLK_32, unlk_32),
@@ -3804,10 +4180,223 @@ fn generate_optable<'a>() -> Vec<OpcodeHandler<'a>> {
     optable
 }
 
-pub fn generate<'a>() -> InstructionSet<'a> {
+// ColdFire drops a lot of the 68000 instruction set, mostly the byte and
+// word forms and the more involved addressing modes. These entries go on
+// top of the 68000 ones, taking out what isn't there and adding what is new.
+// The '000 and '010 don't decode the '020 additions (and the '000 not
+// those of the '010 either), they take the illegal instruction exception.
+fn mc68000_optable<'a>(version: Version) -> Vec<OpcodeHandler<'a>> {
+    let mut optable = vec![
+        op_entry!(MASK_OUT_Y, OP_EXT_BL, illegal),
+        op_entry!(MASK_OUT_Y, OP_LINK_32, illegal),
+        op_entry!(MASK_OUT_X_EA, OP_CHK | LONG_OP, illegal),
+        op_entry!(MASK_OUT_EA, OP_MULL_32, illegal),
+        op_entry!(MASK_OUT_EA, OP_DIVL_32, illegal),
+        op_entry!(MASK_OUT_EA, OP_CHK2_CMP2_8, illegal),
+        op_entry!(MASK_OUT_EA, OP_CHK2_CMP2_16, illegal),
+        op_entry!(MASK_OUT_EA, OP_CHK2_CMP2_32, illegal),
+        op_entry!(MASK_OUT_EA, OP_CAS_8, illegal),
+        op_entry!(MASK_OUT_EA, OP_CAS_16, illegal),
+        op_entry!(MASK_OUT_EA, OP_CAS_32, illegal),
+        op_entry!(MASK_OUT_Y, OP_TST_16_AN, illegal),
+        op_entry!(MASK_OUT_Y, OP_TST_32_AN, illegal),
+    ];
+    // Bcc, BRA and BSR with a 32-bit displacement
+    for cc in 0..16 {
+        optable.push(op_entry!(MASK_EXACT, OP_BRA_32 | cc << 8, illegal));
+    }
+    for &op in [OP_TST_8_PCDI, OP_TST_8_PCIX, OP_TST_8_IMM,
+                OP_TST_16_PCDI, OP_TST_16_PCIX, OP_TST_16_IMM,
+                OP_TST_32_PCDI, OP_TST_32_PCIX, OP_TST_32_IMM,
+                OP_CMPI_8_PCDI, OP_CMPI_8_PCIX,
+                OP_CMPI_16_PCDI, OP_CMPI_16_PCIX,
+                OP_CMPI_32_PCDI, OP_CMPI_32_PCIX].iter() {
+        optable.push(op_entry!(MASK_EXACT, op, illegal));
+    }
+    if !version.has_010_ops() {
+        optable.push(op_entry!(MASK_OUT_EA, OP_MOVE2 | MOVE_FROM_CCR, illegal));
+        optable.push(op_entry!(MASK_EXACT, OP_RTD_32, illegal));
+        optable.push(op_entry!(MASK_OUT_Y, OP_BKPT, illegal));
+        optable.push(op_entry!(MASK_OUT_EA, OP_MOVES_8, illegal));
+        optable.push(op_entry!(MASK_OUT_EA, OP_MOVES_16, illegal));
+        optable.push(op_entry!(MASK_OUT_EA, OP_MOVES_32, illegal));
+    }
+    optable
+}
+
+// The CPU32 has the '020 integer set but for CAS and CAS2
+fn cpu32_optable<'a>() -> Vec<OpcodeHandler<'a>> {
+    vec![
+        op_entry!(MASK_OUT_EA, OP_CAS_8, illegal),
+        op_entry!(MASK_OUT_EA, OP_CAS_16, illegal),
+        op_entry!(MASK_OUT_EA, OP_CAS_32, illegal),
+    ]
+}
+
+fn coldfire_optable<'a>(version: Version) -> Vec<OpcodeHandler<'a>> {
+    let isa_b = version != Version::ColdFireIsaA;   // ISA_C has the ISA_B additions
+    let mut optable = vec![
+        // ABCD, SBCD, NBCD, EXG, CHK, CMPM.L, MOVEP, ADDX/SUBX -(Ay),-(Ax)
+        op_entry!(MASK_OUT_X_Y, OP_EXG_32_DA, illegal),
+        op_entry!(MASK_OUT_EA, OP_NBCD, illegal),
+        op_entry!(MASK_OUT_X_EA, OP_CHK | LONG_OP, illegal),
+        op_entry!(MASK_OUT_X_EA, OP_CHK | WORD_OP, illegal),
+        op_entry!(MASK_OUT_X_Y, OP_CMPM_32, illegal),
+        op_entry!(MASK_OUT_X_SZ_Y, OP_MOVEP | MOVEP_MEMORY_TO_REGISTER, illegal),
+        op_entry!(MASK_OUT_X_Y, OP_ADDX_32_MM, illegal),
+        op_entry!(MASK_OUT_X_Y, OP_SUBX_32_MM, illegal),
+        // MOVES, RESET, TRAPV, RTR, MOVEC Rc,Rn and DBcc
+        op_entry!(MASK_LOBYTE, OP_MOVES_8, illegal),
+        op_entry!(MASK_EXACT, OP_RESET, illegal),
+        op_entry!(MASK_EXACT, OP_TRAPV, illegal),
+        op_entry!(MASK_EXACT, OP_RTR_32, illegal),
+        op_entry!(MASK_EXACT, OP_MOVE_32_CR, illegal),
+        op_entry!(MASK_OUT_CC_Y, OP_DBCC, illegal),
+        // LINK.L, RTD, BKPT, CHK2/CMP2, CAS and CAS2
+        op_entry!(MASK_OUT_Y, OP_LINK_32, illegal),
+        op_entry!(MASK_EXACT, OP_RTD_32, illegal),
+        op_entry!(MASK_OUT_Y, OP_BKPT, illegal),
+        op_entry!(MASK_OUT_EA, OP_CHK2_CMP2_8, illegal),
+        op_entry!(MASK_OUT_EA, OP_CHK2_CMP2_16, illegal),
+        op_entry!(MASK_OUT_EA, OP_CHK2_CMP2_32, illegal),
+        op_entry!(MASK_OUT_EA, OP_CAS_8, illegal),
+        op_entry!(MASK_OUT_EA, OP_CAS_16, illegal),
+        // memory shifts and rotates, and register rotates of any size
+        op_entry!(MASK_OUT_SHIFT_EA, OP_SHIFT | ARIT_MEM_SHIFT, illegal),
+        op_entry!(MASK_OUT_SHIFT_REG, OP_SHIFT | LONG_SIZED | ROTA_REG_SHIFT, illegal),
+        op_entry!(MASK_OUT_SHIFT_REG, OP_SHIFT | LONG_SIZED | ROTX_REG_SHIFT, illegal),
+    ];
+    // only the long forms of the arithmetic and logical ops
+    for &op in [OP_OR, OP_SUB, OP_AND, OP_ADD].iter() {
+        for &opmode in [BYTE_SIZED, WORD_SIZED].iter() {
+            optable.push(op_entry!(MASK_OUT_X_EA, op | opmode | DEST_DX, illegal));
+            optable.push(op_entry!(MASK_OUT_X_EA, op | opmode | DEST_EA, illegal));
+        }
+    }
+    // ADDA.W and SUBA.W, EOR.B and EOR.W
+    optable.push(op_entry!(MASK_OUT_X_EA, OP_SUB | DEST_AX_WORD, illegal));
+    optable.push(op_entry!(MASK_OUT_X_EA, OP_ADD | DEST_AX_WORD, illegal));
+    optable.push(op_entry!(MASK_OUT_X_EA, OP_EOR | BYTE_SIZED | DEST_EA, illegal));
+    optable.push(op_entry!(MASK_OUT_X_EA, OP_EOR | WORD_SIZED | DEST_EA, illegal));
+    if !isa_b {
+        // CMP.B, CMP.W and CMPA.W came with ISA_B
+        for &opmode in [BYTE_SIZED, WORD_SIZED, DEST_AX_WORD].iter() {
+            optable.push(op_entry!(MASK_OUT_X_EA, OP_CMP | opmode, illegal));
+        }
+    }
+    // ADDQ, SUBQ and register shifts, long only
+    for &op in [OP_ADDQ, OP_SUBQ, OP_SHIFT | SHIFT_RIGHT, OP_SHIFT | SHIFT_LEFT].iter() {
+        optable.push(op_entry!(MASK_OUT_X_EA, op | BYTE_SIZED, illegal));
+        optable.push(op_entry!(MASK_OUT_X_EA, op | WORD_SIZED, illegal));
+    }
+    // ORI, ANDI, SUBI, ADDI, EORI and CMPI are long to Dn only, which
+    // takes out the CCR and SR forms too. ISA_B has CMPI.B and CMPI.W.
+    for &op in [OP_ORI, OP_ANDI, OP_SUBI, OP_ADDI, OP_EORI, OP_CMPI].iter() {
+        for &size in [BYTE_SIZED, WORD_SIZED, LONG_SIZED].iter() {
+            for mode in 0..8 {
+                let keep = mode == 0 && (size == LONG_SIZED || (isa_b && op == OP_CMPI));
+                if !keep {
+                    optable.push(op_entry!(MASK_OUT_Y, op | size | mode << 3, illegal));
+                }
+            }
+        }
+    }
+    // NEGX, NEG and NOT are long Dn only, Scc and MOVE from SR and CCR
+    // only have Dn
+    for &op in [OP_NEGX, OP_NEG, OP_NOT].iter() {
+        optable.push(op_entry!(MASK_OUT_EA, op | BYTE_SIZED, illegal));
+        optable.push(op_entry!(MASK_OUT_EA, op | WORD_SIZED, illegal));
+    }
+    for mode in 2..8 {
+        for &op in [OP_NEGX | LONG_SIZED, OP_NEG | LONG_SIZED, OP_NOT | LONG_SIZED,
+                    OP_MOVE2 | MOVE_FROM_SR, OP_MOVE2 | MOVE_FROM_CCR].iter() {
+            optable.push(op_entry!(MASK_OUT_Y, op | mode << 3, illegal));
+        }
+        optable.push(op_entry!(MASK_OUT_CC_Y, OP_SCC | mode << 3, illegal));
+    }
+    // MOVE to CCR and SR from Dn or an immediate
+    for &op in [OP_MOVE2 | MOVE_TO_CCR, OP_MOVE2 | MOVE_TO_SR].iter() {
+        for mode in 2..7 {
+            optable.push(op_entry!(MASK_OUT_Y, op | mode << 3, illegal));
+        }
+        for &oper in [OPER_AW, OPER_AL, OPER_PCDI, OPER_PCIX].iter() {
+            optable.push(op_entry!(MASK_EXACT, op | oper, illegal));
+        }
+    }
+    // MOVEM is long only to or from (An) and (d16,An)
+    for mode in 2..8 {
+        optable.push(op_entry!(MASK_OUT_Y, OP_MOVEM | REGISTER_TO_MEMORY | WORD_TRANSFER | mode << 3, illegal));
+        optable.push(op_entry!(MASK_OUT_Y, OP_MOVEM | MEMORY_TO_REGISTER | WORD_TRANSFER | mode << 3, illegal));
+    }
+    for &mode in [3, 4, 6, 7].iter() {
+        optable.push(op_entry!(MASK_OUT_Y, OP_MOVEM | REGISTER_TO_MEMORY | LONG_TRANSFER | mode << 3, illegal));
+        optable.push(op_entry!(MASK_OUT_Y, OP_MOVEM | MEMORY_TO_REGISTER | LONG_TRANSFER | mode << 3, illegal));
+    }
+    // MOVE can't use two extension words for both operands
+    for &size in [BYTE_MOVE, LONG_MOVE, WORD_MOVE].iter() {
+        for &dst in [MOVE_TO_DI, MOVE_TO_IX, MOVE_TO_AW].iter() {
+            optable.push(op_entry!(MASK_OUT_X_Y, OP_MOVE | size | dst | OPER_IX, illegal));
+            for &src in [OPER_AW, OPER_AL, OPER_PCIX, OPER_IMM].iter() {
+                // ISA_B can move an immediate to (d16,An)
+                if !(isa_b && src == OPER_IMM && dst == MOVE_TO_DI) {
+                    optable.push(op_entry!(MASK_OUT_X, OP_MOVE | size | dst | src, illegal));
+                }
+            }
+            if dst != MOVE_TO_DI {
+                optable.push(op_entry!(MASK_OUT_X_Y, OP_MOVE | size | dst | OPER_DI, illegal));
+                optable.push(op_entry!(MASK_OUT_X, OP_MOVE | size | dst | OPER_PCDI, illegal));
+            }
+        }
+    }
+    if !isa_b {
+        // TAS, MOVE USP and 32-bit Bcc displacements came with ISA_B
+        for cc in 0..16 {
+            optable.push(op_entry!(MASK_EXACT, OP_BRA_32 | cc << 8, illegal));
+        }
+        optable.push(op_entry!(MASK_OUT_EA, OP_TAS, illegal));
+        optable.push(op_entry!(MASK_OUT_Y, OP_MOVE_32_TOU, illegal));
+        optable.push(op_entry!(MASK_OUT_Y, OP_MOVE_32_FRU, illegal));
+    } else {
+        // TAS.B is to memory only
+        optable.push(op_entry!(MASK_OUT_Y, OP_TAS, illegal));
+    }
+    optable.push(op_entry!(MASK_EXACT, OP_ILLEGAL, real_illegal));
+
+    // what ColdFire adds
+    optable.push(op_entry!(MASK_LO3NIB, OP_MAC, mac));
+    if isa_b {
+        for mode in 0..8 {
+            optable.push(op_entry!(MASK_OUT_X_Y, OP_MOV3Q_32 | mode << 3, mov3q_32));
+            optable.push(op_entry!(MASK_OUT_X_Y, OP_MVS_16 | mode << 3, mvs_16));
+            optable.push(op_entry!(MASK_OUT_X_Y, OP_MVZ_16 | mode << 3, mvz_16));
+            if mode != 1 {
+                optable.push(op_entry!(MASK_OUT_X_Y, OP_MVS_8 | mode << 3, mvs_8));
+                optable.push(op_entry!(MASK_OUT_X_Y, OP_MVZ_8 | mode << 3, mvz_8));
+            }
+        }
+        optable.push(op_entry!(MASK_OUT_Y, OP_SATS_32, sats_32));
+    }
+    if version == Version::ColdFireIsaC {
+        optable.push(op_entry!(MASK_OUT_Y, OP_BITREV_32, bitrev_32));
+        optable.push(op_entry!(MASK_OUT_Y, OP_BYTEREV_32, byterev_32));
+        optable.push(op_entry!(MASK_OUT_Y, OP_FF1_32, ff1_32));
+    }
+    optable
+}
+
+pub fn generate<'a>(version: Version) -> InstructionSet<'a> {
+    fill(version, illegal, |op| op.handler)
+}
+
+// Which IR values the Version decodes to something other than the illegal
+// instruction exception, for the assembler to check its encodings against
+pub fn implemented(version: Version) -> Vec<bool> {
+    fill(version, false, |op| op.name != "illegal")
+}
+
+fn fill<'a, T: Copy, F: Fn(&OpcodeHandler<'a>) -> T>(version: Version, default: T, entry: F) -> Vec<T> {
     // Covers all possible IR values (64k entries)
-    let mut handler: InstructionSet = Vec::with_capacity(0x10000);
-    for _ in 0..0x10000 { handler.push(illegal); }
+    let mut handler = vec![default; 0x10000];
 
     // two of the commonly used op-masks (MASK_OUT_X (280+ uses) and
     // MASK_OUT_X_Y (500+)) are non-contiguous, so optimize for that.
@@ -3832,7 +4421,14 @@ pub fn generate<'a>() -> InstructionSet<'a> {
     offset_cache.insert(MASK_OUT_X, x_offset(1));
     offset_cache.insert(MASK_OUT_X_Y, x_offset(8));
     offset_cache.insert(MASK_LOBYTX, x_offset(256));
-    let optable = generate_optable();
+    let mut optable = generate_optable();
+    if version.is_coldfire() {
+        optable.extend(coldfire_optable(version));
+    } else if !version.has_020_ops() {
+        optable.extend(mc68000_optable(version));
+    } else if version == Version::CPU32 {
+        optable.extend(cpu32_optable());
+    }
     let _ops = optable.len();
     let mut _implemented = 0;
 
@@ -3840,7 +4436,7 @@ pub fn generate<'a>() -> InstructionSet<'a> {
         match offset_cache.get(&op.mask) {
             Some(offsets) => {
                 for opcode in offsets.iter().flat_map(|&(start, len)| (start..(start+len)).map(|o| o + op.matching)) {
-                    handler[opcode as usize] = op.handler;
+                    handler[opcode as usize] = entry(&op);
                     _implemented += 1;
                 }
             },
@@ -3850,7 +4446,7 @@ pub fn generate<'a>() -> InstructionSet<'a> {
                 let mut matching = 0;
                 for opcode in op.matching..0x10000 {
                     if (opcode & op.mask) == op.matching {
-                        handler[opcode as usize] = op.handler;
+                        handler[opcode as usize] = entry(&op);
                         _implemented += 1;
                         matching += 1;
                         if matching >= max_count {
@@ -3867,4 +4463,65 @@ pub fn generate<'a>() -> InstructionSet<'a> {
     // M68020 implements 55611 opcodes (9925 illegal)
     //println!("{:?} opcodes implemented ({:.2}% done) in {:?} instruction variants", _implemented, _implemented as f32 / 540.07f32, _ops);
     handler
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::{fill, implemented};
+    use Version;
+
+    // (instruction, opcode word, ISA_A, ISA_B, ISA_C); line A falls
+    // back to MAC wherever MOV3Q isn't overlaid on it
+    const OVERLAY: &[(&str, u16, bool, bool, bool)] = &[
+        ("add.l d1,d0", 0xd081, true, true, true),
+        ("add.w d1,d0", 0xd041, false, false, false),
+        ("add.b d1,d0", 0xd001, false, false, false),
+        ("adda.w d1,a0", 0xd0c1, false, false, false),
+        ("cmp.l d1,d0", 0xb081, true, true, true),
+        ("cmp.b d1,d0", 0xb001, false, true, true),
+        ("addi.l #1,d0", 0x0680, true, true, true),
+        ("addi.l #1,(a0)", 0x0690, false, false, false),
+        ("cmpi.w #1,d0", 0x0c40, false, true, true),
+        ("neg.l d0", 0x4480, true, true, true),
+        ("neg.l (a0)", 0x4490, false, false, false),
+        ("st d0", 0x50c0, true, true, true),
+        ("st (a0)", 0x50d0, false, false, false),
+        ("dbra d0", 0x51c8, false, false, false),
+        ("movem.l (a0),d0", 0x4cd0, true, true, true),
+        ("movem.l (a0)+,d0", 0x4cd8, false, false, false),
+        ("movem.w (a0),d0", 0x4c90, false, false, false),
+        ("move.l #1,(8,a0)", 0x217c, false, true, true),
+        ("tas (a0)", 0x4ad0, false, true, true),
+        ("abcd d1,d0", 0xc101, false, false, false),
+        ("exg d0,d1", 0xc141, false, false, false),
+        ("link.l a6,#0", 0x480e, false, false, false),
+        ("mov3q #1,d0", 0xa340, false, true, true),
+        ("mvs.b d1,d0", 0x7101, false, true, true),
+        ("bitrev d0", 0x00c0, false, false, true),
+        ("byterev d0", 0x02c0, false, false, true),
+        ("ff1 d0", 0x04c0, false, false, true),
+    ];
+
+    #[test]
+    fn coldfire_keeps_only_its_subset_of_each_instruction() {
+        let names = |version| fill(version, "illegal", |op| op.name);
+        let isa = [
+            (Version::ColdFireIsaA, names(Version::ColdFireIsaA)),
+            (Version::ColdFireIsaB, names(Version::ColdFireIsaB)),
+            (Version::ColdFireIsaC, names(Version::ColdFireIsaC)),
+        ];
+        for &(name, op, a, b, c) in OVERLAY {
+            for (&(version, ref table), &expected) in isa.iter().zip(&[a, b, c]) {
+                let handler = table[op as usize];
+                assert_eq!(expected, handler != "illegal" && handler != "mac", "{} ({:04x}) on {:?} is {}", name, op, version, handler);
+            }
+        }
+    }
+
+    #[test]
+    fn the_overlay_leaves_the_680x0_tables_alone() {
+        let table = implemented(Version::MC68020);
+        for &(name, op, ..) in OVERLAY.iter().filter(|&&(name, ..)| !["mov3q", "mvs", "bitrev", "byterev", "ff1"].iter().any(|m| name.starts_with(m))) {
+            assert!(table[op as usize], "{} ({:04x}) on the 68020", name, op);
+        }
+    }
+}
@@ -5,15 +5,43 @@
 #![allow(dead_code)]
 
 mod instructions;
+mod exception;
+mod prefetch;
+pub mod pmmu;
+pub mod cache040;
+pub mod mmu040;
+pub mod mac;
+pub mod timing020;
+pub mod scheduler;
+pub mod memory;
+pub mod multicore;
+pub mod vcd;
+pub mod watch;
+pub mod disasm;
+pub mod asm;
+pub mod gdb;
+pub mod trace;
 
 use std::num::Wrapping;
+use std::mem;
 use instructions::constants::*;
 use instructions::optable::generate;
+use instructions::cycles::CycleTable;
+use pmmu::Pmmu;
+use cache040::{Cache040, CacheMode, CACR_DE, CACR_IE, SETS, SETS_060};
+use mmu040::{Atc040, Writeback};
+use mac::Mac;
+use timing020::{Timing020, BUS_CYCLE};
+use watch::{Watchpoint, WatchHit};
+use trace::{Access, Record, Trace};
+use prefetch::Prefetch;
 use std::result;
 
 #[derive(Debug)]
 pub enum Exception {
     AddressError,
+    BusError(u32, u8, bool),                // address, function code, write
+    AccessFault(u32, u8, bool),             // '040 MMU, address, function code, write
     IllegalInstruction(u16, u32),           // opcode, pc
     Trap(u8, u32),                          // trap number, cycles
     PrivilegeViolation(u16, u32),           // opcode, pc
@@ -25,6 +53,8 @@ impl fmt::Display for Exception {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Exception::AddressError => write!(f, "Address Error"),
+            Exception::BusError(addr, fc, write) => write!(f, "Bus Error {} {:08x} (fc {})", if write {"writing"} else {"reading"}, addr, fc),
+            Exception::AccessFault(addr, fc, write) => write!(f, "Access Fault {} {:08x} (fc {})", if write {"writing"} else {"reading"}, addr, fc),
             Exception::IllegalInstruction(ir, pc) => write!(f, "Illegal Instruction {:04x} at {:08x}", ir, pc),
             Exception::Trap(num, ea_cyc) => write!(f, "Trap: {:02x} (ea cyc {})", num, ea_cyc),
             Exception::PrivilegeViolation(ir, pc) => write!(f, "Privilege Violation {:04x} at {:08x}", ir, pc),
@@ -65,6 +95,14 @@ impl AddressSpace {
             SUPERVISOR_PROGRAM => 6,
         }
     }
+
+    // MOVES takes any function code, CPU space and the reserved ones go
+    // out as data
+    fn from_fc(fc: u32) -> AddressSpace {
+        let mode = if fc & 4 != 0 { Mode::Supervisor } else { Mode::User };
+        let segment = if fc & 3 == 2 { Segment::Program } else { Segment::Data };
+        AddressSpace(mode, segment)
+    }
 }
 use std::fmt;
 impl fmt::Debug for AddressSpace {
@@ -93,22 +131,162 @@ pub type Result<T> = result::Result<T, Exception>;
 pub type Handler<'a> = fn(&mut M68k, &mut (Bus + 'a)) -> Result<u32>;
 pub type InstructionSet<'a> = Vec<Handler<'a>>;
 
+// The core only ever reads and writes, which can have side effects on
+// devices. Debuggers and other tools only peek and poke, which must not.
 pub trait Bus {
-    fn read_8(&self, space: AddressSpace, addr: u32) -> u8;
-    fn read_16(&self, space: AddressSpace, addr: u32) -> u16;
-    fn read_32(&self, space: AddressSpace, addr: u32) -> u32;
+    fn read_8(&mut self, space: AddressSpace, addr: u32) -> u8;
+    fn read_16(&mut self, space: AddressSpace, addr: u32) -> u16;
+    fn read_32(&mut self, space: AddressSpace, addr: u32) -> u32;
 
     fn write_8(&mut self, space: AddressSpace, addr: u32, value: u8);
     fn write_16(&mut self, space: AddressSpace, addr: u32, value: u16);
     fn write_32(&mut self, space: AddressSpace, addr: u32, value: u32);
+
+    fn peek_8(&self, space: AddressSpace, addr: u32) -> u8;
+    fn poke_8(&mut self, space: AddressSpace, addr: u32, value: u8);
+
+    fn peek_16(&self, space: AddressSpace, addr: u32) -> u16 {
+        (self.peek_8(space, addr) as u16) << 8 | self.peek_8(space, addr.wrapping_add(1)) as u16
+    }
+    fn peek_32(&self, space: AddressSpace, addr: u32) -> u32 {
+        (self.peek_16(space, addr) as u32) << 16 | self.peek_16(space, addr.wrapping_add(2)) as u32
+    }
+    fn poke_16(&mut self, space: AddressSpace, addr: u32, value: u16) {
+        self.poke_8(space, addr, (value >> 8) as u8);
+        self.poke_8(space, addr.wrapping_add(1), value as u8);
+    }
+    fn poke_32(&mut self, space: AddressSpace, addr: u32, value: u32) {
+        self.poke_16(space, addr, (value >> 16) as u16);
+        self.poke_16(space, addr.wrapping_add(2), value as u16);
+    }
+
+    // Only asked in cycle exact mode, once per bus cycle before the access.
+    // `clock` is when the cycle starts, counted from when the core was
+    // created. Returns how many clocks DTACK is held off.
+    fn wait_states(&mut self, _space: AddressSpace, _addr: u32, _write: bool, _clock: u64) -> u32 {
+        0
+    }
+
+    // '020 dynamic bus sizing, the width in bytes (1, 2 or 4) of the port
+    // at `addr`, what it would answer on DSACK1/DSACK0
+    fn port_size(&mut self, _space: AddressSpace, _addr: u32) -> u32 {
+        4
+    }
+
+    // RMC, held over the read and write of TAS so that nothing else gets
+    // the bus in between
+    fn read_modify_write(&mut self, _locked: bool) {}
+
+    // which core the next step is for, when several share the bus
+    fn select_core(&mut self, _core: usize) {}
+
+    // Asked before every access of `size` bytes, true ends it with BERR and
+    // the core takes a bus error.
+    fn bus_error(&mut self, _space: AddressSpace, _addr: u32, _size: u32, _write: bool) -> bool {
+        false
+    }
+
+    // Bus arbitration, asked wherever the core could give up the bus.
+    // Returns how many clocks another master holds it (BGACK) once granted,
+    // 0 while nobody asserts BR.
+    fn bus_request(&mut self, _clock: u64) -> u32 {
+        0
+    }
+}
+
+// Reads through peeks and drops writes, to walk the translation tables
+// for a debugger without disturbing anything
+struct Peek<'b, T: Bus + ?Sized + 'b>(&'b T);
+
+impl<'b, T: Bus + ?Sized> Bus for Peek<'b, T> {
+    fn read_8(&mut self, space: AddressSpace, addr: u32) -> u8 {
+        self.0.peek_8(space, addr)
+    }
+    fn read_16(&mut self, space: AddressSpace, addr: u32) -> u16 {
+        self.0.peek_16(space, addr)
+    }
+    fn read_32(&mut self, space: AddressSpace, addr: u32) -> u32 {
+        self.0.peek_32(space, addr)
+    }
+
+    fn write_8(&mut self, _space: AddressSpace, _addr: u32, _value: u8) {}
+    fn write_16(&mut self, _space: AddressSpace, _addr: u32, _value: u16) {}
+    fn write_32(&mut self, _space: AddressSpace, _addr: u32, _value: u32) {}
+
+    fn peek_8(&self, space: AddressSpace, addr: u32) -> u8 {
+        self.0.peek_8(space, addr)
+    }
+    fn poke_8(&mut self, _space: AddressSpace, _addr: u32, _value: u8) {}
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Version {
     MC68000,
+    MC68008,    // 8 bit data bus
     MC68010,
     MC68020,
+    MC68EC020,  // 24 bit address bus, no MMU interface
     //MC68030, // todo !!!!
-    //MC68040,
+    MC68040,
+    MC68060,
+    CPU32,      // 683xx microcontrollers
+    ColdFireIsaA,
+    ColdFireIsaB,
+    ColdFireIsaC,
+}
+
+impl Version {
+    // the cut down parts behave like the one they come from apart from
+    // their bus
+    pub fn base(self) -> Version {
+        match self {
+            Version::MC68008 => Version::MC68000,
+            Version::MC68EC020 => Version::MC68020,
+            version => version,
+        }
+    }
+
+    // the address lines the part has, the rest of the address is dropped.
+    // This is for the 48 pin 68008, the 52 pin one has 22 lines.
+    pub fn address_mask(self) -> u32 {
+        match self {
+            Version::MC68000 | Version::MC68010 | Version::MC68EC020 => 0x00ff_ffff,
+            Version::MC68008 => 0x000f_ffff,
+            _ => 0xffff_ffff,
+        }
+    }
+
+    // the '060 keeps the '040 MMU, caches and their instructions
+    pub fn is_040_class(self) -> bool {
+        matches!(self, Version::MC68040 | Version::MC68060)
+    }
+
+    pub fn is_coldfire(self) -> bool {
+        matches!(self, Version::ColdFireIsaA | Version::ColdFireIsaB | Version::ColdFireIsaC)
+    }
+
+    // only these have the M bit, the others always use the interrupt stack
+    pub fn has_master_stack(self) -> bool {
+        matches!(self.base(), Version::MC68020 | Version::MC68040)
+    }
+
+    // what the '010 added, MOVE from CCR, RTD, MOVES and BKPT
+    pub fn has_010_ops(self) -> bool {
+        self.base() != Version::MC68000 && !self.is_coldfire()
+    }
+
+    // the '020 integer additions the CPU32 shares, ColdFire picks its own
+    pub fn has_020_ops(self) -> bool {
+        self.has_010_ops() && self != Version::MC68010
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProcessingState {
+    Normal,
+    Stopped,    // STOP, waiting for an interrupt
+    Halted,     // double bus fault
+    Background, // CPU32 background debug mode, the host has control
 }
 
 #[derive(Copy, Clone, Default)]
@@ -118,9 +296,34 @@ pub struct CacheLine020 {
     pub word: [u16; 2],
 }
 
+// '020 instruction cache accesses, fills are the misses that replaced an
+// entry, so a frozen cache misses without filling
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub fills: u64,
+}
+
 pub struct M68k<'a> {
     pub version: Version,
+    pub processing_state: ProcessingState,
     pub pc: u32,
+    pub ppc: u32,           // address of the instruction being executed
+    pub address_mask: u32,  // from the version, set 0x3f_ffff for a 52 pin 68008
+    pub extra_cycles: u32,  // bus cycles of the current step the handlers don't count
+    pub cycle_exact: bool,  // 68000/68008, place each bus cycle at its clock and ask the bus for wait states, see prefetch
+    pub clock: u64,         // clocks since the core was created
+    pub step_clock: u32,    // clocks into the current step, cycle exact mode only
+    pub bus_request: u32,   // BR from a master outside the Bus, clocks it wants to hold the bus
+    pub bus_held: u64,      // clocks other masters had the bus
+    pub predec_long: Option<u32>,   // a long -(An) operand of this step, the 68000 writes it low word first
+    pub rmc: bool,          // in a read-modify-write, the bus isn't given up
+    prefetch: Prefetch,     // the queue and the bus cycles still to place, cycle exact mode
+    pub watchpoints: Vec<Watchpoint>,
+    pub watch_hit: Option<WatchHit>,    // take it to carry on running
+    pub tracer: Option<Box<dyn Trace + 'a>>,   // gets a record of each step
+    trace: Option<(Record, [u32; 21])>,     // the step being traced and the registers before it
     pub inactive_msp: u32, // when in user mode
     pub inactive_usp: u32, // when in supervisor mode
     pub ir: u16,
@@ -146,15 +349,50 @@ pub struct M68k<'a> {
 
     pub cache_enabled: bool,    // this represents the external pin???
     pub cache: [CacheLine020; 64], // '020 only! other caches are different
+    pub cache_stats: CacheStats,
+    pub timing020: Timing020,
+    pub pmmu: Option<Pmmu>,     // external MC68851, '020 only, see has_pmmu
+    // '040
+    pub tc: u32,
+    pub itt0: u32,
+    pub itt1: u32,
+    pub dtt0: u32,
+    pub dtt1: u32,
+    pub urp: u32,
+    pub srp: u32,
+    pub mmusr: u32,
+    pub icache: Cache040,
+    pub dcache: Cache040,
+    pub iatc: Atc040,
+    pub datc: Atc040,
+    pub writebacks: Vec<Writeback>,   // faulted writes of the current instruction
+    // '060
+    pub pcr: u32,       // processor configuration, the upper half is the read only ID
+    pub buscr: u32,
+    // CPU32
+    pub bdm_enabled: bool,  // BKPT was held at reset, BGND enters background mode
+    // ColdFire
+    pub mac: Option<Mac>,   // MAC or EMAC unit, the A-line traps without one
+    pub acr: [u32; 4],      // access control, kept for the host, nothing here decodes by them
+    pub rombar: [u32; 2],   // on-chip ROM, RAM and module base addresses, likewise
+    pub rambar: [u32; 2],
+    pub mbar: u32,
 
     pub ops: InstructionSet<'a>,
+    pub cycles: CycleTable,     // per opcode difference from the 68000 count of the handlers
 }
 
 impl<'a> M68k<'a> {
     pub fn new(version: Version) -> Self {
+        let cache_sets = if version == Version::MC68060 { SETS_060 } else { SETS };
         M68k {
-            version: version,
-            pc: 0, inactive_msp: 0, inactive_usp: 0, inactive_isp: 0, ir: 0,
+            version,
+            processing_state: ProcessingState::Normal,
+            pc: 0, ppc: 0, address_mask: version.address_mask(), extra_cycles: 0,
+            cycle_exact: false, clock: 0, step_clock: 0, bus_request: 0, bus_held: 0, predec_long: None, rmc: false,
+            prefetch: Prefetch::default(),
+            watchpoints: Vec::new(), watch_hit: None, tracer: None, trace: None,
+            inactive_msp: 0, inactive_usp: 0, inactive_isp: 0, ir: 0,
             dar: [0u32; 16], 
             irq_level: 0, 
             s: SFLAG_SET, m: MFLAG_SET, int_mask: 0, x: 0, v: 0, c: 0, n: 0, not_z: 0xffffffff,
@@ -165,38 +403,168 @@ impl<'a> M68k<'a> {
             sfc: 0, dfc: 0,
             cache_enabled: true,
             cache: [CacheLine020::default(); 64],
+            cache_stats: CacheStats::default(),
+            timing020: Timing020::default(),
+            pmmu: None,
+            tc: 0, itt0: 0, itt1: 0, dtt0: 0, dtt1: 0, urp: 0, srp: 0, mmusr: 0,
+            icache: Cache040::with_sets(cache_sets),
+            dcache: Cache040::with_sets(cache_sets),
+            iatc: Atc040::new(),
+            datc: Atc040::new(),
+            writebacks: Vec::new(),
+            pcr: PCR_ID_060, buscr: 0,
+            bdm_enabled: false,
+            mac: None,
+            acr: [0; 4], rombar: [0; 2], rambar: [0; 2], mbar: 0,
 
-            ops: generate(),
+            ops: generate(version),
+            cycles: instructions::cycles::generate(version),
         }
     }
 
     pub fn reset<T: Bus + ?Sized>(&mut self, bus: &mut T) {
+        self.processing_state = ProcessingState::Normal;
         self.s = SFLAG_SET;
+        self.m = MFLAG_CLEAR;
         self.int_mask = 0x7;
+        self.vbr = 0;
+        self.cacr = 0;
+        self.invalidate_cache_020();
+        if self.version.is_040_class() {
+            self.tc = 0;
+            self.itt0 = 0; self.itt1 = 0;
+            self.dtt0 = 0; self.dtt1 = 0;
+            self.icache.invalidate_all();
+            self.dcache.invalidate_all();
+            self.iatc = Atc040::new();
+            self.datc = Atc040::new();
+        }
+        if self.version == Version::MC68060 {
+            self.pcr = PCR_ID_060;
+            self.buscr = 0;
+        }
+        if self.version.is_coldfire() {
+            // clears the valid bits, the memories are off until set up
+            self.acr = [0; 4];
+            self.rombar = [0; 2];
+            self.rambar = [0; 2];
+            self.mbar = 0;
+        }
         self.pc = 0;
-        sp!(self) = self.read_imm_prog_32(bus).unwrap();
-        self.pc = self.read_imm_prog_32(bus).unwrap();
+        self.prefetch.flush();
+        // a bus error on the vectors halts the core, as a double bus fault
+        match self.read_imm_prog_32(bus).and_then(|sp| Ok((sp, self.read_imm_prog_32(bus)?))) {
+            Ok((sp, pc)) => {
+                sp!(self) = sp;
+                self.pc = pc;
+                // the reset sequence ends by filling the queue
+                if self.exact() && self.fill_queue(bus).is_err() {
+                    self.processing_state = ProcessingState::Halted;
+                }
+            },
+            Err(_) => self.processing_state = ProcessingState::Halted,
+        }
+    }
+
+    // the GO command of a background debug host, carries on from pc
+    pub fn bdm_go(&mut self) {
+        if self.processing_state == ProcessingState::Background {
+            self.processing_state = ProcessingState::Normal;
+        }
     }
 
     // returns # of cycles used
     pub fn step<T: Bus + 'a>(&mut self, bus: &mut T) -> u32 {
-        // handle interrupts here
-        
-        // this isn't correct for any model currently
-        self.ir = self.read_imm_prog_16(bus).unwrap();
-        let op = self.ops[self.ir as usize];
-        let cycles_used = (op)(self, bus);
+        if self.tracer.is_some() {
+            self.trace_start();
+        }
+        self.extra_cycles = 0;
+        self.step_clock = 0;
+        self.predec_long = None;
+        self.start_step();
+        self.arbitrate(bus);
+        let cycles = self.execute(bus) + self.extra_cycles;
+        // a placed step takes as long as its bus cycles and internal clocks
+        let cycles = if self.step_placed() { self.step_clock } else { cycles.max(self.step_clock) };
+        self.clock += cycles as u64;
+        if self.tracer.is_some() {
+            self.trace_end(cycles);
+        }
+        cycles
+    }
+
+    // Steps until `clock` reaches `until`. Instructions aren't split, so it
+    // stops on the first instruction boundary at or past it, or after the
+    // instruction that hit a watchpoint. Returns the clocks run.
+    pub fn run_until<T: Bus + 'a>(&mut self, bus: &mut T, until: u64) -> u64 {
+        let start = self.clock;
+        while self.clock < until && self.watch_hit.is_none() {
+            self.step(bus);
+        }
+        self.clock - start
+    }
+
+    fn execute<T: Bus + 'a>(&mut self, bus: &mut T) -> u32 {
+        // interrupts don't get the core out of these
+        if let ProcessingState::Halted | ProcessingState::Background = self.processing_state {
+            return 4;
+        }
+        // handle interrupts here, autovectored only
+        // (level 7 isn't edge triggered yet, it is masked like the others)
+        if self.irq_level as u32 > self.int_mask {
+            let irq = self.irq_level;
+            self.processing_state = ProcessingState::Normal;
+            return self.exception(bus, Exception::Interrupt(irq, EXCEPTION_INTERRUPT_AUTOVECTOR + irq));
+        }
+        if self.processing_state != ProcessingState::Normal {
+            return 4;
+        }
+
+        self.ppc = self.pc;
+        self.writebacks.clear();
+        self.timing020.start();
+        let opcode = if self.exact() { self.take_opcode(bus) } else { self.read_imm_prog_16(bus) };
+        let cycles_used = match opcode {
+            Ok(ir) => {
+                self.ir = ir;
+                let op = self.ops[ir as usize];
+                (op)(self, bus)
+            },
+            Err(e) => Err(e),
+        };
 
         match cycles_used {
-            Ok(cycles) => cycles,
-            Err(e) => unimplemented!(),
+            Ok(cycles) if !self.writebacks.is_empty() => {
+                // '040 writes that faulted are reported once the instruction is done
+                let wb = self.writebacks[0];
+                cycles + self.exception(bus, Exception::AccessFault(wb.addr, wb.fc, true))
+            },
+            Ok(cycles) if self.version.base() == Version::MC68020 => {
+                self.timing020.cycles(self.ir, self.ppc, self.pc, cycles)
+            },
+            Ok(cycles) => {
+                let cycles = (cycles as i32 + self.cycles[self.ir as usize] as i32) as u32;
+                if self.placing() {
+                    if let Err(e) = self.finish_step(bus, cycles) {
+                        return cycles + self.exception(bus, e);
+                    }
+                }
+                cycles
+            },
+            Err(e) => {
+                self.timing020.tail = 0;
+                // a fault in the middle of TAS lets go of the bus
+                if self.rmc {
+                    self.lock_bus(bus, false);
+                }
+                self.exception(bus, e)
+            },
         }
-        //self.pc = self.pc.wrapping_add(2);
     }
 
     pub fn status_register(&self) -> u16 {
-        ((self.s << SFLAG_BIT)         |
-        (self.m << MFLAG_BIT)          |
+        (self.s                        |   // s & m are kept in place
+        self.m                         |
         (self.int_mask << INT_BITS)    |
         ((self.x & XFLAG_SET) >> 4)    |
         ((self.n & NFLAG_SET) >> 4)    |
@@ -215,7 +583,7 @@ impl<'a> M68k<'a> {
         let old_sflag = self.s;                                             // save old status
         self.int_mask = (sr & CPU_SR_INT_MASK) >> INT_BITS;                 // get interrupt level mask
         self.s = sr & SFLAG_SET;                                            // get s flag
-        self.m = sr & MFLAG_SET;                                            // get m flag
+        self.m = if self.version.has_master_stack() { sr & MFLAG_SET } else { MFLAG_CLEAR }; // get m flag
         // below remains unchanged so far
         self.x = (sr <<  4) & XFLAG_SET;
         self.n = (sr <<  4) & NFLAG_SET;
@@ -268,152 +636,558 @@ impl<'a> M68k<'a> {
             Condition::LE => (self.not_z == ZFLAG_SET) || (self.n & NFLAG_SET!=0) && (self.v & VFLAG_SET==0) || (self.n & NFLAG_SET==0) && (self.v & VFLAG_SET!=0),
         }
     }
-    fn push_sp<T: Bus + ?Sized>(&mut self, bus: &mut T) -> u32 {
+    // A fault on the stack comes back for the exception path, the stack
+    // pointer only moves once the access has gone through.
+    fn push_sp<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<u32> {
          let new_sp = (Wrapping(sp!(self)) - Wrapping(4)).0;
+         self.write_data_32(bus, new_sp, new_sp)?;
          sp!(self) = new_sp;
-         self.write_data_32(bus, new_sp, new_sp).unwrap();
-         new_sp
+         Ok(new_sp)
     }
-    fn push_32<T: Bus + ?Sized>(&mut self, bus: &mut T, value: u32) -> u32 {
+    fn push_32<T: Bus + ?Sized>(&mut self, bus: &mut T, value: u32) -> Result<u32> {
          let new_sp = (Wrapping(sp!(self)) - Wrapping(4)).0;
+         self.write_data_32(bus, new_sp, value)?;
          sp!(self) = new_sp;
-         self.write_data_32(bus, new_sp, value).unwrap();
-         new_sp
+         Ok(new_sp)
     }
-    fn pop_32<T: Bus + ?Sized>(&mut self, bus: &mut T) -> u32 {
+    fn pop_32<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<u32> {
         let sp = sp!(self);
-        let data = self.read_data_32(bus, sp).unwrap();
+        let data = self.read_data_32(bus, sp)?;
         sp!(self) = sp.wrapping_add(4);
-        data
+        Ok(data)
     }
-    fn push_16<T: Bus + ?Sized>(&mut self, bus: &mut T, value: u16) -> u32 {
+    fn push_16<T: Bus + ?Sized>(&mut self, bus: &mut T, value: u16) -> Result<u32> {
          let new_sp = (Wrapping(sp!(self)) - Wrapping(2)).0;
+         self.write_data_16(bus, new_sp, value)?;
          sp!(self) = new_sp;
-         self.write_data_16(bus, new_sp, value).unwrap();
-         new_sp
+         Ok(new_sp)
     }
-    fn pop_16<T: Bus + ?Sized>(&mut self, bus: &mut T) -> u16 {
+    fn pop_16<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<u16> {
         let sp = sp!(self);
-        let data = self.read_data_32(bus, sp).unwrap() as u16;
+        let data = self.read_data_16(bus, sp)?;
         sp!(self) = sp.wrapping_add(2);
-        data
+        Ok(data)
+    }
+
+    // The MC68851 in `pmmu` only works for a full '020. The other parts
+    // have no coprocessor interface for it and the EC020 no MMU pins, there
+    // it is left out and its instructions take the F-line exception.
+    pub fn has_pmmu(&self) -> bool {
+        self.version == Version::MC68020 && self.pmmu.is_some()
+    }
+
+    // logical to physical, through the MC68851 when one is attached or the
+    // '040/'060's own MMU. For those this also gives the cache mode when the
+    // access can be cached.
+    fn translate<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, write: bool) -> Result<(u32, Option<CacheMode>)> {
+        match self.pmmu {
+            Some(ref mut pmmu) if self.version == Version::MC68020 => {
+                return pmmu.translate(bus, space.fc(), addr, write).map(|addr| (addr, None));
+            },
+            _ => (),
+        }
+        if !self.version.is_040_class() {
+            return Ok((addr, None));
+        }
+        let (addr, mode) = self.translate_040(bus, space, addr, write)?;
+        let enabled = match space.1 {
+            Segment::Program => self.cacr & CACR_IE != 0,
+            Segment::Data => self.cacr & CACR_DE != 0,
+        };
+        Ok((addr, if enabled && mode.cachable() { Some(mode) } else { None }))
+    }
+
+    // Logical to the address on the pins for a debugger that peeks and
+    // pokes, without loading an ATC, setting U or M bits or faulting. None
+    // where a read would fault.
+    pub fn probe<T: Bus + ?Sized>(&self, bus: &T, space: AddressSpace, addr: u32) -> Option<u32> {
+        let physical = match self.pmmu {
+            Some(ref pmmu) if self.has_pmmu() => pmmu.probe(&mut Peek(bus), space.fc(), addr)?,
+            _ if self.version.is_040_class() => self.probe_040(&mut Peek(bus), space, addr)?,
+            _ => addr,
+        };
+        Some(physical & self.address_mask)
+    }
+
+    // every memory access ends up here
+    fn load<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32) -> Result<u32> {
+        if self.placing() {
+            self.place_access(bus, size)?;
+        }
+        let logical = addr;
+        let (addr, cache_mode) = self.translate(bus, space, addr, false)?;
+        let value = match (cache_mode, space.1) {
+            (Some(_), _) => self.cache_read(bus, space, addr, size)?,
+            (None, _) => self.bus_read(bus, space, addr, size)?,
+        };
+        if !self.watchpoints.is_empty() {
+            self.watch(space, logical, size, false, value);
+        }
+        if let Some((ref mut record, _)) = self.trace {
+            record.accesses.push(Access { space, addr: logical, size, write: false, value });
+        }
+        Ok(value)
+    }
+
+    fn store<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32, value: u32) -> Result<()> {
+        self.store_cached(bus, space, addr, size, value, true)
+    }
+
+    // MOVE16 writes around the data cache
+    fn store_around<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32, value: u32) -> Result<()> {
+        self.store_cached(bus, space, addr, size, value, false)
+    }
+
+    fn store_cached<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32, value: u32, cached: bool) -> Result<()> {
+        self.timing020.writes += 1;
+        if self.placing() {
+            self.place_access(bus, size)?;
+        }
+        if !self.watchpoints.is_empty() {
+            self.watch(space, addr, size, true, value);
+        }
+        if let Some((ref mut record, _)) = self.trace {
+            record.accesses.push(Access { space, addr, size, write: true, value });
+        }
+        let (addr, cache_mode) = match self.translate(bus, space, addr, true) {
+            Ok(physical) => physical,
+            // the '060 restarts the instruction instead
+            Err(Exception::AccessFault(..)) if self.version == Version::MC68040 => {
+                // held back for the access error handler, the instruction carries on
+                if self.writebacks.len() < 2 {
+                    self.writebacks.push(Writeback { fc: space.fc() as u8, addr, size, data: value });
+                }
+                return Ok(());
+            },
+            Err(e) => return Err(e),
+        };
+        if let (Some(mode), true) = (cache_mode, cached) {
+            return self.cache_write(bus, space, addr, size, value, mode == CacheMode::Copyback);
+        }
+        self.bus_write(bus, space, addr, size, value)
+    }
+
+    // Data accesses against the watchpoints, by logical address. The first
+    // hit is kept until it is taken, the instruction runs to the end.
+    fn watch(&mut self, space: AddressSpace, addr: u32, size: u32, write: bool, value: u32) {
+        if space.1 != Segment::Data || self.watch_hit.is_some() {
+            return;
+        }
+        let hit = self.watchpoints.iter().position(|w| w.matches(space, addr, size, write, value));
+        if let Some(index) = hit {
+            self.watch_hit = Some(WatchHit { index, pc: self.ppc, space, addr, size, write, value });
+        }
+    }
+
+    // what goes out on the pins, the address is cut down to the lines the
+    // part has and the 68008 takes a byte cycle, 4 clocks, per byte
+    fn bus_read<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32) -> Result<u32> {
+        if self.version.base() == Version::MC68020 {
+            return self.sized_read(bus, space, addr, size);
+        }
+        let mask = self.address_mask;
+        self.bus_cycles(bus, space, addr, size, false, false);
+        if bus.bus_error(space, addr & mask, size, false) {
+            return Err(Exception::BusError(addr & mask, space.fc() as u8, false));
+        }
+        if self.version == Version::MC68008 {
+            self.extra_cycles += 4 * (size / 2);   // twice the bus cycles of the 68000
+            return Ok((0..size).fold(0, |acc, i| acc << 8 | bus.read_8(space, addr.wrapping_add(i) & mask) as u32));
+        }
+        Ok(match size {
+            1 => bus.read_8(space, addr & mask) as u32,
+            2 => bus.read_16(space, addr & mask) as u32,
+            _ => bus.read_32(space, addr & mask),
+        })
+    }
+
+    fn bus_write<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32, value: u32) -> Result<()> {
+        if self.version.base() == Version::MC68020 {
+            return self.sized_write(bus, space, addr, size, value);
+        }
+        let mask = self.address_mask;
+        let low_first = size == 4 && self.predec_long == Some(addr)
+            && matches!(self.version, Version::MC68000 | Version::MC68010);
+        self.bus_cycles(bus, space, addr, size, true, low_first);
+        if bus.bus_error(space, addr & mask, size, true) {
+            return Err(Exception::BusError(addr & mask, space.fc() as u8, true));
+        }
+        if low_first {
+            bus.write_16(space, addr.wrapping_add(2) & mask, value as u16);
+            bus.write_16(space, addr & mask, (value >> 16) as u16);
+            return Ok(());
+        }
+        if self.version == Version::MC68008 {
+            self.extra_cycles += 4 * (size / 2);   // twice the bus cycles of the 68000
+            for i in 0..size {
+                bus.write_8(space, addr.wrapping_add(i) & mask, (value >> ((size - 1 - i) * 8)) as u8);
+            }
+            return Ok(());
+        }
+        match size {
+            1 => bus.write_8(space, addr & mask, value as u8),
+            2 => bus.write_16(space, addr & mask, value as u16),
+            _ => bus.write_32(space, addr & mask, value),
+        }
+        Ok(())
+    }
+
+    // '020 dynamic bus sizing (ref MC68020UM 7.2). Each bus cycle moves the
+    // operand bytes from the address up to the end of the port that answers
+    // it, so an 8 bit port takes a cycle a byte and a misaligned operand is
+    // split where it crosses a port boundary. Returns the address and length
+    // of each cycle. The timing counts one cycle per operand, the others
+    // are charged here.
+    fn sized_cycles<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32, write: bool) -> Result<Vec<(u32, u32)>> {
+        let mask = self.address_mask;
+        let mut cycles = Vec::with_capacity(size as usize);
+        let mut addr = addr & mask;
+        let mut left = size;
+        while left > 0 {
+            let port = match bus.port_size(space, addr) { 1 => 1, 2 => 2, _ => 4 };
+            let len = left.min(port - (addr & (port - 1)));
+            if bus.bus_error(space, addr, len, write) {
+                return Err(Exception::BusError(addr, space.fc() as u8, write));
+            }
+            cycles.push((addr, len));
+            addr = addr.wrapping_add(len) & mask;
+            left -= len;
+        }
+        self.extra_cycles += (cycles.len() as u32 - 1) * BUS_CYCLE;
+        Ok(cycles)
+    }
+
+    // the Bus only takes aligned words and longs, a cycle moving three bytes
+    // or an odd word is handed over in pieces
+    fn pieces(addr: u32, len: u32) -> impl Iterator<Item = (u32, u32)> {
+        let mut addr = addr;
+        let mut left = len;
+        std::iter::from_fn(move || {
+            if left == 0 {
+                return None;
+            }
+            let size = match (addr & 3, left) {
+                (0, 4) => 4,
+                (a, l) if a & 1 == 0 && l >= 2 => 2,
+                _ => 1,
+            };
+            let piece = (addr, size);
+            addr = addr.wrapping_add(size);
+            left -= size;
+            Some(piece)
+        })
+    }
+
+    fn sized_read<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32) -> Result<u32> {
+        let mut value = 0u64;
+        for (addr, len) in self.sized_cycles(bus, space, addr, size, false)? {
+            for (addr, size) in Self::pieces(addr, len) {
+                let piece = match size {
+                    1 => bus.read_8(space, addr) as u32,
+                    2 => bus.read_16(space, addr) as u32,
+                    _ => bus.read_32(space, addr),
+                };
+                value = value << (size * 8) | piece as u64;
+            }
+        }
+        Ok(value as u32)
+    }
+
+    fn sized_write<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32, value: u32) -> Result<()> {
+        let mut left = size;
+        for (addr, len) in self.sized_cycles(bus, space, addr, size, true)? {
+            for (addr, size) in Self::pieces(addr, len) {
+                left -= size;
+                let piece = ((value as u64) >> (left * 8)) as u32;
+                match size {
+                    1 => bus.write_8(space, addr, piece as u8),
+                    2 => bus.write_16(space, addr, piece as u16),
+                    _ => bus.write_32(space, addr, piece),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Cycle exact mode. Each 4 clock bus cycle starts where the one before
+    // it ended, after the internal clocks prefetch has placed in between,
+    // and is stretched by the wait states the bus asks for.
+    fn bus_cycles<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32, write: bool, low_first: bool) {
+        if !self.exact() {
+            return;
+        }
+        let port = if self.version == Version::MC68008 { 1 } else { 2 };
+        let count = size.div_ceil(port);
+        for n in 0..count {
+            let i = if low_first { count - 1 - n } else { n };
+            self.arbitrate(bus);
+            let clock = self.clock + self.step_clock as u64;
+            let wait = bus.wait_states(space, addr.wrapping_add(i * port) & self.address_mask, write, clock);
+            self.step_clock += 4 + wait;
+            self.extra_cycles += wait;
+        }
+    }
+
+    // Bus arbitration. A master asserting BR is granted the bus before the
+    // next bus cycle in cycle exact mode, otherwise at the next instruction
+    // boundary, and the core stalls for as long as it holds the bus. A
+    // stopped or halted core still gives up the bus.
+    fn arbitrate<T: Bus + ?Sized>(&mut self, bus: &mut T) {
+        if self.rmc {
+            return;
+        }
+        let clock = self.clock + self.step_clock as u64;
+        let held = mem::replace(&mut self.bus_request, 0) + bus.bus_request(clock);
+        self.step_clock += held;
+        self.extra_cycles += held;
+        self.bus_held += held as u64;
+    }
+
+    pub fn lock_bus<T: Bus + ?Sized>(&mut self, bus: &mut T, locked: bool) {
+        self.rmc = locked;
+        bus.read_modify_write(locked);
+    }
+
+    fn data_space(&self) -> AddressSpace {
+        if self.s != 0 {SUPERVISOR_DATA} else {USER_DATA}
+    }
+
+    fn program_space(&self) -> AddressSpace {
+        if self.s != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM}
     }
 
     fn write_data_8<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32, value: u8) -> Result<()> {
-        let address_space = if self.s != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(bus.write_8(address_space, addr, value))
+        let address_space = self.data_space();
+        self.store(bus, address_space, addr, 1, value as u32)
     }
 
     fn write_data_16<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32, value: u16) -> Result<()> {
-        let address_space = if self.s != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(bus.write_16(address_space, addr, value))
+        let address_space = self.data_space();
+        self.store(bus, address_space, addr, 2, value as u32)
     }
 
     fn write_data_32<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32, value: u32) -> Result<()> {
-        let address_space = if self.s != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(bus.write_32(address_space, addr, value))
+        let address_space = self.data_space();
+        self.store(bus, address_space, addr, 4, value)
     }
 
     fn read_data_8<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32) -> Result<u8> {
-        let address_space = if self.s != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(bus.read_8(address_space, addr))
+        self.timing020.reads += 1;
+        let address_space = self.data_space();
+        self.load(bus, address_space, addr, 1).map(|v| v as u8)
     }
 
     fn read_data_16<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32) -> Result<u16> {
-        let address_space = if self.s != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(bus.read_16(address_space, addr))
+        self.timing020.reads += 1;
+        let address_space = self.data_space();
+        self.load(bus, address_space, addr, 2).map(|v| v as u16)
     }
 
     fn read_data_32<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32) -> Result<u32> {
-        let address_space = if self.s != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(bus.read_32(address_space, addr))
+        self.timing020.reads += 1;
+        let address_space = self.data_space();
+        self.load(bus, address_space, addr, 4)
     }
 
     fn read_prog_8<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32) -> Result<u8> {
-        let address_space = if self.s != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        Ok(bus.read_8(address_space, addr))
+        self.timing020.reads += 1;
+        let address_space = self.program_space();
+        self.load(bus, address_space, addr, 1).map(|v| v as u8)
     }
 
     fn read_prog_16<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32) -> Result<u16> {
-        let address_space = if self.s != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        Ok(bus.read_16(address_space, addr))
+        self.timing020.reads += 1;
+        let address_space = self.program_space();
+        self.load(bus, address_space, addr, 2).map(|v| v as u16)
     }
 
     fn read_prog_32<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32) -> Result<u32> {
-        let address_space = if self.s != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        Ok(bus.read_32(address_space, addr))
+        self.timing020.reads += 1;
+        let address_space = self.program_space();
+        self.load(bus, address_space, addr, 4)
     }
 
+    // extension words and immediates, these advance the pc past what they
+    // read. They are instruction stream, fetched in program space, so a data
+    // watchpoint doesn't see them.
     fn read_imm_data_16<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<u16> {
-        let address_space = if self.s != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(bus.read_16(address_space, self.pc))
+        if self.placing() {
+            return self.take_word(bus);
+        }
+        let address_space = self.program_space();
+        let pc = self.pc;
+        let value = self.load(bus, address_space, pc, 2)? as u16;
+        self.pc = pc.wrapping_add(2);
+        self.trace_words(&[value]);
+        Ok(value)
     }
 
     fn read_imm_data_32<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<u32> {
-        let address_space = if self.s != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(bus.read_32(address_space, self.pc))
+        if self.placing() {
+            let high = self.take_word(bus)? as u32;
+            return Ok(high << 16 | self.take_word(bus)? as u32);
+        }
+        let address_space = self.program_space();
+        let pc = self.pc;
+        let value = self.load(bus, address_space, pc, 4)?;
+        self.pc = pc.wrapping_add(4);
+        self.trace_words(&[(value >> 16) as u16, value as u16]);
+        Ok(value)
     }
 
     fn read_imm_prog_16<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<u16> {
-        let address_space = if self.s != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        match self.version {
-            Version::MC68000 => {
-                // uses prefetch
-                unimplemented!()
-             },
-            Version::MC68010 => { 
-                // loop mode???
-                unimplemented!()
+        let address_space = self.program_space();
+        if self.pc & 1 > 0 {
+            return Err(Exception::AddressError)
+        }
+        let word = match self.version {
+            Version::MC68000 | Version::MC68008 | Version::MC68010 | Version::CPU32 |
+            Version::ColdFireIsaA | Version::ColdFireIsaB | Version::ColdFireIsaC => {
+                // prefetch and loop mode aren't modelled, fetch a word at a time
+                let pc = self.pc;
+                self.load(bus, address_space, pc, 2)? as u16
             },
-            Version::MC68020 => { 
-                if self.pc & 1 > 0 {
-                    return Err(Exception::AddressError)
-                }
-
+            Version::MC68020 | Version::MC68EC020 => {
                 // instruction cache
-                if self.cache_enabled && (self.cacr & 1) == 1 { // TODO - make these consts????
-                    let tag = ((self.pc & 0xFFFFFF00) >> 8) as u32;
+                if self.cache_enabled && self.cacr & CACR_E != 0 {
+                    // FC2 is part of the tag, user and supervisor code don't share entries
+                    let tag = (self.pc >> 8) | (self.s & SFLAG_SET) << 11;
                     let index = ((self.pc & 0xfc) >> 2) as usize;
                     let word_sel = ((self.pc & 0x2) >> 1) as usize;
                     let line = self.cache[index];
                     if line.v && line.tag == tag { // line must be valid and tag same to get a hit
                         // cache hit! set ir from cache
-                        Ok(line.word[word_sel])
+                        self.cache_stats.hits += 1;
+                        line.word[word_sel]
                     } else {
                         // cache miss! do a real fetch!
-                        let lw = bus.read_32(address_space, self.pc & 0xffff_fffc);  // man says we always do a long word aligned instruction fetches, pc should be aligned here by masking?
-                        let low_w = ((lw & 0x0000_ffff) >> 0) as u16;
+                        self.cache_stats.misses += 1;
+                        self.timing020.fetches += 1;
+                        let aligned_pc = self.pc & 0xffff_fffc;
+                        let lw = self.load(bus, address_space, aligned_pc, 4)?;  // man says we always do a long word aligned instruction fetches, pc should be aligned here by masking?
+                        let low_w = (lw & 0x0000_ffff) as u16;
                         let high_w = ((lw & 0xffff_0000) >> 16) as u16;
-                        if self.caar & 2 >> 1 == 0 {    // if the cache isn't frozen, update it
+                        if self.cacr & CACR_F == 0 {    // if the cache isn't frozen, update it
+                            self.cache_stats.fills += 1;
                             self.cache[index].v = true;
-                            self.cache[index].tag = tag;                            
-                            self.cache[index].word[0] = low_w;
-                            self.cache[index].word[1] = high_w;
+                            self.cache[index].tag = tag;
+                            self.cache[index].word[0] = high_w;
+                            self.cache[index].word[1] = low_w;
                         }
                         // finally set the ir to the correct part of the 32 bits we read
                         if word_sel == 0 {
-                            Ok(low_w)
+                            high_w
                         } else {
-                            Ok(high_w)
+                            low_w
                         }
                     }
                 } else {
-                    let lw = bus.read_32(address_space, self.pc & 0xffff_fffc);  // man says we always do a long word aligned instruction fetches, pc should be aligned here by masking?
-                    let low_w = ((lw & 0x0000_ffff) >> 0) as u16;
+                    self.timing020.fetches += 1;
+                    let aligned_pc = self.pc & 0xffff_fffc;
+                    let lw = self.load(bus, address_space, aligned_pc, 4)?;  // man says we always do a long word aligned instruction fetches, pc should be aligned here by masking?
+                    let low_w = (lw & 0x0000_ffff) as u16;
                     let high_w = ((lw & 0xffff_0000) >> 16) as u16;
                     let word_sel = ((self.pc & 0x2) >> 1) as usize;
                     // finally set the ir to the correct part of the 32 bits we read
                     if word_sel == 0 {
-                        Ok(low_w)
+                        high_w
                     } else {
-                        Ok(high_w)
+                        low_w
                     }
                 }
             },
+            Version::MC68040 | Version::MC68060 => {
+                // goes through the instruction cache when CACR IE is set
+                let pc = self.pc;
+                self.load(bus, address_space, pc, 2)? as u16
+            },
+        };
+        self.pc = self.pc.wrapping_add(2);
+        self.trace_words(&[word]);
+        Ok(word)
+    }
+
+    // instruction words as they're fetched, for the tracer
+    fn trace_words(&mut self, words: &[u16]) {
+        if let Some((ref mut record, _)) = self.trace {
+            record.words.extend_from_slice(words);
+        }
+    }
+
+    // MOVEC to the '020 CACR
+    pub fn write_cacr_020(&mut self, value: u32) {
+        if value & CACR_C != 0 {
+            self.invalidate_cache_020();
+        }
+        if value & CACR_CE != 0 {
+            self.cache[((self.caar >> 2) & 0x3f) as usize].v = false;
+        }
+        self.cacr = value & (CACR_E | CACR_F);
+    }
+
+    pub fn invalidate_cache_020(&mut self) {
+        for line in self.cache.iter_mut() {
+            line.v = false;
         }
     }
 
     fn read_imm_prog_32<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<u32> {
-        unimplemented!()
+        let high = self.read_imm_prog_16(bus)? as u32;
+        let low = self.read_imm_prog_16(bus)? as u32;
+        Ok((high << 16) | low)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory::{MemoryMap, Unmapped};
+
+    // 64K of RAM with the vectors in it and `code` at 0x400, the rest of
+    // the map bus errors
+    pub fn small_map(ssp: u32, code: &[u16]) -> MemoryMap {
+        let mut map = MemoryMap::new();
+        map.ram(0, 0x10000);
+        map.unmapped = Unmapped::BusError;
+        map.poke_32(SUPERVISOR_DATA, 0, ssp);
+        map.poke_32(SUPERVISOR_DATA, 4, 0x400);
+        for (i, &word) in code.iter().enumerate() {
+            map.poke_16(SUPERVISOR_PROGRAM, 0x400 + 2 * i as u32, word);
+        }
+        map
+    }
+
+    #[test]
+    fn stack_fault_takes_the_bus_error() {
+        // bsr.w with the stack above the RAM, the exception frame can't go
+        // there either so the core halts on the double bus fault
+        let mut bus = small_map(0x20000, &[0x6100, 0x0010]);
+        let mut cpu = M68k::new(Version::MC68000);
+        cpu.reset(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.processing_state, ProcessingState::Halted);
+        assert_eq!(cpu.dar[15], 0x20000);
+    }
+
+    #[test]
+    fn long_running_off_the_end_of_ram_faults() {
+        // move.l d0,($fffe).w, the low word would land past the RAM
+        let mut bus = small_map(0x8000, &[0x21c0, 0xfffe]);
+        bus.poke_32(SUPERVISOR_DATA, 8, 0x600);
+        let mut cpu = M68k::new(Version::MC68000);
+        cpu.reset(&mut bus);
+        cpu.dar[0] = 0x12345678;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x600);
+        assert_eq!(bus.peek_16(SUPERVISOR_DATA, 0xfffe), 0);
+    }
+
+    #[test]
+    fn unmapped_reset_vectors_halt() {
+        let mut bus = MemoryMap::new();
+        bus.unmapped = Unmapped::BusError;
+        let mut cpu = M68k::new(Version::MC68000);
+        cpu.reset(&mut bus);
+        assert_eq!(cpu.processing_state, ProcessingState::Halted);
+    }
+}
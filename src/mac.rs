@@ -0,0 +1,160 @@
+// ColdFire MAC and EMAC units (ref ColdFire PRM sections 5 and 6)
+//
+// Optional multiply-accumulate units living in the A-line. The MAC has a
+// single 32 bit accumulator, the EMAC has four of them with 16 extension
+// bits each, giving 48 bits of headroom. Operands are the upper or lower
+// word of a register or the whole register, and the product can be scaled
+// by a bit either way before it is added to or taken from an accumulator.
+//
+// Fractional results are kept in the same units as the accumulator
+// register, the extra precision bits of the EMAC aren't modelled.
+
+// MAC status register
+pub const MACSR_OMC: u32 = 0x0080;      // overflow saturation mode
+pub const MACSR_SU: u32 = 0x0040;       // unsigned operands
+pub const MACSR_FI: u32 = 0x0020;       // fractional mode
+pub const MACSR_RT: u32 = 0x0010;       // round, not modelled
+pub const MACSR_N: u32 = 0x0008;
+pub const MACSR_Z: u32 = 0x0004;
+pub const MACSR_V: u32 = 0x0002;
+pub const MACSR_EV: u32 = 0x0001;       // EMAC, result is past 32 bits
+pub const MACSR_PAV0: u32 = 0x0100;     // EMAC, sticky overflow per accumulator
+
+pub struct Mac {
+    pub emac: bool,
+    pub acc: [i64; 4],
+    pub macsr: u32,
+    pub mask: u32,
+}
+
+impl Mac {
+    pub fn new(emac: bool) -> Self {
+        Mac {
+            emac,
+            acc: [0; 4],
+            macsr: 0,
+            mask: 0xffff,
+        }
+    }
+
+    fn signed(&self) -> bool {
+        self.macsr & MACSR_SU == 0
+    }
+
+    fn width(&self) -> u32 {
+        if self.emac { 48 } else { 32 }
+    }
+
+    // `x` and `y` are the operand words already picked out of the registers
+    // for word sized operations
+    pub fn product(&self, x: u32, y: u32, word: bool, scale: u16) -> i64 {
+        let extend = |v: u32| -> i128 {
+            match (word, self.signed()) {
+                (true, true) => v as u16 as i16 as i128,
+                (true, false) => v as u16 as i128,
+                (false, true) => v as i32 as i128,
+                (false, false) => v as i128,
+            }
+        };
+        let mut product = extend(x) * extend(y);
+        if self.macsr & MACSR_FI != 0 {
+            product = if word { product << 1 } else { product >> 31 };
+        }
+        match scale {
+            1 => product <<= 1,
+            3 => product >>= 1,
+            _ => (),
+        }
+        product as i64
+    }
+
+    // MAC and MSAC, updates N, Z and V along with the sticky overflow
+    pub fn accumulate(&mut self, acc: usize, product: i64, subtract: bool) {
+        let result = if subtract {
+            self.acc[acc] as i128 - product as i128
+        } else {
+            self.acc[acc] as i128 + product as i128
+        };
+        let bits = self.width();
+        let (min, max) = if self.signed() {
+            (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+        } else {
+            (0, (1i128 << bits) - 1)
+        };
+        let overflow = result < min || result > max;
+        let value = if overflow && self.macsr & MACSR_OMC != 0 {
+            if result < min { min } else { max }
+        } else if self.signed() {
+            (result << (128 - bits)) >> (128 - bits)
+        } else {
+            result & max
+        };
+        self.acc[acc] = value as i64;
+        self.macsr &= !(MACSR_N | MACSR_Z | MACSR_V | MACSR_EV);
+        if overflow {
+            self.macsr |= MACSR_V;
+            if self.emac {
+                self.macsr |= MACSR_PAV0 << acc;
+            }
+        }
+        self.set_flags(acc);
+    }
+
+    fn set_flags(&mut self, acc: usize) {
+        let value = self.acc[acc];
+        if value == 0 {
+            self.macsr |= MACSR_Z;
+        }
+        if value < 0 {
+            self.macsr |= MACSR_N;
+        }
+        if self.emac && (value < i32::MIN as i64 || value > u32::MAX as i64) {
+            self.macsr |= MACSR_EV;
+        }
+    }
+
+    // MOVE ACCx,Rx saturates to 32 bits in saturation mode
+    pub fn read_acc(&self, acc: usize) -> u32 {
+        let value = self.acc[acc];
+        if self.macsr & MACSR_OMC != 0 {
+            let (min, max) = if self.signed() {
+                (i32::MIN as i64, i32::MAX as i64)
+            } else {
+                (0, u32::MAX as i64)
+            };
+            if value < min {
+                return min as u32;
+            }
+            if value > max {
+                return max as u32;
+            }
+        }
+        value as u32
+    }
+
+    // MOVE Ry,ACCx, also clears the sticky overflow of the accumulator
+    pub fn write_acc(&mut self, acc: usize, value: u32) {
+        self.acc[acc] = if self.signed() { value as i32 as i64 } else { value as i64 };
+        self.macsr &= !(MACSR_PAV0 << acc);
+    }
+
+    // EMAC MOVE ACCy,ACCx
+    pub fn move_acc(&mut self, src: usize, dst: usize) {
+        self.acc[dst] = self.acc[src];
+        self.macsr &= !(MACSR_N | MACSR_Z | MACSR_V | MACSR_EV | MACSR_PAV0 << dst);
+        self.set_flags(dst);
+    }
+
+    // ACCEXT01 and ACCEXT23, the upper 16 bits of a pair of accumulators
+    pub fn accext(&self, pair: usize) -> u32 {
+        let ext = |acc: i64| ((acc >> 32) & 0xffff) as u32;
+        ext(self.acc[pair * 2]) << 16 | ext(self.acc[pair * 2 + 1])
+    }
+
+    pub fn set_accext(&mut self, pair: usize, value: u32) {
+        for (i, ext) in [value >> 16, value & 0xffff].iter().enumerate() {
+            let acc = &mut self.acc[pair * 2 + i];
+            *acc = (*acc & 0xffff_ffff) | ((*ext as u16 as i16 as i64) << 32);
+        }
+    }
+}
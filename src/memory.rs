@@ -0,0 +1,399 @@
+// A ready made Bus
+//
+// RAM, ROM and memory mapped devices are placed in a 32 bit address map.
+// A region can show up at more than one place, and a window bigger than
+// its region repeats it, which covers the partial decoding most boards
+// do. Regions can be limited to some address spaces, so program and data
+// or user and supervisor accesses can go to different places. Later
+// mappings cover earlier ones.
+//
+// Devices see the offset into their region and get the access at the
+// size the core made it, the default methods put words and longs
+// together from bytes, big endian.
+
+use AddressSpace;
+use Bus;
+
+// Reads and writes come from the core and can have side effects, like a
+// read clearing a status flag. Peeks and pokes come from tools and must
+// not, a device that can't take a poke without one leaves it out.
+pub trait Device {
+    fn read_8(&mut self, offset: u32) -> u8;
+    fn write_8(&mut self, offset: u32, value: u8);
+    fn peek_8(&self, offset: u32) -> u8;
+    fn poke_8(&mut self, _offset: u32, _value: u8) {}
+
+    fn read_16(&mut self, offset: u32) -> u16 {
+        (self.read_8(offset) as u16) << 8 | self.read_8(offset.wrapping_add(1)) as u16
+    }
+    fn read_32(&mut self, offset: u32) -> u32 {
+        (self.read_16(offset) as u32) << 16 | self.read_16(offset.wrapping_add(2)) as u32
+    }
+    fn write_16(&mut self, offset: u32, value: u16) {
+        self.write_8(offset, (value >> 8) as u8);
+        self.write_8(offset.wrapping_add(1), value as u8);
+    }
+    fn write_32(&mut self, offset: u32, value: u32) {
+        self.write_16(offset, (value >> 16) as u16);
+        self.write_16(offset.wrapping_add(2), value as u16);
+    }
+
+    fn peek_16(&self, offset: u32) -> u16 {
+        (self.peek_8(offset) as u16) << 8 | self.peek_8(offset.wrapping_add(1)) as u16
+    }
+    fn peek_32(&self, offset: u32) -> u32 {
+        (self.peek_16(offset) as u32) << 16 | self.peek_16(offset.wrapping_add(2)) as u32
+    }
+    fn poke_16(&mut self, offset: u32, value: u16) {
+        self.poke_8(offset, (value >> 8) as u8);
+        self.poke_8(offset.wrapping_add(1), value as u8);
+    }
+    fn poke_32(&mut self, offset: u32, value: u32) {
+        self.poke_16(offset, (value >> 16) as u16);
+        self.poke_16(offset.wrapping_add(2), value as u16);
+    }
+}
+
+// what accesses to addresses nothing is mapped at do
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Unmapped {
+    Ignore(u8),     // writes are dropped, reads see this in every byte
+    BusError,
+}
+
+// what writes to ROM do
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RomWrites {
+    Ignore,
+    BusError,
+}
+
+// handle to something mapped, for mirrors and to get at it later
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Region(usize);
+
+enum Contents {
+    Ram(Vec<u8>),
+    Rom(Vec<u8>),
+    Device(u32, Box<dyn Device>),   // size
+}
+
+impl Contents {
+    fn size(&self) -> u32 {
+        match *self {
+            Contents::Ram(ref data) | Contents::Rom(ref data) => data.len() as u32,
+            Contents::Device(size, _) => size,
+        }
+    }
+}
+
+struct Window {
+    start: u32,
+    last: u32,      // inclusive, so a window can reach the top of the map
+    region: usize,
+}
+
+const ALL_SPACES: u8 = 1 << 1 | 1 << 2 | 1 << 5 | 1 << 6;
+
+pub struct MemoryMap {
+    regions: Vec<(Contents, u8)>,   // and the function codes it answers, one bit each
+    windows: Vec<Window>,
+    pub unmapped: Unmapped,
+    pub rom_writes: RomWrites,
+}
+
+impl MemoryMap {
+    pub fn new() -> Self {
+        MemoryMap {
+            regions: Vec::new(),
+            windows: Vec::new(),
+            unmapped: Unmapped::Ignore(0xff),
+            rom_writes: RomWrites::Ignore,
+        }
+    }
+
+    fn add(&mut self, start: u32, contents: Contents) -> Region {
+        assert!(contents.size() != 0, "can't map an empty region");
+        let region = Region(self.regions.len());
+        let size = contents.size();
+        self.regions.push((contents, ALL_SPACES));
+        self.mirror(region, start, size);
+        region
+    }
+
+    pub fn ram(&mut self, start: u32, size: u32) -> Region {
+        self.add(start, Contents::Ram(vec![0; size as usize]))
+    }
+
+    pub fn rom(&mut self, start: u32, data: Vec<u8>) -> Region {
+        self.add(start, Contents::Rom(data))
+    }
+
+    pub fn device(&mut self, start: u32, size: u32, device: Box<dyn Device>) -> Region {
+        self.add(start, Contents::Device(size, device))
+    }
+
+    // shows `region` again at `start`, repeating it over `len` bytes
+    pub fn mirror(&mut self, region: Region, start: u32, len: u32) {
+        assert!(len != 0, "can't map an empty window");
+        let last = start.checked_add(len - 1).expect("window runs past the top of the address map");
+        self.windows.push(Window { start, last, region: region.0 });
+    }
+
+    // only accesses in these address spaces go to `region`
+    pub fn spaces(&mut self, region: Region, spaces: &[AddressSpace]) {
+        self.regions[region.0].1 = spaces.iter().fold(0, |fcs, space| fcs | 1 << space.fc());
+    }
+
+    // RAM or ROM contents, to load them
+    pub fn data_mut(&mut self, region: Region) -> Option<&mut [u8]> {
+        match self.regions[region.0].0 {
+            Contents::Ram(ref mut data) | Contents::Rom(ref mut data) => Some(data),
+            Contents::Device(..) => None,
+        }
+    }
+
+    pub fn device_mut(&mut self, region: Region) -> Option<&mut (dyn Device + 'static)> {
+        match self.regions[region.0].0 {
+            Contents::Device(_, ref mut device) => Some(device.as_mut()),
+            _ => None,
+        }
+    }
+
+    // the window `addr` falls in and the offset into its region
+    fn find(&self, space: AddressSpace, addr: u32) -> Option<(&Window, u32)> {
+        let fc = 1 << space.fc();
+        self.windows.iter().rev()
+            .find(|w| addr >= w.start && addr <= w.last && self.regions[w.region].1 & fc != 0)
+            .map(|w| (w, (addr - w.start) % self.regions[w.region].0.size()))
+    }
+
+    // region index and offset, when all `size` bytes fall in one window
+    // and don't wrap around the region
+    fn whole(&self, space: AddressSpace, addr: u32, size: u32) -> Option<(usize, u32)> {
+        let (window, offset) = self.find(space, addr)?;
+        let fits = addr.checked_add(size - 1).is_some_and(|end| end <= window.last)
+            && offset + size <= self.regions[window.region].0.size();
+        if fits { Some((window.region, offset)) } else { None }
+    }
+
+    // what a debugger sees, devices are asked to peek
+    fn peek(&self, space: AddressSpace, addr: u32, size: u32) -> u32 {
+        let (region, offset) = match self.whole(space, addr, size) {
+            Some(found) => found,
+            None if size == 1 => return match self.unmapped {
+                Unmapped::Ignore(value) => value as u32,
+                Unmapped::BusError => 0xff,     // the core has already taken the bus error
+            },
+            // straddles a window or the end of a region, a byte at a time
+            None => return (0..size).fold(0, |acc, i| acc << 8 | self.peek(space, addr.wrapping_add(i), 1)),
+        };
+        match self.regions[region].0 {
+            Contents::Ram(ref data) | Contents::Rom(ref data) => {
+                let offset = offset as usize;
+                data[offset..offset + size as usize].iter().fold(0, |acc, &b| acc << 8 | b as u32)
+            },
+            Contents::Device(_, ref device) => match size {
+                1 => device.peek_8(offset) as u32,
+                2 => device.peek_16(offset) as u32,
+                _ => device.peek_32(offset),
+            },
+        }
+    }
+
+    // RAM and ROM read the same either way, only devices see the difference
+    fn read(&mut self, space: AddressSpace, addr: u32, size: u32) -> u32 {
+        match self.whole(space, addr, size) {
+            Some((region, offset)) => match self.regions[region].0 {
+                Contents::Device(_, ref mut device) => match size {
+                    1 => device.read_8(offset) as u32,
+                    2 => device.read_16(offset) as u32,
+                    _ => device.read_32(offset),
+                },
+                _ => self.peek(space, addr, size),
+            },
+            None if size == 1 => self.peek(space, addr, size),
+            None => (0..size).fold(0, |acc, i| acc << 8 | self.read(space, addr.wrapping_add(i), 1)),
+        }
+    }
+
+    // a poke goes into ROM, a write doesn't
+    fn write(&mut self, space: AddressSpace, addr: u32, size: u32, value: u32, poke: bool) {
+        let (region, offset) = match self.whole(space, addr, size) {
+            Some(found) => found,
+            None if size == 1 => return,
+            None => {
+                for i in 0..size {
+                    self.write(space, addr.wrapping_add(i), 1, value >> ((size - 1 - i) * 8), poke);
+                }
+                return;
+            },
+        };
+        match self.regions[region].0 {
+            Contents::Rom(..) if !poke => (),
+            Contents::Ram(ref mut data) | Contents::Rom(ref mut data) => {
+                for i in 0..size {
+                    data[(offset + i) as usize] = (value >> ((size - 1 - i) * 8)) as u8;
+                }
+            },
+            Contents::Device(_, ref mut device) => match (size, poke) {
+                (1, false) => device.write_8(offset, value as u8),
+                (2, false) => device.write_16(offset, value as u16),
+                (_, false) => device.write_32(offset, value),
+                (1, true) => device.poke_8(offset, value as u8),
+                (2, true) => device.poke_16(offset, value as u16),
+                (_, true) => device.poke_32(offset, value),
+            },
+        }
+    }
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus for MemoryMap {
+    fn read_8(&mut self, space: AddressSpace, addr: u32) -> u8 {
+        self.read(space, addr, 1) as u8
+    }
+    fn read_16(&mut self, space: AddressSpace, addr: u32) -> u16 {
+        self.read(space, addr, 2) as u16
+    }
+    fn read_32(&mut self, space: AddressSpace, addr: u32) -> u32 {
+        self.read(space, addr, 4)
+    }
+
+    fn write_8(&mut self, space: AddressSpace, addr: u32, value: u8) {
+        self.write(space, addr, 1, value as u32, false)
+    }
+    fn write_16(&mut self, space: AddressSpace, addr: u32, value: u16) {
+        self.write(space, addr, 2, value as u32, false)
+    }
+    fn write_32(&mut self, space: AddressSpace, addr: u32, value: u32) {
+        self.write(space, addr, 4, value, false)
+    }
+
+    fn peek_8(&self, space: AddressSpace, addr: u32) -> u8 {
+        self.peek(space, addr, 1) as u8
+    }
+    fn peek_16(&self, space: AddressSpace, addr: u32) -> u16 {
+        self.peek(space, addr, 2) as u16
+    }
+    fn peek_32(&self, space: AddressSpace, addr: u32) -> u32 {
+        self.peek(space, addr, 4)
+    }
+
+    fn poke_8(&mut self, space: AddressSpace, addr: u32, value: u8) {
+        self.write(space, addr, 1, value as u32, true)
+    }
+    fn poke_16(&mut self, space: AddressSpace, addr: u32, value: u16) {
+        self.write(space, addr, 2, value as u32, true)
+    }
+    fn poke_32(&mut self, space: AddressSpace, addr: u32, value: u32) {
+        self.write(space, addr, 4, value, true)
+    }
+
+    // every byte of the access, one that runs off the end of a region into
+    // nothing faults like one that starts there
+    fn bus_error(&mut self, space: AddressSpace, addr: u32, size: u32, write: bool) -> bool {
+        (0..size).any(|i| match self.find(space, addr.wrapping_add(i)) {
+            None => self.unmapped == Unmapped::BusError,
+            Some((window, _)) => write && self.rom_writes == RomWrites::BusError
+                && matches!(self.regions[window.region].0, Contents::Rom(..)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use {SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA};
+
+    struct Latch(u8, Rc<Cell<u32>>);    // the value, and how many reads it has seen
+
+    impl Device for Latch {
+        fn read_8(&mut self, _offset: u32) -> u8 {
+            self.1.set(self.1.get() + 1);
+            self.0
+        }
+        fn write_8(&mut self, _offset: u32, value: u8) {
+            self.0 = value;
+        }
+        fn peek_8(&self, _offset: u32) -> u8 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn mirrors_repeat_the_region() {
+        let mut map = MemoryMap::new();
+        let ram = map.ram(0x1000, 0x100);
+        map.mirror(ram, 0x8000, 0x400);
+        map.write_32(SUPERVISOR_DATA, 0x1010, 0x12345678);
+        assert_eq!(map.read_32(SUPERVISOR_DATA, 0x8010), 0x12345678);
+        assert_eq!(map.read_32(SUPERVISOR_DATA, 0x8310), 0x12345678);
+        // a long across the end of the region wraps back to its start
+        map.write_16(SUPERVISOR_DATA, 0x1000, 0xabcd);
+        map.write_16(SUPERVISOR_DATA, 0x10fe, 0x5555);
+        assert_eq!(map.read_32(SUPERVISOR_DATA, 0x80fe), 0x5555abcd);
+    }
+
+    #[test]
+    fn rom_takes_pokes_but_not_writes() {
+        let mut map = MemoryMap::new();
+        map.rom(0, vec![1, 2, 3, 4]);
+        map.write_16(SUPERVISOR_DATA, 0, 0xffff);
+        assert_eq!(map.read_32(SUPERVISOR_DATA, 0), 0x01020304);
+        assert!(!map.bus_error(SUPERVISOR_DATA, 0, 2, true));
+        map.poke_16(SUPERVISOR_DATA, 2, 0xeeee);
+        assert_eq!(map.peek_32(SUPERVISOR_DATA, 0), 0x0102eeee);
+        map.rom_writes = RomWrites::BusError;
+        assert!(map.bus_error(SUPERVISOR_DATA, 0, 2, true));
+        assert!(!map.bus_error(SUPERVISOR_DATA, 0, 2, false));
+    }
+
+    #[test]
+    fn unmapped_addresses() {
+        let mut map = MemoryMap::new();
+        map.ram(0, 0x100);
+        assert_eq!(map.read_16(SUPERVISOR_DATA, 0x200), 0xffff);
+        assert_eq!(map.read_32(SUPERVISOR_DATA, 0xfe), 0x0000ffff);
+        assert!(!map.bus_error(SUPERVISOR_DATA, 0x200, 4, false));
+        map.unmapped = Unmapped::BusError;
+        assert!(map.bus_error(SUPERVISOR_DATA, 0x200, 1, false));
+        assert!(!map.bus_error(SUPERVISOR_DATA, 0xfc, 4, false));
+        // starting in RAM isn't enough, every byte has to be there
+        assert!(map.bus_error(SUPERVISOR_DATA, 0xfe, 4, false));
+        assert!(map.bus_error(SUPERVISOR_DATA, 0xff, 2, true));
+    }
+
+    #[test]
+    fn spaces_pick_the_region() {
+        let mut map = MemoryMap::new();
+        map.ram(0, 0x100);
+        let program = map.rom(0, vec![0x4e, 0x71]);
+        map.spaces(program, &[SUPERVISOR_PROGRAM]);
+        map.write_16(SUPERVISOR_DATA, 0, 0x1234);
+        assert_eq!(map.read_16(SUPERVISOR_PROGRAM, 0), 0x4e71);
+        assert_eq!(map.read_16(SUPERVISOR_DATA, 0), 0x1234);
+        assert_eq!(map.read_16(USER_DATA, 0), 0x1234);
+    }
+
+    #[test]
+    fn devices_see_reads_but_not_peeks() {
+        let mut map = MemoryMap::new();
+        let reads = Rc::new(Cell::new(0));
+        let latch = map.device(0xff00, 1, Box::new(Latch(0x5a, reads.clone())));
+        map.write_8(SUPERVISOR_DATA, 0xff00, 0xa5);
+        assert_eq!(map.peek_8(SUPERVISOR_DATA, 0xff00), 0xa5);
+        assert_eq!(reads.get(), 0);
+        assert_eq!(map.read_8(SUPERVISOR_DATA, 0xff00), 0xa5);
+        assert_eq!(reads.get(), 1);
+        assert!(map.data_mut(latch).is_none());
+        assert!(map.device_mut(latch).is_some());
+    }
+}
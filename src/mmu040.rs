@@ -0,0 +1,431 @@
+// MC68040 Memory Management Unit (ref MC68040UM section 3)
+//
+// Unlike the MC68851 the '040 table layout is fixed: a 128 entry root
+// table indexed by A31-A25, 128 entry pointer tables indexed by A24-A18 and
+// page tables indexed by what is left above the 4K or 8K page offset.
+// Instruction and data accesses have their own 64 entry ATCs and their own
+// pair of transparent translation registers.
+//
+// Faulted reads restart the instruction. Faulted writes let the
+// instruction finish, the write is held back and handed to the access error
+// handler as writeback data in the format 7 frame, like the hardware does.
+
+#![allow(dead_code)]
+
+use Bus;
+use M68k;
+use Result;
+use Exception;
+use AddressSpace;
+use Segment;
+use Mode;
+use SUPERVISOR_DATA;
+use cache040::CacheMode;
+
+// Translation Control register
+pub const TC_E: u32 = 0x8000;           // enable
+pub const TC_P: u32 = 0x4000;           // 8K pages
+
+// Transparent translation registers
+pub const TTR_E: u32 = 0x8000;          // enable
+pub const TTR_W: u32 = 0x0004;          // write protect
+
+// Table descriptor fields
+const UDT_RESIDENT: u32 = 0x0002;
+const DESC_W: u32 = 0x0004;
+const DESC_U: u32 = 0x0008;
+// Page descriptor fields
+const PDT: u32 = 0x0003;
+const PDT_INDIRECT: u32 = 0x0002;
+const DESC_M: u32 = 0x0010;
+const DESC_S: u32 = 0x0080;
+const DESC_G: u32 = 0x0400;
+
+// MMU status register, as set by PTEST
+pub const MMUSR_B: u32 = 0x0800;        // bus error
+pub const MMUSR_G: u32 = 0x0400;        // global
+pub const MMUSR_S: u32 = 0x0080;        // supervisor only
+pub const MMUSR_M: u32 = 0x0010;        // modified
+pub const MMUSR_W: u32 = 0x0004;        // write protected
+pub const MMUSR_T: u32 = 0x0002;        // transparent
+pub const MMUSR_R: u32 = 0x0001;        // resident
+
+pub const ATC_ENTRIES: usize = 64;
+
+#[derive(Copy, Clone, Default)]
+pub struct AtcEntry040 {
+    pub v: bool,
+    pub supervisor: bool,   // the access space the entry was loaded for
+    pub logical: u32,
+    pub physical: u32,
+    pub r: bool,            // resident, false caches an invalid translation
+    pub b: bool,            // the search ended in a bus error, never loaded
+    pub g: bool,
+    pub s: bool,            // supervisor only page
+    pub w: bool,
+    pub m: bool,
+    pub cm: u32,
+    pub u: u32,             // U1/U0 user page attributes
+}
+
+pub struct Atc040 {
+    pub entries: [AtcEntry040; ATC_ENTRIES],
+    replace: usize,
+}
+
+impl Default for Atc040 {
+    fn default() -> Self {
+        Atc040::new()
+    }
+}
+
+impl Atc040 {
+    pub fn new() -> Self {
+        Atc040 {
+            entries: [AtcEntry040::default(); ATC_ENTRIES],
+            replace: 0,
+        }
+    }
+
+    fn lookup(&self, supervisor: bool, page: u32) -> Option<usize> {
+        self.entries.iter()
+            .position(|e| e.v && e.supervisor == supervisor && e.logical == page)
+    }
+
+    fn install(&mut self, entry: AtcEntry040) -> usize {
+        let index = match self.lookup(entry.supervisor, entry.logical) {
+            Some(i) => i,
+            None => {
+                let i = self.replace;
+                self.replace = (i + 1) % ATC_ENTRIES;
+                i
+            }
+        };
+        self.entries[index] = entry;
+        index
+    }
+
+    // PFLUSH, `page` is None for every entry, globals survive unless
+    // `global` is set
+    pub fn flush(&mut self, supervisor: bool, page: Option<u32>, global: bool) {
+        for e in self.entries.iter_mut() {
+            let selected = match page {
+                Some(page) => e.supervisor == supervisor && e.logical == page,
+                None => true,
+            };
+            if selected && (global || !e.g) {
+                e.v = false;
+            }
+        }
+    }
+}
+
+// A write held back by a translation fault, reported in the access error
+// frame for the handler to complete
+#[derive(Copy, Clone, Debug)]
+pub struct Writeback {
+    pub fc: u8,
+    pub addr: u32,
+    pub size: u32,
+    pub data: u32,
+}
+
+impl Writeback {
+    // WBxS, valid, size, TT of normal access and TM
+    pub fn status(&self) -> u16 {
+        let size = match self.size { 1 => 0x20, 2 => 0x40, _ => 0x00 };
+        0x80 | size | self.fc as u16
+    }
+}
+
+// transparent translation register match, the base and mask cover A31-A24
+// and the S field selects user, supervisor or both
+pub fn ttr_matches(ttr: u32, fc: u32, addr: u32) -> bool {
+    if ttr & TTR_E == 0 {
+        return false;
+    }
+    let base = ttr >> 24;
+    let mask = (ttr >> 16) & 0xff;
+    if (addr >> 24) & !mask & 0xff != base & !mask & 0xff {
+        return false;
+    }
+    match (ttr >> 13) & 3 {
+        0 => fc & 4 == 0,
+        1 => fc & 4 != 0,
+        _ => true,
+    }
+}
+
+// a table or page descriptor, None when the fetch ends in a bus error
+fn descriptor_040<T: Bus + ?Sized>(bus: &mut T, addr: u32) -> Option<u32> {
+    if bus.bus_error(SUPERVISOR_DATA, addr, 4, false) {
+        return None;
+    }
+    Some(bus.read_32(SUPERVISOR_DATA, addr))
+}
+
+impl<'a> M68k<'a> {
+    fn page_mask_040(&self) -> u32 {
+        if self.tc & TC_P != 0 { 0x1fff } else { 0x0fff }
+    }
+
+    fn transparent_040(&self, segment: Segment, fc: u32, addr: u32) -> Option<u32> {
+        let ttrs = match segment {
+            Segment::Program => [self.itt0, self.itt1],
+            Segment::Data => [self.dtt0, self.dtt1],
+        };
+        ttrs.iter().cloned().find(|&ttr| ttr_matches(ttr, fc, addr))
+    }
+
+    // logical to physical plus the cache mode to use for the access
+    pub fn translate_040<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, write: bool) -> Result<(u32, CacheMode)> {
+        let fc = space.fc();
+        if let Some(ttr) = self.transparent_040(space.1, fc, addr) {
+            if write && ttr & TTR_W != 0 {
+                return Err(Exception::AccessFault(addr, fc as u8, write));
+            }
+            return Ok((addr, CacheMode::from_bits(ttr >> 5)));
+        }
+        if self.tc & TC_E == 0 {
+            return Ok((addr, CacheMode::Writethrough));
+        }
+        let supervisor = fc & 4 != 0;
+        let page_mask = self.page_mask_040();
+        let page = addr & !page_mask;
+        let lookup = match space.1 {
+            Segment::Program => self.iatc.lookup(supervisor, page),
+            Segment::Data => self.datc.lookup(supervisor, page),
+        };
+        let entry = match lookup.map(|i| match space.1 {
+            Segment::Program => self.iatc.entries[i],
+            Segment::Data => self.datc.entries[i],
+        }) {
+            // the first write to a clean page walks the tables again so
+            // the M bit gets set in the page descriptor
+            Some(entry) if !write || !entry.r || entry.m || entry.w => entry,
+            _ => {
+                let (entry, _) = self.table_search_040(bus, supervisor, addr, write, true);
+                // a walk that ended in a bus error isn't loaded, the next
+                // access tries again
+                if !entry.b {
+                    match space.1 {
+                        Segment::Program => self.iatc.install(entry),
+                        Segment::Data => self.datc.install(entry),
+                    };
+                }
+                entry
+            }
+        };
+        if !entry.r || (entry.s && !supervisor) || (write && entry.w) {
+            return Err(Exception::AccessFault(addr, fc as u8, write));
+        }
+        Ok((entry.physical | (addr & page_mask), CacheMode::from_bits(entry.cm)))
+    }
+
+    // The translation a read would get, for a debugger. The ATCs and the U
+    // and M bits are left alone, None where the read would fault.
+    pub fn probe_040<T: Bus + ?Sized>(&self, bus: &mut T, space: AddressSpace, addr: u32) -> Option<u32> {
+        let fc = space.fc();
+        if self.transparent_040(space.1, fc, addr).is_some() || self.tc & TC_E == 0 {
+            return Some(addr);
+        }
+        let supervisor = fc & 4 != 0;
+        let page_mask = self.page_mask_040();
+        let atc = match space.1 {
+            Segment::Program => &self.iatc,
+            Segment::Data => &self.datc,
+        };
+        let entry = match atc.lookup(supervisor, addr & !page_mask) {
+            Some(i) => atc.entries[i],
+            None => self.table_search_040(bus, supervisor, addr, false, false).0,
+        };
+        if !entry.r || (entry.s && !supervisor) {
+            return None;
+        }
+        Some(entry.physical | (addr & page_mask))
+    }
+
+    // Walks root, pointer and page tables. Sets the U bits on the way down
+    // and M on a write when `update` is set. Returns the ATC entry along
+    // with the address of the last descriptor read.
+    fn table_search_040<T: Bus + ?Sized>(&self, bus: &mut T, supervisor: bool, addr: u32, write: bool, update: bool) -> (AtcEntry040, u32) {
+        let page_mask = self.page_mask_040();
+        let mut entry = AtcEntry040 {
+            v: true, supervisor, logical: addr & !page_mask,
+            ..AtcEntry040::default()
+        };
+        let root = if supervisor { self.srp } else { self.urp };
+        let root_addr = (root & 0xffff_fe00) + ((addr >> 25) << 2);
+        let root_desc = match descriptor_040(bus, root_addr) {
+            Some(desc) => desc,
+            None => return (AtcEntry040 { b: true, ..entry }, root_addr),
+        };
+        if root_desc & UDT_RESIDENT == 0 {
+            return (entry, root_addr);
+        }
+        if update && root_desc & DESC_U == 0 {
+            bus.write_32(SUPERVISOR_DATA, root_addr, root_desc | DESC_U);
+        }
+        let pointer_addr = (root_desc & 0xffff_fe00) + (((addr >> 18) & 0x7f) << 2);
+        let pointer_desc = match descriptor_040(bus, pointer_addr) {
+            Some(desc) => desc,
+            None => return (AtcEntry040 { b: true, ..entry }, pointer_addr),
+        };
+        if pointer_desc & UDT_RESIDENT == 0 {
+            return (entry, pointer_addr);
+        }
+        if update && pointer_desc & DESC_U == 0 {
+            bus.write_32(SUPERVISOR_DATA, pointer_addr, pointer_desc | DESC_U);
+        }
+        let mut page_addr = if page_mask == 0x1fff {
+            (pointer_desc & 0xffff_ff80) + (((addr >> 13) & 0x1f) << 2)
+        } else {
+            (pointer_desc & 0xffff_ff00) + (((addr >> 12) & 0x3f) << 2)
+        };
+        let mut page_desc = match descriptor_040(bus, page_addr) {
+            Some(desc) => desc,
+            None => return (AtcEntry040 { b: true, ..entry }, page_addr),
+        };
+        if page_desc & PDT == PDT_INDIRECT {
+            page_addr = page_desc & 0xffff_fffc;
+            page_desc = match descriptor_040(bus, page_addr) {
+                Some(desc) => desc,
+                None => return (AtcEntry040 { b: true, ..entry }, page_addr),
+            };
+        }
+        // an indirect descriptor can't point at another one
+        if page_desc & PDT == 0 || page_desc & PDT == PDT_INDIRECT {
+            return (entry, page_addr);
+        }
+        let w = (root_desc | pointer_desc | page_desc) & DESC_W != 0;
+        if update {
+            let mut updated = page_desc | DESC_U;
+            if write && !w {
+                updated |= DESC_M;
+            }
+            if updated != page_desc {
+                bus.write_32(SUPERVISOR_DATA, page_addr, updated);
+                page_desc = updated;
+            }
+        }
+        entry.r = true;
+        entry.physical = page_desc & !page_mask;
+        entry.w = w;
+        entry.g = page_desc & DESC_G != 0;
+        entry.s = page_desc & DESC_S != 0;
+        entry.m = page_desc & DESC_M != 0;
+        entry.cm = (page_desc >> 5) & 3;
+        entry.u = (page_desc >> 8) & 3;
+        (entry, page_addr)
+    }
+
+    // PTEST, searches the tables for the DFC space, loads the ATC and
+    // reports the result in MMUSR
+    pub fn ptest_040<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32, write: bool) {
+        let fc = self.dfc & 7;
+        let segment = if fc & 3 == 2 { Segment::Program } else { Segment::Data };
+        if let Some(ttr) = self.transparent_040(segment, fc, addr) {
+            self.mmusr = (addr & 0xffff_f000) | ((ttr >> 5) & 3) << 5 | (ttr & TTR_W) | MMUSR_T | MMUSR_R;
+            return;
+        }
+        let supervisor = fc & 4 != 0;
+        let (entry, _) = self.table_search_040(bus, supervisor, addr, write, true);
+        if !entry.b {
+            match segment {
+                Segment::Program => self.iatc.install(entry),
+                Segment::Data => self.datc.install(entry),
+            };
+        }
+        self.mmusr = if entry.b {
+            MMUSR_B
+        } else if entry.r {
+            entry.physical & 0xffff_f000 |
+            if entry.g { MMUSR_G } else { 0 } |
+            entry.u << 8 |
+            if entry.s { MMUSR_S } else { 0 } |
+            entry.cm << 5 |
+            if entry.m { MMUSR_M } else { 0 } |
+            if entry.w { MMUSR_W } else { 0 } |
+            MMUSR_R
+        } else {
+            0
+        };
+    }
+
+    // '060 PLPAR/PLPAW, translates `addr` in the DFC space like a real
+    // access would, faulting the same way
+    pub fn plpa_060<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32, write: bool) -> Result<u32> {
+        let fc = self.dfc & 7;
+        let mode = if fc & 4 != 0 { Mode::Supervisor } else { Mode::User };
+        let segment = if fc & 3 == 2 { Segment::Program } else { Segment::Data };
+        let (physical, _) = self.translate_040(bus, AddressSpace(mode, segment), addr, write)?;
+        Ok(physical)
+    }
+
+    // PFLUSH, PFLUSHN, PFLUSHA and PFLUSHAN, the page forms use the DFC space
+    pub fn pflush_040(&mut self, addr: Option<u32>, global: bool) {
+        let supervisor = self.dfc & 4 != 0;
+        let page = addr.map(|addr| addr & !self.page_mask_040());
+        self.iatc.flush(supervisor, page, global);
+        self.datc.flush(supervisor, page, global);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory::MemoryMap;
+    use tests::small_map;
+    use Version;
+
+    // 4K pages, logical $5000 is physical $3000 through the tables at $9000,
+    // the vector and stack pages map onto themselves and the code is
+    // transparently translated
+    fn mapped(code: &[u16]) -> (M68k<'static>, MemoryMap) {
+        let mut bus = small_map(0x8000, code);
+        bus.poke_32(SUPERVISOR_DATA, 8, 0x600);
+        bus.poke_32(SUPERVISOR_DATA, 0x9000, 0x9200 | UDT_RESIDENT);
+        bus.poke_32(SUPERVISOR_DATA, 0x9200, 0x9400 | UDT_RESIDENT);
+        bus.poke_32(SUPERVISOR_DATA, 0x9400, 0x0001);
+        bus.poke_32(SUPERVISOR_DATA, 0x9400 + 5 * 4, 0x3001);
+        bus.poke_32(SUPERVISOR_DATA, 0x9400 + 7 * 4, 0x7001);
+        let mut cpu = M68k::new(Version::MC68040);
+        cpu.reset(&mut bus);
+        cpu.itt0 = 0x00ff_c000;
+        cpu.srp = 0x9000;
+        cpu.tc = TC_E;
+        (cpu, bus)
+    }
+
+    #[test]
+    fn walk_translates_and_updates() {
+        // move.l d0,(a0)
+        let (mut cpu, mut bus) = mapped(&[0x2080]);
+        cpu.dar[0] = 0x12345678;
+        cpu.dar[8] = 0x5010;
+        cpu.step(&mut bus);
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x3010), 0x12345678);
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x9400 + 5 * 4), 0x3001 | DESC_U | DESC_M);
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x9000), 0x9200 | UDT_RESIDENT | DESC_U);
+        cpu.dfc = 5;
+        cpu.ptest_040(&mut bus, 0x5000, false);
+        assert_eq!(cpu.mmusr, 0x3000 | MMUSR_M | MMUSR_R);
+        cpu.ptest_040(&mut bus, 0x6000, false);
+        assert_eq!(cpu.mmusr, 0);
+    }
+
+    #[test]
+    fn walk_bus_errors() {
+        // the page table for $40000 up is past the end of the RAM
+        let (mut cpu, mut bus) = mapped(&[0x2080]);
+        bus.poke_32(SUPERVISOR_DATA, 0x9204, 0x20000 | UDT_RESIDENT);
+        cpu.dfc = 5;
+        cpu.ptest_040(&mut bus, 0x40000, false);
+        assert_eq!(cpu.mmusr, MMUSR_B);
+        assert!(cpu.datc.lookup(true, 0x40000).is_none());
+        cpu.dar[8] = 0x40000;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x600);
+        assert!(cpu.datc.lookup(true, 0x40000).is_none());
+    }
+}
@@ -0,0 +1,75 @@
+// Several cores on one bus
+//
+// The cores take turns a step at a time, always the one that is furthest
+// behind, so they stay within an instruction of each other. Each core's
+// clock is put on a common timebase through a divider, so cores running
+// at different rates interleave at the right ratio.
+//
+// A step has the bus to itself, so anything a core does inside one
+// instruction, TAS included, can't be seen half done by the others. The
+// Bus is told which core is stepping, so it can give each one its own
+// view of memory or its own side of a mailbox.
+
+use scheduler::ClockDivider;
+use Bus;
+use M68k;
+
+pub struct Cores<'a> {
+    pub cores: Vec<M68k<'a>>,
+    pub dividers: Vec<ClockDivider>,    // common timebase clocks per core clock
+}
+
+impl<'a> Cores<'a> {
+    pub fn new() -> Self {
+        Cores {
+            cores: Vec::new(),
+            dividers: Vec::new(),
+        }
+    }
+
+    // a core running at the timebase rate
+    pub fn add(&mut self, core: M68k<'a>) -> usize {
+        self.add_with_divider(core, ClockDivider::CPU)
+    }
+
+    // `divider` takes the core's clocks to the common timebase, a core at
+    // half the timebase rate is ClockDivider::new(2, 1)
+    pub fn add_with_divider(&mut self, core: M68k<'a>, divider: ClockDivider) -> usize {
+        self.cores.push(core);
+        self.dividers.push(divider);
+        self.cores.len() - 1
+    }
+
+    // where core `index` has got to on the common timebase
+    pub fn time(&self, index: usize) -> u64 {
+        self.dividers[index].to_cpu(self.cores[index].clock)
+    }
+
+    // where the cores have all got to
+    pub fn now(&self) -> u64 {
+        (0..self.cores.len()).map(|i| self.time(i)).min().unwrap_or(0)
+    }
+
+    // Steps the core that is furthest behind, the first one on a tie.
+    // Returns which one it was and the clocks it took.
+    pub fn step<T: Bus + 'a>(&mut self, bus: &mut T) -> (usize, u32) {
+        let index = (0..self.cores.len()).min_by_key(|&i| self.time(i)).expect("no cores to step");
+        bus.select_core(index);
+        let cycles = self.cores[index].step(bus);
+        (index, cycles)
+    }
+
+    // steps until every core has reached `until` on the common timebase,
+    // or one of them hits a watchpoint
+    pub fn run_until<T: Bus + 'a>(&mut self, bus: &mut T, until: u64) {
+        while !self.cores.is_empty() && self.now() < until && self.cores.iter().all(|c| c.watch_hit.is_none()) {
+            self.step(bus);
+        }
+    }
+}
+
+impl<'a> Default for Cores<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,609 @@
+// MC68851 Paged Memory Management Unit
+//
+// The '020 has no on-chip MMU, systems that need paging pair it with the
+// MC68851. It sits on the coprocessor interface as coprocessor id 0 and
+// translates every logical address the core puts out before it reaches
+// the bus (ref MC68851UM).
+//
+// Not modelled (yet): the root pointer table, breakpoint acknowledge
+// cycles (BADx/BACx are only storage) and the PSAVE/PRESTORE frames.
+
+#![allow(dead_code)]
+
+use Bus;
+use Result;
+use Exception;
+use SUPERVISOR_DATA;
+
+// Translation Control register (ref MC68851UM 6.1.1)
+pub const TC_E: u32   = 0x8000_0000;     // enable
+pub const TC_SRE: u32 = 0x0200_0000;     // supervisor root pointer enable
+pub const TC_FCL: u32 = 0x0100_0000;     // function code lookup
+
+// Descriptor types, in the DT field of root pointers and descriptors
+pub const DT_INVALID: u32 = 0;
+pub const DT_PAGE: u32    = 1;          // page descriptor (early termination)
+pub const DT_VALID_4: u32 = 2;          // next table holds short descriptors
+pub const DT_VALID_8: u32 = 3;          // next table holds long descriptors
+
+// Descriptor bits shared by the short format and the low long word of the
+// long format
+const DESC_DT: u32 = 0x0003;
+const DESC_WP: u32 = 0x0004;
+const DESC_U: u32  = 0x0008;
+const DESC_M: u32  = 0x0010;
+const DESC_L: u32  = 0x0020;
+const DESC_CI: u32 = 0x0040;
+const DESC_G: u32  = 0x0080;
+// long format only
+const DESC_S: u32  = 0x0100;
+const DESC_SG: u32 = 0x0200;
+const DESC_LU: u32 = 0x8000_0000;
+
+// PMMU Status Register (ref MC68851UM 6.1.6)
+pub const PSR_B: u16 = 0x8000;          // bus error
+pub const PSR_L: u16 = 0x4000;          // limit violation
+pub const PSR_S: u16 = 0x2000;          // supervisor violation
+pub const PSR_A: u16 = 0x1000;          // access level violation
+pub const PSR_W: u16 = 0x0800;          // write protected
+pub const PSR_I: u16 = 0x0400;          // invalid
+pub const PSR_M: u16 = 0x0200;          // modified
+pub const PSR_G: u16 = 0x0100;          // gate
+pub const PSR_C: u16 = 0x0080;          // globally shared
+pub const PSR_N: u16 = 0x0007;          // number of levels
+
+pub const ATC_ENTRIES: usize = 64;
+
+#[derive(Copy, Clone, Default)]
+pub struct AtcEntry {
+    pub v: bool,
+    pub fc: u32,
+    pub logical: u32,           // logical page address
+    pub physical: u32,          // physical page address
+    pub b: bool,                // table search ended in an invalid descriptor or a bus error
+    pub wp: bool,
+    pub m: bool,
+    pub ci: bool,
+    pub s: bool,                // supervisor only
+    pub sg: bool,               // shared globally, survives a non-shared flush
+    pub l: bool,                // locked, never picked for replacement
+    pub g: bool,
+    pub ral: u8,
+    pub wal: u8,
+}
+
+// What a table search found, used both to load the ATC and to answer PTEST
+#[derive(Copy, Clone, Default)]
+pub struct Search {
+    pub psr: u16,
+    pub physical: u32,          // physical page address
+    pub descriptor: u32,        // address of the last descriptor fetched
+    pub entry: AtcEntry,
+}
+
+pub struct Pmmu {
+    pub tc: u32,
+    pub crp: u64,
+    pub srp: u64,
+    pub drp: u64,
+    pub cal: u8,
+    pub val: u8,
+    pub scc: u8,
+    pub ac: u16,
+    pub psr: u16,
+    pub pcsr: u16,
+    pub bad: [u16; 8],
+    pub bac: [u16; 8],
+    pub atc: [AtcEntry; ATC_ENTRIES],
+    replace: usize,             // next ATC entry to be replaced (round robin)
+}
+
+// A descriptor and, for the long format, its second long that holds the
+// address. None when the fetch ends in a bus error, the search stops there.
+fn descriptor<T: Bus + ?Sized>(bus: &mut T, addr: u32, long: bool) -> Option<(u32, u32)> {
+    let mut read = |addr: u32| if bus.bus_error(SUPERVISOR_DATA, addr, 4, false) {
+        None
+    } else {
+        Some(bus.read_32(SUPERVISOR_DATA, addr))
+    };
+    let desc = read(addr)?;
+    let address = if long { read(addr.wrapping_add(4))? } else { desc };
+    Some((desc, address))
+}
+
+impl Default for Pmmu {
+    fn default() -> Self {
+        Pmmu::new()
+    }
+}
+
+impl Pmmu {
+    pub fn new() -> Self {
+        Pmmu {
+            tc: 0, crp: 0, srp: 0, drp: 0,
+            cal: 0, val: 0, scc: 0, ac: 0,
+            psr: 0, pcsr: 0,
+            bad: [0; 8], bac: [0; 8],
+            atc: [AtcEntry::default(); ATC_ENTRIES],
+            replace: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Pmmu::new();
+    }
+
+    fn page_size(&self) -> u32 {
+        (self.tc >> 20) & 0xf
+    }
+
+    fn page_mask(&self) -> u32 {
+        (1 << self.page_size()) - 1
+    }
+
+    // number of logical address bits used for access levels, 0 = disabled
+    fn access_level_bits(&self) -> u32 {
+        ((self.ac >> 4) & 3) as u32
+    }
+
+    // Checks a new TC value: the initial shift, the table index fields up to
+    // the first zero one and the page size must add up to 32 bits.
+    pub fn tc_valid(tc: u32) -> bool {
+        if tc & TC_E == 0 {
+            return true;
+        }
+        let ps = (tc >> 20) & 0xf;
+        let is = (tc >> 16) & 0xf;
+        if ps < 8 || (tc >> 12) & 0xf == 0 {
+            return false;
+        }
+        let mut total = ps + is;
+        for shift in [12, 8, 4, 0].iter() {
+            let bits = (tc >> shift) & 0xf;
+            if bits == 0 {
+                break;
+            }
+            total += bits;
+        }
+        total == 32
+    }
+
+    pub fn root_pointer_valid(rp: u64) -> bool {
+        ((rp >> 32) as u32) & DESC_DT != DT_INVALID
+    }
+
+    // Loading the translation control or a root pointer changes every
+    // translation, so the whole ATC goes.
+    pub fn flush_all(&mut self) {
+        for entry in self.atc.iter_mut() {
+            entry.v = false;
+        }
+    }
+
+    // PFLUSH by function code (and optionally page). Entries marked shared
+    // globally are only removed by the PFLUSHS forms.
+    pub fn flush(&mut self, fc: u32, mask: u32, page: Option<u32>, shared: bool) {
+        let page_mask = self.page_mask();
+        for entry in self.atc.iter_mut() {
+            if !entry.v || (entry.sg && !shared) {
+                continue;
+            }
+            if (entry.fc & mask) != (fc & mask) {
+                continue;
+            }
+            if let Some(addr) = page {
+                if entry.logical != addr & !page_mask {
+                    continue;
+                }
+            }
+            entry.v = false;
+        }
+    }
+
+    fn lookup(&self, fc: u32, page: u32) -> Option<usize> {
+        self.atc.iter().position(|e| e.v && e.fc == fc && e.logical == page)
+    }
+
+    fn install(&mut self, entry: AtcEntry) -> usize {
+        // reuse an entry for the same page, then any invalid one, then round
+        // robin over the unlocked ones
+        let index = match self.lookup(entry.fc, entry.logical) {
+            Some(i) => i,
+            None => match self.atc.iter().position(|e| !e.v) {
+                Some(i) => i,
+                None => {
+                    let mut i = self.replace;
+                    for _ in 0..ATC_ENTRIES {
+                        if !self.atc[i].l {
+                            break;
+                        }
+                        i = (i + 1) % ATC_ENTRIES;
+                    }
+                    self.replace = (i + 1) % ATC_ENTRIES;
+                    i
+                }
+            }
+        };
+        self.atc[index] = entry;
+        index
+    }
+
+    // Translates a logical address, for the function code of the access.
+    // Faults come back as a bus error, which is how the '851 reports them
+    // to the '020.
+    pub fn translate<T: Bus + ?Sized>(&mut self, bus: &mut T, fc: u32, addr: u32, write: bool) -> Result<u32> {
+        if self.tc & TC_E == 0 {
+            return Ok(addr);
+        }
+        let page_mask = self.page_mask();
+        let page = addr & !page_mask;
+        let index = match self.lookup(fc, page) {
+            // the first write to a clean page goes through the tables again
+            // so the M bit gets set in the page descriptor
+            Some(i) if !(write && !self.atc[i].m && !self.atc[i].wp && !self.atc[i].b) => i,
+            _ => {
+                let search = self.search(bus, fc, addr, write, 7, true);
+                self.install(search.entry)
+            }
+        };
+        let entry = self.atc[index];
+        if entry.b || (entry.s && fc & 4 == 0) || (write && entry.wp) || self.level_violation(&entry, write) {
+            return Err(Exception::BusError(addr, fc as u8, write));
+        }
+        Ok(entry.physical | (addr & page_mask))
+    }
+
+    // The translation a read would get, for a debugger. The ATC and the U
+    // and M bits are left alone, None where the read would fault.
+    pub fn probe<T: Bus + ?Sized>(&self, bus: &mut T, fc: u32, addr: u32) -> Option<u32> {
+        if self.tc & TC_E == 0 {
+            return Some(addr);
+        }
+        let page_mask = self.page_mask();
+        let entry = match self.lookup(fc, addr & !page_mask) {
+            Some(i) => self.atc[i],
+            None => self.search(bus, fc, addr, false, 7, false).entry,
+        };
+        if entry.b || (entry.s && fc & 4 == 0) || self.level_violation(&entry, false) {
+            return None;
+        }
+        Some(entry.physical | (addr & page_mask))
+    }
+
+    fn level_violation(&self, entry: &AtcEntry, write: bool) -> bool {
+        if self.access_level_bits() == 0 {
+            return false;
+        }
+        let level = self.cal >> 5;
+        level > if write { entry.wal } else { entry.ral }
+    }
+
+    // PLOAD, search the tables and load the ATC without doing an access
+    pub fn load<T: Bus + ?Sized>(&mut self, bus: &mut T, fc: u32, addr: u32, write: bool) {
+        if self.tc & TC_E == 0 {
+            return;
+        }
+        let search = self.search(bus, fc, addr, write, 7, true);
+        self.install(search.entry);
+    }
+
+    // PTEST, level 0 only looks in the ATC, levels 1-7 search the tables up
+    // to that many levels without changing the U and M bits or the ATC.
+    pub fn test<T: Bus + ?Sized>(&mut self, bus: &mut T, fc: u32, addr: u32, write: bool, level: u32) -> u32 {
+        if level == 0 {
+            let page = addr & !self.page_mask();
+            self.psr = match self.lookup(fc, page) {
+                Some(i) => {
+                    let e = self.atc[i];
+                    let mut psr = 0;
+                    if e.b { psr |= PSR_B; }
+                    if e.s && fc & 4 == 0 { psr |= PSR_S; }
+                    if e.wp { psr |= PSR_W; }
+                    if e.m { psr |= PSR_M; }
+                    if e.g { psr |= PSR_G; }
+                    if e.sg { psr |= PSR_C; }
+                    if self.level_violation(&e, write) { psr |= PSR_A; }
+                    psr
+                },
+                None => PSR_I,
+            };
+            0
+        } else {
+            let search = self.search(bus, fc, addr, write, level, false);
+            self.psr = search.psr;
+            search.descriptor
+        }
+    }
+
+    fn root_pointer(&self, fc: u32) -> u64 {
+        if self.tc & TC_SRE != 0 && fc & 4 != 0 {
+            self.srp
+        } else {
+            self.crp
+        }
+    }
+
+    // Walks the translation tables for one logical address (ref MC68851UM
+    // 5.2). Returns the ATC entry to load along with the PTEST status.
+    pub fn search<T: Bus + ?Sized>(&self, bus: &mut T, fc: u32, addr: u32, write: bool, max_level: u32, update: bool) -> Search {
+        let page_mask = self.page_mask();
+        let mut result = Search {
+            entry: AtcEntry {
+                v: true, fc, logical: addr & !page_mask,
+                ral: 7, wal: 7,
+                ..AtcEntry::default()
+            },
+            ..Search::default()
+        };
+
+        // build the list of table indexes, function code first if enabled,
+        // along with the lowest logical address bit left unused after each
+        let mut indexes = [0u32; 5];
+        let mut unused = [0u32; 6];
+        let mut count = 0;
+        let mut pos = 32 - ((self.tc >> 16) & 0xf);
+        unused[0] = pos;
+        if self.tc & TC_FCL != 0 {
+            indexes[count] = fc & 7;
+            count += 1;
+            unused[count] = pos;
+        }
+        for shift in [12, 8, 4, 0].iter() {
+            let bits = (self.tc >> shift) & 0xf;
+            if bits == 0 {
+                break;
+            }
+            pos -= bits;
+            indexes[count] = (addr >> pos) & ((1 << bits) - 1);
+            count += 1;
+            unused[count] = pos;
+        }
+        // a page descriptor found early maps the logical bits not used for
+        // indexing onto a contiguous physical area
+        let page_address = |level: usize, page: u32| {
+            let bits = unused[level];
+            let mask = if bits >= 32 { 0xffff_ffff } else { (1u32 << bits) - 1 };
+            (page & 0xffff_ff00).wrapping_add(addr & mask & !page_mask)
+        };
+
+        let root = self.root_pointer(fc);
+        let mut dt = ((root >> 32) as u32) & DESC_DT;
+        let mut next = root as u32;
+        let mut limit = (root >> 32) as u32;
+        let mut has_limit = true;
+        let mut level = 0;
+
+        let mut psr = 0;
+        loop {
+            match dt {
+                DT_INVALID => {
+                    psr |= PSR_I;
+                    result.entry.b = true;
+                    break;
+                },
+                DT_PAGE => {
+                    result.physical = page_address(level, next);
+                    break;
+                },
+                _ => {
+                    if level >= count || level as u32 >= max_level {
+                        if level >= count {
+                            // a table pointer where a page descriptor belongs
+                            psr |= PSR_I;
+                            result.entry.b = true;
+                        }
+                        break;
+                    }
+                    let index = indexes[level];
+                    if has_limit {
+                        let lower = limit & DESC_LU != 0;
+                        let bound = (limit >> 16) & 0x7fff;
+                        if (lower && index < bound) || (!lower && index > bound) {
+                            psr |= PSR_L | PSR_I;
+                            result.entry.b = true;
+                            break;
+                        }
+                    }
+                    let long = dt == DT_VALID_8;
+                    let table = next & 0xffff_fff0;
+                    let mut desc_addr = table.wrapping_add(index * if long { 8 } else { 4 });
+                    level += 1;
+                    let (mut desc, mut address) = match descriptor(bus, desc_addr, long) {
+                        Some(fetched) => fetched,
+                        None => {
+                            result.descriptor = desc_addr;
+                            psr |= PSR_B;
+                            result.entry.b = true;
+                            break;
+                        },
+                    };
+                    let mut long_desc = long;
+
+                    // the last level may hold an indirect descriptor instead
+                    // of the page descriptor itself
+                    if level >= count && desc & DESC_DT >= DT_VALID_4 {
+                        long_desc = desc & DESC_DT == DT_VALID_8;
+                        desc_addr = address & 0xffff_fffc;
+                        match descriptor(bus, desc_addr, long_desc) {
+                            Some(fetched) => (desc, address) = fetched,
+                            None => {
+                                result.descriptor = desc_addr;
+                                psr |= PSR_B;
+                                result.entry.b = true;
+                                break;
+                            },
+                        }
+                        if desc & DESC_DT != DT_PAGE {
+                            result.descriptor = desc_addr;
+                            psr |= PSR_I;
+                            result.entry.b = true;
+                            break;
+                        }
+                    }
+                    result.descriptor = desc_addr;
+
+                    self.descriptor_flags(&mut result, &mut psr, desc, long_desc, fc, write);
+                    if update {
+                        self.update_descriptor(bus, desc_addr, desc, desc & DESC_DT == DT_PAGE, write);
+                    }
+                    dt = desc & DESC_DT;
+                    next = address;
+                    limit = desc;
+                    has_limit = long_desc;
+                }
+            }
+        }
+
+        if self.level_violation(&result.entry, write) {
+            psr |= PSR_A;
+        }
+        result.entry.physical = result.physical;
+        result.psr = psr | (level as u16 & PSR_N);
+        result
+    }
+
+    fn descriptor_flags(&self, result: &mut Search, psr: &mut u16, desc: u32, long: bool, fc: u32, write: bool) {
+        if desc & DESC_WP != 0 {
+            result.entry.wp = true;
+            *psr |= PSR_W;
+        }
+        if long {
+            if desc & DESC_S != 0 {
+                result.entry.s = true;
+                if fc & 4 == 0 {
+                    *psr |= PSR_S;
+                }
+            }
+            if desc & DESC_SG != 0 {
+                result.entry.sg = true;
+                *psr |= PSR_C;
+            }
+            // the most restrictive level along the way wins
+            let ral = ((desc >> 13) & 7) as u8;
+            let wal = ((desc >> 10) & 7) as u8;
+            if ral < result.entry.ral { result.entry.ral = ral; }
+            if wal < result.entry.wal { result.entry.wal = wal; }
+        }
+        if desc & DESC_DT == DT_PAGE {
+            result.entry.ci = desc & DESC_CI != 0;
+            result.entry.l = desc & DESC_L != 0;
+            result.entry.g = desc & DESC_G != 0;
+            result.entry.m = desc & DESC_M != 0 || (write && !result.entry.wp);
+            if desc & DESC_G != 0 { *psr |= PSR_G; }
+            if desc & DESC_M != 0 { *psr |= PSR_M; }
+        }
+    }
+
+    // sets U on every descriptor used, and M on the page descriptor of a write
+    fn update_descriptor<T: Bus + ?Sized>(&self, bus: &mut T, addr: u32, desc: u32, page: bool, write: bool) {
+        let mut new = desc | DESC_U;
+        if page && write && desc & DESC_WP == 0 {
+            new |= DESC_M;
+        }
+        if new != desc {
+            bus.write_32(SUPERVISOR_DATA, addr, new);
+        }
+    }
+
+    // Access level of a logical address, from its upper bits. None when
+    // access levels are disabled in AC.
+    pub fn access_level(&self, addr: u32) -> Option<u32> {
+        let bits = self.access_level_bits();
+        if bits == 0 {
+            None
+        } else {
+            Some(addr >> (32 - bits))
+        }
+    }
+
+    // VAL holds its level in the upper three bits, only as many as AC
+    // enables are compared
+    pub fn val_level(&self) -> u32 {
+        ((self.val >> 5) as u32) >> (3 - self.access_level_bits())
+    }
+
+    // MMU conditions for PBcc, PDBcc, PScc and PTRAPcc, set ones are even
+    // and their clear counterparts odd (ref MC68851UM 9.2)
+    pub fn condition(&self, cc: u16) -> bool {
+        let bit = match (cc >> 1) & 7 {
+            0 => PSR_B,
+            1 => PSR_L,
+            2 => PSR_S,
+            3 => PSR_A,
+            4 => PSR_W,
+            5 => PSR_I,
+            6 => PSR_G,
+            _ => PSR_C,
+        };
+        let set = self.psr & bit != 0;
+        if cc & 1 == 0 { set } else { !set }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory::{MemoryMap, Unmapped};
+    use tests::small_map;
+    use {Bus, M68k, Version};
+
+    // 4K pages and two levels of ten bits, logical $5000 is physical $3000
+    // through a root table at $9000 and a page table at $a000
+    fn mapped() -> (Pmmu, MemoryMap) {
+        let mut bus = MemoryMap::new();
+        bus.ram(0, 0x10000);
+        bus.unmapped = Unmapped::BusError;
+        bus.poke_32(SUPERVISOR_DATA, 0x9000, 0xa000 | DT_VALID_4);
+        bus.poke_32(SUPERVISOR_DATA, 0xa000 + 5 * 4, 0x3000 | DT_PAGE);
+        bus.poke_32(SUPERVISOR_DATA, 0xa000 + 6 * 4, 0x4000 | DT_PAGE | DESC_WP);
+        let mut pmmu = Pmmu::new();
+        pmmu.tc = TC_E | 0xc << 20 | 0xa << 12 | 0xa << 8;
+        pmmu.crp = (0x7fff_0000 | DT_VALID_4 as u64) << 32 | 0x9000;
+        assert!(Pmmu::tc_valid(pmmu.tc));
+        (pmmu, bus)
+    }
+
+    #[test]
+    fn walk_translates_and_updates() {
+        let (mut pmmu, mut bus) = mapped();
+        assert_eq!(pmmu.translate(&mut bus, 5, 0x5123, false).ok(), Some(0x3123));
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0xa014), 0x3000 | DT_PAGE | DESC_U);
+        assert_eq!(pmmu.translate(&mut bus, 5, 0x5124, true).ok(), Some(0x3124));
+        assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0xa014), 0x3000 | DT_PAGE | DESC_U | DESC_M);
+        assert!(matches!(pmmu.translate(&mut bus, 5, 0x6000, true), Err(Exception::BusError(0x6000, 5, true))));
+        assert!(matches!(pmmu.translate(&mut bus, 5, 0x7000, false), Err(Exception::BusError(0x7000, 5, false))));
+        pmmu.test(&mut bus, 5, 0x7000, false, 7);
+        assert_eq!(pmmu.psr, PSR_I | 2);
+    }
+
+    #[test]
+    fn walk_bus_errors() {
+        // the page table for $400000 up is past the end of the RAM
+        let (mut pmmu, mut bus) = mapped();
+        bus.poke_32(SUPERVISOR_DATA, 0x9004, 0x20000 | DT_VALID_4);
+        assert!(matches!(pmmu.translate(&mut bus, 5, 0x40_0000, false), Err(Exception::BusError(0x40_0000, 5, false))));
+        assert_eq!(pmmu.test(&mut bus, 5, 0x40_0000, false, 7), 0x20000);
+        assert_eq!(pmmu.psr, PSR_B | 2);
+        assert!(pmmu.condition(0));
+    }
+
+    #[test]
+    fn only_a_full_020_has_one() {
+        // pmove tc,(a0) takes the F-line exception without the coprocessor interface
+        for &(version, pc) in [(Version::MC68020, 0x404), (Version::MC68EC020, 0x600),
+                               (Version::MC68000, 0x600), (Version::CPU32, 0x600)].iter() {
+            let mut bus = small_map(0x8000, &[0xf010, 0x4200]);
+            bus.poke_32(SUPERVISOR_DATA, 0x2c, 0x600);
+            let mut cpu = M68k::new(version);
+            cpu.reset(&mut bus);
+            let mut pmmu = Pmmu::new();
+            pmmu.tc = 0xc << 20 | 0xa << 12 | 0xa << 8;
+            cpu.pmmu = Some(pmmu);
+            cpu.dar[8] = 0x1000;
+            cpu.step(&mut bus);
+            assert_eq!(cpu.pc, pc, "{:?}", version);
+            assert_eq!(cpu.has_pmmu(), pc == 0x404);
+            assert_eq!(bus.peek_32(SUPERVISOR_DATA, 0x1000), if pc == 0x404 { 0x00c0_aa00 } else { 0 });
+        }
+    }
+}
@@ -0,0 +1,692 @@
+// The 68000 prefetch queue and where each bus cycle of a step falls, for
+// cycle exact mode (ref MC68000UM 8, the bus cycle orders are yacht's).
+//
+// The 68000 runs a word ahead. When an instruction starts its opcode is
+// in IRD and the word after it already in IRC, and every word taken from
+// IRC is made up by a prefetch somewhere later in the microcode. Where the
+// prefetches, reads, writes and internal clocks fall is fixed for each
+// instruction and addressing mode, so before the handler runs the step
+// gets a plan of them in that order:
+//
+//   n    2 internal clocks
+//   n*   the internal clocks the handler counts beyond the rest of the
+//        plan, shifts, MULx/DIVx and the like, they come last
+//   np   a prefetch, of the word after those already fetched
+//   |    the queue is thrown away, the prefetches after it start at pc
+//   nr   a read or write the handler makes, nR nw ns nV and the others of
+//        the tables are all the same here, one bus cycle each
+//
+// The handler runs as it always has. A read or write it makes first places
+// what comes before it in the plan, an extension word not yet in the queue
+// places what comes before the prefetch that brings it in, and what is
+// left is placed once the handler returns. Each bus cycle starts where the
+// one before it ended and the Bus can stretch it with wait states, so it
+// is asked about every cycle at the clock the cycle really starts.
+
+use std::collections::VecDeque;
+use Bus;
+use AddressSpace;
+use M68k;
+use Condition;
+use Exception;
+use Result;
+use Version;
+use trace::Access;
+use instructions::constants::*;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Cycle {
+    Internal,
+    Rest,
+    Prefetch,
+    Refill,
+    Access,
+    Fetch(u32),     // a program read that is thrown away, DBcc reads the branch target
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Until {
+    Access,         // the next read or write the handler makes
+    Word,           // a word in the queue
+    End(u32),       // the end of the step, the handler counted this many clocks
+}
+
+#[derive(Default)]
+pub struct Prefetch {
+    queue: VecDeque<(u32, u16)>,    // fetched and not taken yet, address and word
+    next: u32,                      // where the next prefetch reads
+    plan: VecDeque<Cycle>,          // what is left of the step
+    fixed: u32,                     // clocks the plan takes without n*
+    running: bool,                  // the step is being placed
+    placed: bool,                   // it was, it took step_clock
+}
+
+impl Prefetch {
+    pub fn flush(&mut self) {
+        self.queue.clear();
+        self.plan.clear();
+        self.running = false;
+    }
+
+    fn add(&mut self, cycles: &str) {
+        for c in cycles.split_whitespace() {
+            self.push(match c {
+                "n" => Cycle::Internal,
+                "n*" => Cycle::Rest,
+                "np" => Cycle::Prefetch,
+                "|" => Cycle::Refill,
+                _ => Cycle::Access,
+            });
+        }
+    }
+
+    fn push(&mut self, cycle: Cycle) {
+        self.fixed += match cycle {
+            Cycle::Internal => 2,
+            Cycle::Rest | Cycle::Refill => 0,
+            _ => 4,
+        };
+        self.plan.push_back(cycle);
+    }
+
+    // `taken` is the condition in bits 11-8, `count` the low word of the
+    // Dn in bits 2-0, as they are before the handler runs
+    fn build(&mut self, ir: u16, irc: u16, pc: u32, taken: bool, count: u32) {
+        self.plan.clear();
+        self.fixed = 0;
+        let ea = ir & 0x3f;
+        let mode = ea >> 3;
+        let size = ir >> 6 & 3;
+        let long = size == 2;
+        match ir >> 12 {
+            // MOVEP
+            0x0 if ir & 0x0138 == 0x0108 => self.add(match size {
+                0 => "np nr nr np",
+                1 => "np nr nr nr nr np",
+                2 => "np nw nw np",
+                _ => "np nw nw nw nw np",
+            }),
+            // BTST, BCHG, BCLR, BSET
+            0x0 if ir & 0x0100 != 0 || ir & 0x0f00 == 0x0800 => {
+                if ir & 0x0100 == 0 {
+                    self.add("np");
+                }
+                if mode == 0 {
+                    self.add("np n*");
+                } else {
+                    self.add(operand(ea, false));
+                    self.add(if size == 0 { "np" } else { "np nw" });
+                }
+            },
+            // to CCR and SR
+            0x0 if ea == 0x3c => self.add("np n n n n | np np"),
+            // ORI, ANDI, SUBI, ADDI, EORI, CMPI
+            0x0 => {
+                self.add(if long { "np np" } else { "np" });
+                if mode == 0 {
+                    self.add("np n*");
+                } else {
+                    self.add(operand(ea, long));
+                    self.add(if ir & 0x0e00 == 0x0c00 { "np" } else { modify(long) });
+                }
+            },
+            // MOVE, MOVEA
+            0x1..=0x3 => {
+                let long = ir >> 12 == 2;
+                self.add(operand(ea, long));
+                let memory = mode >= 2 && ea != 0x3c;
+                self.add(match (ir >> 6 & 7, ir >> 9 & 7, long) {
+                    (0, _, _) | (1, _, _) => "np",
+                    (2, _, false) | (3, _, false) => "nw np",
+                    (2, _, true) | (3, _, true) => "nW nw np",
+                    (4, _, false) => "np nw",
+                    (4, _, true) => "np nw nW",
+                    (5, _, false) | (7, 0, false) => "np nw np",
+                    (5, _, true) | (7, 0, true) => "np nW nw np",
+                    (6, _, false) => "n np nw np",
+                    (6, _, true) => "n np nW nw np",
+                    (_, _, false) if memory => "np nw np np",
+                    (_, _, true) if memory => "np nW nw np np",
+                    (_, _, false) => "np np nw np",
+                    (_, _, true) => "np np nW nw np",
+                });
+            },
+            0x4 => self.build_4(ir, irc),
+            // DBcc
+            0x5 if size == 3 && mode == 1 => {
+                if taken {
+                    self.add("n n np np");
+                } else if count != 0 {
+                    self.add("n | np np");
+                } else {
+                    self.push(Cycle::Internal);
+                    self.push(Cycle::Fetch(pc.wrapping_add(irc as i16 as u32)));
+                    self.add("np np");
+                }
+            },
+            // Scc
+            0x5 if size == 3 => {
+                if mode == 0 {
+                    self.add("np n*");
+                } else {
+                    self.add(operand(ea, false));
+                    self.add("np nw");
+                }
+            },
+            // ADDQ, SUBQ
+            0x5 => match mode {
+                0 if !long => self.add("np"),
+                0 | 1 => self.add("np n*"),
+                _ => {
+                    self.add(operand(ea, long));
+                    self.add(modify(long));
+                },
+            },
+            // BSR, BRA, Bcc
+            0x6 => self.add(match ir >> 8 & 0xf {
+                1 => "n nS ns | np np",
+                0 => "n | np np",
+                _ if taken => "n | np np",
+                _ if ir & 0xff == 0 => "n n np np",
+                _ => "n n np",
+            }),
+            // MOVEQ
+            0x7 => self.add("np"),
+            // DIVU, DIVS, MULU, MULS
+            0x8 | 0xc if size == 3 => {
+                self.add(operand(ea, false));
+                self.add("np n*");
+            },
+            // SBCD, ABCD
+            0x8 | 0xc if ir & 0x01f0 == 0x0100 => {
+                self.add(if ir & 8 == 0 { "np n*" } else { "n nr nr np nw" });
+            },
+            // EXG
+            0xc if ir & 0x0130 == 0x0100 => self.add("np n*"),
+            // SUBA, ADDA, CMPA
+            0x9 | 0xb | 0xd if size == 3 => {
+                self.add(operand(ea, ir & 0x0100 != 0));
+                self.add("np n*");
+            },
+            // SUBX, ADDX
+            0x9 | 0xd if ir & 0x0130 == 0x0100 => self.add(match (ir & 8 != 0, long) {
+                (false, false) => "np",
+                (false, true) => "np n*",
+                (true, false) => "n nr nr np nw",
+                (true, true) => "n nR nr nR nr np nw nW",
+            }),
+            // CMPM
+            0xb if ir & 0x0138 == 0x0108 => self.add(if long { "nR nr nR nr np" } else { "nr nr np" }),
+            // EOR
+            0xb if ir & 0x0100 != 0 && mode == 0 => self.add("np n*"),
+            // OR, SUB, CMP, AND, ADD, EOR
+            0x8 | 0x9 | 0xb | 0xc | 0xd => {
+                self.add(operand(ea, long));
+                if ir & 0x0100 == 0 {
+                    self.add("np n*");
+                } else {
+                    self.add(modify(long));
+                }
+            },
+            // shifts and rotates
+            0xe if size == 3 => {
+                self.add(operand(ea, false));
+                self.add("np nw");
+            },
+            0xe => self.add("np n*"),
+            // line A and F
+            _ => (),
+        }
+    }
+
+    fn build_4(&mut self, ir: u16, irc: u16) {
+        let ea = ir & 0x3f;
+        let mode = ea >> 3;
+        let size = ir >> 6 & 3;
+        let long = size == 2;
+        // the extension words of the address LEA, PEA, JMP, JSR and MOVEM
+        // calculate, 3 for an index
+        let words = match (mode, ea & 7) {
+            (2, _) | (3, _) | (4, _) => 0,
+            (5, _) | (7, 0) | (7, 2) => 1,
+            (7, 1) => 2,
+            _ => 3,
+        };
+        match ir {
+            // MOVE from SR
+            _ if ir & 0xffc0 == 0x40c0 => if mode == 0 {
+                self.add("np n*");
+            } else {
+                self.add(operand(ea, false));
+                self.add("np nw");
+            },
+            // MOVE to CCR and SR
+            _ if ir & 0xfdc0 == 0x44c0 => {
+                self.add(operand(ea, false));
+                self.add("n n | np np");
+            },
+            // NEGX, CLR, NEG, NOT
+            _ if ir & 0xf900 == 0x4000 && size != 3 => if mode == 0 {
+                self.add(if long { "np n*" } else { "np" });
+            } else {
+                self.add(operand(ea, long));
+                self.add(modify(long));
+            },
+            // NBCD
+            _ if ir & 0xffc0 == 0x4800 => if mode == 0 {
+                self.add("np n*");
+            } else {
+                self.add(operand(ea, false));
+                self.add("np nw");
+            },
+            // SWAP, EXT
+            _ if ir & 0xfff8 == 0x4840 || ir & 0xffb8 == 0x4880 => self.add("np"),
+            // PEA
+            _ if ir & 0xffc0 == 0x4840 => {
+                self.add(["", "np", "np np", "n np n"][words]);
+                self.add("np nS ns");
+            },
+            // MOVEM
+            _ if ir & 0xfb80 == 0x4880 => {
+                self.add(["np", "np np", "np np np", "np n np"][words]);
+                for _ in 0..irc.count_ones() {
+                    self.add(if ir & 0x40 != 0 { "nR nr" } else { "nr" });
+                }
+                self.add(if ir & 0x0400 != 0 { "nr np" } else { "np" });
+            },
+            // ILLEGAL
+            0x4afc => (),
+            // TAS
+            _ if ir & 0xffc0 == 0x4ac0 => if mode == 0 {
+                self.add("np");
+            } else {
+                self.add(operand(ea, false));
+                self.add("n n n nw np");
+            },
+            // TST
+            _ if ir & 0xff00 == 0x4a00 => {
+                self.add(operand(ea, long));
+                self.add("np");
+            },
+            // TRAP
+            _ if ir & 0xfff0 == 0x4e40 => (),
+            0x4e50..=0x4e57 => self.add("np nS ns np"),     // LINK
+            0x4e58..=0x4e5f => self.add("nU nu np"),        // UNLK
+            0x4e60..=0x4e6f => self.add("np"),              // MOVE USP
+            0x4e70 => self.add("n* np"),                    // RESET
+            0x4e71 => self.add("np"),                       // NOP
+            0x4e72 => self.add("n*"),                       // STOP
+            0x4e73 | 0x4e77 => self.add("nu nU nu | np np"),    // RTE, RTR
+            0x4e75 => self.add("nU nu | np np"),            // RTS
+            0x4e76 => self.add("np"),                       // TRAPV
+            // JSR, JMP
+            _ if ir & 0xff80 == 0x4e80 => {
+                self.add(["", "n", "np", "n n n"][words]);
+                self.add(if ir & 0x40 == 0 { "| np nS ns np" } else { "| np np" });
+            },
+            // CHK
+            _ if ir & 0xf1c0 == 0x4180 => {
+                self.add(operand(ea, false));
+                self.add("np n*");
+            },
+            // LEA
+            _ if ir & 0xf1c0 == 0x41c0 => {
+                self.add(["np", "np np", "np np np", "n np n np"][words]);
+            },
+            _ => (),
+        }
+    }
+
+    // the exception sequences, after the frame has been written and the
+    // vector read the handler's first two words are fetched
+    fn build_exception(&mut self, e: &Exception, vector: u8) {
+        self.plan.clear();
+        self.fixed = 0;
+        self.add(match *e {
+            Exception::AddressError | Exception::BusError(..) => "n n ns ns nS ns ns nS ns",
+            Exception::Interrupt(..) => "n n n ns n n n n nS ns",
+            _ => match vector {
+                EXCEPTION_TRAPV => "np ns nS ns",
+                EXCEPTION_CHK => "np n n n ns nS ns",
+                EXCEPTION_ZERO_DIVIDE => "np n n ns nS ns",
+                _ => "n n ns nS ns",
+            },
+        });
+        self.add("nV nv | np n np");
+    }
+}
+
+// reading an operand, the extension words and the read
+fn operand(ea: u16, long: bool) -> &'static str {
+    match (ea >> 3, ea & 7, long) {
+        (0, _, _) | (1, _, _) => "",
+        (2, _, false) | (3, _, false) => "nr",
+        (2, _, true) | (3, _, true) => "nR nr",
+        (4, _, false) => "n nr",
+        (4, _, true) => "n nR nr",
+        (5, _, false) | (7, 0, false) | (7, 2, false) => "np nr",
+        (5, _, true) | (7, 0, true) | (7, 2, true) => "np nR nr",
+        (6, _, false) | (7, 3, false) => "n np nr",
+        (6, _, true) | (7, 3, true) => "n np nR nr",
+        (7, 1, false) => "np np nr",
+        (7, 1, true) => "np np nR nr",
+        (7, 4, false) => "np",
+        (7, 4, true) => "np np",
+        _ => "",
+    }
+}
+
+// after an operand is read, writing it back
+fn modify(long: bool) -> &'static str {
+    if long { "np nw nW" } else { "np nw" }
+}
+
+fn condition(cc: u16) -> Condition {
+    match cc & 0xf {
+        0x0 => Condition::True,
+        0x1 => Condition::False,
+        0x2 => Condition::HI,
+        0x3 => Condition::LS,
+        0x4 => Condition::CC,
+        0x5 => Condition::CS,
+        0x6 => Condition::NE,
+        0x7 => Condition::EQ,
+        0x8 => Condition::VC,
+        0x9 => Condition::VS,
+        0xa => Condition::PL,
+        0xb => Condition::MI,
+        0xc => Condition::GE,
+        0xd => Condition::LT,
+        0xe => Condition::GT,
+        _ => Condition::LE,
+    }
+}
+
+impl<'a> M68k<'a> {
+    // the 8 bit bus 68008 runs the same microcode
+    pub(crate) fn exact(&self) -> bool {
+        self.cycle_exact && matches!(self.version, Version::MC68000 | Version::MC68008)
+    }
+
+    // whether the step placed its bus cycles, then it took step_clock
+    pub(crate) fn step_placed(&self) -> bool {
+        self.prefetch.placed
+    }
+
+    pub(crate) fn start_step(&mut self) {
+        self.prefetch.running = false;
+        self.prefetch.placed = false;
+    }
+
+    // The opcode, from IRD, and the plan of the step. A queue that doesn't
+    // hold the pc, after a reset or a debugger moving it, is filled first.
+    pub(crate) fn take_opcode<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<u16> {
+        self.prefetch.running = true;
+        self.prefetch.placed = true;
+        self.prefetch.plan.clear();
+        self.prefetch.fixed = 0;
+        if self.pc & 1 != 0 {
+            return Err(Exception::AddressError);
+        }
+        self.skip_taken();
+        if self.prefetch.queue.front().map(|&(addr, _)| addr) != Some(self.pc) {
+            self.fill_queue(bus)?;
+        }
+        let ir = self.prefetch.queue.pop_front().map_or(0, |(_, word)| word);
+        let irc = self.prefetch.queue.front().map_or(0, |&(_, word)| word);
+        self.pc = self.pc.wrapping_add(2);
+        self.trace_words(&[ir]);
+        let taken = self.condition(condition(ir >> 8));
+        let count = self.dar[(ir & 7) as usize] & 0xffff;
+        let pc = self.pc;
+        self.prefetch.build(ir, irc, pc, taken, count);
+        Ok(ir)
+    }
+
+    pub(crate) fn fill_queue<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<()> {
+        self.refill();
+        self.prefetch_word(bus)?;
+        self.prefetch_word(bus)
+    }
+
+    // an extension word, from the queue once the plan has fetched it
+    pub(crate) fn take_word<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<u16> {
+        self.skip_taken();
+        self.place(bus, Until::Word)?;
+        let pc = self.pc;
+        let word = match self.prefetch.queue.pop_front() {
+            Some((addr, word)) if addr == pc => word,
+            // not where the plan has it, fetched when it's needed
+            _ => {
+                self.refill();
+                let word = self.fetch(bus, pc)?;
+                self.prefetch.next = pc.wrapping_add(2);
+                word
+            },
+        };
+        self.pc = pc.wrapping_add(2);
+        self.trace_words(&[word]);
+        Ok(word)
+    }
+
+    pub(crate) fn placing(&self) -> bool {
+        self.prefetch.running
+    }
+
+    // a read or write of the handler, after what comes before it
+    pub(crate) fn place_access<T: Bus + ?Sized>(&mut self, bus: &mut T, size: u32) -> Result<()> {
+        self.place(bus, Until::Access)?;
+        if size == 4 {
+            self.place(bus, Until::Access)?;
+        }
+        Ok(())
+    }
+
+    // what is left once the handler is done, `count` the clocks it counted
+    pub(crate) fn finish_step<T: Bus + ?Sized>(&mut self, bus: &mut T, count: u32) -> Result<()> {
+        let result = self.place(bus, Until::End(count));
+        self.prefetch.running = false;
+        result
+    }
+
+    // the exception takes over the rest of the step
+    pub(crate) fn plan_exception(&mut self, e: &Exception, vector: u8) {
+        self.prefetch.running = true;
+        self.prefetch.placed = true;
+        self.prefetch.build_exception(e, vector);
+    }
+
+    // A read the 68000 makes and throws away. CLR, Scc and MOVE from SR
+    // read what they are about to write over and MOVEM to registers reads
+    // a word past the last one. Only while placing, where the bus is meant
+    // to see every cycle.
+    pub(crate) fn dummy_read<T: Bus + ?Sized>(&mut self, bus: &mut T, space: AddressSpace, addr: u32, size: u32) -> Result<()> {
+        if self.prefetch.running {
+            self.load(bus, space, addr, size)?;
+        }
+        Ok(())
+    }
+
+    fn place<T: Bus + ?Sized>(&mut self, bus: &mut T, until: Until) -> Result<()> {
+        loop {
+            if until == Until::Word && !self.prefetch.queue.is_empty() {
+                return Ok(());
+            }
+            let cycle = match self.prefetch.plan.front() {
+                Some(&cycle) => cycle,
+                None => return Ok(()),
+            };
+            match (cycle, until) {
+                (Cycle::Access, Until::Access) => {
+                    self.prefetch.plan.pop_front();
+                    return Ok(());
+                },
+                (Cycle::Access, Until::Word) | (Cycle::Rest, Until::Access) | (Cycle::Rest, Until::Word) => return Ok(()),
+                _ => (),
+            }
+            self.prefetch.plan.pop_front();
+            match cycle {
+                Cycle::Internal => self.step_clock += 2,
+                Cycle::Rest => if let Until::End(count) = until {
+                    self.step_clock += count.saturating_sub(self.prefetch.fixed);
+                },
+                Cycle::Prefetch => self.prefetch_word(bus)?,
+                Cycle::Refill => self.refill(),
+                Cycle::Fetch(addr) => {
+                    self.fetch(bus, addr)?;
+                },
+                // planned but the handler didn't make it
+                Cycle::Access => self.step_clock += 4,
+            }
+        }
+    }
+
+    // words the pc has moved past, they weren't needed after all
+    fn skip_taken(&mut self) {
+        while let Some(&(addr, _)) = self.prefetch.queue.front() {
+            let behind = self.pc.wrapping_sub(addr);
+            if behind == 0 || behind >= 0x8000_0000 {
+                break;
+            }
+            self.prefetch.queue.pop_front();
+        }
+    }
+
+    fn refill(&mut self) {
+        self.prefetch.queue.clear();
+        self.prefetch.next = self.pc;
+    }
+
+    fn prefetch_word<T: Bus + ?Sized>(&mut self, bus: &mut T) -> Result<()> {
+        let addr = self.prefetch.next;
+        let word = self.fetch(bus, addr)?;
+        self.prefetch.queue.push_back((addr, word));
+        self.prefetch.next = addr.wrapping_add(2);
+        Ok(())
+    }
+
+    fn fetch<T: Bus + ?Sized>(&mut self, bus: &mut T, addr: u32) -> Result<u16> {
+        if addr & 1 != 0 {
+            return Err(Exception::AddressError);
+        }
+        let space = self.program_space();
+        let word = self.bus_read(bus, space, addr, 2)? as u16;
+        if let Some((ref mut record, _)) = self.trace {
+            record.accesses.push(Access { space, addr, size: 2, write: false, value: word as u32 });
+        }
+        Ok(word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory::MemoryMap;
+    use tests::small_map;
+    use SUPERVISOR_DATA;
+
+    // the bus cycles as the core asks for wait states, clock and address,
+    // with `slow` held off at one address
+    struct Timed {
+        map: MemoryMap,
+        cycles: Vec<(u64, u32, bool)>,
+        slow: Option<(u32, u32)>,
+    }
+
+    impl Bus for Timed {
+        fn read_8(&mut self, space: AddressSpace, addr: u32) -> u8 { self.map.read_8(space, addr) }
+        fn read_16(&mut self, space: AddressSpace, addr: u32) -> u16 { self.map.read_16(space, addr) }
+        fn read_32(&mut self, space: AddressSpace, addr: u32) -> u32 { self.map.read_32(space, addr) }
+        fn write_8(&mut self, space: AddressSpace, addr: u32, value: u8) { self.map.write_8(space, addr, value) }
+        fn write_16(&mut self, space: AddressSpace, addr: u32, value: u16) { self.map.write_16(space, addr, value) }
+        fn write_32(&mut self, space: AddressSpace, addr: u32, value: u32) { self.map.write_32(space, addr, value) }
+        fn peek_8(&self, space: AddressSpace, addr: u32) -> u8 { self.map.peek_8(space, addr) }
+        fn poke_8(&mut self, space: AddressSpace, addr: u32, value: u8) { self.map.poke_8(space, addr, value) }
+        fn bus_error(&mut self, space: AddressSpace, addr: u32, size: u32, write: bool) -> bool {
+            self.map.bus_error(space, addr, size, write)
+        }
+        fn wait_states(&mut self, _space: AddressSpace, addr: u32, write: bool, clock: u64) -> u32 {
+            self.cycles.push((clock, addr, write));
+            match self.slow {
+                Some((slow, wait)) if slow == addr => wait,
+                _ => 0,
+            }
+        }
+    }
+
+    fn exact<'a>(code: &[u16], slow: Option<(u32, u32)>) -> (M68k<'a>, Timed) {
+        let mut bus = Timed { map: small_map(0x1000, code), cycles: Vec::new(), slow };
+        let mut core = M68k::new(Version::MC68000);
+        core.cycle_exact = true;
+        core.reset(&mut bus);
+        core.dar[8] = 0x2000;
+        bus.cycles.clear();
+        (core, bus)
+    }
+
+    #[test]
+    fn bus_cycles_fall_where_the_microcode_has_them() {
+        // move.w (8,a0),d1: the displacement is already in IRC, the
+        // prefetch that makes up for it comes before the read
+        let (mut core, mut bus) = exact(&[0x3228, 0x0008, 0x4e71, 0x4e71], None);
+        assert_eq!(core.step(&mut bus), 12);
+        assert_eq!(bus.cycles, vec![(0, 0x404, false), (4, 0x2008, false), (8, 0x406, false)]);
+
+        // nop, one prefetch
+        bus.cycles.clear();
+        assert_eq!(core.step(&mut bus), 4);
+        assert_eq!(bus.cycles, vec![(12, 0x408, false)]);
+    }
+
+    #[test]
+    fn wait_states_hold_off_the_cycles_after() {
+        // add.w d0,(a0): read, prefetch, write
+        let (mut core, mut bus) = exact(&[0xd150, 0x4e71], Some((0x2000, 2)));
+        assert_eq!(core.step(&mut bus), 16);
+        assert_eq!(bus.cycles, vec![(0, 0x2000, false), (6, 0x404, false), (10, 0x2000, true)]);
+        assert_eq!(core.clock, 16);
+    }
+
+    #[test]
+    fn exceptions_write_the_68000_frame_in_its_order() {
+        // trap #0, the low word of the pc goes first
+        let (mut core, mut bus) = exact(&[0x4e40], None);
+        bus.map.poke_32(SUPERVISOR_DATA, 0x80, 0x600);
+        assert_eq!(core.step(&mut bus), 34);
+        let cycles: Vec<(u64, u32)> = bus.cycles.iter().map(|&(clock, addr, _)| (clock, addr)).collect();
+        assert_eq!(cycles, vec![(4, 0xffe), (8, 0xffa), (12, 0xffc), (16, 0x80), (20, 0x82), (24, 0x600), (30, 0x602)]);
+        assert_eq!(bus.map.peek_32(SUPERVISOR_DATA, 0xffc), 0x402);
+        assert_eq!(core.pc, 0x600);
+    }
+
+    #[test]
+    fn placed_steps_take_the_handlers_clocks_without_wait_states() {
+        // every 68000 instruction that doesn't trap, the placed bus cycles
+        // and internal clocks add up to what the handler counts
+        let code = [0, 0x0000, 0x3000, 0x0000, 0x3000, 0x0000, 0x3000];
+        let mut cores = [M68k::new(Version::MC68000), M68k::new(Version::MC68000)];
+        cores[1].cycle_exact = true;
+        let mut wrong = Vec::new();
+        for ir in (0..0x10000u32).filter(|ir| ir >> 12 != 0xa && ir >> 12 != 0xf) {
+            let mut code = code;
+            code[0] = ir as u16;
+            let mut steps = [(0, 0); 2];
+            for (core, step) in cores.iter_mut().zip(steps.iter_mut()) {
+                let mut bus = Timed { map: small_map(0x1000, &code), cycles: Vec::new(), slow: None };
+                for vector in 2..64 {
+                    bus.map.poke_32(SUPERVISOR_DATA, vector * 4, 0x7000);
+                }
+                core.reset(&mut bus);
+                core.sr_to_flags(0x2700);
+                for (i, reg) in core.dar.iter_mut().enumerate().take(15) {
+                    *reg = 0x2000 + 0x100 * i as u32;
+                }
+                *step = (core.step(&mut bus), core.pc);
+            }
+            if steps.iter().all(|&(_, pc)| pc != 0x7000) && steps[0].0 != steps[1].0 {
+                wrong.push((ir, steps[0].0, steps[1].0));
+            }
+        }
+        assert!(wrong.is_empty(), "{} differ, opcode, handler, placed {:04x?}", wrong.len(), &wrong[..wrong.len().min(40)]);
+    }
+}
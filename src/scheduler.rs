@@ -0,0 +1,144 @@
+// Event scheduler
+//
+// Devices register callbacks that are due at a CPU clock. `run` steps the
+// core up to the next event, fires everything that is due and carries on
+// until the clock asked for. A callback returns how long until it wants to
+// run again, or None to drop out of the queue.
+//
+// Devices that don't run off the CPU clock give their delays in their own
+// ticks along with a divider. Due times are worked out from the absolute
+// tick count, so a periodic event doesn't drift when the ratio isn't a
+// whole number of CPU clocks.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use Bus;
+use M68k;
+
+// one device tick is `cpu` CPU clocks per `device` ticks, a device at a
+// tenth of the CPU clock is ClockDivider::new(10, 1)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClockDivider {
+    pub cpu: u64,
+    pub device: u64,
+}
+
+impl ClockDivider {
+    pub const CPU: ClockDivider = ClockDivider { cpu: 1, device: 1 };
+
+    pub fn new(cpu: u64, device: u64) -> Self {
+        assert!(cpu != 0 && device != 0, "clock divider can't be zero");
+        ClockDivider { cpu, device }
+    }
+
+    // the CPU clock at which device tick `ticks` starts, rounded up
+    pub fn to_cpu(&self, ticks: u64) -> u64 {
+        ((ticks as u128 * self.cpu as u128).div_ceil(self.device as u128)) as u64
+    }
+
+    // device ticks that have started by CPU clock `clock`
+    pub fn to_device(&self, clock: u64) -> u64 {
+        (clock as u128 * self.device as u128 / self.cpu as u128) as u64
+    }
+}
+
+pub type EventId = u64;
+
+// called with the core, the bus and the clock the event was due, which can
+// be before the core's clock as instructions aren't split
+pub type Callback<'a, T> = Box<dyn FnMut(&mut M68k<'a>, &mut T, u64) -> Option<u64> + 'a>;
+
+struct Event<'a, T> {
+    divider: ClockDivider,
+    tick: u64,      // device tick it is due on
+    callback: Callback<'a, T>,
+}
+
+pub struct Scheduler<'a, T> {
+    queue: BinaryHeap<Reverse<(u64, EventId)>>,
+    events: HashMap<EventId, Event<'a, T>>,
+    next_id: EventId,
+}
+
+impl<'a, T: Bus + 'a> Scheduler<'a, T> {
+    pub fn new() -> Self {
+        Scheduler {
+            queue: BinaryHeap::new(),
+            events: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    // `callback` runs at CPU clock `clock`
+    pub fn schedule_at(&mut self, clock: u64, callback: Callback<'a, T>) -> EventId {
+        self.schedule_ticks(ClockDivider::CPU, clock, callback)
+    }
+
+    // `callback` runs `delay` ticks of `divider` after CPU clock `now`
+    pub fn schedule_in(&mut self, divider: ClockDivider, now: u64, delay: u64, callback: Callback<'a, T>) -> EventId {
+        // a device tick already under way counts as the first one
+        let tick = divider.to_device(now) + delay;
+        self.schedule_ticks(divider, tick, callback)
+    }
+
+    fn schedule_ticks(&mut self, divider: ClockDivider, tick: u64, callback: Callback<'a, T>) -> EventId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.queue.push(Reverse((divider.to_cpu(tick), id)));
+        self.events.insert(id, Event { divider, tick, callback });
+        id
+    }
+
+    pub fn cancel(&mut self, id: EventId) -> bool {
+        // the queue entry is skipped once it comes up
+        self.events.remove(&id).is_some()
+    }
+
+    // CPU clock the next event is due
+    pub fn next_due(&mut self) -> Option<u64> {
+        while let Some(&Reverse((due, id))) = self.queue.peek() {
+            if self.events.contains_key(&id) {
+                return Some(due);
+            }
+            self.queue.pop();
+        }
+        None
+    }
+
+    // fires everything due by the core's clock, events that reschedule
+    // within the same clock fire again
+    pub fn fire_due(&mut self, core: &mut M68k<'a>, bus: &mut T) {
+        while let Some(due) = self.next_due() {
+            if due > core.clock {
+                break;
+            }
+            let Reverse((_, id)) = self.queue.pop().unwrap();
+            let mut event = self.events.remove(&id).unwrap();
+            if let Some(delay) = (event.callback)(core, bus, due) {
+                event.tick += delay.max(1);
+                self.queue.push(Reverse((event.divider.to_cpu(event.tick), id)));
+                self.events.insert(id, event);
+            }
+        }
+    }
+
+    // Runs the core and the events until CPU clock `until`, stopping the
+    // core on each event, or early on a watchpoint hit. Returns the clocks
+    // run.
+    pub fn run(&mut self, core: &mut M68k<'a>, bus: &mut T, until: u64) -> u64 {
+        let start = core.clock;
+        self.fire_due(core, bus);
+        while core.clock < until && core.watch_hit.is_none() {
+            let stop = self.next_due().map_or(until, |due| due.min(until));
+            core.run_until(bus, stop);
+            self.fire_due(core, bus);
+        }
+        core.clock - start
+    }
+}
+
+impl<'a, T: Bus + 'a> Default for Scheduler<'a, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,224 @@
+// MC68020 instruction timing (ref MC68020UM section 8)
+//
+// The '020 overlaps instructions, so there's no one number per opcode.
+// The manual gives best, cache and worst cases. The cache case is every
+// instruction word coming from the cache and each data access taking a
+// 3 clock bus cycle. We start from the cache case, add a bus cycle for
+// every instruction fetch that had to go out to memory and let the head
+// of an instruction overlap the tail of the one before it.
+//
+// Instructions without a number here keep what their handler returns.
+
+pub const BUS_CYCLE: u32 = 3;
+
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Timing020 {
+    pub tail: u32,      // clocks the last instruction leaves for the next head
+    // counted over the current instruction
+    pub reads: u32,     // operand reads
+    pub writes: u32,    // operand writes
+    pub fetches: u32,   // instruction fetches that missed the cache
+}
+
+impl Timing020 {
+    pub fn start(&mut self) {
+        self.reads = 0;
+        self.writes = 0;
+        self.fetches = 0;
+    }
+
+    // the instruction in `ir` completed, `ppc` and `pc` tell whether a
+    // branch was taken
+    pub fn cycles(&mut self, ir: u16, ppc: u32, pc: u32, handler: u32) -> u32 {
+        let (cycles, flow) = match cache_case(ir, self.reads + self.writes, ppc, pc) {
+            Some(timing) => timing,
+            None => {
+                self.tail = 0;
+                return handler;
+            },
+        };
+        // register only instructions can run in the shadow of a write
+        // that is still finishing, a change of flow empties the pipe
+        let head = if self.reads + self.writes == 0 && !flow { cycles.min(2) } else { 0 };
+        let overlap = head.min(self.tail);
+        self.tail = if flow || self.writes == 0 { 0 } else { BUS_CYCLE.min(cycles) };
+        cycles + self.fetches * BUS_CYCLE - overlap
+    }
+}
+
+// fetch effective address, cache case, for an operand that is read
+pub fn fea(mode: u16, reg: u16, long: bool) -> u32 {
+    match (mode, reg) {
+        (0, _) | (1, _) => 0,
+        (2, _) | (3, _) => 4,
+        (4, _) | (5, _) => 5,
+        (6, _) => 7,
+        (7, 0) | (7, 1) => 4,   // (xxx).W, (xxx).L
+        (7, 2) => 5,            // (d16,PC)
+        (7, 3) => 7,            // (d8,PC,Xn)
+        (7, 4) => if long { 4 } else { 2 },
+        _ => 0,
+    }
+}
+
+// calculate effective address, cache case, when only the address is used
+pub fn cea(mode: u16, reg: u16) -> u32 {
+    match (mode, reg) {
+        (5, _) | (7, 0) | (7, 2) => 1,
+        (6, _) | (7, 3) => 3,
+        (7, 1) => 2,
+        _ => 0,
+    }
+}
+
+// cache case clocks and whether the flow of instructions changed. `n` is
+// the number of operand accesses, for MOVEM and MOVEP.
+fn cache_case(ir: u16, n: u32, ppc: u32, pc: u32) -> Option<(u32, bool)> {
+    let mode = (ir >> 3) & 7;
+    let reg = ir & 7;
+    let memory = mode >= 2 && !(mode == 7 && reg == 4);
+    let long = (ir >> 6) & 3 == 2;
+    let ea = fea(mode, reg, long);
+    let timing = match ir >> 12 {
+        0x0 => {
+            if ir & 0x0138 == 0x0108 {
+                4 + n * BUS_CYCLE                                   // MOVEP
+            } else if ir & 0x0100 != 0 || ir & 0x0f00 == 0x0800 {
+                let imm = if ir & 0x0100 == 0 { 2 } else { 0 };
+                let op = if ir & 0xc0 == 0 { 4 } else { 6 };        // BTST or the others
+                op + imm + ea
+            } else if ir & 0x00bf == 0x003c {
+                12                                                  // to CCR or SR
+            } else if (ir >> 6) & 3 == 3 {
+                16 + ea                                             // CAS, CHK2, CMP2
+            } else {
+                let imm = if long { 4 } else { 2 };
+                imm + if memory { 4 + ea } else { 2 }
+            }
+        },
+        0x1..=0x3 => {
+            let src = fea(mode, reg, ir >> 12 == 2);
+            let dst = match ((ir >> 6) & 7, (ir >> 9) & 7) {
+                (0, _) | (1, _) => 2,
+                (2, _) | (3, _) => 4,
+                (4, _) | (5, _) => 5,
+                (6, _) => 7,
+                (_, 0) => 5,
+                _ => 6,
+            };
+            src + dst
+        },
+        0x4 => return misc(ir, n, mode, reg, memory, ea),
+        0x5 => {
+            if ir & 0xf8 == 0xc8 {
+                // DBcc, taken, expired or condition true
+                if pc != ppc.wrapping_add(4) {
+                    return Some((6, true));
+                }
+                if (ir >> 8) & 0xf == 1 { 10 } else { 4 }
+            } else if ir & 0xc0 == 0xc0 {
+                if mode == 7 && reg >= 2 { 4 }                      // TRAPcc
+                else if memory { 6 + cea(mode, reg) } else { 4 }   // Scc
+            } else if memory {
+                4 + ea                                              // ADDQ, SUBQ
+            } else {
+                2
+            }
+        },
+        0x6 => {
+            let fallthrough = ppc.wrapping_add(match ir & 0xff { 0 => 4, 0xff => 6, _ => 2 });
+            if (ir >> 8) & 0xf == 1 {
+                return Some((7, true));                             // BSR
+            }
+            if pc != fallthrough {
+                return Some((6, true));
+            }
+            4
+        },
+        0x7 => 2,
+        0x8 | 0x9 | 0xb | 0xc | 0xd => arithmetic(ir, mode, memory, ea),
+        0xe => {
+            if ir & 0xf8c0 == 0xe8c0 {
+                if memory { 13 + cea(mode, reg) } else { 8 }         // bit fields
+            } else if ir & 0xc0 == 0xc0 {
+                5 + ea                                              // memory shifts
+            } else {
+                match ((ir >> 3) & 3, ir & 0x0100 != 0) {
+                    (0, true) => 8,                                 // ASL
+                    (0, false) => 6,                                // ASR
+                    (1, _) => if ir & 0x20 != 0 { 6 } else { 4 },   // LSd
+                    (2, _) => 12,                                   // ROXd
+                    _ => 8,                                         // ROd
+                }
+            }
+        },
+        _ => return None,
+    };
+    Some((timing, false))
+}
+
+fn misc(ir: u16, n: u32, mode: u16, reg: u16, memory: bool, ea: u32) -> Option<(u32, bool)> {
+    let timing = match ir {
+        0x4e70 => 518,                                              // RESET
+        0x4e71 => 2,                                                // NOP
+        0x4e72 => 8,                                                // STOP
+        0x4e73 => return Some((20, true)),                          // RTE
+        0x4e74 | 0x4e75 => return Some((10, true)),                 // RTD, RTS
+        0x4e76 => 4,                                                // TRAPV
+        0x4e77 => return Some((14, true)),                          // RTR
+        0x4e7a => 6,                                                // MOVEC Rc,Rn
+        0x4e7b => 9,                                                // MOVEC Rn,Rc
+        _ if ir & 0xfff8 == 0x4e50 => 5,                            // LINK.W
+        _ if ir & 0xfff8 == 0x4808 => 6,                            // LINK.L
+        _ if ir & 0xfff8 == 0x4e58 => 6,                            // UNLK
+        _ if ir & 0xfff0 == 0x4e60 => 3,                            // MOVE USP
+        _ if ir & 0xffc0 == 0x4ec0 => return Some((4 + cea(mode, reg), true)),     // JMP
+        _ if ir & 0xffc0 == 0x4e80 => return Some((5 + cea(mode, reg), true)),     // JSR
+        _ if ir & 0xf1c0 == 0x41c0 => 2 + cea(mode, reg),          // LEA
+        _ if ir & 0xf140 == 0x4100 => 8 + ea,                       // CHK
+        _ if ir & 0xfff8 == 0x4840 => 4,                            // SWAP
+        _ if ir & 0xfff8 == 0x4848 => return None,                  // BKPT
+        _ if ir & 0xffc0 == 0x4840 => 5 + cea(mode, reg),          // PEA
+        _ if ir & 0xfeb8 == 0x4880 => 4,                            // EXT, EXTB
+        _ if ir & 0xfb80 == 0x4880 => {
+            // MOVEM, registers to memory or back
+            let per_register = if ir & 0x0400 == 0 { 3 } else { 4 };
+            let base = if ir & 0x0400 == 0 { 4 } else { 8 };
+            base + cea(mode, reg) + n * per_register
+        },
+        _ if ir & 0xffc0 == 0x4c00 => 43 + ea,                      // MULx.L
+        _ if ir & 0xffc0 == 0x4c40 => 90 + ea,                      // DIVx.L
+        _ if ir & 0xffc0 == 0x40c0 => if memory { 5 + cea(mode, reg) } else { 2 },     // MOVE from SR
+        _ if ir & 0xffc0 == 0x42c0 => if memory { 5 + cea(mode, reg) } else { 2 },     // MOVE from CCR
+        _ if ir & 0xffc0 == 0x44c0 => 4 + ea,                       // MOVE to CCR
+        _ if ir & 0xffc0 == 0x46c0 => 8 + ea,                       // MOVE to SR
+        _ if ir & 0xffc0 == 0x4800 => if memory { 8 + ea } else { 6 },      // NBCD
+        _ if ir == 0x4afa || ir == 0x4afc => return None,           // BGND, ILLEGAL
+        _ if ir & 0xffc0 == 0x4ac0 => if memory { 12 + ea } else { 4 },     // TAS
+        _ if ir & 0xff00 == 0x4a00 => 2 + ea,                       // TST
+        _ if ir & 0xf900 == 0x4000 => if memory { 4 + ea } else { 2 },      // NEGX, CLR, NEG, NOT
+        _ => return None,
+    };
+    Some((timing, false))
+}
+
+fn arithmetic(ir: u16, mode: u16, memory: bool, ea: u32) -> u32 {
+    let op = ir >> 12;
+    let opmode = (ir >> 6) & 7;
+    let register_pair = ir & 0x30 == 0;     // the Rx,Ry and -(Ay),-(Ax) forms
+    match (op, opmode) {
+        (0x8, 3) => 44 + ea,                                        // DIVU.W
+        (0x8, 7) => 56 + ea,                                        // DIVS.W
+        (0xc, 3) => 27 + ea,                                        // MULU.W
+        (0xc, 7) => 28 + ea,                                        // MULS.W
+        (0x8, 4) | (0xc, 4) if register_pair => if mode & 1 != 0 { 16 } else { 4 },    // SBCD, ABCD
+        (0x8, 5) | (0x8, 6) if register_pair => if mode & 1 != 0 { 13 } else { 6 },    // PACK, UNPK
+        (0xc, 5) | (0xc, 6) if register_pair => 2,                  // EXG
+        (0x9, 4..=6) | (0xd, 4..=6) if register_pair => if mode & 1 != 0 { 10 } else { 2 },     // SUBX, ADDX
+        (0xb, 4..=6) if mode == 1 => 10,                            // CMPM
+        (_, 3) | (_, 7) => 2 + fea(mode, ir & 7, opmode == 7),      // ADDA, SUBA, CMPA
+        (0xb, 4..=6) => if memory { 4 + ea } else { 2 },            // EOR
+        (_, 0..=2) => 2 + ea,
+        _ => 4 + ea,
+    }
+}
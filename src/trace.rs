@@ -0,0 +1,330 @@
+// Instruction trace
+//
+// With a tracer on `M68k::tracer` every step hands it a Record of what it
+// did: the address of the instruction, the opcode and extension words as
+// they were fetched, the disassembly, the registers that changed, the
+// memory accesses made and the clocks charged. An interrupt or exception
+// taken between instructions gets a record with no words, steps that only
+// wait, stopped or halted, don't get one.
+//
+// Accesses are by logical address as the core made them, instruction
+// fetches and exception frames included. What a cache answers is there on
+// the '040, not on the '020 whose instruction cache sits in front.
+//
+// TraceWriter puts records out as text, a line each, or in a compact
+// binary form that Reader turns back into Records, so two runs can be
+// compared to find the first instruction where they part ways.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use disasm::disassemble_words;
+use AddressSpace;
+use M68k;
+use Version;
+use {SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA, USER_PROGRAM};
+
+// the registers a Change can be to, in the order of the index
+pub const REGISTERS: [&str; 21] = ["d0", "d1", "d2", "d3", "d4", "d5", "d6", "d7",
+                                   "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7",
+                                   "usp", "isp", "msp", "vbr", "sr"];
+const SR: usize = 20;
+
+// SR bits named when they flip
+const SR_BITS: [(u16, &str); 9] = [(15, "T1"), (14, "T0"), (13, "S"), (12, "M"),
+                                   (4, "X"), (3, "N"), (2, "Z"), (1, "V"), (0, "C")];
+
+const MAGIC: &[u8; 8] = b"M68KTRC\x01";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub reg: usize,     // into REGISTERS
+    pub value: u32,     // after the step
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub space: AddressSpace,
+    pub addr: u32,
+    pub size: u32,
+    pub write: bool,
+    pub value: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Record {
+    pub pc: u32,
+    pub sr: u16,                // before the step
+    pub words: Vec<u16>,        // opcode then extension words, as fetched
+    pub disassembly: String,
+    pub changes: Vec<Change>,
+    pub accesses: Vec<Access>,
+    pub cycles: u32,
+}
+
+// A line of `pc words disassembly changes | accesses | cycles`, flipped SR
+// bits follow the new SR with a + or - and accesses are r or w, the
+// function code and the size, like `w5.l 00007ffc=00000406`.
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let words: Vec<String> = self.words.iter().map(|w| format!("{:04x}", w)).collect();
+        write!(f, "{:08x}  {:<24} {:<30}", self.pc, words.join(" "), self.disassembly)?;
+        for change in self.changes.iter() {
+            if change.reg != SR {
+                write!(f, " {}={:08x}", REGISTERS[change.reg], change.value)?;
+            } else {
+                write!(f, " sr={:04x}", change.value)?;
+                let new = change.value as u16;
+                let flipped: String = SR_BITS.iter()
+                    .filter(|&&(bit, _)| (self.sr ^ new) & 1 << bit != 0)
+                    .map(|&(bit, name)| format!("{}{}", if new & 1 << bit != 0 { '+' } else { '-' }, name))
+                    .collect();
+                if !flipped.is_empty() {
+                    write!(f, "({})", flipped)?;
+                }
+            }
+        }
+        write!(f, " |")?;
+        for access in self.accesses.iter() {
+            let size = match access.size { 1 => 'b', 2 => 'w', _ => 'l' };
+            write!(f, " {}{}.{} {:08x}={:0width$x}", if access.write { 'w' } else { 'r' }, access.space.fc(),
+                   size, access.addr, access.value, width = access.size as usize * 2)?;
+        }
+        write!(f, " | {}", self.cycles)
+    }
+}
+
+// gets a record for every step of the core it's set on
+pub trait Trace {
+    fn record(&mut self, record: &Record);
+}
+
+impl Trace for Vec<Record> {
+    fn record(&mut self, record: &Record) {
+        self.push(record.clone());
+    }
+}
+
+impl<T: Trace + ?Sized> Trace for &mut T {
+    fn record(&mut self, record: &Record) {
+        (**self).record(record);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Binary,
+}
+
+pub struct TraceWriter<W: Write> {
+    out: W,
+    format: Format,
+    pub error: Option<io::Error>,   // the first write that failed, nothing is written after it
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut out: W, format: Format) -> io::Result<Self> {
+        if format == Format::Binary {
+            out.write_all(MAGIC)?;
+        }
+        Ok(TraceWriter { out, format, error: None })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    // Big endian, pc, SR before, cycles, then counted lists of the words,
+    // the changes as index and value, and the accesses as a byte of
+    // function code, write in bit 3 and size from bit 4, the address and
+    // the value in as many bytes as the access. The disassembly is left
+    // out, Reader does it again from the words.
+    fn binary(&mut self, record: &Record) -> io::Result<()> {
+        let mut data = Vec::with_capacity(16 + record.words.len() * 2 + record.accesses.len() * 9);
+        data.extend_from_slice(&record.pc.to_be_bytes());
+        data.extend_from_slice(&record.sr.to_be_bytes());
+        data.extend_from_slice(&record.cycles.to_be_bytes());
+        data.push(record.words.len() as u8);
+        for word in record.words.iter() {
+            data.extend_from_slice(&word.to_be_bytes());
+        }
+        data.push(record.changes.len() as u8);
+        for change in record.changes.iter() {
+            data.push(change.reg as u8);
+            data.extend_from_slice(&change.value.to_be_bytes());
+        }
+        let accesses = &record.accesses[..record.accesses.len().min(0xffff)];
+        data.extend_from_slice(&(accesses.len() as u16).to_be_bytes());
+        for access in accesses.iter() {
+            let size = access.size.min(4);
+            data.push((access.space.fc() | (access.write as u32) << 3 | size << 4) as u8);
+            data.extend_from_slice(&access.addr.to_be_bytes());
+            data.extend_from_slice(&access.value.to_be_bytes()[4 - size as usize..]);
+        }
+        self.out.write_all(&data)
+    }
+}
+
+impl<W: Write> Trace for TraceWriter<W> {
+    fn record(&mut self, record: &Record) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = match self.format {
+            Format::Text => writeln!(self.out, "{}", record),
+            Format::Binary => self.binary(record),
+        };
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+}
+
+// the records of a binary trace, disassembled for `version`
+pub struct Reader<R: Read> {
+    input: R,
+    version: Version,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut input: R, version: Version) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a binary trace"));
+        }
+        Ok(Reader { input, version })
+    }
+
+    fn bytes(&mut self, len: usize) -> io::Result<u32> {
+        let mut data = [0u8; 4];
+        self.input.read_exact(&mut data[4 - len..])?;
+        Ok(u32::from_be_bytes(data))
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<Record>> {
+        // a clean end is only allowed between records
+        let mut first = [0u8; 1];
+        if self.input.read(&mut first)? == 0 {
+            return Ok(None);
+        }
+        let pc = (first[0] as u32) << 24 | self.bytes(3)?;
+        let mut record = Record { pc, sr: self.bytes(2)? as u16, cycles: self.bytes(4)?, ..Record::default() };
+        for _ in 0..self.bytes(1)? {
+            let word = self.bytes(2)? as u16;
+            record.words.push(word);
+        }
+        for _ in 0..self.bytes(1)? {
+            let reg = self.bytes(1)? as usize;
+            if reg >= REGISTERS.len() {
+                return Err(invalid("bad register in trace"));
+            }
+            record.changes.push(Change { reg, value: self.bytes(4)? });
+        }
+        for _ in 0..self.bytes(2)? {
+            let flags = self.bytes(1)?;
+            let space = match flags & 7 {
+                1 => USER_DATA,
+                2 => USER_PROGRAM,
+                5 => SUPERVISOR_DATA,
+                6 => SUPERVISOR_PROGRAM,
+                _ => return Err(invalid("bad function code in trace")),
+            };
+            let size = flags >> 4;
+            if size == 0 || size > 4 {
+                return Err(invalid("bad access size in trace"));
+            }
+            let addr = self.bytes(4)?;
+            let value = self.bytes(size as usize)?;
+            record.accesses.push(Access { space, addr, size, write: flags & 8 != 0, value });
+        }
+        if !record.words.is_empty() {
+            record.disassembly = disassemble_words(self.version, record.pc, &record.words).to_string();
+        }
+        Ok(Some(record))
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<io::Result<Record>> {
+        self.next_record().transpose()
+    }
+}
+
+impl<'a> M68k<'a> {
+    fn trace_registers(&self) -> [u32; 21] {
+        let sp = self.dar[15];
+        let supervisor = self.s != 0;
+        let mut regs = [0u32; 21];
+        regs[..16].copy_from_slice(&self.dar);
+        regs[16] = if supervisor { self.inactive_usp } else { sp };
+        regs[17] = if supervisor && self.m == 0 { sp } else { self.inactive_isp };
+        regs[18] = if supervisor && self.m != 0 { sp } else { self.inactive_msp };
+        regs[19] = self.vbr;
+        regs[SR] = self.status_register() as u32;
+        regs
+    }
+
+    // step calls these around an instruction when there is a tracer
+    pub fn trace_start(&mut self) {
+        let record = Record { pc: self.pc, sr: self.status_register(), ..Record::default() };
+        self.trace = Some((record, self.trace_registers()));
+    }
+
+    pub fn trace_end(&mut self, cycles: u32) {
+        let (mut record, before) = match self.trace.take() {
+            Some(trace) => trace,
+            None => return,
+        };
+        let after = self.trace_registers();
+        record.changes = (0..after.len()).filter(|&reg| before[reg] != after[reg])
+            .map(|reg| Change { reg, value: after[reg] }).collect();
+        if record.words.is_empty() && record.accesses.is_empty() && record.changes.is_empty() {
+            return;
+        }
+        if !record.words.is_empty() {
+            record.pc = self.ppc;
+            record.disassembly = disassemble_words(self.version, record.pc, &record.words).to_string();
+        }
+        record.cycles = cycles;
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.record(&record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::small_map;
+
+    #[test]
+    fn immediates_are_in_the_words() {
+        // mulu.l #3,d0 then a binary round trip
+        let mut bus = small_map(0x8000, &[0x4c3c, 0x0000, 0x0000, 0x0003]);
+        let mut records: Vec<Record> = Vec::new();
+        {
+            let mut cpu = M68k::new(Version::MC68020);
+            cpu.reset(&mut bus);
+            cpu.dar[0] = 5;
+            cpu.tracer = Some(Box::new(&mut records));
+            cpu.step(&mut bus);
+        }
+        let record = &records[0];
+        assert_eq!(record.words, [0x4c3c, 0x0000, 0x0000, 0x0003]);
+        assert_eq!(record.changes, [Change { reg: 0, value: 15 }]);
+        assert!(record.accesses.iter().all(|a| a.space == SUPERVISOR_PROGRAM));
+        assert_eq!(record.disassembly, "mulu.l  #$3,d0");
+        let mut writer = TraceWriter::new(Vec::new(), Format::Binary).unwrap();
+        writer.record(record);
+        let data = writer.into_inner();
+        let read: Vec<Record> = Reader::new(&data[..], Version::MC68020).unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(read, records);
+    }
+}
@@ -0,0 +1,212 @@
+// Value Change Dump of the bus
+//
+// Wraps a Bus and writes every access the core makes to a VCD file that
+// GTKWave and friends can open next to a logic analyser capture. Each
+// access shows up as address, data, function code, R/W, size and a
+// strobe, along with the IPL lines and the processing state, which are
+// sampled around each step.
+//
+// In cycle exact mode accesses are placed on the clock the core starts them,
+// which is as close as its bus timing gets (see M68k::bus_cycles).
+// Otherwise they're spread out one 4 clock bus cycle apart from the start
+// of the step, close enough to line up with a capture. Peeks and pokes
+// aren't recorded.
+
+use std::io::{self, Write};
+use AddressSpace;
+use Bus;
+use M68k;
+use ProcessingState;
+
+// identifiers of the signals in the dump
+const ADDR: &str = "!";
+const DATA: &str = "\"";
+const FC: &str = "#";
+const RW: &str = "$";
+const SIZE: &str = "%";
+const STROBE: &str = "&";
+const IPL: &str = "'";
+const STATE: &str = "(";
+
+pub struct VcdBus<B, W: Write> {
+    pub bus: B,
+    out: W,
+    period: u64,            // time units, nanoseconds, per clock
+    time: u64,              // clock the next access starts on
+    cycles: Option<(u64, u64)>, // first and end clock of the bus cycles, in cycle exact mode
+    strobe_off: Option<u64>,
+    written: Option<u64>,   // clock of the last change written, the dump can't go back
+    ipl: Option<u8>,
+    state: Option<ProcessingState>,
+    pub error: Option<io::Error>,   // the first write that failed, nothing is written after it
+}
+
+impl<B: Bus, W: Write> VcdBus<B, W> {
+    // `period` is the length of a CPU clock in nanoseconds
+    pub fn new(bus: B, mut out: W, period: u64) -> io::Result<Self> {
+        writeln!(out, "$timescale 1ns $end")?;
+        writeln!(out, "$scope module m68k $end")?;
+        for &(width, id, name) in [(32, ADDR, "addr"), (32, DATA, "data"), (3, FC, "fc"), (1, RW, "rw"),
+                                   (3, SIZE, "size"), (1, STROBE, "as"), (3, IPL, "ipl"), (2, STATE, "state")].iter() {
+            writeln!(out, "$var wire {} {} {} $end", width, id, name)?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+        Ok(VcdBus {
+            bus,
+            out,
+            period,
+            time: 0,
+            cycles: None,
+            strobe_off: None,
+            written: None,
+            ipl: None,
+            state: None,
+            error: None,
+        })
+    }
+
+    pub fn into_inner(self) -> (B, W) {
+        (self.bus, self.out)
+    }
+
+    // steps the core through the wrapper, recording IPL and state changes
+    pub fn step<'a>(&mut self, core: &mut M68k<'a>) -> u32 where B: 'a, W: 'a {
+        self.sample(core);
+        self.time = core.clock;
+        let cycles = core.step(self);
+        self.sample(core);
+        cycles
+    }
+
+    fn sample(&mut self, core: &M68k) {
+        let clock = core.clock;
+        if self.ipl != Some(core.irq_level) {
+            self.ipl = Some(core.irq_level);
+            self.change(clock, core.irq_level as u32, 3, IPL);
+        }
+        if self.state != Some(core.processing_state) {
+            self.state = Some(core.processing_state);
+            let state = match core.processing_state {
+                ProcessingState::Normal => 0,
+                ProcessingState::Stopped => 1,
+                ProcessingState::Halted => 2,
+                ProcessingState::Background => 3,
+            };
+            self.change(clock, state, 2, STATE);
+        }
+    }
+
+    fn access(&mut self, space: AddressSpace, addr: u32, size: u32, write: bool, value: u32) {
+        let (clock, end) = self.cycles.take().unwrap_or((self.time, self.time + 4));
+        self.change(clock, addr, 32, ADDR);
+        self.change(clock, value, 32, DATA);
+        self.change(clock, space.fc(), 3, FC);
+        self.change(clock, !write as u32, 1, RW);
+        self.change(clock, size, 3, SIZE);
+        self.change(clock, 1, 1, STROBE);
+        self.strobe_off = Some(end - 1);
+        self.time = end;
+    }
+
+    fn change(&mut self, clock: u64, value: u32, width: u32, id: &str) {
+        if let Some(off) = self.strobe_off {
+            if off <= clock {
+                self.strobe_off = None;
+                self.change(off, 0, 1, STROBE);
+            }
+        }
+        if self.error.is_some() {
+            return;
+        }
+        let clock = self.written.map_or(clock, |written| clock.max(written));
+        let time = clock * self.period;
+        let result = (|| {
+            if self.written != Some(clock) {
+                writeln!(self.out, "#{}", time)?;
+                self.written = Some(clock);
+            }
+            if width == 1 {
+                writeln!(self.out, "{}{}", value, id)
+            } else {
+                writeln!(self.out, "b{:b} {}", value, id)
+            }
+        })();
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+}
+
+impl<B: Bus, W: Write> Bus for VcdBus<B, W> {
+    fn read_8(&mut self, space: AddressSpace, addr: u32) -> u8 {
+        let value = self.bus.read_8(space, addr);
+        self.access(space, addr, 1, false, value as u32);
+        value
+    }
+    fn read_16(&mut self, space: AddressSpace, addr: u32) -> u16 {
+        let value = self.bus.read_16(space, addr);
+        self.access(space, addr, 2, false, value as u32);
+        value
+    }
+    fn read_32(&mut self, space: AddressSpace, addr: u32) -> u32 {
+        let value = self.bus.read_32(space, addr);
+        self.access(space, addr, 4, false, value);
+        value
+    }
+
+    fn write_8(&mut self, space: AddressSpace, addr: u32, value: u8) {
+        self.access(space, addr, 1, true, value as u32);
+        self.bus.write_8(space, addr, value)
+    }
+    fn write_16(&mut self, space: AddressSpace, addr: u32, value: u16) {
+        self.access(space, addr, 2, true, value as u32);
+        self.bus.write_16(space, addr, value)
+    }
+    fn write_32(&mut self, space: AddressSpace, addr: u32, value: u32) {
+        self.access(space, addr, 4, true, value);
+        self.bus.write_32(space, addr, value)
+    }
+
+    fn peek_8(&self, space: AddressSpace, addr: u32) -> u8 {
+        self.bus.peek_8(space, addr)
+    }
+    fn poke_8(&mut self, space: AddressSpace, addr: u32, value: u8) {
+        self.bus.poke_8(space, addr, value)
+    }
+    fn peek_16(&self, space: AddressSpace, addr: u32) -> u16 {
+        self.bus.peek_16(space, addr)
+    }
+    fn peek_32(&self, space: AddressSpace, addr: u32) -> u32 {
+        self.bus.peek_32(space, addr)
+    }
+    fn poke_16(&mut self, space: AddressSpace, addr: u32, value: u16) {
+        self.bus.poke_16(space, addr, value)
+    }
+    fn poke_32(&mut self, space: AddressSpace, addr: u32, value: u32) {
+        self.bus.poke_32(space, addr, value)
+    }
+
+    fn wait_states(&mut self, space: AddressSpace, addr: u32, write: bool, clock: u64) -> u32 {
+        // a long on the 16 bit bus is two cycles but one access
+        let wait = self.bus.wait_states(space, addr, write, clock);
+        let start = self.cycles.map_or(clock, |(start, _)| start);
+        self.cycles = Some((start, clock + 4 + wait as u64));
+        wait
+    }
+    fn port_size(&mut self, space: AddressSpace, addr: u32) -> u32 {
+        self.bus.port_size(space, addr)
+    }
+    fn read_modify_write(&mut self, locked: bool) {
+        self.bus.read_modify_write(locked)
+    }
+    fn select_core(&mut self, core: usize) {
+        self.bus.select_core(core)
+    }
+    fn bus_error(&mut self, space: AddressSpace, addr: u32, size: u32, write: bool) -> bool {
+        self.bus.bus_error(space, addr, size, write)
+    }
+    fn bus_request(&mut self, clock: u64) -> u32 {
+        self.bus.bus_request(clock)
+    }
+}
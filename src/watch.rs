@@ -0,0 +1,101 @@
+// Watchpoints on data accesses
+//
+// A watchpoint covers a range of logical addresses and can be limited to
+// reads, writes or both, to one address space and to one value. The core
+// checks the data accesses of every instruction, stack and exception
+// frame accesses included, and records the first hit with the address of
+// the instruction that made it. `run_until` stops once there is a hit.
+
+use AddressSpace;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,     // either
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub kind: WatchKind,
+    pub start: u32,
+    pub end: u32,                   // inclusive
+    pub space: Option<AddressSpace>,
+    pub value: Option<u32>,         // the value read or written, at the size of the access
+}
+
+impl Watchpoint {
+    pub fn new(kind: WatchKind, start: u32, end: u32) -> Self {
+        Watchpoint { kind, start, end, space: None, value: None }
+    }
+
+    pub fn matches(&self, space: AddressSpace, addr: u32, size: u32, write: bool, value: u32) -> bool {
+        let kind = match self.kind {
+            WatchKind::Read => !write,
+            WatchKind::Write => write,
+            WatchKind::Access => true,
+        };
+        let last = addr.wrapping_add(size - 1);
+        // an access that wraps at the top of memory is taken as two pieces
+        let overlaps = if last < addr {
+            self.end >= addr || self.start <= last
+        } else {
+            self.start <= last && self.end >= addr
+        };
+        kind && overlaps
+            && self.space.is_none_or(|s| s == space)
+            && self.value.is_none_or(|v| v == value)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub index: usize,   // which watchpoint
+    pub pc: u32,        // the instruction that made the access
+    pub space: AddressSpace,
+    pub addr: u32,
+    pub size: u32,
+    pub write: bool,
+    pub value: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::small_map;
+    use {M68k, Version, SUPERVISOR_DATA, SUPERVISOR_PROGRAM};
+
+    #[test]
+    fn ranges_kinds_and_values() {
+        let mut watch = Watchpoint::new(WatchKind::Write, 0x100, 0x103);
+        assert!(watch.matches(SUPERVISOR_DATA, 0xfe, 4, true, 0));
+        assert!(!watch.matches(SUPERVISOR_DATA, 0x104, 4, true, 0));
+        assert!(!watch.matches(SUPERVISOR_DATA, 0x100, 1, false, 0));
+        watch.space = Some(SUPERVISOR_PROGRAM);
+        assert!(!watch.matches(SUPERVISOR_DATA, 0x100, 1, true, 0));
+        let mut watch = Watchpoint::new(WatchKind::Access, 0, 1);
+        assert!(watch.matches(SUPERVISOR_DATA, 0xffff_fffe, 4, false, 0));
+        watch.value = Some(0x12);
+        assert!(!watch.matches(SUPERVISOR_DATA, 0, 1, false, 0x34));
+        assert!(watch.matches(SUPERVISOR_DATA, 0, 1, false, 0x12));
+    }
+
+    #[test]
+    fn immediates_are_not_data_reads() {
+        // mulu.l #3,d0 ; move.l (a0),d1 with the watchpoint over both
+        let mut bus = small_map(0x8000, &[0x4c3c, 0x0000, 0x0000, 0x0003, 0x2210]);
+        let mut cpu = M68k::new(Version::MC68020);
+        cpu.reset(&mut bus);
+        cpu.watchpoints.push(Watchpoint::new(WatchKind::Read, 0x400, 0x40f));
+        cpu.dar[0] = 5;
+        cpu.dar[8] = 0x404;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.dar[0], 15);
+        assert_eq!(cpu.watch_hit, None);
+        let clock = cpu.clock;
+        cpu.run_until(&mut bus, clock + 1000);
+        let hit = cpu.watch_hit.expect("the move reads the watched range");
+        assert_eq!((hit.pc, hit.addr, hit.size, hit.value), (0x408, 0x404, 4, 3));
+        assert_eq!(cpu.pc, 0x40a);
+    }
+}